@@ -0,0 +1 @@
+Abuse reports are now stored in the database. Repeated reports of the same thing are merged. Admins can list, claim, resolve and act on reports with `!admin reports` or `/_continuwuity/admin/reports`. Acting on a report redacts the event, bans the room or suspends the user.
@@ -0,0 +1 @@
+Media can now be uploaded asynchronously: `POST /_matrix/media/v1/create` reserves an MXC which can be sent in messages before its content is uploaded with `PUT /_matrix/media/v3/upload/{serverName}/{mediaId}`. Pending uploads per user are limited by `max_pending_media_uploads` and expire after `pending_media_upload_timeout`.
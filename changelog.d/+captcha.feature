@@ -0,0 +1 @@
+Added hCaptcha, Cloudflare Turnstile and a self-hosted proof-of-work challenge as alternatives to reCaptcha for registration. Pick one in the `[global.captcha]` section.
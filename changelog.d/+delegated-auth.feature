@@ -0,0 +1 @@
+Added support for delegating authentication to an OAuth 2.0 authorization server such as the Matrix Authentication Service (MSC3861). Access tokens are introspected at the authorization server, users and devices are created on demand, and its metadata is served at `/_matrix/client/v1/auth_metadata`. Configure it in the `[global.oauth]` section.
@@ -0,0 +1 @@
+Logging in with an email address (the `m.id.thirdparty` identifier) and requesting a password reset by email now match the address case-insensitively.
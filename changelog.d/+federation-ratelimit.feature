@@ -0,0 +1 @@
+Requests from other servers can now be rate limited per server with `federation_enabled` in the `[global.ratelimit]` config section. Servers sending more PDUs than their quota allows have their transactions held back, and their backfill, missing events and room state requests are delayed and then rejected with a 429. `!admin ratelimit throttled-origins` lists the servers which were throttled.
@@ -0,0 +1 @@
+Added the `org.matrix.login.jwt` login type, letting trusted services log users in with JSON Web Tokens signed with HS256, RS256 or ES256 keys or a JSON Web Key Set. Configure it in the `[global.jwt]` section.
@@ -0,0 +1 @@
+Added periodic LDAP synchronisation, which updates the display names, emails and admin status of LDAP users, joins members of LDAP groups to mapped rooms and spaces with their power levels, and locks or deactivates users removed or disabled in LDAP. Enable it with `ldap.sync_interval`, or run it at once with `!admin users sync-ldap`.
@@ -0,0 +1 @@
+Media can now be quarantined by MXC, by uploader or by the room referencing it with the `!admin media quarantine` commands. Quarantined media stays in storage but is no longer served to clients or federation, and quarantined remote media is not fetched again.
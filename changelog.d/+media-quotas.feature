@@ -0,0 +1 @@
+Local users' media can now be limited with the `media_quota` and `media_daily_upload_limit` options, with uploads over the limit rejected with `M_RESOURCE_LIMIT_EXCEEDED`. Quotas can be viewed and set per user with `!admin media get-quota` and `set-quota`, and `!admin media top-uploaders` lists the users storing the most media. Only media uploaded from now on is counted.
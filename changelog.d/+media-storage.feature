@@ -0,0 +1 @@
+Media can now be stored in an S3-compatible object store instead of the filesystem, configured in the `[global.media_storage]` section. Existing media can be copied between backends with `!admin media migrate-storage`.
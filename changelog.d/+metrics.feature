@@ -0,0 +1 @@
+Added a Prometheus metrics endpoint covering per-route request counts and latencies, per-server federation traffic, sender queue depths, cache hit rates, RocksDB memory and compaction stats and sync long-polls. Enable it in the `[global.metrics]` config section, either with a bearer token on the main listener or on a separate listener address.
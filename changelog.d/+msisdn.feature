@@ -0,0 +1 @@
+Added phone numbers as third-party identifiers. Validation codes are sent by text message through an HTTP gateway or a local command configured in the `[global.sms]` section. Phone numbers can be required for registration and used to log in.
@@ -0,0 +1 @@
+Added an optional password policy, configured in the `[global.password_policy]` section. It can require a minimum length and character classes, and can reject common or blocklisted passwords and passwords containing the username. It is enforced on registration, password changes and password resets, and advertised to clients (MSC2000).
@@ -0,0 +1 @@
+Added the `!admin rooms purge-history` command and the `/_continuwuity/admin/rooms/{roomID}/purge_history` admin endpoint, which delete the part of a room's timeline before an event or timestamp, optionally along with local media only those events referred to.
@@ -0,0 +1 @@
+Client requests for sending messages, logging in, registering, joining rooms, inviting users and uploading media can now be rate limited, with `M_LIMIT_EXCEEDED` errors telling clients when to retry. Enable it in the `[global.ratelimit]` config section. Admins can exempt users or give them limits of their own with `!admin ratelimit`.
@@ -0,0 +1 @@
+Added support for refresh tokens (MSC2918). Clients which ask for a refresh token when logging in or registering get access tokens which expire after `access_token_ttl` seconds and can be renewed at `/_matrix/client/v3/refresh`. Reusing a replaced refresh token logs the device out, and admins can revoke a device's refresh tokens with `!admin users revoke-refresh-tokens`.
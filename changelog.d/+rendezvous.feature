@@ -0,0 +1 @@
+Added the rendezvous endpoints of MSC4108, letting clients such as Element X sign in new devices by scanning a QR code. Enable them in the `[global.rendezvous]` section.
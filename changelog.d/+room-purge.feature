@@ -0,0 +1 @@
+Added the `!admin rooms purge` command and the `/_continuwuity/admin/rooms/{roomID}/purge` admin endpoint, which remove a room and all of its data from the database in a resumable background job.
@@ -0,0 +1 @@
+Added support for message retention policies (MSC1763). When enabled in the new `[global.retention]` config section, events older than their room's `m.room.retention` `max_lifetime`, or a server default, are periodically removed along with their search index entries.
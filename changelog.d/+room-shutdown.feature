@@ -0,0 +1 @@
+Added `!admin rooms moderation shutdown-room` and `POST /_continuwuity/admin/rooms/{roomID}/shutdown`, which move a room's local members and aliases to a new room explaining why before banning it. The explanation defaults to the new `room_shutdown_message` config option.
@@ -0,0 +1 @@
+Added single sign-on through OpenID Connect identity providers. Users are created or linked on first login from configurable claims, and clients outside `sso.client_allowlist` need the user to confirm before receiving a login token. Providers are configured under `[global.sso.providers]`.
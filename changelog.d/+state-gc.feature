@@ -0,0 +1 @@
+Added the `!admin debug gc-state` command, which reclaims state snapshots and state keys that no event or room refers to any more. Use `--dry-run` to see how much space would be freed first.
@@ -0,0 +1 @@
+Media uploads and downloads are now streamed to and from storage instead of being buffered in memory whole, and downloads support single byte-range requests.
@@ -0,0 +1 @@
+The user directory is now backed by an index kept up to date as memberships and profiles change, so searching it no longer scans every user and also finds remote users who share a room with us. Set `user_directory_search_all_users` to make every local user searchable.
@@ -9,6 +9,8 @@
 	federation::{self, FederationCommand},
 	media::{self, MediaCommand},
 	query::{self, QueryCommand},
+	ratelimit::{self, RatelimitCommand},
+	reports::{self, ReportCommand},
 	room::{self, RoomCommand},
 	server::{self, ServerCommand},
 	token::{self, TokenCommand},
@@ -34,6 +36,14 @@ pub enum AdminCommand {
 	/// Commands for managing rooms
 	Rooms(RoomCommand),
 
+	#[command(subcommand)]
+	/// Commands for triaging abuse reports
+	Reports(ReportCommand),
+
+	#[command(subcommand)]
+	/// Commands for managing rate limits
+	Ratelimit(RatelimitCommand),
+
 	#[command(subcommand)]
 	/// Commands for managing federation
 	Federation(FederationCommand),
@@ -82,6 +92,12 @@ pub(super) async fn process(command: AdminCommand, context: &Context<'_>) -> Res
 			token::process(command, context).await
 		},
 		| Rooms(command) => room::process(command, context).await,
+		| Reports(command) => reports::process(command, context).await,
+		| Ratelimit(command) => {
+			// rate limit commands are all restricted
+			context.bail_restricted()?;
+			ratelimit::process(command, context).await
+		},
 		| Federation(command) => federation::process(command, context).await,
 		| Server(command) => server::process(command, context).await,
 		| Debug(command) => debug::process(command, context).await,
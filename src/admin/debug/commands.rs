@@ -884,6 +884,31 @@ pub(super) async fn purge_outliers(&self) -> Result {
 	write!(self, "Successfully cleared all outlier PDUs.").await
 }
 
+#[admin_command]
+pub(super) async fn gc_state(&self, dry_run: bool) -> Result {
+	self.bail_restricted()?;
+
+	let timer = tokio::time::Instant::now();
+	let report = self
+		.services
+		.rooms
+		.state_compressor
+		.collect_garbage(dry_run)
+		.await?;
+
+	let query_time = timer.elapsed();
+	let verb = if dry_run { "Would free" } else { "Freed" };
+	self.write_str(&format!(
+		"{verb} about {} by removing {} unreferenced state layers, folding {} state layers into \
+		 their child and removing {} unused state keys in {query_time:?}.",
+		utils::bytes::pretty(report.bytes),
+		report.layers,
+		report.folded,
+		report.statekeys,
+	))
+	.await
+}
+
 #[admin_command]
 pub(super) async fn backfill_timestamp_index(&self, room: Option<OwnedRoomId>) -> Result {
 	if let Some(room_id) = room {
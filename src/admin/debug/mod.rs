@@ -228,6 +228,17 @@ pub enum DebugCommand {
 	/// Purge outlier PDUs from the database
 	PurgeOutliers,
 
+	/// Delete state snapshots which no event or room refers to any more
+	///
+	/// Unreferenced state diff layers are deleted, or folded into the layer
+	/// built on top of them, along with the short state keys which are no
+	/// longer used. With --dry-run, only reports how much would be freed.
+	GcState {
+		/// Only report what would be deleted
+		#[arg(long)]
+		dry_run: bool,
+	},
+
 	/// Backfill the timestamp index for MSC3030
 	BackfillTimestampIndex {
 		/// Room ID to backfill (otherwise all rooms)
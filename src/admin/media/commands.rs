@@ -1,12 +1,19 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
 use conduwuit::{
 	Err, Result, debug, debug_info, debug_warn, error, info, trace,
-	utils::time::{TimeDirection, parse_timepoint_ago},
+	utils::{
+		ReadyExt, bytes,
+		time::{TimeDirection, parse_timepoint_ago},
+	},
 	warn,
 };
-use conduwuit_service::media::Dim;
-use ruma::{Mxc, OwnedEventId, OwnedMxcUri, OwnedServerName};
+use conduwuit_service::{
+	Services,
+	media::{Backend, Dim, referenced_mxcs},
+};
+use futures::StreamExt;
+use ruma::{Mxc, OwnedEventId, OwnedMxcUri, OwnedRoomOrAliasId, OwnedServerName, RoomId};
 
 use crate::{admin_command, utils::parse_local_user_id};
 
@@ -334,12 +341,161 @@ pub(super) async fn delete_all_from_server(
 		.await
 }
 
+#[admin_command]
+pub(super) async fn quarantine(&self, mxc: OwnedMxcUri) -> Result {
+	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
+	self.services.media.quarantine(&mxc, true);
+
+	self.write_str(&format!("Quarantined {mxc}.")).await
+}
+
+#[admin_command]
+pub(super) async fn quarantine_all_from_user(&self, username: String) -> Result {
+	let user_id = parse_local_user_id(self.services, &username)?;
+
+	let count = self
+		.services
+		.media
+		.quarantine_from_user(&user_id, true)
+		.await;
+
+	self.write_str(&format!("Quarantined {count} total files uploaded by {user_id}."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn quarantine_room(&self, room: OwnedRoomOrAliasId) -> Result {
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+	let count = quarantine_room_media(self.services, &room_id, true).await;
+
+	self.write_str(&format!("Quarantined {count} total MXCs referenced in {room_id}."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn unquarantine(&self, mxc: OwnedMxcUri) -> Result {
+	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
+	if !self.services.media.is_quarantined(&mxc).await {
+		return Err!("{mxc} is not quarantined.");
+	}
+
+	self.services.media.quarantine(&mxc, false);
+
+	self.write_str(&format!("Unquarantined {mxc}.")).await
+}
+
+#[admin_command]
+pub(super) async fn unquarantine_all_from_user(&self, username: String) -> Result {
+	let user_id = parse_local_user_id(self.services, &username)?;
+
+	let count = self
+		.services
+		.media
+		.quarantine_from_user(&user_id, false)
+		.await;
+
+	self.write_str(&format!("Unquarantined {count} total files uploaded by {user_id}."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn unquarantine_room(&self, room: OwnedRoomOrAliasId) -> Result {
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+	let count = quarantine_room_media(self.services, &room_id, false).await;
+
+	self.write_str(&format!("Unquarantined {count} total MXCs referenced in {room_id}."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn list_quarantined(&self) -> Result {
+	let mxcs: Vec<_> = self
+		.services
+		.media
+		.list_quarantined()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	self.write_str(&format!("Quarantined media ({}):\n```\n{}\n```", mxcs.len(), mxcs.join("\n")))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn get_quota(&self, username: String) -> Result {
+	let user_id = parse_local_user_id(self.services, &username)?;
+	let usage = self.services.media.media_usage(&user_id).await;
+
+	let limit = |limit: Option<u64>| limit.map_or_else(|| "unlimited".to_owned(), pretty_bytes);
+
+	let source = if usage.quota_overridden {
+		"set for this user"
+	} else {
+		"default"
+	};
+
+	self.write_str(&format!(
+		"{user_id} has stored {} of media. Quota: {} ({source}).\nUploaded today: {}. Daily \
+		 limit: {}.",
+		pretty_bytes(usage.used),
+		limit(usage.quota),
+		pretty_bytes(usage.uploaded_today),
+		limit(usage.daily_limit),
+	))
+	.await
+}
+
+#[admin_command]
+pub(super) async fn set_quota(
+	&self,
+	username: String,
+	quota: Option<String>,
+	reset: bool,
+) -> Result {
+	let user_id = parse_local_user_id(self.services, &username)?;
+
+	if reset {
+		self.services.media.set_media_quota(&user_id, None);
+
+		return self
+			.write_str(&format!("Reset the media quota of {user_id} to the default."))
+			.await;
+	}
+
+	let quota = quota.expect("clap enforces quota is required unless --reset");
+	let bytes = match quota.as_str() {
+		| "unlimited" => u64::MAX,
+		| quota => bytes::from_str(quota)?.try_into()?,
+	};
+
+	self.services.media.set_media_quota(&user_id, Some(bytes));
+
+	self.write_str(&format!("Set the media quota of {user_id} to {quota}."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn top_uploaders(&self, limit: usize) -> Result {
+	let uploaders = self.services.media.top_uploaders(limit).await;
+
+	let body = uploaders
+		.iter()
+		.map(|(user_id, used)| format!("{user_id}\t{}", pretty_bytes(*used)))
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	self.write_str(&format!("Top {} uploaders:\n```\n{body}\n```", uploaders.len()))
+		.await
+}
+
 #[admin_command]
 pub(super) async fn get_file_info(&self, mxc: OwnedMxcUri) -> Result {
 	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
 	let metadata = self.services.media.get_metadata(&mxc).await;
+	let quarantined = self.services.media.is_quarantined(&mxc).await;
 
-	self.write_str(&format!("```\n{metadata:#?}\n```")).await
+	self.write_str(&format!("```\n{metadata:#?}\nquarantined: {quarantined}\n```"))
+		.await
 }
 
 #[admin_command]
@@ -351,17 +507,16 @@ pub(super) async fn get_remote_file(
 ) -> Result {
 	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
 	let timeout = Duration::from_millis(timeout.into());
-	let mut result = self
+	let stored = self
 		.services
 		.media
 		.fetch_remote_content(&mxc, None, server.as_deref(), timeout)
 		.await?;
 
-	// Grab the length of the content before clearing it to not flood the output
-	let len = result.content.as_ref().expect("content").len();
-	result.content.as_mut().expect("content").clear();
+	let metadata = self.services.media.get_metadata(&mxc).await;
+	let len = stored.size;
 
-	self.write_str(&format!("```\n{result:#?}\nreceived {len} bytes for file content.\n```"))
+	self.write_str(&format!("```\n{metadata:#?}\nreceived {len} bytes for file content.\n```"))
 		.await
 }
 
@@ -406,3 +561,64 @@ pub(super) async fn delete_url_preview(&self, url: Option<String>, all: bool) ->
 	self.write_str(&format!("Deleted cached URL preview for: {url}"))
 		.await
 }
+
+#[admin_command]
+pub(super) async fn migrate_storage(
+	&self,
+	from: String,
+	to: String,
+	delete_source: bool,
+) -> Result {
+	self.bail_restricted()?;
+
+	let from: Backend = from.parse()?;
+	let to: Backend = to.parse()?;
+
+	self.write_str(&format!("Migrating media from the {from} backend to the {to} backend..."))
+		.await?;
+
+	let (migrated, failed) = self
+		.services
+		.media
+		.migrate_storage(from, to, delete_source)
+		.await?;
+
+	self.write_str(&format!(
+		"Migrated {migrated} media files. {failed} files failed to migrate, check the logs for \
+		 details."
+	))
+	.await
+}
+
+/// Quarantines or unquarantines every MXC URI referenced by the events in a
+/// room's timeline, returning how many there were.
+async fn quarantine_room_media(
+	services: &Services,
+	room_id: &RoomId,
+	quarantined: bool,
+) -> usize {
+	let mut mxcs = HashSet::new();
+	services
+		.rooms
+		.timeline
+		.all_pdus(room_id)
+		.ready_for_each(|(_, pdu)| {
+			mxcs.extend(referenced_mxcs(pdu.content.get()).map(ToOwned::to_owned));
+		})
+		.await;
+
+	let mut count: usize = 0;
+	for mxc in &mxcs {
+		let Ok(mxc) = Mxc::try_from(mxc.as_str()) else {
+			debug_warn!("Ignoring invalid MXC URI {mxc} referenced in {room_id}");
+			continue;
+		};
+
+		services.media.quarantine(&mxc, quarantined);
+		count = count.saturating_add(1);
+	}
+
+	count
+}
+
+fn pretty_bytes(bytes: u64) -> String { bytes::pretty(bytes.try_into().unwrap_or(usize::MAX)) }
@@ -3,7 +3,7 @@
 
 use clap::Subcommand;
 use conduwuit::Result;
-use ruma::{OwnedEventId, OwnedMxcUri, OwnedServerName};
+use ruma::{OwnedEventId, OwnedMxcUri, OwnedRoomOrAliasId, OwnedServerName};
 
 use crate::admin_command_dispatch;
 
@@ -76,6 +76,73 @@ pub enum MediaCommand {
 		yes_i_want_to_delete_local_media: bool,
 	},
 
+	/// Quarantines a single media file, hiding it from clients and federation
+	///   while keeping it in storage. Quarantined remote media is not fetched
+	///   again.
+	Quarantine {
+		/// The MXC URL to quarantine
+		mxc: OwnedMxcUri,
+	},
+
+	/// Quarantines all the local media from a local user on our server.
+	QuarantineAllFromUser {
+		username: String,
+	},
+
+	/// Quarantines every MXC URL referenced by the events in a room's
+	///   timeline, including remote media which has not been fetched yet.
+	QuarantineRoom {
+		room: OwnedRoomOrAliasId,
+	},
+
+	/// Lifts the quarantine on a single media file.
+	Unquarantine {
+		/// The MXC URL to unquarantine
+		mxc: OwnedMxcUri,
+	},
+
+	/// Lifts the quarantine on all the local media from a local user on our
+	///   server.
+	UnquarantineAllFromUser {
+		username: String,
+	},
+
+	/// Lifts the quarantine on every MXC URL referenced by the events in a
+	///   room's timeline.
+	UnquarantineRoom {
+		room: OwnedRoomOrAliasId,
+	},
+
+	/// Lists all the quarantined MXC URLs.
+	ListQuarantined,
+
+	/// Shows how much media a local user has stored and uploaded today, and
+	///   their limits.
+	GetQuota {
+		username: String,
+	},
+
+	/// Sets the media quota of a local user, overriding the `media_quota`
+	///   config option. Use --reset to go back to the configured default.
+	SetQuota {
+		username: String,
+
+		/// The quota as a byte size, e.g. "10GiB", or "unlimited"
+		#[arg(required_unless_present = "reset")]
+		quota: Option<String>,
+
+		/// Remove the user's own quota
+		#[arg(long, conflicts_with = "quota")]
+		reset: bool,
+	},
+
+	/// Lists the local users who have stored the most media.
+	TopUploaders {
+		/// Number of users to list
+		#[arg(default_value("10"))]
+		limit: usize,
+	},
+
 	GetFileInfo {
 		/// The MXC URL to lookup info for.
 		mxc: OwnedMxcUri,
@@ -120,4 +187,18 @@ pub enum MediaCommand {
 		#[arg(long, conflicts_with = "url")]
 		all: bool,
 	},
+
+	/// Copies all media files from one storage backend to another, such as
+	/// before changing `media_storage.backend` in the config.
+	MigrateStorage {
+		/// Backend to copy media from: "filesystem" or "s3"
+		from: String,
+
+		/// Backend to copy media to: "filesystem" or "s3"
+		to: String,
+
+		/// Delete each file from the source backend once it has been copied
+		#[arg(long)]
+		delete_source: bool,
+	},
 }
@@ -17,6 +17,8 @@
 pub(crate) mod federation;
 pub(crate) mod media;
 pub(crate) mod query;
+pub(crate) mod ratelimit;
+pub(crate) mod reports;
 pub(crate) mod room;
 pub(crate) mod server;
 pub(crate) mod token;
@@ -0,0 +1,112 @@
+use std::fmt::Write as _;
+
+use conduwuit::{Err, Result, utils::time};
+use conduwuit_macros::admin_command;
+use futures::StreamExt;
+use ruma::OwnedUserId;
+use service::ratelimit::Override;
+
+#[admin_command]
+pub(super) async fn set_override(
+	&self,
+	user_id: OwnedUserId,
+	exempt: bool,
+	per_second: Option<f64>,
+	burst_count: u32,
+) -> Result {
+	if !self.services.globals.user_is_local(&user_id) {
+		return Err!("Only local users can be given rate limits of their own.");
+	}
+
+	let limits = match per_second {
+		| Some(per_second) if !exempt => Override::Quota { per_second, burst_count },
+		| _ => Override::Exempt,
+	};
+
+	self.services.ratelimit.set_override(&user_id, limits);
+	if !self.services.server.config.ratelimit.enabled {
+		return self
+			.write_str(&format!(
+				"Set the rate limits of {user_id} to {}, but rate limiting is disabled in the \
+				 config.",
+				describe(&limits)
+			))
+			.await;
+	}
+
+	self.write_str(&format!("Set the rate limits of {user_id} to {}.", describe(&limits)))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn remove_override(&self, user_id: OwnedUserId) -> Result {
+	if self
+		.services
+		.ratelimit
+		.get_override(&user_id)
+		.await
+		.is_err()
+	{
+		return Err!("{user_id} has no rate limits of their own.");
+	}
+
+	self.services.ratelimit.remove_override(&user_id);
+	self.write_str(&format!("{user_id} is subject to the configured rate limits again."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn list_overrides(&self) -> Result {
+	let overrides: Vec<_> = self.services.ratelimit.overrides().collect().await;
+	if overrides.is_empty() {
+		return self
+			.write_str("No users have rate limits of their own.")
+			.await;
+	}
+
+	let mut out = format!("{} users have rate limits of their own:\n\n", overrides.len());
+	for (user_id, limits) in &overrides {
+		writeln!(out, "- {user_id}: {}", describe(limits))?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+pub(super) async fn throttled_origins(&self) -> Result {
+	if !self.services.server.config.ratelimit.federation_enabled {
+		return Err!("Federation rate limiting is disabled in the config.");
+	}
+
+	let throttled = self.services.ratelimit.throttled_origins();
+	if throttled.is_empty() {
+		return self
+			.write_str("No servers were throttled in the last day.")
+			.await;
+	}
+
+	let mut out = format!("{} servers were throttled in the last day:\n\n", throttled.len());
+	for (origin, throttled) in &throttled {
+		let last_throttled = throttled
+			.last_throttled
+			.elapsed()
+			.map(time::pretty)
+			.unwrap_or_default();
+
+		writeln!(
+			out,
+			"- {origin}: {} delayed, {} rejected, last throttled {last_throttled} ago",
+			throttled.delayed, throttled.rejected
+		)?;
+	}
+
+	self.write_str(&out).await
+}
+
+fn describe(limits: &Override) -> String {
+	match limits {
+		| Override::Exempt => "exempt".to_owned(),
+		| Override::Quota { per_second, burst_count } =>
+			format!("{per_second} requests per second with bursts of {burst_count}"),
+	}
+}
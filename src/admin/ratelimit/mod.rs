@@ -0,0 +1,43 @@
+mod commands;
+
+use clap::Subcommand;
+use conduwuit::Result;
+use ruma::OwnedUserId;
+
+use crate::admin_command_dispatch;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum RatelimitCommand {
+	/// Give a user rate limits of their own in place of the configured ones
+	///
+	/// The quota applies to every kind of rate limited request and is shared
+	/// between all of the user's devices.
+	SetOverride {
+		user_id: OwnedUserId,
+
+		/// Never rate limit the user, e.g. for bots
+		#[arg(long, conflicts_with_all = ["per_second", "burst_count"])]
+		exempt: bool,
+
+		/// Average number of requests per second the user may make
+		#[arg(long, required_unless_present = "exempt")]
+		per_second: Option<f64>,
+
+		/// Number of requests the user may make in a burst
+		#[arg(long, default_value = "10")]
+		burst_count: u32,
+	},
+
+	/// Make a user subject to the configured rate limits again
+	RemoveOverride {
+		user_id: OwnedUserId,
+	},
+
+	/// List the users with rate limits of their own
+	ListOverrides,
+
+	/// List the servers which went over their federation quota in the last
+	///   day, most recently throttled first
+	ThrottledOrigins,
+}
@@ -0,0 +1,125 @@
+use std::fmt::Write as _;
+
+use api::admin::reports::{ReportAction, act_on_report};
+use conduwuit::{Err, Result, utils::ReadyExt};
+use conduwuit_macros::admin_command;
+use futures::StreamExt;
+use ruma::{OwnedRoomId, OwnedUserId};
+use service::reports::{Report, ReportFilter, ReportStatus, ReportTarget};
+
+#[admin_command]
+pub(super) async fn list(
+	&self,
+	status: Option<ReportStatus>,
+	room: Option<OwnedRoomId>,
+	user: Option<OwnedUserId>,
+	assignee: Option<OwnedUserId>,
+	limit: usize,
+) -> Result {
+	let filter = ReportFilter {
+		status,
+		room_id: room,
+		user_id: user,
+		assignee,
+	};
+	let reports: Vec<_> = self
+		.services
+		.reports
+		.reports()
+		.ready_filter(|(_, report)| filter.matches(report))
+		.take(limit)
+		.collect()
+		.await;
+
+	if reports.is_empty() {
+		return self.write_str("No reports found.").await;
+	}
+
+	let mut out = format!("Found {} reports:\n\n", reports.len());
+	for (id, report) in &reports {
+		writeln!(out, "- {}", summary(*id, report))?;
+	}
+
+	self.write_str(&out).await
+}
+
+#[admin_command]
+pub(super) async fn show(&self, id: u64) -> Result {
+	let Ok(report) = self.services.reports.get(id).await else {
+		return Err!("Report {id} does not exist.");
+	};
+
+	self.write_str(&format!("{}\n\n```\n{report:#?}\n```", summary(id, &report)))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn claim(&self, id: u64) -> Result {
+	let report = self
+		.services
+		.reports
+		.claim(id, self.sender_or_service_user())
+		.await?;
+
+	self.write_str(&format!("Claimed {}", summary(id, &report)))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn resolve(&self, id: u64, resolution: Option<String>) -> Result {
+	let report = self
+		.services
+		.reports
+		.resolve(id, self.sender_or_service_user(), resolution)
+		.await?;
+
+	self.write_str(&format!("Resolved {}", summary(id, &report)))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn redact(&self, id: u64) -> Result { self.act(id, ReportAction::Redact).await }
+
+#[admin_command]
+pub(super) async fn ban(&self, id: u64) -> Result { self.act(id, ReportAction::Ban).await }
+
+#[admin_command]
+pub(super) async fn suspend(&self, id: u64) -> Result {
+	self.act(id, ReportAction::Suspend).await
+}
+
+#[admin_command]
+async fn act(&self, id: u64, action: ReportAction) -> Result {
+	self.bail_restricted()?;
+
+	let report = act_on_report(self.services, id, action, self.sender_or_service_user()).await?;
+
+	self.write_str(&format!(
+		"{}\n\nResolved {}",
+		report.resolution.as_deref().unwrap_or_default(),
+		summary(id, &report)
+	))
+	.await
+}
+
+/// One line describing a report.
+fn summary(id: u64, report: &Report) -> String {
+	let target = match &report.target {
+		| ReportTarget::Event { event_id, sender, .. } => format!("event {event_id} by {sender}"),
+		| ReportTarget::Room { room_id } => format!("room {room_id}"),
+		| ReportTarget::User { user_id } => format!("user {user_id}"),
+	};
+
+	let assignee = report
+		.assignee
+		.as_ref()
+		.map(|assignee| format!(" ({assignee})"))
+		.unwrap_or_default();
+
+	format!(
+		"report {id} [{}{assignee}] from {} about {target}: {}",
+		report.status,
+		report.reporter,
+		report.reason.as_deref().unwrap_or("no reason given"),
+	)
+}
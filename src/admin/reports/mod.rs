@@ -0,0 +1,71 @@
+mod commands;
+
+use clap::Subcommand;
+use conduwuit::Result;
+use ruma::{OwnedRoomId, OwnedUserId};
+use service::reports::ReportStatus;
+
+use crate::admin_command_dispatch;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum ReportCommand {
+	/// List reports, most recent first
+	List {
+		/// Only list reports with this status (open, claimed or resolved)
+		#[arg(long)]
+		status: Option<ReportStatus>,
+
+		/// Only list reports about this room or events in it
+		#[arg(long)]
+		room: Option<OwnedRoomId>,
+
+		/// Only list reports made by or about this user
+		#[arg(long)]
+		user: Option<OwnedUserId>,
+
+		/// Only list reports claimed or resolved by this admin
+		#[arg(long)]
+		assignee: Option<OwnedUserId>,
+
+		/// Maximum number of reports to list
+		#[arg(long, default_value = "50")]
+		limit: usize,
+	},
+
+	/// Show the details of a report
+	Show {
+		id: u64,
+	},
+
+	/// Claim a report so other admins know you are looking into it
+	Claim {
+		id: u64,
+	},
+
+	/// Resolve a report without acting on it
+	Resolve {
+		id: u64,
+
+		/// Why the report was resolved
+		#[arg(long)]
+		resolution: Option<String>,
+	},
+
+	/// Redact the event an event report is about, then resolve the report
+	Redact {
+		id: u64,
+	},
+
+	/// Ban the room a report is about as `rooms moderation ban-room` does,
+	///   then resolve the report
+	Ban {
+		id: u64,
+	},
+
+	/// Suspend the user a report is about, or the sender of the reported
+	///   event, then resolve the report
+	Suspend {
+		id: u64,
+	},
+}
@@ -1,6 +1,7 @@
 use conduwuit::{Err, Result};
+use conduwuit_service::rooms::purge::HistoryBound;
 use futures::StreamExt;
-use ruma::{OwnedRoomId, OwnedRoomOrAliasId};
+use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
 
 use crate::{PAGE_SIZE, admin_command, get_room_info};
 
@@ -92,3 +93,108 @@ pub(super) async fn purge_sync_tokens(&self, _room: OwnedRoomOrAliasId) -> Resul
 	)
 	.await
 }
+
+#[admin_command]
+pub(super) async fn purge(&self, room: OwnedRoomOrAliasId, force: bool) -> Result {
+	self.bail_restricted()?;
+
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+
+	if let Ok(admin_room_id) = self.services.admin.get_admin_room().await {
+		if room_id == admin_room_id {
+			return Err!("Not allowed to purge the admin room.");
+		}
+	}
+
+	let local_members = self
+		.services
+		.rooms
+		.state_cache
+		.local_users_in_room(&room_id)
+		.count()
+		.await;
+
+	if local_members > 0 && !force {
+		return Err!(
+			"{room_id} still has {local_members} local members. Ban the room first, or use \
+			 --force to purge it anyway."
+		);
+	}
+
+	let job = self.services.rooms.purge.queue(&room_id).await?;
+
+	// Without a ban, the next federated event or invite would bring the room back
+	let metadata = &self.services.rooms.metadata;
+	let ban = force && !metadata.is_banned(&room_id).await;
+	if ban {
+		metadata.ban_room(&room_id, true);
+		metadata.disable_room(&room_id, true);
+	}
+
+	self.write_str(&format!(
+		"{}Queued {room_id} for purging, starting with {}. Progress will be reported in the \
+		 admin room.",
+		if ban { "Banned the room. " } else { "" },
+		job.stage
+	))
+	.await
+}
+
+#[admin_command]
+pub(super) async fn purge_status(&self) -> Result {
+	let jobs: Vec<_> = self.services.rooms.purge.jobs().collect().await;
+
+	if jobs.is_empty() {
+		return self.write_str("No room purges are in progress.").await;
+	}
+
+	let body = jobs
+		.iter()
+		.map(|(room_id, job)| {
+			let status = job
+				.error
+				.as_deref()
+				.map_or_else(|| "running".to_owned(), |e| format!("failed: {e}"));
+
+			format!(
+				"{room_id}\tStage: {}\tEvents: {}\tOther events: {}\tState layers: {}\t{status}",
+				job.stage, job.events, job.outliers, job.state_layers
+			)
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	self.write_str(&format!("Room purges ({}):\n```\n{body}\n```", jobs.len()))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn purge_history(
+	&self,
+	room: OwnedRoomOrAliasId,
+	before_event: Option<OwnedEventId>,
+	before_ts: Option<u64>,
+	delete_media: bool,
+) -> Result {
+	self.bail_restricted()?;
+
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+	let bound = match (before_event, before_ts) {
+		| (Some(event_id), None) => HistoryBound::Event(event_id),
+		| (None, Some(ts)) => HistoryBound::Timestamp(ts),
+		| _ => return Err!("Specify either --before-event or --before-ts."),
+	};
+
+	let purged = self
+		.services
+		.rooms
+		.purge
+		.purge_history(&room_id, &bound, delete_media)
+		.await?;
+
+	self.write_str(&format!(
+		"Deleted {} events and {} local media files from the history of {room_id}.",
+		purged.events, purged.media
+	))
+	.await
+}
@@ -6,7 +6,7 @@
 
 use clap::Subcommand;
 use conduwuit::Result;
-use ruma::{OwnedRoomId, OwnedRoomOrAliasId};
+use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
 
 use self::{
 	alias::RoomAliasCommand, directory::RoomDirectoryCommand, info::RoomInfoCommand,
@@ -61,6 +61,47 @@ pub enum RoomCommand {
 		room_id: OwnedRoomId,
 	},
 
+	/// Purge a room and everything the server stores about it
+	///
+	/// The purge runs in the background and reports its progress in the admin
+	/// room. Local users must have left the room first, for example by banning
+	/// it, unless --force is given. A room purged with --force is banned so
+	/// that it is not recreated by federation.
+	Purge {
+		/// Room ID or alias to purge
+		room: OwnedRoomOrAliasId,
+
+		/// Purge the room even if local users are still joined to it
+		#[arg(long)]
+		force: bool,
+	},
+
+	/// Show the progress of queued, running and failed room purges
+	PurgeStatus,
+
+	/// Delete the part of a room's timeline before an event or a point in time
+	///
+	/// State events and the room's most recent events are kept, so the room
+	/// keeps working locally and over federation. The removed events' search
+	/// index entries and relations are deleted along with them.
+	PurgeHistory {
+		/// Room ID or alias to purge the history of
+		room: OwnedRoomOrAliasId,
+
+		/// Delete the events before this event
+		#[arg(long, conflicts_with = "before_ts", required_unless_present = "before_ts")]
+		before_event: Option<OwnedEventId>,
+
+		/// Delete the events sent before this time, in milliseconds since the
+		/// unix epoch
+		#[arg(long)]
+		before_ts: Option<u64>,
+
+		/// Also delete local media which only the deleted events referred to
+		#[arg(long)]
+		delete_media: bool,
+	},
+
 	/// - Delete all sync tokens for a room
 	PurgeSyncTokens {
 		/// Room ID or alias to purge sync tokens for
@@ -1,4 +1,4 @@
-use api::client::leave_room;
+use api::{admin::rooms::shutdown::shutdown_room, client::leave_room};
 use clap::Subcommand;
 use conduwuit::{
 	Err, Result, debug, info,
@@ -6,7 +6,7 @@
 	warn,
 };
 use futures::{FutureExt, StreamExt};
-use ruma::{OwnedRoomId, OwnedRoomOrAliasId, RoomAliasId, RoomId, RoomOrAliasId};
+use ruma::{OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId, RoomAliasId, RoomId, RoomOrAliasId};
 
 use crate::{admin_command, admin_command_dispatch, get_room_info};
 
@@ -29,6 +29,31 @@ pub enum RoomModerationCommand {
 	///   steps as ban-room
 	BanListOfRooms,
 
+	/// Shuts down a room by moving all our local users and local aliases to a
+	///   new room, then banning the room as ban-room does.
+	///
+	/// The new room is owned by the given local user (the server user by
+	/// default), who posts an explanation there. Only they can send messages
+	/// in it.
+	ShutdownRoom {
+		/// The room in the format of `!roomid:example.com` or a room alias in
+		/// the format of `#roomalias:example.com`
+		room: OwnedRoomOrAliasId,
+
+		/// Local user who owns the new room
+		#[arg(long)]
+		new_room_user: Option<OwnedUserId>,
+
+		/// Name of the new room
+		#[arg(long)]
+		room_name: Option<String>,
+
+		/// Message posted in the new room, instead of the configured
+		/// `room_shutdown_message`
+		#[arg(long)]
+		message: Option<String>,
+	},
+
 	/// Unbans a room to allow local users to join again
 	UnbanRoom {
 		/// The room in the format of `!roomid:example.com` or a room alias in
@@ -333,6 +358,35 @@ async fn ban_list_of_rooms(&self) -> Result {
 	.await
 }
 
+#[admin_command]
+async fn shutdown_room(
+	&self,
+	room: OwnedRoomOrAliasId,
+	new_room_user: Option<OwnedUserId>,
+	room_name: Option<String>,
+	message: Option<String>,
+) -> Result {
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+	let shutdown = shutdown_room(
+		self.services,
+		&room_id,
+		new_room_user.as_deref(),
+		room_name.as_deref(),
+		message.as_deref(),
+	)
+	.boxed()
+	.await?;
+
+	self.write_str(&format!(
+		"Room shut down. Moved {} local users ({} failed) and {} aliases to {}.",
+		shutdown.moved.len(),
+		shutdown.failed.len(),
+		shutdown.aliases.len(),
+		shutdown.new_room_id,
+	))
+	.await
+}
+
 #[admin_command]
 async fn unban_room(&self, room: OwnedRoomOrAliasId) -> Result {
 	let room_id = if room.is_room_id() {
@@ -16,7 +16,8 @@
 use futures::{FutureExt, StreamExt};
 use lettre::Address;
 use ruma::{
-	OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId, UserId,
+	OwnedDeviceId, OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName, OwnedUserId,
+	UserId,
 	events::{
 		RoomAccountDataEventType, StateEventType,
 		room::{
@@ -94,6 +95,11 @@ pub(super) async fn create_user(&self, username: String, password: Option<String
 		.users
 		.set_displayname(&user_id, Some(displayname));
 
+	self.services
+		.user_directory
+		.update_local_profile(&user_id)
+		.await;
+
 	// Initial account data
 	self.services
 		.account_data
@@ -190,6 +196,10 @@ pub(super) async fn deactivate(&self, no_leave_rooms: bool, user_id: String) ->
 	}
 
 	self.services.users.deactivate_account(&user_id).await?;
+	self.services
+		.user_directory
+		.update_local_profile(&user_id)
+		.await;
 
 	if !no_leave_rooms {
 		self.services
@@ -396,6 +406,10 @@ pub(super) async fn deactivate_all(&self, no_leave_rooms: bool, force: bool) ->
 			},
 			| Ok(()) => {
 				deactivation_count = deactivation_count.saturating_add(1);
+				self.services
+					.user_directory
+					.update_local_profile(&user_id)
+					.await;
 				if !no_leave_rooms {
 					info!("Forcing user {user_id} to leave all rooms apart of deactivate-all");
 					let all_joined_rooms: Vec<OwnedRoomId> = self
@@ -1067,6 +1081,35 @@ pub(super) async fn logout(&self, user_id: String) -> Result {
 		.await
 }
 
+#[admin_command]
+pub(super) async fn revoke_refresh_tokens(
+	&self,
+	user_id: String,
+	device_id: OwnedDeviceId,
+) -> Result {
+	self.bail_restricted()?;
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+	if self
+		.services
+		.users
+		.get_device_metadata(&user_id, &device_id)
+		.await
+		.is_err()
+	{
+		return Err!("User {user_id} has no device {device_id}.");
+	}
+
+	self.services
+		.users
+		.revoke_refresh_tokens(&user_id, &device_id)
+		.await;
+
+	self.write_str(&format!(
+		"Revoked the refresh tokens of device {device_id} of user {user_id}."
+	))
+	.await
+}
+
 #[admin_command]
 pub(super) async fn disable_login(&self, user_id: String) -> Result {
 	self.bail_restricted()?;
@@ -1222,3 +1265,17 @@ pub(super) async fn reset_push_rules(&self, user_id: String) -> Result {
 	self.write_str("Reset user's push rules to the server default.")
 		.await
 }
+
+#[admin_command]
+pub(super) async fn sync_ldap(&self) -> Result {
+	self.bail_restricted()?;
+
+	let synced = self.services.ldap_sync.sync().await?;
+
+	self.write_str(&format!(
+		"Synchronised {} LDAP users: {} updated, {} locked or deactivated, {} joined to rooms \
+		 and {} removed from rooms.",
+		synced.users, synced.updated, synced.removed, synced.joined, synced.left
+	))
+	.await
+}
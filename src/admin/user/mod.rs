@@ -2,7 +2,7 @@
 
 use clap::Subcommand;
 use conduwuit::Result;
-use ruma::{OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
+use ruma::{OwnedDeviceId, OwnedEventId, OwnedRoomId, OwnedRoomOrAliasId};
 
 use crate::admin_command_dispatch;
 
@@ -98,6 +98,18 @@ pub enum UserCommand {
 		user_id: String,
 	},
 
+	/// Revoke the refresh tokens of one of a user's devices.
+	///
+	/// The device's current access token is revoked along with them, so the
+	/// device has to log in again. Use this when a refresh token may have
+	/// leaked.
+	RevokeRefreshTokens {
+		/// Username of the user owning the device
+		user_id: String,
+		/// The device whose tokens to revoke
+		device_id: OwnedDeviceId,
+	},
+
 	/// Suspend a user
 	///
 	/// Suspended users are able to log in, sync, and read messages, but are not
@@ -263,4 +275,8 @@ pub enum UserCommand {
 	ResetPushRules {
 		user_id: String,
 	},
+
+	/// Synchronise users who log in through LDAP with their entries now,
+	/// rather than waiting for `ldap.sync_interval` to pass.
+	SyncLdap,
 }
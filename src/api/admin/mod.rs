@@ -1 +1,2 @@
+pub mod reports;
 pub mod rooms;
@@ -0,0 +1,277 @@
+use axum::extract::{Path, Query, State};
+use conduwuit::{Err, Event, Result, err, info, matrix::pdu::PduBuilder, utils::ReadyExt};
+use conduwuit_service::{
+	Services,
+	reports::{Report, ReportFilter, ReportStatus, ReportTarget},
+};
+use futures::StreamExt;
+use ruma::{
+	CanonicalJsonValue, EventId, UserId, events::room::redaction::RoomRedactionEventContent,
+};
+
+use super::rooms::ban::{BannedRoom, evict_and_ban};
+use crate::Ruma;
+
+/// Something which can be done about a report from its ID.
+#[derive(Clone, Copy, Debug)]
+pub enum ReportAction {
+	/// Redact the reported event.
+	Redact,
+
+	/// Ban the reported room, or the room of the reported event.
+	Ban,
+
+	/// Suspend the reported user, or the sender of the reported event.
+	Suspend,
+}
+
+/// # `GET /_continuwuity/admin/reports`
+///
+/// Lists reports, most recent first. The `status`, `room_id`, `user_id` and
+/// `assignee` query parameters narrow down the results.
+pub(crate) async fn list_reports_route(
+	State(services): State<crate::State>,
+	Query(filter): Query<ReportFilter>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let reports: Vec<_> = services
+		.reports
+		.reports()
+		.ready_filter(|(_, report)| filter.matches(report))
+		.map(|(id, report)| serde_json::json!({ "id": id, "report": report }))
+		.collect()
+		.await;
+
+	Ok(axum::Json(serde_json::json!({ "reports": reports })))
+}
+
+/// # `GET /_continuwuity/admin/reports/{reportID}`
+pub(crate) async fn get_report_route(
+	State(services): State<crate::State>,
+	Path(id): Path<u64>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let Ok(report) = services.reports.get(id).await else {
+		return Err!(Request(NotFound("Report {id} does not exist")));
+	};
+
+	Ok(axum::Json(serde_json::json!({ "id": id, "report": report })))
+}
+
+/// # `POST /_continuwuity/admin/reports/{reportID}/claim`
+///
+/// Assigns the report to the requesting admin.
+pub(crate) async fn claim_report_route(
+	State(services): State<crate::State>,
+	Path(id): Path<u64>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let report = services.reports.claim(id, sender_user).await?;
+
+	Ok(axum::Json(serde_json::json!({ "id": id, "report": report })))
+}
+
+/// # `POST /_continuwuity/admin/reports/{reportID}/resolve`
+///
+/// Closes the report without acting on it, with an optional `resolution`
+/// explaining why.
+pub(crate) async fn resolve_report_route(
+	State(services): State<crate::State>,
+	Path(id): Path<u64>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let resolution = match body.json_body.as_ref() {
+		| Some(CanonicalJsonValue::Object(object)) => match object.get("resolution") {
+			| Some(CanonicalJsonValue::String(resolution)) => Some(resolution.clone()),
+			| _ => None,
+		},
+		| _ => None,
+	};
+
+	let report = services
+		.reports
+		.resolve(id, sender_user, resolution)
+		.await?;
+
+	Ok(axum::Json(serde_json::json!({ "id": id, "report": report })))
+}
+
+/// # `POST /_continuwuity/admin/reports/{reportID}/action`
+///
+/// Redacts, bans or suspends what the report is about depending on `action`,
+/// then resolves the report.
+pub(crate) async fn report_action_route(
+	State(services): State<crate::State>,
+	Path(id): Path<u64>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let Some(CanonicalJsonValue::Object(object)) = body.json_body.as_ref() else {
+		return Err!(Request(BadJson("Expected a JSON object")));
+	};
+
+	let action = match object.get("action") {
+		| Some(CanonicalJsonValue::String(action)) => match action.as_str() {
+			| "redact" => ReportAction::Redact,
+			| "ban" => ReportAction::Ban,
+			| "suspend" => ReportAction::Suspend,
+			| _ =>
+				return Err!(Request(InvalidParam(
+					"Unknown action {action:?}, expected redact, ban or suspend"
+				))),
+		},
+		| _ => return Err!(Request(InvalidParam("Missing action"))),
+	};
+
+	let report = act_on_report(&services, id, action, sender_user)
+		.await
+		.map_err(|e| err!(Request(InvalidParam("{e}"))))?;
+
+	Ok(axum::Json(serde_json::json!({ "id": id, "report": report })))
+}
+
+/// Carries out `action` against what the report is about and resolves the
+/// report on behalf of `admin`.
+pub async fn act_on_report(
+	services: &Services,
+	id: u64,
+	action: ReportAction,
+	admin: &UserId,
+) -> Result<Report> {
+	let Ok(report) = services.reports.get(id).await else {
+		return Err!("Report {id} does not exist.");
+	};
+
+	if report.status == ReportStatus::Resolved {
+		return Err!("Report {id} has already been resolved.");
+	}
+
+	let resolution = match action {
+		| ReportAction::Redact => {
+			let ReportTarget::Event { event_id, .. } = &report.target else {
+				return Err!("Only event reports can be acted on by redacting.");
+			};
+
+			redact(services, event_id).await?
+		},
+		| ReportAction::Ban => {
+			let Some(room_id) = report.target.room_id() else {
+				return Err!("User reports have no room to ban, suspend the user instead.");
+			};
+
+			if services
+				.admin
+				.get_admin_room()
+				.await
+				.is_ok_and(|admin_room_id| admin_room_id == room_id)
+			{
+				return Err!("Not allowed to ban the admin room.");
+			}
+
+			let BannedRoom { evicted, failed_evicted, aliases } =
+				evict_and_ban(services, room_id).await?;
+
+			format!(
+				"Banned {room_id}: Removed {} users ({} failed) and {} aliases",
+				evicted.len(),
+				failed_evicted.len(),
+				aliases.len()
+			)
+		},
+		| ReportAction::Suspend => {
+			let Some(user_id) = report.target.user_id() else {
+				return Err!("Room reports have no user to suspend, ban the room instead.");
+			};
+
+			suspend(services, user_id, admin).await?
+		},
+	};
+
+	info!(%admin, ?action, "Acted on report {id}: {resolution}");
+	services.reports.resolve(id, admin, Some(resolution)).await
+}
+
+async fn redact(services: &Services, event_id: &EventId) -> Result<String> {
+	let Ok(event) = services.rooms.timeline.get_non_outlier_pdu(event_id).await else {
+		return Err!("Event {event_id} does not exist in our database.");
+	};
+
+	if event.is_redacted() {
+		return Ok(format!("{event_id} was already redacted"));
+	}
+
+	if !services.globals.user_is_local(event.sender()) {
+		return Err!("Only events sent by local users can be redacted.");
+	}
+
+	let room_id = event
+		.room_id_or_hash()
+		.ok_or_else(|| err!(Database("Event has no room_id")))?;
+
+	let reason = format!(
+		"The administrator(s) of {} has redacted this user's message.",
+		services.globals.server_name()
+	);
+
+	let state_lock = services.rooms.state.mutex.lock(&room_id).await;
+	let redaction_event_id = services
+		.rooms
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				redacts: Some(event_id.to_owned()),
+				..PduBuilder::timeline(&RoomRedactionEventContent {
+					redacts: Some(event_id.to_owned()),
+					reason: Some(reason),
+				})
+			},
+			event.sender(),
+			Some(&room_id),
+			&state_lock,
+		)
+		.await?;
+
+	Ok(format!("Redacted {event_id} with {redaction_event_id}"))
+}
+
+async fn suspend(services: &Services, user_id: &UserId, admin: &UserId) -> Result<String> {
+	if !services.globals.user_is_local(user_id) {
+		return Err!("Only local users can be suspended.");
+	}
+
+	if user_id == services.globals.server_user {
+		return Err!("Not allowed to suspend the server service account.");
+	}
+
+	if services.users.is_admin(user_id).await {
+		return Err!("Admin users cannot be suspended.");
+	}
+
+	services.users.suspend_account(user_id, admin).await;
+
+	Ok(format!("Suspended {user_id}"))
+}
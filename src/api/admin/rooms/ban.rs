@@ -1,13 +1,21 @@
 use axum::extract::State;
 use conduwuit::{Err, Result, info, utils::ReadyExt, warn};
+use conduwuit_service::Services;
 use futures::{FutureExt, StreamExt};
 use ruma::{
-	OwnedRoomAliasId, continuwuity_admin_api::rooms,
+	OwnedRoomAliasId, OwnedUserId, RoomId, UserId, continuwuity_admin_api::rooms,
 	events::room::message::RoomMessageEventContent,
 };
 
 use crate::{Ruma, client::leave_room};
 
+/// Local users and aliases removed from a banned room.
+pub struct BannedRoom {
+	pub evicted: Vec<OwnedUserId>,
+	pub failed_evicted: Vec<OwnedUserId>,
+	pub aliases: Vec<OwnedRoomAliasId>,
+}
+
 /// # `PUT /_continuwuity/admin/rooms/{roomID}/ban`
 ///
 /// Bans or unbans a room.
@@ -32,52 +40,8 @@ pub(crate) async fn ban_room(
 			.notice(&format!("{sender_user} banned {} (ban in progress)", body.room_id))
 			.await;
 
-		let mut users = services
-			.rooms
-			.state_cache
-			.room_members(&body.room_id)
-			.map(ToOwned::to_owned)
-			.ready_filter(|user| services.globals.user_is_local(user))
-			.boxed();
-		let mut evicted = Vec::new();
-		let mut failed_evicted = Vec::new();
-
-		while let Some(ref user_id) = users.next().await {
-			info!("Evicting user {} from room {}", user_id, body.room_id);
-			match leave_room(&services, user_id, &body.room_id, None)
-				.boxed()
-				.await
-			{
-				| Ok(()) => {
-					services.rooms.state_cache.forget(&body.room_id, user_id);
-					evicted.push(user_id.clone());
-				},
-				| Err(e) => {
-					warn!("Failed to evict user {} from room {}: {}", user_id, body.room_id, e);
-					failed_evicted.push(user_id.clone());
-				},
-			}
-		}
-
-		let aliases: Vec<OwnedRoomAliasId> = services
-			.rooms
-			.alias
-			.local_aliases_for_room(&body.room_id)
-			.map(ToOwned::to_owned)
-			.collect::<Vec<_>>()
-			.await;
-		for alias in &aliases {
-			info!("Removing alias {} for banned room {}", alias, body.room_id);
-			services
-				.rooms
-				.alias
-				.remove_alias(alias, &services.globals.server_user)
-				.await?;
-		}
-
-		services.rooms.directory.set_not_public(&body.room_id); // remove from the room directory
-		services.rooms.metadata.ban_room(&body.room_id, true); // prevent further joins
-		services.rooms.metadata.disable_room(&body.room_id, true); // disable federation
+		let BannedRoom { evicted, failed_evicted, aliases } =
+			evict_and_ban(&services, &body.room_id).await?;
 
 		services
 			.admin
@@ -130,3 +94,90 @@ pub(crate) async fn ban_room(
 		Ok(rooms::ban::v1::Response::new(Vec::new(), Vec::new(), Vec::new()))
 	}
 }
+
+/// Evicts all local users from a room and removes its local aliases, then
+/// bans the room and disables federation with it.
+pub async fn evict_and_ban(services: &Services, room_id: &RoomId) -> Result<BannedRoom> {
+	let (evicted, failed_evicted) =
+		evict_local_members(services, room_id, async |_| Ok(())).await;
+	let aliases = remove_local_aliases(services, room_id).await?;
+	ban_and_disable(services, room_id);
+
+	Ok(BannedRoom { evicted, failed_evicted, aliases })
+}
+
+/// Evicts all local users from a room, returning those who were evicted and
+/// those who could not be. `before_leave` runs for each user first; users it
+/// fails for are left in the room.
+pub async fn evict_local_members<F>(
+	services: &Services,
+	room_id: &RoomId,
+	mut before_leave: F,
+) -> (Vec<OwnedUserId>, Vec<OwnedUserId>)
+where
+	F: AsyncFnMut(&UserId) -> Result,
+{
+	let mut users = services
+		.rooms
+		.state_cache
+		.room_members(room_id)
+		.map(ToOwned::to_owned)
+		.ready_filter(|user| services.globals.user_is_local(user))
+		.boxed();
+	let mut evicted = Vec::new();
+	let mut failed_evicted = Vec::new();
+
+	while let Some(ref user_id) = users.next().await {
+		if let Err(e) = before_leave(user_id).await {
+			warn!("Failed to prepare user {} for eviction from room {}: {}", user_id, room_id, e);
+			failed_evicted.push(user_id.clone());
+			continue;
+		}
+
+		info!("Evicting user {} from room {}", user_id, room_id);
+		match leave_room(services, user_id, room_id, None).boxed().await {
+			| Ok(()) => {
+				services.rooms.state_cache.forget(room_id, user_id);
+				evicted.push(user_id.clone());
+			},
+			| Err(e) => {
+				warn!("Failed to evict user {} from room {}: {}", user_id, room_id, e);
+				failed_evicted.push(user_id.clone());
+			},
+		}
+	}
+
+	(evicted, failed_evicted)
+}
+
+/// Removes all of a room's local aliases, returning them.
+pub async fn remove_local_aliases(
+	services: &Services,
+	room_id: &RoomId,
+) -> Result<Vec<OwnedRoomAliasId>> {
+	let aliases: Vec<OwnedRoomAliasId> = services
+		.rooms
+		.alias
+		.local_aliases_for_room(room_id)
+		.map(ToOwned::to_owned)
+		.collect::<Vec<_>>()
+		.await;
+	for alias in &aliases {
+		info!("Removing alias {} for banned room {}", alias, room_id);
+		services
+			.rooms
+			.alias
+			.remove_alias(alias, &services.globals.server_user)
+			.await?;
+	}
+
+	Ok(aliases)
+}
+
+/// Removes a room from the room directory, bans it and disables federation
+/// with it.
+pub fn ban_and_disable(services: &Services, room_id: &RoomId) {
+	services.rooms.directory.set_not_public(room_id); // remove from the room directory
+	services.rooms.metadata.ban_room(room_id, true); // prevent further joins
+	services.rooms.metadata.disable_room(room_id, true); // disable federation
+}
@@ -1,2 +1,4 @@
 pub mod ban;
 pub mod list;
+pub mod purge;
+pub mod shutdown;
@@ -0,0 +1,158 @@
+use axum::extract::{Path, State};
+use conduwuit::{Err, Result, err, info};
+use conduwuit_service::rooms::purge::HistoryBound;
+use futures::StreamExt;
+use ruma::{CanonicalJsonValue, OwnedEventId, OwnedRoomId};
+
+use crate::Ruma;
+
+/// # `POST /_continuwuity/admin/rooms/{roomID}/purge`
+///
+/// Queues a room to be purged from the database. Refuses rooms which still
+/// have local members unless `force` is set in the request body.
+pub(crate) async fn purge_room_route(
+	State(services): State<crate::State>,
+	Path(room_id): Path<OwnedRoomId>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	if services
+		.admin
+		.get_admin_room()
+		.await
+		.is_ok_and(|admin_room_id| admin_room_id == room_id)
+	{
+		return Err!(Request(Forbidden("Not allowed to purge the admin room")));
+	}
+
+	let force = body
+		.json_body
+		.as_ref()
+		.and_then(|json| match json {
+			| CanonicalJsonValue::Object(object) => object.get("force"),
+			| _ => None,
+		})
+		.is_some_and(|force| matches!(force, CanonicalJsonValue::Bool(true)));
+
+	let local_members = services
+		.rooms
+		.state_cache
+		.local_users_in_room(&room_id)
+		.count()
+		.await;
+
+	if local_members > 0 && !force {
+		return Err!(Request(InvalidParam(
+			"Room still has local members; ban it first or set force to purge it anyway"
+		)));
+	}
+
+	let job = services
+		.rooms
+		.purge
+		.queue(&room_id)
+		.await
+		.map_err(|e| err!(Request(InvalidParam("{e}"))))?;
+
+	info!(%sender_user, "Queued {room_id} for purging");
+	services
+		.admin
+		.notice(&format!("{sender_user} queued {room_id} for purging"))
+		.await;
+
+	Ok(axum::Json(serde_json::json!({
+		"room_id": room_id,
+		"purge": job,
+	})))
+}
+
+/// # `GET /_continuwuity/admin/rooms/{roomID}/purge`
+///
+/// Returns the progress of a queued, running or failed room purge.
+pub(crate) async fn get_purge_status_route(
+	State(services): State<crate::State>,
+	Path(room_id): Path<OwnedRoomId>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let Ok(job) = services.rooms.purge.job(&room_id).await else {
+		return Err!(Request(NotFound("No purge of this room is in progress")));
+	};
+
+	Ok(axum::Json(serde_json::json!({
+		"room_id": room_id,
+		"purge": job,
+	})))
+}
+
+/// # `POST /_continuwuity/admin/rooms/{roomID}/purge_history`
+///
+/// Deletes the part of a room's timeline before `before_event_id` or
+/// `before_ts`, keeping state events and the room's most recent events. Local
+/// media which only the deleted events referred to is deleted too if
+/// `delete_media` is set.
+pub(crate) async fn purge_history_route(
+	State(services): State<crate::State>,
+	Path(room_id): Path<OwnedRoomId>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let Some(CanonicalJsonValue::Object(object)) = body.json_body.as_ref() else {
+		return Err!(Request(BadJson("Expected a JSON object")));
+	};
+
+	let bound = match (object.get("before_event_id"), object.get("before_ts")) {
+		| (Some(CanonicalJsonValue::String(event_id)), None) => {
+			let event_id = OwnedEventId::try_from(event_id.as_str())
+				.map_err(|e| err!(Request(InvalidParam("Invalid before_event_id: {e}"))))?;
+
+			HistoryBound::Event(event_id)
+		},
+		| (None, Some(CanonicalJsonValue::Integer(ts))) => {
+			let ts = u64::try_from(i64::from(*ts))
+				.map_err(|e| err!(Request(InvalidParam("Invalid before_ts: {e}"))))?;
+
+			HistoryBound::Timestamp(ts)
+		},
+		| _ =>
+			return Err!(Request(InvalidParam(
+				"Exactly one of before_event_id or before_ts must be given"
+			))),
+	};
+
+	let delete_media = matches!(object.get("delete_media"), Some(CanonicalJsonValue::Bool(true)));
+
+	let purged = services
+		.rooms
+		.purge
+		.purge_history(&room_id, &bound, delete_media)
+		.await
+		.map_err(|e| err!(Request(InvalidParam("{e}"))))?;
+
+	info!(%sender_user, ?bound, ?purged, "Purged history of {room_id}");
+	services
+		.admin
+		.notice(&format!(
+			"{sender_user} purged {} events and {} local media files from the history of \
+			 {room_id}",
+			purged.events, purged.media
+		))
+		.await;
+
+	Ok(axum::Json(serde_json::json!({
+		"room_id": room_id,
+		"purged": purged,
+	})))
+}
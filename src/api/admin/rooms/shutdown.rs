@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, State};
+use conduwuit::{Err, Result, err, info, matrix::pdu::PduBuilder};
+use futures::FutureExt;
+use ruma::{
+	CanonicalJsonValue, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId,
+	UserId,
+	events::room::{
+		create::RoomCreateEventContent,
+		guest_access::{GuestAccess, RoomGuestAccessEventContent},
+		history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+		join_rules::{JoinRule, RoomJoinRulesEventContent},
+		member::{MembershipState, RoomMemberEventContent},
+		message::RoomMessageEventContent,
+		name::RoomNameEventContent,
+		power_levels::RoomPowerLevelsEventContent,
+	},
+};
+use serde::Serialize;
+use service::Services;
+
+use super::ban::{ban_and_disable, evict_local_members, remove_local_aliases};
+use crate::Ruma;
+
+/// Outcome of shutting down a room.
+#[derive(Clone, Debug, Serialize)]
+pub struct Shutdown {
+	/// The room which members and aliases were moved to.
+	pub new_room_id: OwnedRoomId,
+
+	/// Local users who were moved to the replacement room.
+	pub moved: Vec<OwnedUserId>,
+
+	/// Local users who could not be removed from the room or joined to the
+	/// replacement room.
+	pub failed: Vec<OwnedUserId>,
+
+	/// Local aliases which now point to the replacement room.
+	pub aliases: Vec<OwnedRoomAliasId>,
+}
+
+/// # `POST /_continuwuity/admin/rooms/{roomID}/shutdown`
+///
+/// Shuts down a room: local members and aliases are moved to a new room owned
+/// by `new_room_user_id` (the server user by default) which explains what
+/// happened, and the old room is banned.
+pub(crate) async fn shutdown_room_route(
+	State(services): State<crate::State>,
+	Path(room_id): Path<OwnedRoomId>,
+	body: Ruma<ruma::api::client::device::get_devices::v3::Request>,
+) -> Result<axum::Json<serde_json::Value>> {
+	let sender_user = body.sender_user();
+	if !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	let object = match body.json_body.as_ref() {
+		| Some(CanonicalJsonValue::Object(object)) => Some(object),
+		| _ => None,
+	};
+
+	let get_str = |key: &str| match object.and_then(|object| object.get(key)) {
+		| Some(CanonicalJsonValue::String(value)) => Some(value.as_str()),
+		| _ => None,
+	};
+
+	let owner = get_str("new_room_user_id")
+		.map(UserId::parse)
+		.transpose()
+		.map_err(|e| err!(Request(InvalidParam("Invalid new_room_user_id: {e}"))))?;
+
+	let shutdown = shutdown_room(
+		&services,
+		&room_id,
+		owner.as_deref(),
+		get_str("room_name"),
+		get_str("message"),
+	)
+	.await?;
+
+	info!(%sender_user, ?shutdown, "Shut down {room_id}");
+
+	Ok(axum::Json(serde_json::json!({
+		"room_id": room_id,
+		"shutdown": shutdown,
+	})))
+}
+
+/// Creates a replacement room owned by `owner`, posts `message` (or the
+/// configured `room_shutdown_message`) there and moves the room's local
+/// members and aliases over before banning the room.
+pub async fn shutdown_room(
+	services: &Services,
+	room_id: &RoomId,
+	owner: Option<&UserId>,
+	room_name: Option<&str>,
+	message: Option<&str>,
+) -> Result<Shutdown> {
+	if services
+		.admin
+		.get_admin_room()
+		.await
+		.is_ok_and(|admin_room_id| admin_room_id == room_id)
+	{
+		return Err!(Request(Forbidden("Not allowed to shut down the admin room.")));
+	}
+
+	if services.rooms.metadata.is_banned(room_id).await {
+		return Err!(Request(InvalidParam("{room_id} is already banned.")));
+	}
+
+	let owner = owner.unwrap_or(services.globals.server_user.as_ref());
+	if !services.globals.user_is_local(owner) {
+		return Err!(Request(InvalidParam(
+			"The owner of the replacement room must be a local user."
+		)));
+	}
+
+	if !services.users.exists(owner).await {
+		return Err!(Request(InvalidParam("{owner} does not exist.")));
+	}
+
+	services
+		.admin
+		.notice(&format!("Shutting down {room_id} (shutdown in progress)"))
+		.await;
+
+	let room_name = room_name.unwrap_or("Room shut down");
+	let message = message.unwrap_or(services.config.room_shutdown_message.as_str());
+	let new_room_id = create_replacement_room(services, owner, room_name, message).await?;
+
+	// Join members to the replacement room before evicting them, so that nobody
+	// is left without a room to go to
+	let (moved, failed) = evict_local_members(services, room_id, async |user_id| {
+		if user_id == owner {
+			return Ok(());
+		}
+
+		info!("Moving user {user_id} from room {room_id} to {new_room_id}");
+		force_join(services, user_id, &new_room_id, owner).await
+	})
+	.await;
+
+	let aliases = remove_local_aliases(services, room_id).await?;
+	for alias in &aliases {
+		info!("Moving alias {alias} from room {room_id} to {new_room_id}");
+		services
+			.rooms
+			.alias
+			.set_alias(alias, &new_room_id, &services.globals.server_user)?;
+	}
+
+	ban_and_disable(services, room_id);
+
+	services
+		.admin
+		.notice(&format!(
+			"Finished shutting down {room_id}: Moved {} users ({} failed) and {} aliases to \
+			 {new_room_id}",
+			moved.len(),
+			failed.len(),
+			aliases.len()
+		))
+		.await;
+
+	Ok(Shutdown { new_room_id, moved, failed, aliases })
+}
+
+/// Creates a room which only `owner` can send messages in and posts `message`
+/// there.
+async fn create_replacement_room(
+	services: &Services,
+	owner: &UserId,
+	room_name: &str,
+	message: &str,
+) -> Result<OwnedRoomId> {
+	let room_id = RoomId::new(services.globals.server_name());
+	let room_version = RoomVersionId::V11;
+
+	let _short_id = services
+		.rooms
+		.short
+		.get_or_create_shortroomid(&room_id)
+		.await;
+
+	let state_lock = services.rooms.state.mutex.lock(&room_id).await;
+
+	let events = [
+		PduBuilder::state(String::new(), &RoomCreateEventContent {
+			federate: true,
+			predecessor: None,
+			room_version,
+			..RoomCreateEventContent::new_v11()
+		}),
+		PduBuilder::state(
+			String::from(owner),
+			&RoomMemberEventContent::new(MembershipState::Join),
+		),
+		PduBuilder::state(String::new(), &RoomPowerLevelsEventContent {
+			users: BTreeMap::from_iter([(owner.to_owned(), 100.into())]),
+			events_default: 100.into(),
+			..Default::default()
+		}),
+		PduBuilder::state(String::new(), &RoomJoinRulesEventContent::new(JoinRule::Invite)),
+		PduBuilder::state(
+			String::new(),
+			&RoomHistoryVisibilityEventContent::new(HistoryVisibility::Shared),
+		),
+		PduBuilder::state(
+			String::new(),
+			&RoomGuestAccessEventContent::new(GuestAccess::Forbidden),
+		),
+		PduBuilder::state(String::new(), &RoomNameEventContent::new(room_name.to_owned())),
+		PduBuilder::timeline(&RoomMessageEventContent::text_markdown(message)),
+	];
+
+	for event in events {
+		services
+			.rooms
+			.timeline
+			.build_and_append_pdu(event, owner, Some(&room_id), &state_lock)
+			.boxed()
+			.await?;
+	}
+
+	Ok(room_id)
+}
+
+/// Invites a local user to the replacement room and joins them on their
+/// behalf.
+async fn force_join(
+	services: &Services,
+	user_id: &UserId,
+	room_id: &RoomId,
+	owner: &UserId,
+) -> Result {
+	let state_lock = services.rooms.state.mutex.lock(room_id).await;
+	for (membership, sender) in
+		[(MembershipState::Invite, owner), (MembershipState::Join, user_id)]
+	{
+		services
+			.rooms
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder::state(
+					String::from(user_id),
+					&RoomMemberEventContent::new(membership),
+				),
+				sender,
+				Some(room_id),
+				&state_lock,
+			)
+			.boxed()
+			.await?;
+	}
+
+	Ok(())
+}
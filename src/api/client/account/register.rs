@@ -16,7 +16,7 @@
 	api::client::{
 		account::{
 			register::{self, LoginType},
-			request_registration_token_via_email,
+			request_registration_token_via_email, request_registration_token_via_msisdn,
 		},
 		uiaa::{AuthFlow, AuthType},
 	},
@@ -26,7 +26,10 @@
 use serde_json::value::RawValue;
 use service::mailer::messages;
 
-use super::{DEVICE_ID_LENGTH, TOKEN_LENGTH, join_room_by_id_helper};
+use super::{
+	DEVICE_ID_LENGTH, TOKEN_LENGTH, join_room_by_id_helper,
+	threepid::{msisdn_submit_url, unused_msisdn},
+};
 use crate::Ruma;
 
 const RANDOM_USER_ID_LENGTH: usize = 10;
@@ -125,6 +128,13 @@ pub(crate) async fn register_route(
 	// Appeservices and guests get to skip auth
 	let skip_auth = body.appservice_info.is_some() || is_guest;
 
+	// Reject weak passwords before UIAA, which may use up a registration token
+	if let Some(password) = body.password.as_deref().filter(|_| !skip_auth) {
+		services
+			.users
+			.check_password_policy(body.username.as_deref(), password)?;
+	}
+
 	let identity = if skip_auth {
 		// Appservices and guests have no identity
 		None
@@ -185,6 +195,13 @@ pub(crate) async fn register_route(
 
 	let password = if is_guest { None } else { body.password.as_deref() };
 
+	// The localpart may only be known now that one has been picked
+	if let Some(password) = password.filter(|_| !skip_auth && body.username.is_none()) {
+		services
+			.users
+			.check_password_policy(Some(user_id.localpart()), password)?;
+	}
+
 	// Create user
 	services.users.create(&user_id, password, None).await?;
 
@@ -202,6 +219,8 @@ pub(crate) async fn register_route(
 		.users
 		.set_displayname(&user_id, Some(displayname.clone()));
 
+	services.user_directory.update_local_profile(&user_id).await;
+
 	// Initial account data
 	services
 		.account_data
@@ -224,7 +243,7 @@ pub(crate) async fn register_route(
 			.as_ref()
 			.is_some_and(|aps| aps.registration.device_management);
 
-	let (token, device) = if !no_device {
+	let (token, device, refresh_token, expires_in) = if !no_device {
 		// Don't create a device for inhibited logins
 		let device_id = if is_guest { None } else { body.device_id.clone() }
 			.unwrap_or_else(|| utils::random_string(DEVICE_ID_LENGTH).into());
@@ -244,23 +263,43 @@ pub(crate) async fn register_route(
 			)
 			.await?;
 		debug_info!(%user_id, %device_id, "User account was created");
-		(Some(new_token), Some(device_id))
+
+		let (refresh_token, expires_in) = if body.refresh_token {
+			let (refresh_token, expires_in) = services
+				.users
+				.issue_refresh_token(&user_id, &device_id)
+				.await;
+
+			(Some(refresh_token), Some(expires_in))
+		} else {
+			(None, None)
+		};
+
+		(Some(new_token), Some(device_id), refresh_token, expires_in)
 	} else {
-		(None, None)
+		(None, None, None, None)
 	};
 
-	// If the user registered with an email, associate it with their account.
-	if let Some(identity) = identity
-		&& let Some(email) = identity.email
-	{
-		// This may fail if the email is already in use, but we already check for that
+	// If the user registered with an email or phone number, associate it with
+	// their account.
+	if let Some(identity) = identity {
+		// This may fail if the 3PID is already in use, but we already check for that
 		// in `/requestToken`, so ignoring the error is acceptable here in the rare case
-		// that an email is sniped by another user between the `/requestToken` request
+		// that a 3PID is sniped by another user between the `/requestToken` request
 		// and the `/register` request.
-		let _ = services
-			.threepid
-			.associate_localpart_email(user_id.localpart(), &email)
-			.await;
+		if let Some(email) = identity.email {
+			let _ = services
+				.threepid
+				.associate_localpart_email(user_id.localpart(), &email)
+				.await;
+		}
+
+		if let Some(msisdn) = identity.msisdn {
+			let _ = services
+				.threepid
+				.associate_localpart_msisdn(user_id.localpart(), &msisdn)
+				.await;
+		}
 	}
 
 	let device_display_name = body.initial_device_display_name.as_deref().unwrap_or("");
@@ -398,8 +437,8 @@ pub(crate) async fn register_route(
 		access_token: token,
 		user_id,
 		device_id: device,
-		refresh_token: None,
-		expires_in: None,
+		refresh_token,
+		expires_in,
 	})
 }
 
@@ -440,23 +479,23 @@ async fn create_registration_uiaa_session(
 				token_flow.stages.push(AuthType::EmailIdentity);
 			}
 
+			if let Some(sms) = &services.config.sms
+				&& sms.require_msisdn_for_token_registration
+			{
+				// A phone number is required for token registrations
+				token_flow.stages.push(AuthType::Msisdn);
+			}
+
 			flows.push(token_flow);
 		}
 
 		let mut untrusted_flow = AuthFlow::default();
 
-		if services.config.recaptcha_private_site_key.is_some() {
-			if let Some(pubkey) = &services.config.recaptcha_site_key {
-				// ReCaptcha is configured for untrusted registrations
-				untrusted_flow.stages.push(AuthType::ReCaptcha);
+		if let Some(captcha) = services.uiaa.captcha_params() {
+			// A captcha is configured for untrusted registrations
+			untrusted_flow.stages.push(AuthType::ReCaptcha);
 
-				params.insert(
-					AuthType::ReCaptcha.as_str().to_owned(),
-					serde_json::json!({
-						"public_key": pubkey,
-					}),
-				);
-			}
+			params.insert(AuthType::ReCaptcha.as_str().to_owned(), captcha);
 		}
 
 		if let Some(smtp) = &services.config.smtp
@@ -466,6 +505,13 @@ async fn create_registration_uiaa_session(
 			untrusted_flow.stages.push(AuthType::EmailIdentity);
 		}
 
+		if let Some(sms) = &services.config.sms
+			&& sms.require_msisdn_for_registration
+		{
+			// A phone number is required for untrusted registrations
+			untrusted_flow.stages.push(AuthType::Msisdn);
+		}
+
 		if !untrusted_flow.stages.is_empty() {
 			flows.push(untrusted_flow);
 		}
@@ -631,3 +677,23 @@ pub(crate) async fn request_registration_token_via_email_route(
 
 	Ok(request_registration_token_via_email::v3::Response::new(session))
 }
+
+/// # `POST /_matrix/client/v3/register/msisdn/requestToken`
+///
+/// Requests a validation code by text message for the purpose of registering a
+/// new account.
+pub(crate) async fn request_registration_token_via_msisdn_route(
+	State(services): State<crate::State>,
+	body: Ruma<request_registration_token_via_msisdn::v3::Request>,
+) -> Result<request_registration_token_via_msisdn::v3::Response> {
+	let msisdn = unused_msisdn(&services, &body.country, &body.phone_number).await?;
+	let session = services
+		.threepid
+		.send_validation_sms(&msisdn, &body.client_secret, body.send_attempt.try_into().unwrap())
+		.await?;
+
+	let mut response = request_registration_token_via_msisdn::v3::Response::new(session);
+	response.submit_url = Some(msisdn_submit_url(&services));
+
+	Ok(response)
+}
@@ -1,17 +1,24 @@
 use std::time::SystemTime;
 
-use axum::extract::State;
+use axum::{Json, extract::State};
 use conduwuit::{Err, Result, err};
+use conduwuit_service::Services;
 use lettre::{Address, message::Mailbox};
 use ruma::{
-	MilliSecondsSinceUnixEpoch,
+	MilliSecondsSinceUnixEpoch, OwnedClientSecret, OwnedSessionId,
 	api::client::account::{
 		ThirdPartyIdRemovalStatus, add_3pid, delete_3pid, get_3pids,
 		request_3pid_management_token_via_email, request_3pid_management_token_via_msisdn,
 	},
 	thirdparty::{Medium, ThirdPartyIdentifierInit},
 };
-use service::{mailer::messages, uiaa::Identity};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use service::{
+	mailer::messages,
+	threepid::{Threepid, msisdn},
+	uiaa::Identity,
+};
 
 use crate::Ruma;
 
@@ -23,25 +30,34 @@ pub(crate) async fn third_party_route(
 	body: Ruma<get_3pids::v3::Request>,
 ) -> Result<get_3pids::v3::Response> {
 	let sender_user = body.sender_user();
-	let mut threepids = vec![];
 
-	if let Some(email) = services
+	let email = services
 		.threepid
 		.get_email_for_localpart(sender_user.localpart())
 		.await
-	{
-		threepids.push(
+		.map(Threepid::Email);
+
+	let msisdn = services
+		.threepid
+		.get_msisdn_for_localpart(sender_user.localpart())
+		.await
+		.map(Threepid::Msisdn);
+
+	let threepids = email
+		.into_iter()
+		.chain(msisdn)
+		.map(|threepid| {
 			ThirdPartyIdentifierInit {
-				address: email.to_string(),
-				medium: Medium::Email,
+				address: threepid.to_string(),
+				medium: threepid.medium(),
 				// We don't currently track these, and they aren't used for much
 				validated_at: MilliSecondsSinceUnixEpoch::now(),
 				added_at: MilliSecondsSinceUnixEpoch::from_system_time(SystemTime::UNIX_EPOCH)
 					.unwrap(),
 			}
-			.into(),
-		);
-	}
+			.into()
+		})
+		.collect();
 
 	Ok(get_3pids::v3::Response::new(threepids))
 }
@@ -89,17 +105,85 @@ pub(crate) async fn request_3pid_management_token_via_email_route(
 
 /// # `POST /_matrix/client/v3/account/3pid/msisdn/requestToken`
 ///
-/// "This API should be used to request validation tokens when adding an email
-/// address to an account"
-///
-/// - 403 signals that The homeserver does not allow the third party identifier
-///   as a contact option.
+/// Requests a validation code by text message for the purpose of changing an
+/// account's phone number.
 pub(crate) async fn request_3pid_management_token_via_msisdn_route(
-	_body: Ruma<request_3pid_management_token_via_msisdn::v3::Request>,
+	State(services): State<crate::State>,
+	body: Ruma<request_3pid_management_token_via_msisdn::v3::Request>,
 ) -> Result<request_3pid_management_token_via_msisdn::v3::Response> {
-	Err!(Request(ThreepidMediumNotSupported(
-		"MSISDN third-party identifiers are not supported."
-	)))
+	if !services.threepid.msisdn_requirement().may_change() {
+		return Err!(Request(ThreepidMediumNotSupported(
+			"You may not change your phone number."
+		)));
+	}
+
+	let msisdn = unused_msisdn(&services, &body.country, &body.phone_number).await?;
+	let session = services
+		.threepid
+		.send_validation_sms(&msisdn, &body.client_secret, body.send_attempt.try_into().unwrap())
+		.await?;
+
+	let mut response = request_3pid_management_token_via_msisdn::v3::Response::new(session);
+	response.submit_url = Some(msisdn_submit_url(&services));
+
+	Ok(response)
+}
+
+/// Converts a phone number from a `requestToken` request to an MSISDN which
+/// isn't already in use.
+pub(crate) async fn unused_msisdn(
+	services: &Services,
+	country: &str,
+	phone_number: &str,
+) -> Result<String> {
+	let Some(msisdn) = msisdn::to_msisdn(country, phone_number) else {
+		return Err!(Request(InvalidParam("Invalid phone number.")));
+	};
+
+	if services
+		.threepid
+		.get_localpart_for_msisdn(&msisdn)
+		.await
+		.is_some()
+	{
+		return Err!(Request(ThreepidInUse("This phone number is already in use.")));
+	}
+
+	Ok(msisdn)
+}
+
+pub(crate) const MSISDN_SUBMIT_PATH: &str =
+	"/_matrix/client/unstable/add_threepid/msisdn/submit_token";
+
+/// The URL the code sent to a phone number is submitted to.
+pub(crate) fn msisdn_submit_url(services: &Services) -> String {
+	let mut url = services.config.get_client_domain();
+	url.set_path(MSISDN_SUBMIT_PATH);
+	url.to_string()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SubmitTokenBody {
+	sid: OwnedSessionId,
+	client_secret: OwnedClientSecret,
+	token: String,
+}
+
+/// # `POST /_matrix/client/unstable/add_threepid/msisdn/submit_token`
+///
+/// Validates a phone number with the code sent to it, as the `submit_url`
+/// returned by `requestToken`.
+pub(crate) async fn submit_msisdn_token_route(
+	State(services): State<crate::State>,
+	Json(body): Json<SubmitTokenBody>,
+) -> Result<Json<Value>> {
+	services
+		.threepid
+		.submit_validation_token(&body.sid, &body.client_secret, &body.token)
+		.await
+		.map_err(|message| err!(Request(ThreepidAuthFailed("{message}"))))?;
+
+	Ok(Json(json!({ "success": true })))
 }
 
 /// # `POST /_matrix/client/v3/account/3pid/add`
@@ -109,26 +193,46 @@ pub(crate) async fn add_3pid_route(
 ) -> Result<add_3pid::v3::Response> {
 	let sender_user = body.sender_user();
 
-	if !services.threepid.email_requirement().may_change() {
-		return Err!(Request(Forbidden("You may not change your email address.")));
+	if !services.threepid.email_requirement().may_change()
+		&& !services.threepid.msisdn_requirement().may_change()
+	{
+		return Err!(Request(Forbidden("You may not change your third-party identifiers.")));
 	}
 
-	// Require password auth to add an email
+	// Require password auth to add a 3PID
 	let _ = services
 		.uiaa
 		.authenticate_password(&body.auth, Some(Identity::from_user_id(sender_user)))
 		.await?;
 
-	let email = services
+	let threepid = services
 		.threepid
 		.consume_valid_session(&body.sid, &body.client_secret)
 		.await
 		.map_err(|message| err!(Request(ThreepidAuthFailed("{message}"))))?;
 
-	services
-		.threepid
-		.associate_localpart_email(sender_user.localpart(), &email)
-		.await?;
+	match threepid {
+		| Threepid::Email(email) => {
+			if !services.threepid.email_requirement().may_change() {
+				return Err!(Request(Forbidden("You may not change your email address.")));
+			}
+
+			services
+				.threepid
+				.associate_localpart_email(sender_user.localpart(), &email)
+				.await?;
+		},
+		| Threepid::Msisdn(msisdn) => {
+			if !services.threepid.msisdn_requirement().may_change() {
+				return Err!(Request(Forbidden("You may not change your phone number.")));
+			}
+
+			services
+				.threepid
+				.associate_localpart_msisdn(sender_user.localpart(), &msisdn)
+				.await?;
+		},
+	}
 
 	Ok(add_3pid::v3::Response::new())
 }
@@ -140,23 +244,47 @@ pub(crate) async fn delete_3pid_route(
 ) -> Result<delete_3pid::v3::Response> {
 	let sender_user = body.sender_user();
 
-	if body.medium != Medium::Email {
-		return Ok(delete_3pid::v3::Response {
-			id_server_unbind_result: ThirdPartyIdRemovalStatus::NoSupport,
-		});
-	}
+	match body.medium {
+		| Medium::Email => {
+			if !services.threepid.email_requirement().may_remove() {
+				return Err!(Request(Forbidden("You may not remove your email address.")));
+			}
 
-	if !services.threepid.email_requirement().may_remove() {
-		return Err!(Request(Forbidden("You may not remove your email address.")));
-	}
+			if services
+				.threepid
+				.disassociate_localpart_email(sender_user.localpart())
+				.await
+				.is_none()
+			{
+				return Err!(Request(ThreepidNotFound("Your account has no associated email.")));
+			}
+		},
+		| Medium::Msisdn => {
+			if !services.threepid.msisdn_requirement().may_remove() {
+				return Err!(Request(Forbidden("You may not remove your phone number.")));
+			}
 
-	if services
-		.threepid
-		.disassociate_localpart_email(sender_user.localpart())
-		.await
-		.is_none()
-	{
-		return Err!(Request(ThreepidNotFound("Your account has no associated email.")));
+			if services
+				.threepid
+				.get_msisdn_for_localpart(sender_user.localpart())
+				.await
+				.is_none_or(|msisdn| msisdn::normalize_msisdn(&body.address) != Some(msisdn))
+			{
+				return Err!(Request(ThreepidNotFound(
+					"This phone number is not associated with your account."
+				)));
+			}
+
+			services
+				.threepid
+				.disassociate_localpart_msisdn(sender_user.localpart())
+				.await;
+		},
+		| _ => {
+			return Ok(delete_3pid::v3::Response {
+				id_server_unbind_result: ThirdPartyIdRemovalStatus::NoSupport,
+			});
+		},
 	}
 
 	Ok(delete_3pid::v3::Response {
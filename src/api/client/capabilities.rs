@@ -30,9 +30,10 @@ pub(crate) async fn get_capabilities_route(
 		default: services.server.config.default_room_version.clone(),
 	};
 
-	// Only allow 3pid changes if SMTP is configured
+	// Only allow 3pid changes if SMTP or SMS is configured
 	capabilities.thirdparty_id_changes = ThirdPartyIdChangesCapability {
-		enabled: services.threepid.email_requirement().may_change(),
+		enabled: services.threepid.email_requirement().may_change()
+			|| services.threepid.msisdn_requirement().may_change(),
 	};
 
 	capabilities.get_login_token = GetLoginTokenCapability {
@@ -42,6 +43,26 @@ pub(crate) async fn get_capabilities_route(
 	// MSC4133 capability
 	capabilities.set("uk.tcpip.msc4133.profile_fields", json!({"enabled": true}))?;
 
+	capabilities.set(
+		"org.matrix.msc4108.rendezvous",
+		json!({"enabled": services.config.rendezvous.enable}),
+	)?;
+
+	// MSC2000 password policy
+	let password_policy = &services.config.password_policy;
+	if password_policy.enable {
+		capabilities.set(
+			"m.password_policy",
+			json!({
+				"m.minimum_length": password_policy.minimum_length,
+				"m.require_digit": password_policy.require_digit,
+				"m.require_symbol": password_policy.require_symbol,
+				"m.require_lowercase": password_policy.require_lowercase,
+				"m.require_uppercase": password_policy.require_uppercase,
+			}),
+		)?;
+	}
+
 	capabilities.set(
 		"org.matrix.msc4267.forget_forced_upon_leave",
 		json!({"enabled": services.config.forget_forced_upon_leave}),
@@ -203,6 +203,13 @@ pub(crate) async fn upload_signing_keys_route(
 			);
 			// Some of the keys weren't found, so we let them upload
 		},
+		| _ if services.sso.is_delegated() => {
+			// There is no password to ask for; resets are approved by the
+			// authorization server instead
+			return Err!(Request(Forbidden(
+				"Cross-signing keys can only be reset through the authorization server."
+			)));
+		},
 		| _ => {
 			let _ = services
 				.uiaa
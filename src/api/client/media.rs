@@ -1,35 +1,48 @@
 use std::time::Duration;
 
-use axum::extract::State;
+use axum::{body::Body, extract::State, response::Response};
 use axum_client_ip::ClientIp;
+use bytes::Bytes;
 use conduwuit::{
 	Err, Result, debug_warn, err, error,
-	utils::{self, content_disposition::make_content_disposition, math::ruma_from_usize},
+	utils::{self, content_disposition::make_content_disposition},
 };
 use conduwuit_service::{
 	Services,
-	media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta, MXC_LENGTH},
+	media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta, FileStream, MXC_LENGTH},
 };
+use futures::{Stream, TryStreamExt};
+use http::{
+	HeaderMap, StatusCode,
+	header::{
+		ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+		CONTENT_TYPE, RANGE,
+	},
+};
+use http_body_util::LengthLimitError;
 use ruma::{
-	Mxc, UserId,
+	MilliSecondsSinceUnixEpoch, Mxc, UInt, UserId,
 	api::client::{
 		authenticated_media::{
 			get_content, get_content_as_filename, get_content_thumbnail, get_media_config,
 			get_media_preview,
 		},
-		media::create_content,
+		error::ErrorKind,
+		media::{create_content, create_content_async, create_mxc_uri},
 	},
 };
 
-use crate::Ruma;
+use crate::{Ruma, RumaStreamed};
 
 /// # `GET /_matrix/client/v1/media/config`
 pub(crate) async fn get_media_config_route(
 	State(services): State<crate::State>,
-	_body: Ruma<get_media_config::v1::Request>,
+	body: Ruma<get_media_config::v1::Request>,
 ) -> Result<get_media_config::v1::Response> {
+	let upload_size = services.media.max_upload_size(body.sender_user()).await;
+
 	Ok(get_media_config::v1::Response {
-		upload_size: ruma_from_usize(services.server.config.max_request_size),
+		upload_size: UInt::new_saturating(upload_size),
 	})
 }
 
@@ -38,7 +51,7 @@ pub(crate) async fn get_media_config_route(
 /// Permanently save media in the server.
 ///
 /// - Some metadata will be saved in the database
-/// - Media will be saved in the media/ directory
+/// - Media will be streamed to storage as it is received
 #[tracing::instrument(
 	name = "media_upload",
 	level = "debug",
@@ -48,8 +61,10 @@ pub(crate) async fn get_media_config_route(
 pub(crate) async fn create_content_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
-	body: Ruma<create_content::v3::Request>,
+	headers: HeaderMap,
+	body: RumaStreamed<create_content::v3::Request>,
 ) -> Result<create_content::v3::Response> {
+	let RumaStreamed { args: body, stream } = body;
 	let user = body.sender_user();
 	if services.users.is_suspended(user).await? {
 		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
@@ -63,29 +78,133 @@ pub(crate) async fn create_content_route(
 		media_id: &utils::random_string(MXC_LENGTH),
 	};
 
-	if let Err(e) = services
+	let stored = match services
 		.media
-		.create(mxc, Some(user), Some(&content_disposition), content_type, &body.file)
+		.create_stream(
+			mxc,
+			Some(user),
+			Some(&content_disposition),
+			content_type,
+			upload_stream(stream),
+			content_length(&headers),
+		)
 		.await
 	{
-		err!("Failed to save uploaded media: {e}");
-		return Err!(Request(Unknown("Failed to save uploaded media")));
-	}
+		| Ok(stored) => stored,
+		| Err(e)
+			if matches!(
+				e.kind(),
+				ErrorKind::TooLarge | ErrorKind::ResourceLimitExceeded { .. }
+			) =>
+			return Err(e),
+		| Err(e) => {
+			err!("Failed to save uploaded media: {e}");
+			return Err!(Request(Unknown("Failed to save uploaded media")));
+		},
+	};
 
-	let blurhash = body.generate_blurhash.then(|| {
+	// Blurhashing needs the whole image, so it is read back for small enough files
+	let blurhash = if body.generate_blurhash
+		&& stored.size <= services.server.config.blurhashing.blurhash_max_raw_size
+	{
 		services
 			.media
-			.create_blurhash(&body.file, content_type, filename)
-			.ok()
-			.flatten()
-	});
+			.get(mxc)
+			.await?
+			.and_then(|file| file.content)
+			.and_then(|file| {
+				services
+					.media
+					.create_blurhash(&file, content_type, filename)
+					.ok()
+					.flatten()
+			})
+	} else {
+		None
+	};
 
 	Ok(create_content::v3::Response {
 		content_uri: mxc.to_string().into(),
-		blurhash: blurhash.flatten(),
+		blurhash,
+	})
+}
+
+/// # `POST /_matrix/media/v1/create`
+///
+/// Reserve an MXC for media to be uploaded to later with
+/// `PUT /_matrix/media/v3/upload/{serverName}/{mediaId}`.
+#[tracing::instrument(
+	name = "media_create_mxc",
+	level = "debug",
+	skip_all,
+	fields(%client),
+)]
+pub(crate) async fn create_mxc_uri_route(
+	State(services): State<crate::State>,
+	ClientIp(client): ClientIp,
+	body: Ruma<create_mxc_uri::v1::Request>,
+) -> Result<create_mxc_uri::v1::Response> {
+	let user = body.sender_user();
+	if services.users.is_suspended(user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	let (content_uri, expires_at) = services.media.create_pending(user).await?;
+
+	Ok(create_mxc_uri::v1::Response {
+		content_uri,
+		unused_expires_at: UInt::new(expires_at).map(MilliSecondsSinceUnixEpoch),
 	})
 }
 
+/// # `PUT /_matrix/media/v3/upload/{serverName}/{mediaId}`
+///
+/// Upload the content of an MXC reserved with `POST /_matrix/media/v1/create`.
+#[tracing::instrument(
+	name = "media_upload_async",
+	level = "debug",
+	skip_all,
+	fields(%client),
+)]
+pub(crate) async fn create_content_async_route(
+	State(services): State<crate::State>,
+	ClientIp(client): ClientIp,
+	headers: HeaderMap,
+	body: RumaStreamed<create_content_async::v3::Request>,
+) -> Result<create_content_async::v3::Response> {
+	let RumaStreamed { args: body, stream } = body;
+	let user = body.sender_user();
+	if services.users.is_suspended(user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	if !services.globals.server_is_ours(&body.server_name) {
+		return Err!(Request(NotFound("Unknown media ID.")));
+	}
+
+	let filename = body.filename.as_deref();
+	let content_type = body.content_type.as_deref();
+	let content_disposition = make_content_disposition(None, content_type, filename);
+	let mxc = Mxc {
+		server_name: &body.server_name,
+		media_id: &body.media_id,
+	};
+
+	services
+		.media
+		.upload_pending(
+			&mxc,
+			user,
+			Some(&content_disposition),
+			content_type,
+			upload_stream(stream),
+			content_length(&headers),
+		)
+		.await?;
+
+	Ok(create_content_async::v3::Response {})
+}
+
 /// # `GET /_matrix/client/v1/media/thumbnail/{serverName}/{mediaId}`
 ///
 /// Load media thumbnail from our server or over federation.
@@ -122,6 +241,7 @@ pub(crate) async fn get_content_thumbnail_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching file."))),
 		},
+		| Err(e) if matches!(e.kind(), ErrorKind::NotYetUploaded) => return Err(e),
 		| Err(e) => {
 			debug_warn!(%mxc, "Fetching thumbnail failed: {e:?}");
 			return Err!(Request(NotFound("Media not found.")));
@@ -153,8 +273,9 @@ pub(crate) async fn get_content_thumbnail_route(
 pub(crate) async fn get_content_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content::v1::Request>,
-) -> Result<get_content::v1::Response> {
+) -> Result<Response> {
 	let user = body.sender_user();
 
 	let mxc = Mxc {
@@ -162,11 +283,9 @@ pub(crate) async fn get_content_route(
 		media_id: &body.media_id,
 	};
 
-	let FileMeta {
-		content,
-		content_type,
-		content_disposition,
-	} = match fetch_file(&services, &mxc, Some(user), body.timeout_ms, None).await {
+	let range = headers.get(RANGE).and_then(|range| range.to_str().ok());
+	let file = match fetch_file_stream(&services, &mxc, Some(user), body.timeout_ms, range).await
+	{
 		| Ok(meta) => meta,
 		| Err(conduwuit::Error::Io(e)) => match e.kind() {
 			| std::io::ErrorKind::NotFound => return Err!(Request(NotFound("Media not found."))),
@@ -176,23 +295,14 @@ pub(crate) async fn get_content_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching file."))),
 		},
+		| Err(e) if matches!(e.kind(), ErrorKind::NotYetUploaded) => return Err(e),
 		| Err(e) => {
 			debug_warn!(%mxc, "Fetching media failed: {e:?}");
 			return Err!(Request(NotFound("Media not found.")));
 		},
 	};
 
-	let Some(file) = content else {
-		return Err!(Request(NotFound("Media not found.")));
-	};
-
-	Ok(get_content::v1::Response {
-		file,
-		content_type: content_type.map(Into::into),
-		cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-		cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-		content_disposition,
-	})
+	file_response(file, None)
 }
 
 /// # `GET /_matrix/client/v1/media/download/{serverName}/{mediaId}/{fileName}`
@@ -207,8 +317,9 @@ pub(crate) async fn get_content_route(
 pub(crate) async fn get_content_as_filename_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_as_filename::v1::Request>,
-) -> Result<get_content_as_filename::v1::Response> {
+) -> Result<Response> {
 	let user = body.sender_user();
 
 	let mxc = Mxc {
@@ -218,11 +329,9 @@ pub(crate) async fn get_content_as_filename_route(
 
 	let filename = (!body.filename.is_empty()).then_some(body.filename.as_str());
 
-	let FileMeta {
-		content,
-		content_type,
-		content_disposition,
-	} = match fetch_file(&services, &mxc, Some(user), body.timeout_ms, filename).await {
+	let range = headers.get(RANGE).and_then(|range| range.to_str().ok());
+	let file = match fetch_file_stream(&services, &mxc, Some(user), body.timeout_ms, range).await
+	{
 		| Ok(meta) => meta,
 		| Err(conduwuit::Error::Io(e)) => match e.kind() {
 			| std::io::ErrorKind::NotFound => return Err!(Request(NotFound("Media not found."))),
@@ -232,23 +341,14 @@ pub(crate) async fn get_content_as_filename_route(
 			},
 			| _ => return Err!(Request(Unknown("Unknown error when fetching file."))),
 		},
+		| Err(e) if matches!(e.kind(), ErrorKind::NotYetUploaded) => return Err(e),
 		| Err(e) => {
 			debug_warn!(%mxc, "Fetching media failed: {e:?}");
 			return Err!(Request(NotFound("Media not found.")));
 		},
 	};
 
-	let Some(file) = content else {
-		return Err!(Request(NotFound("Media not found.")));
-	};
-
-	Ok(get_content_as_filename::v1::Response {
-		file,
-		content_type: content_type.map(Into::into),
-		cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-		cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-		content_disposition,
-	})
+	file_response(file, filename)
 }
 
 /// # `GET /_matrix/client/v1/media/preview_url`
@@ -325,32 +425,6 @@ async fn fetch_thumbnail(
 	})
 }
 
-async fn fetch_file(
-	services: &Services,
-	mxc: &Mxc<'_>,
-	user: Option<&UserId>,
-	timeout_ms: Duration,
-	filename: Option<&str>,
-) -> Result<FileMeta> {
-	let FileMeta {
-		content,
-		content_type,
-		content_disposition,
-	} = fetch_file_meta(services, mxc, user, timeout_ms).await?;
-
-	let content_disposition = Some(make_content_disposition(
-		content_disposition.as_ref(),
-		content_type.as_deref(),
-		filename,
-	));
-
-	Ok(FileMeta {
-		content,
-		content_type,
-		content_disposition,
-	})
-}
-
 async fn fetch_thumbnail_meta(
 	services: &Services,
 	mxc: &Mxc<'_>,
@@ -363,7 +437,13 @@ async fn fetch_thumbnail_meta(
 	}
 
 	if services.globals.server_is_ours(mxc.server_name) {
-		return Err!(Request(NotFound("Local thumbnail not found.")));
+		services.media.wait_for_upload(mxc, timeout_ms).await?;
+
+		return services
+			.media
+			.get_thumbnail(mxc, dim)
+			.await?
+			.ok_or_else(|| err!(Request(NotFound("Local thumbnail not found."))));
 	}
 
 	services
@@ -372,22 +452,93 @@ async fn fetch_thumbnail_meta(
 		.await
 }
 
-async fn fetch_file_meta(
+async fn fetch_file_stream(
 	services: &Services,
 	mxc: &Mxc<'_>,
 	user: Option<&UserId>,
 	timeout_ms: Duration,
-) -> Result<FileMeta> {
-	if let Some(filemeta) = services.media.get(mxc).await? {
-		return Ok(filemeta);
+	range: Option<&str>,
+) -> Result<FileStream> {
+	if let Some(file) = services.media.get_stream(mxc, range).await? {
+		return Ok(file);
 	}
 
 	if services.globals.server_is_ours(mxc.server_name) {
-		return Err!(Request(NotFound("Local media not found.")));
+		// Media reserved with `/create` may still be being uploaded
+		services.media.wait_for_upload(mxc, timeout_ms).await?;
+
+		return services
+			.media
+			.get_stream(mxc, range)
+			.await?
+			.ok_or_else(|| err!(Request(NotFound("Local media not found."))));
 	}
 
+	// Remote media is stored as it is fetched, then streamed from storage
 	services
 		.media
 		.fetch_remote_content(mxc, user, None, timeout_ms)
-		.await
+		.await?;
+
+	services
+		.media
+		.get_stream(mxc, range)
+		.await?
+		.ok_or_else(|| err!(Request(NotFound("Media not found."))))
+}
+
+/// Size of an upload, if the client said.
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+	headers
+		.get(CONTENT_LENGTH)
+		.and_then(|length| length.to_str().ok())
+		.and_then(|length| length.parse().ok())
+}
+
+/// Content of an upload, read as it arrives.
+fn upload_stream(body: Body) -> impl Stream<Item = Result<Bytes>> + Send {
+	body.into_data_stream().map_err(|e| match e.into_inner() {
+		| e if e.is::<LengthLimitError>() =>
+			err!(Request(TooLarge("File is larger than the maximum upload size."))),
+		| e => err!(Request(Unknown("Failed to read uploaded media: {e}"))),
+	})
+}
+
+/// Response streaming `file`, or the part of it requested with `Range`.
+pub(super) fn file_response(file: FileStream, filename: Option<&str>) -> Result<Response> {
+	let FileStream {
+		content,
+		size,
+		range,
+		content_type,
+		content_disposition,
+	} = file;
+
+	let content_disposition =
+		make_content_disposition(content_disposition.as_ref(), content_type.as_deref(), filename);
+
+	let mut response = Response::builder()
+		.header(CONTENT_DISPOSITION, content_disposition.to_string())
+		.header(ACCEPT_RANGES, "bytes")
+		.header(CACHE_CONTROL, CACHE_CONTROL_IMMUTABLE)
+		.header("cross-origin-resource-policy", CORP_CROSS_ORIGIN);
+
+	if let Some(content_type) = content_type {
+		response = response.header(CONTENT_TYPE, content_type);
+	}
+
+	response = match range {
+		| Some(range) => response
+			.status(StatusCode::PARTIAL_CONTENT)
+			.header(CONTENT_LENGTH, range.end.saturating_sub(range.start))
+			.header(
+				CONTENT_RANGE,
+				format!("bytes {}-{}/{size}", range.start, range.end.saturating_sub(1)),
+			),
+		| None => response.status(StatusCode::OK).header(CONTENT_LENGTH, size),
+	};
+
+	let body = Body::from_stream(content.map_err(|e| std::io::Error::other(e.to_string())));
+
+	Ok(response.body(body)?)
 }
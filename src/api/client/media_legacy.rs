@@ -1,31 +1,39 @@
 #![allow(deprecated)]
 
-use axum::extract::State;
+use std::time::Duration;
+
+use axum::{extract::State, response::Response};
 use axum_client_ip::ClientIp;
 use conduwuit::{
-	Err, Result, debug_info, err,
-	utils::{content_disposition::make_content_disposition, math::ruma_from_usize},
+	Err, Result, debug_info, err, utils::content_disposition::make_content_disposition,
+};
+use conduwuit_service::{
+	Services,
+	media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta, FileStream},
 };
-use conduwuit_service::media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta};
+use http::{HeaderMap, header::RANGE};
 use ruma::{
-	Mxc,
+	Mxc, UInt,
 	api::client::media::{
 		create_content, get_content, get_content_as_filename, get_content_thumbnail,
 		get_media_config, get_media_preview,
 	},
 };
 
-use crate::{Ruma, RumaResponse, client::create_content_route};
+use super::media::file_response;
+use crate::{Ruma, RumaResponse, RumaStreamed, client::create_content_route};
 
 /// # `GET /_matrix/media/v3/config`
 ///
 /// Returns max upload size.
 pub(crate) async fn get_media_config_legacy_route(
 	State(services): State<crate::State>,
-	_body: Ruma<get_media_config::v3::Request>,
+	body: Ruma<get_media_config::v3::Request>,
 ) -> Result<get_media_config::v3::Response> {
+	let upload_size = services.media.max_upload_size(body.sender_user()).await;
+
 	Ok(get_media_config::v3::Response {
-		upload_size: ruma_from_usize(services.server.config.max_request_size),
+		upload_size: UInt::new_saturating(upload_size),
 	})
 }
 
@@ -114,9 +122,10 @@ pub(crate) async fn get_media_preview_legacy_legacy_route(
 pub(crate) async fn create_content_legacy_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
-	body: Ruma<create_content::v3::Request>,
+	headers: HeaderMap,
+	body: RumaStreamed<create_content::v3::Request>,
 ) -> Result<RumaResponse<create_content::v3::Response>> {
-	create_content_route(State(services), ClientIp(client), body)
+	create_content_route(State(services), ClientIp(client), headers, body)
 		.await
 		.map(RumaResponse)
 }
@@ -133,70 +142,20 @@ pub(crate) async fn create_content_legacy_route(
 pub(crate) async fn get_content_legacy_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content::v3::Request>,
-) -> Result<get_content::v3::Response> {
+) -> Result<Response> {
 	let mxc = Mxc {
 		server_name: &body.server_name,
 		media_id: &body.media_id,
 	};
 
-	match services.media.get(&mxc).await? {
-		| Some(FileMeta {
-			content,
-			content_type,
-			content_disposition,
-		}) => {
-			let content_disposition = make_content_disposition(
-				content_disposition.as_ref(),
-				content_type.as_deref(),
-				None,
-			);
-
-			Ok(get_content::v3::Response {
-				file: content.expect("entire file contents"),
-				content_type: content_type.map(Into::into),
-				content_disposition: Some(content_disposition),
-				cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-				cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-			})
-		},
-		| _ =>
-			if !services.globals.server_is_ours(&body.server_name) && body.allow_remote {
-				debug_info!(%mxc, "Fetching remote media via authenticated federation fallback");
-				services.media.check_legacy_freeze()?;
-				let FileMeta {
-					content,
-					content_type,
-					content_disposition,
-				} = services
-					.media
-					.fetch_remote_content(&mxc, None, None, body.timeout_ms)
-					.await
-					.map_err(|e| {
-						err!(Request(NotFound(debug_warn!(%mxc, "Fetching media failed: {e:?}"))))
-					})?;
-
-				let content_disposition = make_content_disposition(
-					content_disposition.as_ref(),
-					content_type.as_deref(),
-					None,
-				);
+	let range = headers.get(RANGE).and_then(|range| range.to_str().ok());
+	let file =
+		fetch_file_stream_legacy(&services, &mxc, body.allow_remote, body.timeout_ms, range)
+			.await?;
 
-				let Some(file) = content else {
-					return Err!(Request(NotFound("Media not found.")));
-				};
-
-				Ok(get_content::v3::Response {
-					file,
-					content_type: content_type.map(Into::into),
-					content_disposition: Some(content_disposition),
-					cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-					cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-				})
-			} else {
-				Err!(Request(NotFound("Media not found.")))
-			},
-	}
+	file_response(file, None)
 }
 
 /// # `GET /_matrix/media/v1/download/{serverName}/{mediaId}`
@@ -215,11 +174,10 @@ pub(crate) async fn get_content_legacy_route(
 pub(crate) async fn get_content_legacy_legacy_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content::v3::Request>,
-) -> Result<RumaResponse<get_content::v3::Response>> {
-	get_content_legacy_route(State(services), ClientIp(client), body)
-		.await
-		.map(RumaResponse)
+) -> Result<Response> {
+	get_content_legacy_route(State(services), ClientIp(client), headers, body).await
 }
 
 /// # `GET /_matrix/media/v3/download/{serverName}/{mediaId}/{fileName}`
@@ -234,70 +192,20 @@ pub(crate) async fn get_content_legacy_legacy_route(
 pub(crate) async fn get_content_as_filename_legacy_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_as_filename::v3::Request>,
-) -> Result<get_content_as_filename::v3::Response> {
+) -> Result<Response> {
 	let mxc = Mxc {
 		server_name: &body.server_name,
 		media_id: &body.media_id,
 	};
 
-	match services.media.get(&mxc).await? {
-		| Some(FileMeta {
-			content,
-			content_type,
-			content_disposition,
-		}) => {
-			let content_disposition = make_content_disposition(
-				content_disposition.as_ref(),
-				content_type.as_deref(),
-				Some(&body.filename),
-			);
-
-			Ok(get_content_as_filename::v3::Response {
-				file: content.expect("entire file contents"),
-				content_type: content_type.map(Into::into),
-				content_disposition: Some(content_disposition),
-				cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-				cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-			})
-		},
-		| _ =>
-			if !services.globals.server_is_ours(&body.server_name) && body.allow_remote {
-				debug_info!(%mxc, "Fetching remote media via authenticated federation fallback");
-				services.media.check_legacy_freeze()?;
-				let FileMeta {
-					content,
-					content_type,
-					content_disposition,
-				} = services
-					.media
-					.fetch_remote_content(&mxc, None, None, body.timeout_ms)
-					.await
-					.map_err(|e| {
-						err!(Request(NotFound(debug_warn!(%mxc, "Fetching media failed: {e:?}"))))
-					})?;
+	let range = headers.get(RANGE).and_then(|range| range.to_str().ok());
+	let file =
+		fetch_file_stream_legacy(&services, &mxc, body.allow_remote, body.timeout_ms, range)
+			.await?;
 
-				let content_disposition = make_content_disposition(
-					content_disposition.as_ref(),
-					content_type.as_deref(),
-					Some(&body.filename),
-				);
-
-				let Some(file) = content else {
-					return Err!(Request(NotFound("Media not found.")));
-				};
-
-				Ok(get_content_as_filename::v3::Response {
-					file,
-					content_type: content_type.map(Into::into),
-					content_disposition: Some(content_disposition),
-					cross_origin_resource_policy: Some(CORP_CROSS_ORIGIN.into()),
-					cache_control: Some(CACHE_CONTROL_IMMUTABLE.into()),
-				})
-			} else {
-				Err!(Request(NotFound("Media not found.")))
-			},
-	}
+	file_response(file, Some(body.filename.as_str()))
 }
 
 /// # `GET /_matrix/media/v1/download/{serverName}/{mediaId}/{fileName}`
@@ -315,11 +223,10 @@ pub(crate) async fn get_content_as_filename_legacy_route(
 pub(crate) async fn get_content_as_filename_legacy_legacy_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_as_filename::v3::Request>,
-) -> Result<RumaResponse<get_content_as_filename::v3::Response>> {
-	get_content_as_filename_legacy_route(State(services), ClientIp(client), body)
-		.await
-		.map(RumaResponse)
+) -> Result<Response> {
+	get_content_as_filename_legacy_route(State(services), ClientIp(client), headers, body).await
 }
 
 /// # `GET /_matrix/media/v3/thumbnail/{serverName}/{mediaId}`
@@ -422,3 +329,35 @@ pub(crate) async fn get_content_thumbnail_legacy_legacy_route(
 		.await
 		.map(RumaResponse)
 }
+
+async fn fetch_file_stream_legacy(
+	services: &Services,
+	mxc: &Mxc<'_>,
+	allow_remote: bool,
+	timeout_ms: Duration,
+	range: Option<&str>,
+) -> Result<FileStream> {
+	if let Some(file) = services.media.get_stream(mxc, range).await? {
+		return Ok(file);
+	}
+
+	if services.globals.server_is_ours(mxc.server_name) || !allow_remote {
+		return Err!(Request(NotFound("Media not found.")));
+	}
+
+	debug_info!(%mxc, "Fetching remote media via authenticated federation fallback");
+	services.media.check_legacy_freeze()?;
+	services
+		.media
+		.fetch_remote_content(mxc, None, None, timeout_ms)
+		.await
+		.map_err(|e| {
+			err!(Request(NotFound(debug_warn!(%mxc, "Fetching media failed: {e:?}"))))
+		})?;
+
+	services
+		.media
+		.get_stream(mxc, range)
+		.await?
+		.ok_or_else(|| err!(Request(NotFound("Media not found."))))
+}
@@ -23,12 +23,14 @@
 pub(super) mod read_marker;
 pub(super) mod redact;
 pub(super) mod relations;
+pub(super) mod rendezvous;
 pub(super) mod report;
 pub(super) mod room;
 pub(super) mod search;
 pub(super) mod send;
 pub(super) mod session;
 pub(super) mod space;
+pub(super) mod sso;
 pub(super) mod state;
 pub(super) mod sync;
 pub(super) mod tag;
@@ -71,12 +73,14 @@
 pub(super) use read_marker::*;
 pub(super) use redact::*;
 pub(super) use relations::*;
+pub(super) use rendezvous::*;
 pub(super) use report::*;
 pub(super) use room::*;
 pub(super) use search::*;
 pub(super) use send::*;
 pub(super) use session::*;
 pub(super) use space::*;
+pub(super) use sso::*;
 pub(super) use state::*;
 pub(super) use sync::*;
 pub(super) use tag::*;
@@ -318,6 +318,7 @@ pub async fn update_displayname(
 	}
 
 	services.users.set_displayname(user_id, displayname.clone());
+	services.user_directory.update_local_profile(user_id).await;
 
 	// Send a new join membership event into all joined rooms
 	let avatar_url = &current_avatar_url;
@@ -370,6 +371,7 @@ pub async fn update_avatar_url(
 
 	services.users.set_avatar_url(user_id, avatar_url.clone());
 	services.users.set_blurhash(user_id, blurhash.clone());
+	services.user_directory.update_local_profile(user_id).await;
 
 	// Send a new join membership event into all joined rooms
 	let avatar_url = &avatar_url;
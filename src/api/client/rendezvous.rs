@@ -0,0 +1,143 @@
+use axum::{
+	Json,
+	body::Bytes,
+	extract::{Path, State},
+	response::{IntoResponse, Response},
+};
+use axum_client_ip::ClientIp;
+use axum_extra::{
+	TypedHeader,
+	headers::{CacheControl, ETag, Expires, IfMatch, IfNoneMatch, LastModified},
+};
+use conduwuit::{Err, Result, err};
+use http::{HeaderMap, StatusCode, header};
+use serde_json::json;
+use service::{
+	ratelimit::{Class, Key},
+	rendezvous::Session,
+};
+
+const RENDEZVOUS_PATH: &str = "/_matrix/client/unstable/org.matrix.msc4108/rendezvous";
+
+/// # `POST /_matrix/client/unstable/org.matrix.msc4108/rendezvous`
+///
+/// Opens a rendezvous session holding the request body, returning its URL.
+pub(crate) async fn create_rendezvous_route(
+	State(services): State<crate::State>,
+	ClientIp(client): ClientIp,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Response> {
+	services
+		.ratelimit
+		.check(Class::Rendezvous, &[Key::Ip(client)])
+		.await?;
+
+	let (id, session) = services.rendezvous.create(content_type(&headers), body)?;
+
+	let mut url = services.config.get_client_domain();
+	url.set_path(&format!("{RENDEZVOUS_PATH}/{id}"));
+
+	Ok((
+		StatusCode::CREATED,
+		session_headers(&session)?,
+		[(header::LOCATION, url.as_str())],
+		Json(json!({ "url": url.as_str() })),
+	)
+		.into_response())
+}
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Returns the data of a rendezvous session, or 304 if it has not changed
+/// since the ETag in `If-None-Match`.
+pub(crate) async fn get_rendezvous_route(
+	State(services): State<crate::State>,
+	Path(id): Path<String>,
+	if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Result<Response> {
+	let session = services.rendezvous.get(&id)?;
+	let headers = session_headers(&session)?;
+
+	if if_none_match.is_some_and(|TypedHeader(if_none_match)| {
+		!if_none_match.precondition_passes(&headers.0.0)
+	}) {
+		return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+	}
+
+	Ok((headers, [(header::CONTENT_TYPE, session.content_type)], session.data).into_response())
+}
+
+/// # `PUT /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Replaces the data of a rendezvous session. `If-Match` must hold the ETag
+/// the writer last saw, so that neither device overwrites a message the
+/// other has not read.
+pub(crate) async fn update_rendezvous_route(
+	State(services): State<crate::State>,
+	ClientIp(client): ClientIp,
+	Path(id): Path<String>,
+	if_match: Option<TypedHeader<IfMatch>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Response> {
+	let Some(TypedHeader(if_match)) = if_match else {
+		return Err!(Request(MissingParam("The If-Match header is required.")));
+	};
+
+	services
+		.ratelimit
+		.check(Class::Rendezvous, &[Key::Ip(client)])
+		.await?;
+
+	let session = services.rendezvous.update(
+		&id,
+		|current| etag(current).is_ok_and(|current| if_match.precondition_passes(&current)),
+		content_type(&headers),
+		body,
+	)?;
+
+	Ok((StatusCode::ACCEPTED, session_headers(&session)?).into_response())
+}
+
+/// # `DELETE /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Closes a rendezvous session.
+pub(crate) async fn delete_rendezvous_route(
+	State(services): State<crate::State>,
+	Path(id): Path<String>,
+) -> Result<StatusCode> {
+	services.rendezvous.delete(&id)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+type SessionHeaders = (
+	TypedHeader<ETag>,
+	TypedHeader<Expires>,
+	TypedHeader<LastModified>,
+	TypedHeader<CacheControl>,
+);
+
+fn session_headers(session: &Session) -> Result<SessionHeaders> {
+	Ok((
+		TypedHeader(etag(&session.etag)?),
+		TypedHeader(Expires::from(session.expires_at)),
+		TypedHeader(LastModified::from(session.last_modified)),
+		TypedHeader(CacheControl::new().with_no_store()),
+	))
+}
+
+fn etag(tag: &str) -> Result<ETag> {
+	format!("\"{tag}\"")
+		.parse()
+		.map_err(|_| err!("Invalid rendezvous session ETag {tag:?}"))
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+	headers
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.unwrap_or("text/plain")
+		.to_owned()
+}
@@ -3,9 +3,9 @@
 use axum::extract::State;
 use axum_client_ip::ClientIp;
 use conduwuit::{Err, Event, Result, debug_info, info, matrix::pdu::PduEvent, utils::ReadyExt};
-use conduwuit_service::Services;
+use conduwuit_service::{Services, reports::ReportTarget};
 use ruma::{
-	EventId, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	EventId, Int, RoomId, UserId,
 	api::client::{
 		report_user,
 		room::{report_content, report_room},
@@ -16,15 +16,6 @@
 
 use crate::Ruma;
 
-struct Report {
-	sender: OwnedUserId,
-	room_id: Option<OwnedRoomId>,
-	event_id: Option<OwnedEventId>,
-	user_id: Option<OwnedUserId>,
-	report_type: String,
-	reason: Option<String>,
-}
-
 /// # `POST /_matrix/client/v3/rooms/{roomId}/report`
 ///
 /// Reports an abusive room to homeserver admins
@@ -67,16 +58,8 @@ pub(crate) async fn report_room_route(
 		)));
 	}
 
-	let report = Report {
-		sender: sender_user.to_owned(),
-		room_id: Some(body.room_id.clone()),
-		event_id: None,
-		user_id: None,
-		report_type: "room".to_owned(),
-		reason: body.reason.clone(),
-	};
-
-	services.admin.send_message(build_report(report)).await.ok();
+	let target = ReportTarget::Room { room_id: body.room_id.clone() };
+	store_report(&services, sender_user, target, body.reason.clone(), None).await?;
 
 	Ok(report_room::v3::Response {})
 }
@@ -109,6 +92,7 @@ pub(crate) async fn report_event_route(
 		&body.room_id,
 		sender_user,
 		body.reason.as_ref(),
+		body.score,
 		&pdu,
 	)
 	.await?;
@@ -119,15 +103,13 @@ pub(crate) async fn report_event_route(
 		body.event_id,
 		body.reason.as_deref().unwrap_or("")
 	);
-	let report = Report {
-		sender: sender_user.to_owned(),
-		room_id: Some(body.room_id.clone()),
-		event_id: Some(body.event_id.clone()),
-		user_id: None,
-		report_type: "event".to_owned(),
-		reason: body.reason.clone(),
+	let target = ReportTarget::Event {
+		room_id: body.room_id.clone(),
+		event_id: body.event_id.clone(),
+		sender: pdu.sender.clone(),
 	};
-	services.admin.send_message(build_report(report)).await.ok();
+	let score = body.score.map(i64::from);
+	store_report(&services, sender_user, target, body.reason.clone(), score).await?;
 
 	Ok(report_content::v3::Response {})
 }
@@ -157,22 +139,14 @@ pub(crate) async fn report_user_route(
 		return Ok(report_user::v3::Response {});
 	}
 
-	let report = Report {
-		sender: sender_user.to_owned(),
-		room_id: None,
-		event_id: None,
-		user_id: Some(body.user_id.clone()),
-		report_type: "user".to_owned(),
-		reason: body.reason.clone(),
-	};
-
 	info!(
 		"Received room report from {sender_user} for user {} with reason: \"{}\"",
 		body.user_id,
 		body.reason.as_deref().unwrap_or("")
 	);
 
-	services.admin.send_message(build_report(report)).await.ok();
+	let target = ReportTarget::User { user_id: body.user_id.clone() };
+	store_report(&services, sender_user, target, body.reason.clone(), None).await?;
 
 	Ok(report_user::v3::Response {})
 }
@@ -189,6 +163,7 @@ async fn is_event_report_valid(
 	room_id: &RoomId,
 	sender_user: &UserId,
 	reason: Option<&String>,
+	score: Option<Int>,
 	pdu: &PduEvent,
 ) -> Result<()> {
 	debug_info!(
@@ -200,6 +175,10 @@ async fn is_event_report_valid(
 		return Err!(Request(NotFound("Event ID does not belong to the reported room",)));
 	}
 
+	if score.is_some_and(|score| !(-100..=0).contains(&i64::from(score))) {
+		return Err!(Request(InvalidParam("Invalid score, must be within 0 to -100",)));
+	}
+
 	if reason.as_ref().is_some_and(|s| s.len() > 750) {
 		return Err!(Request(
 			InvalidParam("Reason too long, should be 750 characters or fewer",)
@@ -219,22 +198,58 @@ async fn is_event_report_valid(
 	Ok(())
 }
 
+/// Stores a report and tells the admin room about it, unless the reporter
+/// already has an open report about the same thing.
+async fn store_report(
+	services: &Services,
+	sender_user: &UserId,
+	target: ReportTarget,
+	reason: Option<String>,
+	score: Option<i64>,
+) -> Result {
+	let (id, new) = services
+		.reports
+		.add(sender_user, target.clone(), reason.clone(), score)
+		.await?;
+
+	if new {
+		let report = build_report(id, sender_user, &target, reason.as_deref());
+		services.admin.send_message(report).await.ok();
+	} else {
+		debug_info!("Report from {sender_user} is a duplicate of open report {id}");
+	}
+
+	Ok(())
+}
+
 /// Builds a report message to be sent to the admin room.
-fn build_report(report: Report) -> RoomMessageEventContent {
-	let mut text =
-		format!("@room New {} report received from {}:\n\n", report.report_type, report.sender);
-	if report.user_id.is_some() {
-		let _ = writeln!(text, "- Reported User ID: `{}`", report.user_id.unwrap());
+fn build_report(
+	id: u64,
+	sender: &UserId,
+	target: &ReportTarget,
+	reason: Option<&str>,
+) -> RoomMessageEventContent {
+	let report_type = match target {
+		| ReportTarget::Event { .. } => "event",
+		| ReportTarget::Room { .. } => "room",
+		| ReportTarget::User { .. } => "user",
+	};
+
+	let mut text = format!("@room New {report_type} report {id} received from {sender}:\n\n");
+	if let ReportTarget::User { user_id } = target {
+		let _ = writeln!(text, "- Reported User ID: `{user_id}`");
 	}
-	if report.room_id.is_some() {
-		let _ = writeln!(text, "- Reported Room ID: `{}`", report.room_id.unwrap());
+	if let Some(room_id) = target.room_id() {
+		let _ = writeln!(text, "- Reported Room ID: `{room_id}`");
 	}
-	if report.event_id.is_some() {
-		let _ = writeln!(text, "- Reported Event ID: `{}`", report.event_id.unwrap());
+	if let ReportTarget::Event { event_id, sender, .. } = target {
+		let _ = writeln!(text, "- Reported Event ID: `{event_id}`");
+		let _ = writeln!(text, "- Reported Event Sender: `{sender}`");
 	}
-	if let Some(reason) = report.reason {
+	if let Some(reason) = reason {
 		let _ = writeln!(text, "- Report Reason: {reason}");
 	}
+	let _ = writeln!(text, "\nClaim it with `!admin reports claim {id}`.");
 
 	RoomMessageEventContent::text_markdown(text).add_mentions(Mentions::with_room_mention())
 }
@@ -12,29 +12,35 @@
 use futures::StreamExt;
 use lettre::Address;
 use ruma::{
-	OwnedUserId, UserId,
+	CanonicalJsonValue, OwnedUserId, UserId,
 	api::client::{
 		error::ErrorKind,
 		session::{
 			get_login_token,
 			get_login_types::{
 				self,
-				v3::{ApplicationServiceLoginType, PasswordLoginType, TokenLoginType},
+				v3::{
+					ApplicationServiceLoginType, IdentityProvider, PasswordLoginType,
+					SsoLoginType, TokenLoginType,
+				},
 			},
 			login::{
 				self,
 				v3::{DiscoveryInfo, HomeserverInfo},
 			},
-			logout, logout_all,
+			logout, logout_all, refresh_token,
 		},
 		uiaa::UserIdentifier,
 	},
+	serde::JsonObject,
 };
-use service::uiaa::Identity;
+use service::{threepid::msisdn, uiaa::Identity};
 
 use super::{DEVICE_ID_LENGTH, TOKEN_LENGTH};
 use crate::Ruma;
 
+const JWT_LOGIN_TYPE: &str = "org.matrix.login.jwt";
+
 /// # `GET /_matrix/client/v3/login`
 ///
 /// Get the supported login types of this server. One of these should be used as
@@ -45,13 +51,36 @@ pub(crate) async fn get_login_types_route(
 	ClientIp(client): ClientIp,
 	_body: Ruma<get_login_types::v3::Request>,
 ) -> Result<get_login_types::v3::Response> {
-	Ok(get_login_types::v3::Response::new(vec![
+	let mut flows = vec![
 		get_login_types::v3::LoginType::Password(PasswordLoginType::default()),
 		get_login_types::v3::LoginType::ApplicationService(ApplicationServiceLoginType::default()),
 		get_login_types::v3::LoginType::Token(TokenLoginType {
 			get_login_token: services.server.config.login_via_existing_session,
 		}),
-	]))
+	];
+
+	if services.sso.is_enabled() {
+		let identity_providers = services
+			.config
+			.sso
+			.providers
+			.iter()
+			.map(|(id, provider)| IdentityProvider {
+				id: id.clone(),
+				name: provider.name.clone(),
+				icon: provider.icon.as_deref().map(Into::into),
+				brand: provider.brand.as_deref().map(Into::into),
+			})
+			.collect();
+
+		flows.push(get_login_types::v3::LoginType::Sso(SsoLoginType { identity_providers }));
+	}
+
+	if services.config.jwt.enable {
+		flows.push(get_login_types::v3::LoginType::new(JWT_LOGIN_TYPE, JsonObject::new())?);
+	}
+
+	Ok(get_login_types::v3::Response::new(flows))
 }
 
 /// Authenticates the given user by its ID and its password.
@@ -180,6 +209,21 @@ pub(crate) async fn handle_login(
 				.await
 				.ok_or_else(|| err!(Request(Forbidden("Wrong username or password"))))?
 		},
+		| (
+			Some(
+				identifier @ (UserIdentifier::Msisdn { .. } | UserIdentifier::PhoneNumber { .. }),
+			),
+			_,
+		) => {
+			let msisdn = msisdn::from_identifier(identifier)
+				.ok_or_else(|| err!(Request(InvalidParam("Phone number is malformed"))))?;
+
+			&services
+				.threepid
+				.get_localpart_for_msisdn(&msisdn)
+				.await
+				.ok_or_else(|| err!(Request(Forbidden("Wrong username or password"))))?
+		},
 		| (None, Some(user)) => user,
 		| _ => {
 			return Err!(Request(InvalidParam("Identifier type not recognized")));
@@ -259,7 +303,7 @@ pub(crate) async fn login_route(
 		}) => handle_login(&services, identifier.as_ref(), password, user.as_ref()).await?,
 		| login::v3::LoginInfo::Token(login::v3::Token { token }) => {
 			debug!("Got token login type");
-			if !services.server.config.login_via_existing_session {
+			if !services.server.config.login_via_existing_session && !services.sso.is_enabled() {
 				return Err!(Request(Unknown("Token login is not enabled.")));
 			}
 			services.users.find_from_login_token(token).await?
@@ -297,11 +341,17 @@ pub(crate) async fn login_route(
 
 			user_id
 		},
-		| _ => {
-			debug!("/login json_body: {:?}", &body.json_body);
-			return Err!(Request(Unknown(
-				debug_warn!(?body.login_info, "Invalid or unsupported login type")
-			)));
+		| _ => match jwt_login_token(body.json_body.as_ref()) {
+			| Some(token) => {
+				debug!("Got JWT login type");
+				services.sso.login_with_jwt(token).await?
+			},
+			| None => {
+				debug!("/login json_body: {:?}", &body.json_body);
+				return Err!(Request(Unknown(
+					debug_warn!(?body.login_info, "Invalid or unsupported login type")
+				)));
+			},
 		},
 	};
 
@@ -343,6 +393,17 @@ pub(crate) async fn login_route(
 			.await?;
 	}
 
+	let (refresh_token, expires_in) = if body.refresh_token {
+		let (refresh_token, expires_in) = services
+			.users
+			.issue_refresh_token(&user_id, &device_id)
+			.await;
+
+		(Some(refresh_token), Some(expires_in))
+	} else {
+		(None, None)
+	};
+
 	// send client well-known if specified so the client knows to reconfigure itself
 	let client_discovery_info: Option<DiscoveryInfo> = services
 		.server
@@ -360,12 +421,27 @@ pub(crate) async fn login_route(
 		access_token: token,
 		device_id,
 		well_known: client_discovery_info,
-		expires_in: None,
+		expires_in,
 		home_server: Some(services.config.server_name.clone()),
-		refresh_token: None,
+		refresh_token,
 	})
 }
 
+/// The token of an `org.matrix.login.jwt` login request, which ruma has no
+/// type for.
+fn jwt_login_token(json_body: Option<&CanonicalJsonValue>) -> Option<&str> {
+	let Some(CanonicalJsonValue::Object(body)) = json_body else {
+		return None;
+	};
+
+	match (body.get("type"), body.get("token")) {
+		| (Some(CanonicalJsonValue::String(kind)), Some(CanonicalJsonValue::String(token)))
+			if kind == JWT_LOGIN_TYPE =>
+			Some(token),
+		| _ => None,
+	}
+}
+
 /// # `POST /_matrix/client/v1/login/get_token`
 ///
 /// Allows a logged-in user to get a short-lived token which can be used
@@ -399,6 +475,26 @@ pub(crate) async fn login_token_route(
 	})
 }
 
+/// # `POST /_matrix/client/v3/refresh`
+///
+/// Replaces the access token and refresh token of the device a refresh token
+/// was issued to. Reusing a replaced refresh token logs the device out, as it
+/// means the token has leaked.
+#[tracing::instrument(skip_all, fields(%client), name = "refresh", level = "info")]
+pub(crate) async fn refresh_token_route(
+	State(services): State<crate::State>,
+	ClientIp(client): ClientIp,
+	body: Ruma<refresh_token::v3::Request>,
+) -> Result<refresh_token::v3::Response> {
+	let refreshed = services.users.refresh(&body.refresh_token).await?;
+
+	Ok(refresh_token::v3::Response {
+		access_token: refreshed.access_token,
+		refresh_token: Some(refreshed.refresh_token),
+		expires_in_ms: Some(refreshed.expires_in),
+	})
+}
+
 /// # `POST /_matrix/client/v3/logout`
 ///
 /// Log out the current device.
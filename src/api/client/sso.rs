@@ -0,0 +1,43 @@
+use axum::extract::State;
+use conduwuit::{Err, Result};
+use ruma::api::client::session::{sso_login, sso_login_with_provider};
+use service::sso::SELECT_PATH;
+
+use crate::Ruma;
+
+/// # `GET /_matrix/client/v3/login/sso/redirect`
+///
+/// Sends the user to the identity provider to sign in, or to a page listing
+/// the identity providers to choose from if there are several.
+pub(crate) async fn sso_login_route(
+	State(services): State<crate::State>,
+	body: Ruma<sso_login::v3::Request>,
+) -> Result<sso_login::v3::Response> {
+	let providers = &services.config.sso.providers;
+	let location = match providers.keys().next() {
+		| None => return Err!(Request(NotFound("Single sign-on is not enabled."))),
+		| Some(idp_id) if providers.len() == 1 =>
+			services.sso.start(idp_id, &body.redirect_url).await?,
+		| Some(_) => {
+			let mut url = services.config.get_client_domain();
+			url.set_path(SELECT_PATH);
+			url.query_pairs_mut()
+				.append_pair("redirectUrl", &body.redirect_url);
+			url
+		},
+	};
+
+	Ok(sso_login::v3::Response::new(location.into()))
+}
+
+/// # `GET /_matrix/client/v3/login/sso/redirect/{idpId}`
+///
+/// Sends the user to the given identity provider to sign in.
+pub(crate) async fn sso_login_with_provider_route(
+	State(services): State<crate::State>,
+	body: Ruma<sso_login_with_provider::v3::Request>,
+) -> Result<sso_login_with_provider::v3::Response> {
+	let location = services.sso.start(&body.idp_id, &body.redirect_url).await?;
+
+	Ok(sso_login_with_provider::v3::Response::new(location.into()))
+}
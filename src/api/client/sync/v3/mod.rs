@@ -228,7 +228,9 @@ pub(crate) async fn sync_events_route(
 	// Stop hanging if new info arrives
 	let default = Duration::from_secs(30);
 	let duration = cmp::min(body.body.timeout.unwrap_or(default), default);
+	let longpoll = services.server.metrics.sync_longpoll();
 	_ = tokio::time::timeout(duration, watcher).await;
+	drop(longpoll);
 
 	// Retry returning data
 	let response = build_sync_events(&services, &body, use_state_after).await?;
@@ -219,6 +219,7 @@ pub(crate) async fn sync_events_v5_route(
 		// Stop hanging if new info arrives
 		let default = Duration::from_secs(30);
 		let duration = cmp::min(body.timeout.unwrap_or(default), default);
+		let _longpoll = services.server.metrics.sync_longpoll();
 		_ = tokio::time::timeout(duration, watcher).await;
 	}
 
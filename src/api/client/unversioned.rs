@@ -53,6 +53,10 @@ pub(crate) async fn get_supported_versions_route(
 		unstable_features.insert("org.matrix.msc3266".to_owned(), true); /* room previews (https://github.com/matrix-org/matrix-spec-proposals/pull/3266) */
 	}
 
+	if services.config.rendezvous.enable {
+		unstable_features.insert("org.matrix.msc4108".to_owned(), true); /* sign in with QR code (https://github.com/matrix-org/matrix-spec-proposals/pull/4108) */
+	}
+
 	if services.config.experimental_features.msc4222_enabled {
 		unstable_features.insert("org.matrix.msc4222".to_owned(), true); /* state_after in sync v2 (https://github.com/matrix-org/matrix-spec-proposals/pull/4222) */
 	}
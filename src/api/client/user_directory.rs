@@ -1,13 +1,6 @@
 use axum::extract::State;
-use conduwuit::{
-	Result,
-	utils::{future::BoolExt, stream::BroadbandExt},
-};
-use futures::{FutureExt, StreamExt, pin_mut};
-use ruma::{
-	api::client::user_directory::search_users::{self},
-	events::room::join_rules::JoinRule,
-};
+use conduwuit::Result;
+use ruma::api::client::user_directory::search_users;
 
 use crate::Ruma;
 
@@ -17,10 +10,12 @@
 
 /// # `POST /_matrix/client/r0/user_directory/search`
 ///
-/// Searches all known users for a match.
+/// Searches the user directory for users with a display name or user ID
+/// containing words starting with each word of the search term.
 ///
-/// - Hides any local users that aren't in any public rooms (i.e. those that
-///   have the join rule set to public) and don't share a room with the sender
+/// - Hides any users that aren't in any public rooms (i.e. those that have the
+///   join rule set to public) and don't share a room with the sender, unless
+///   `user_directory_search_all_users` is enabled and they are local
 pub(crate) async fn search_users_route(
 	State(services): State<crate::State>,
 	body: Ruma<search_users::v3::Request>,
@@ -30,57 +25,19 @@ pub(crate) async fn search_users_route(
 		.map_or(LIMIT_DEFAULT, usize::from)
 		.min(LIMIT_MAX);
 
-	let search_term = body.search_term.to_lowercase();
-	let mut users = services
-		.users
-		.stream()
-		.map(ToOwned::to_owned)
-		.broad_filter_map(async |user_id| {
-			let display_name = services.users.displayname(&user_id).await.ok();
-
-			let user_id_matches = user_id.as_str().to_lowercase().contains(&search_term);
-
-			let display_name_matches = display_name
-				.as_deref()
-				.map(str::to_lowercase)
-				.is_some_and(|display_name| display_name.contains(&search_term));
-
-			if !user_id_matches && !display_name_matches {
-				return None;
-			}
-
-			let user_in_public_room = services
-				.rooms
-				.state_cache
-				.rooms_joined(&user_id)
-				.map(ToOwned::to_owned)
-				.broad_any(async |room_id| {
-					services
-						.rooms
-						.state_accessor
-						.get_join_rules(&room_id)
-						.map(|rule| matches!(rule, JoinRule::Public))
-						.await
-				});
-
-			let user_sees_user = services
-				.rooms
-				.state_cache
-				.user_sees_user(sender_user, &user_id);
-
-			pin_mut!(user_in_public_room, user_sees_user);
-			user_in_public_room
-				.or(user_sees_user)
-				.await
-				.then_some(search_users::v3::User {
-					user_id: user_id.clone(),
-					display_name,
-					avatar_url: services.users.avatar_url(&user_id).await.ok(),
-				})
-		});
-
-	let results = users.by_ref().take(limit).collect().await;
-	let limited = users.next().await.is_some();
+	let (users, limited) = services
+		.user_directory
+		.search(sender_user, &body.search_term, limit)
+		.await;
+
+	let results = users
+		.into_iter()
+		.map(|(user_id, profile)| search_users::v3::User {
+			user_id,
+			display_name: profile.display_name,
+			avatar_url: profile.avatar_url,
+		})
+		.collect();
 
 	Ok(search_users::v3::Response { results, limited })
 }
@@ -139,3 +139,32 @@ pub(crate) async fn syncv3_client_server_json(
 		"version": conduwuit::version(),
 	})))
 }
+
+/// # `GET /_matrix/client/v1/auth_metadata`
+/// # `GET /_matrix/client/unstable/org.matrix.msc2965/auth_metadata`
+///
+/// Metadata of the authorization server authentication is delegated to, with
+/// the account management page if one is configured.
+pub(crate) async fn auth_metadata_route(
+	State(services): State<crate::State>,
+) -> Result<impl IntoResponse> {
+	let mut metadata = services.sso.auth_metadata().await?;
+	if let Some(url) = &services.config.oauth.account_management_url {
+		metadata.insert("account_management_uri".to_owned(), url.as_str().into());
+	}
+
+	Ok(Json(metadata))
+}
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc2965/auth_issuer`
+///
+/// Issuer of the authorization server authentication is delegated to.
+pub(crate) async fn auth_issuer_route(
+	State(services): State<crate::State>,
+) -> Result<impl IntoResponse> {
+	let Some(issuer) = &services.config.oauth.issuer else {
+		return Err(Error::BadRequest(ErrorKind::NotFound, "Not found."));
+	};
+
+	Ok(Json(serde_json::json!({ "issuer": issuer })))
+}
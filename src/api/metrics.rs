@@ -0,0 +1,32 @@
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+};
+use axum_extra::{
+	TypedHeader,
+	headers::{Authorization, authorization::Bearer},
+};
+use conduwuit::{Err, Result};
+use http::header;
+
+/// # `GET /_continuwuity/metrics`
+///
+/// Serves the metrics in the Prometheus text format. The same handler serves
+/// `/metrics` on the separate metrics listener.
+pub(crate) async fn metrics_route(
+	State(services): State<crate::State>,
+	bearer: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<Response> {
+	let token = bearer
+		.as_ref()
+		.map(|TypedHeader(Authorization(bearer))| bearer.token());
+
+	if !services.metrics.is_authorized(token) {
+		return Err!(Request(Forbidden("Invalid metrics token.")));
+	}
+
+	let text = services.metrics.render()?;
+
+	Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], text)
+		.into_response())
+}
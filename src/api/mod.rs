@@ -7,12 +7,13 @@
 conduwuit_macros::introspect_crate! {}
 
 pub mod client;
+pub mod metrics;
 pub mod router;
 pub mod server;
 
 pub mod admin;
 
-pub(crate) use self::router::{Ruma, RumaResponse, State};
+pub(crate) use self::router::{Ruma, RumaResponse, RumaStreamed, State};
 
 conduwuit::mod_ctor! {}
 conduwuit::mod_dtor! {}
@@ -1,6 +1,8 @@
 mod args;
 mod auth;
+mod delegated;
 mod handler;
+mod ratelimit;
 mod request;
 mod response;
 
@@ -14,10 +16,24 @@
 use conduwuit::{Server, err};
 pub(super) use conduwuit_service::state::State;
 use http::{Uri, uri};
+use ruma::api::{
+	IncomingRequest,
+	client::{
+		authenticated_media::{get_content, get_content_as_filename},
+		media::{
+			get_content as get_content_legacy,
+			get_content_as_filename as get_content_as_filename_legacy,
+		},
+	},
+	federation::authenticated_media::get_content as get_content_federation,
+};
 
 use self::handler::RouterExt;
-pub(super) use self::{args::Args as Ruma, response::RumaResponse};
-use crate::{admin, client, server};
+pub(super) use self::{
+	args::{Args as Ruma, StreamedArgs as RumaStreamed},
+	response::RumaResponse,
+};
+use crate::{admin, client, metrics, server};
 
 pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 	let config = &server.config;
@@ -30,10 +46,14 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::get_register_available_route)
 		.ruma_route(&client::register::register_route)
 		.ruma_route(&client::register::request_registration_token_via_email_route)
+		.ruma_route(&client::register::request_registration_token_via_msisdn_route)
 		.ruma_route(&client::get_login_types_route)
 		.ruma_route(&client::login_route)
 		.ruma_route(&client::login_token_route)
+		.ruma_route(&client::sso_login_route)
+		.ruma_route(&client::sso_login_with_provider_route)
 		.ruma_route(&client::whoami_route)
+		.ruma_route(&client::refresh_token_route)
 		.ruma_route(&client::logout_route)
 		.ruma_route(&client::logout_all_route)
 		.ruma_route(&client::change_password_route)
@@ -171,9 +191,14 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::turn_server_route)
 		.ruma_route(&client::send_event_to_device_route)
 		.ruma_route(&client::create_content_route)
+		.ruma_route(&client::create_mxc_uri_route)
+		.ruma_route(&client::create_content_async_route)
 		.ruma_route(&client::get_content_thumbnail_route)
-		.ruma_route(&client::get_content_route)
-		.ruma_route(&client::get_content_as_filename_route)
+		.ruma_raw_route(&get_content::v1::Request::METADATA, client::get_content_route)
+		.ruma_raw_route(
+			&get_content_as_filename::v1::Request::METADATA,
+			client::get_content_as_filename_route,
+		)
 		.route(
 			"/_matrix/client/v1/media/download/{server_name}/{media_id}/",
 			get(redirect_download_no_filename),
@@ -251,7 +276,72 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::room_initial_sync_route)
 		.route("/client/server.json", get(client::syncv3_client_server_json))
 		.ruma_route(&admin::rooms::ban::ban_room)
-		.ruma_route(&admin::rooms::list::list_rooms);
+		.ruma_route(&admin::rooms::list::list_rooms)
+		.route(
+			"/_continuwuity/admin/rooms/{room_id}/purge",
+			post(admin::rooms::purge::purge_room_route)
+				.get(admin::rooms::purge::get_purge_status_route),
+		)
+		.route(
+			"/_continuwuity/admin/rooms/{room_id}/purge_history",
+			post(admin::rooms::purge::purge_history_route),
+		)
+		.route(
+			"/_continuwuity/admin/rooms/{room_id}/shutdown",
+			post(admin::rooms::shutdown::shutdown_room_route),
+		)
+		.route("/_continuwuity/admin/reports", get(admin::reports::list_reports_route))
+		.route("/_continuwuity/admin/reports/{report_id}", get(admin::reports::get_report_route))
+		.route(
+			"/_continuwuity/admin/reports/{report_id}/claim",
+			post(admin::reports::claim_report_route),
+		)
+		.route(
+			"/_continuwuity/admin/reports/{report_id}/resolve",
+			post(admin::reports::resolve_report_route),
+		)
+		.route(
+			"/_continuwuity/admin/reports/{report_id}/action",
+			post(admin::reports::report_action_route),
+		);
+
+	if config.metrics.enabled && config.metrics.token.is_some() {
+		router = router.route("/_continuwuity/metrics", get(metrics::metrics_route));
+	}
+
+	if config.sms.is_some() {
+		router = router.route(
+			client::threepid::MSISDN_SUBMIT_PATH,
+			post(client::threepid::submit_msisdn_token_route),
+		);
+	}
+
+	if config.rendezvous.enable {
+		router = router
+			.route(
+				"/_matrix/client/unstable/org.matrix.msc4108/rendezvous",
+				post(client::create_rendezvous_route),
+			)
+			.route(
+				"/_matrix/client/unstable/org.matrix.msc4108/rendezvous/{session_id}",
+				get(client::get_rendezvous_route)
+					.put(client::update_rendezvous_route)
+					.delete(client::delete_rendezvous_route),
+			);
+	}
+
+	if config.oauth.enable {
+		router = router
+			.route("/_matrix/client/v1/auth_metadata", get(client::auth_metadata_route))
+			.route(
+				"/_matrix/client/unstable/org.matrix.msc2965/auth_metadata",
+				get(client::auth_metadata_route),
+			)
+			.route(
+				"/_matrix/client/unstable/org.matrix.msc2965/auth_issuer",
+				get(client::auth_issuer_route),
+			);
+	}
 
 	if config.allow_federation {
 		router = router
@@ -288,7 +378,10 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 			.ruma_route(&server::get_hierarchy_route)
 			.ruma_route(&server::get_event_by_timestamp_route)
 			.ruma_route(&server::well_known_server)
-			.ruma_route(&server::get_content_route)
+			.ruma_raw_route(
+				&get_content_federation::v1::Request::METADATA,
+				server::get_content_route,
+			)
 			.ruma_route(&server::get_content_thumbnail_route)
 			.ruma_route(&server::get_edutypes_route)
 			.route("/_conduwuit/local_user_count", get(client::conduwuit_local_user_count))
@@ -306,8 +399,14 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		router = router
 			.ruma_route(&client::get_media_config_legacy_route)
 			.ruma_route(&client::get_media_preview_legacy_route)
-			.ruma_route(&client::get_content_legacy_route)
-			.ruma_route(&client::get_content_as_filename_legacy_route)
+			.ruma_raw_route(
+				&get_content_legacy::v3::Request::METADATA,
+				client::get_content_legacy_route,
+			)
+			.ruma_raw_route(
+				&get_content_as_filename_legacy::v3::Request::METADATA,
+				client::get_content_as_filename_legacy_route,
+			)
 			.ruma_route(&client::get_content_thumbnail_legacy_route)
 			.route("/_matrix/media/v1/config", get(client::get_media_config_legacy_legacy_route))
 			.route("/_matrix/media/v1/upload", post(client::create_content_legacy_route))
@@ -343,6 +442,11 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 	router
 }
 
+/// Routes of the separate metrics listener.
+pub fn build_metrics(router: Router<State>) -> Router<State> {
+	router.route("/metrics", get(metrics::metrics_route))
+}
+
 async fn redirect_download_no_filename(uri: Uri) -> impl IntoResponse {
 	let path = uri.path().trim_end_matches('/');
 	let query = uri.query().unwrap_or_default();
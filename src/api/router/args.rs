@@ -8,7 +8,7 @@
 	OwnedUserId, ServerName, UserId, api::IncomingRequest,
 };
 
-use super::{auth, request, request::Request};
+use super::{auth, delegated, ratelimit, request, request::Request};
 use crate::{State, service::appservice::RegistrationInfo};
 
 /// Extractor for Ruma request structs
@@ -80,6 +80,55 @@ impl<T> Deref for Args<T>
 	fn deref(&self) -> &Self::Target { &self.body }
 }
 
+/// Extractor for Ruma request structs whose body is streamed rather than read
+/// into memory, such as media uploads. The request struct is parsed with an
+/// empty body.
+pub(crate) struct StreamedArgs<T> {
+	pub(crate) args: Args<T>,
+
+	/// Request body, which is at most `max_request_size` bytes if it declares
+	/// its length, but must still be limited as it is read.
+	pub(crate) stream: Body,
+}
+
+impl<T> Deref for StreamedArgs<T>
+where
+	T: IncomingRequest + Send + Sync + 'static,
+{
+	type Target = Args<T>;
+
+	fn deref(&self) -> &Self::Target { &self.args }
+}
+
+impl<T> FromRequest<State, Body> for StreamedArgs<T>
+where
+	T: IncomingRequest + Send + Sync + 'static,
+{
+	type Rejection = Error;
+
+	async fn from_request(
+		request: hyper::Request<Body>,
+		services: &State,
+	) -> Result<Self, Self::Rejection> {
+		let (mut request, stream) = request::from_streamed(services, request).await?;
+
+		delegated::check(services, &T::METADATA)?;
+		let auth = auth::auth(services, &mut request, None, &T::METADATA).await?;
+		ratelimit::check(services, &mut request, &auth, &T::METADATA).await?;
+		let args = Args {
+			body: make_body::<T>(&mut request, None)?,
+			origin: auth.origin,
+			sender_user: auth.sender_user,
+			sender_device: auth.sender_device,
+			appservice_info: auth.appservice_info,
+			json_body: None,
+			delay: None,
+		};
+
+		Ok(Self { args, stream })
+	}
+}
+
 impl<T> FromRequest<State, Body> for Args<T>
 where
 	T: IncomingRequest + Send + Sync + 'static,
@@ -146,7 +195,9 @@ async fn from_request(
 			None
 		};
 
+		delegated::check(services, &T::METADATA)?;
 		let auth = auth::auth(services, &mut request, json_body.as_ref(), &T::METADATA).await?;
+		ratelimit::check(services, &mut request, &auth, &T::METADATA).await?;
 		Ok(Self {
 			body: make_body::<T>(&mut request, json_body.as_mut())?,
 			origin: auth.origin,
@@ -38,6 +38,7 @@
 enum Token {
 	Appservice(Box<RegistrationInfo>),
 	User((OwnedUserId, OwnedDeviceId)),
+	Expired,
 	Invalid,
 	None,
 }
@@ -90,7 +91,7 @@ pub(super) async fn auth(
 						// we should have validated the token above
 						// already
 					},
-					| Token::None | Token::Invalid => {
+					| Token::None | Token::Expired | Token::Invalid => {
 						return Err(Error::BadRequest(
 							ErrorKind::MissingToken,
 							"Missing or invalid access token.",
@@ -108,7 +109,7 @@ pub(super) async fn auth(
 							// we should have validated the token above
 							// already
 						},
-						| Token::None | Token::Invalid => {
+						| Token::None | Token::Expired | Token::Invalid => {
 							return Err(Error::BadRequest(
 								ErrorKind::MissingToken,
 								"Missing or invalid access token.",
@@ -231,6 +232,16 @@ pub(super) async fn auth(
 				))
 			}
 		},
+		| (AuthScheme::None, Token::Expired) => Ok(Auth {
+			origin: None,
+			sender_user: None,
+			sender_device: None,
+			appservice_info: None,
+		}),
+		| (_, Token::Expired) => Err(Error::BadRequest(
+			ErrorKind::UnknownToken { soft_logout: true },
+			"Access token has expired.",
+		)),
 		| (_, Token::Invalid) => Err(Error::BadRequest(
 			ErrorKind::UnknownToken { soft_logout: false },
 			"Unknown access token.",
@@ -452,6 +463,10 @@ async fn find_token(services: &Services, token: Option<&str>) -> Result<Token> {
 		return Ok(Token::Invalid);
 	}
 
+	if services.sso.is_delegated() {
+		return find_delegated_token(services, token).await;
+	}
+
 	let user_token = services.users.find_from_token(token).map_ok(Token::User);
 
 	let appservice_token = services
@@ -464,11 +479,39 @@ async fn find_token(services: &Services, token: Option<&str>) -> Result<Token> {
 	// Returns Ok if either token type succeeds, Err only if both fail
 	match select_ok([Left(user_token), Right(appservice_token)]).await {
 		| Err(e) if !e.is_not_found() => Err(e),
+		| Ok((Token::User((user_id, device_id)), _)) => {
+			// Tokens issued along with a refresh token expire
+			if services
+				.users
+				.is_access_token_expired(&user_id, &device_id)
+				.await
+			{
+				Ok(Token::Expired)
+			} else {
+				Ok(Token::User((user_id, device_id)))
+			}
+		},
 		| Ok((token, _)) => Ok(token),
 		| _ => Ok(Token::Invalid),
 	}
 }
 
+/// With authentication delegated, user tokens are only known to the
+/// authorization server, but appservices still use their own.
+async fn find_delegated_token(services: &Services, token: &str) -> Result<Token> {
+	match services.appservice.find_from_token(token).await {
+		| Ok(info) => return Ok(Token::Appservice(Box::new(info))),
+		| Err(e) if !e.is_not_found() => return Err(e),
+		| Err(_) => {},
+	}
+
+	Ok(services
+		.sso
+		.introspect(token)
+		.await?
+		.map_or(Token::Invalid, Token::User))
+}
+
 #[cfg(test)]
 mod tests {
 	use ruma::server_name;
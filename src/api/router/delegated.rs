@@ -0,0 +1,56 @@
+use conduwuit::{Error, Result};
+use http::StatusCode;
+use ruma::api::{
+	Metadata,
+	client::{
+		account::{
+			add_3pid, change_password, check_registration_token_validity, deactivate, register,
+			request_password_change_token_via_email, request_registration_token_via_email,
+		},
+		device::{delete_device, delete_devices},
+		error::ErrorKind,
+		session::{
+			get_login_token, get_login_types, login, logout, logout_all, refresh_token,
+			sso_login, sso_login_with_provider,
+		},
+	},
+};
+use service::Services;
+
+/// Refuses the endpoints the authorization server takes over when
+/// authentication is delegated to it: those creating and ending sessions and
+/// those which need the user's password.
+pub(super) fn check(services: &Services, metadata: &Metadata) -> Result {
+	if !services.sso.is_delegated() || !is_delegated(metadata) {
+		return Ok(());
+	}
+
+	Err(Error::Request(
+		ErrorKind::Unrecognized,
+		"This endpoint is handled by the authorization server.".into(),
+		StatusCode::NOT_FOUND,
+	))
+}
+
+fn is_delegated(metadata: &Metadata) -> bool {
+	matches!(
+		metadata,
+		&register::v3::Request::METADATA
+			| &check_registration_token_validity::v1::Request::METADATA
+			| &request_registration_token_via_email::v3::Request::METADATA
+			| &login::v3::Request::METADATA
+			| &get_login_types::v3::Request::METADATA
+			| &get_login_token::v1::Request::METADATA
+			| &sso_login::v3::Request::METADATA
+			| &sso_login_with_provider::v3::Request::METADATA
+			| &logout::v3::Request::METADATA
+			| &logout_all::v3::Request::METADATA
+			| &refresh_token::v3::Request::METADATA
+			| &change_password::v3::Request::METADATA
+			| &request_password_change_token_via_email::v3::Request::METADATA
+			| &deactivate::v3::Request::METADATA
+			| &add_3pid::v3::Request::METADATA
+			| &delete_device::v3::Request::METADATA
+			| &delete_devices::v3::Request::METADATA
+	)
+}
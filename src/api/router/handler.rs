@@ -1,15 +1,16 @@
 use axum::{
 	Router,
 	extract::FromRequestParts,
+	handler::Handler,
 	response::IntoResponse,
 	routing::{MethodFilter, on},
 };
 use conduwuit::Result;
 use futures::{Future, TryFutureExt};
 use http::Method;
-use ruma::api::IncomingRequest;
+use ruma::api::{IncomingRequest, Metadata};
 
-use super::{Ruma, RumaResponse, State};
+use super::{Ruma, RumaResponse, RumaStreamed, State};
 
 pub(in super::super) trait RumaHandler<T> {
 	fn add_route(&'static self, router: Router<State>, path: &str) -> Router<State>;
@@ -20,6 +21,13 @@ pub(in super::super) trait RouterExt {
 	fn ruma_route<H, T>(self, handler: &'static H) -> Self
 	where
 		H: RumaHandler<T>;
+
+	/// Routes every path of the endpoint `metadata` describes to an axum
+	/// handler which builds its own response, such as a streamed download.
+	fn ruma_raw_route<H, T>(self, metadata: &Metadata, handler: H) -> Self
+	where
+		H: Handler<T, State>,
+		T: 'static;
 }
 
 impl RouterExt for Router<State> {
@@ -29,14 +37,26 @@ fn ruma_route<H, T>(self, handler: &'static H) -> Self
 	{
 		handler.add_routes(self)
 	}
+
+	fn ruma_raw_route<H, T>(self, metadata: &Metadata, handler: H) -> Self
+	where
+		H: Handler<T, State>,
+		T: 'static,
+	{
+		let method = method_to_filter(&metadata.method);
+		metadata
+			.history
+			.all_paths()
+			.fold(self, |router, path| router.route(path, on(method, handler.clone())))
+	}
 }
 
 macro_rules! ruma_handler {
-	( $($tx:ident),* $(,)? ) => {
+	( $args:ident; $($tx:ident),* $(,)? ) => {
 		#[allow(non_snake_case)]
-		impl<Err, Req, Fut, Fun, $($tx,)*> RumaHandler<($($tx,)* Ruma<Req>,)> for Fun
+		impl<Err, Req, Fut, Fun, $($tx,)*> RumaHandler<($($tx,)* $args<Req>,)> for Fun
 		where
-			Fun: Fn($($tx,)* Ruma<Req>,) -> Fut + Send + Sync + 'static,
+			Fun: Fn($($tx,)* $args<Req>,) -> Fut + Send + Sync + 'static,
 			Fut: Future<Output = Result<Req::OutgoingResponse, Err>> + Send,
 			Req: IncomingRequest + Send + Sync + 'static,
 			Err: IntoResponse + Send,
@@ -58,11 +78,16 @@ fn add_route(&'static self, router: Router<State>, path: &str) -> Router<State>
 		}
 	}
 }
-ruma_handler!();
-ruma_handler!(T1);
-ruma_handler!(T1, T2);
-ruma_handler!(T1, T2, T3);
-ruma_handler!(T1, T2, T3, T4);
+ruma_handler!(Ruma;);
+ruma_handler!(Ruma; T1);
+ruma_handler!(Ruma; T1, T2);
+ruma_handler!(Ruma; T1, T2, T3);
+ruma_handler!(Ruma; T1, T2, T3, T4);
+ruma_handler!(RumaStreamed;);
+ruma_handler!(RumaStreamed; T1);
+ruma_handler!(RumaStreamed; T1, T2);
+ruma_handler!(RumaStreamed; T1, T2, T3);
+ruma_handler!(RumaStreamed; T1, T2, T3, T4);
 
 const fn method_to_filter(method: &Method) -> MethodFilter {
 	match *method {
@@ -0,0 +1,97 @@
+use axum::RequestPartsExt;
+use axum_client_ip::ClientIp;
+use conduwuit::Result;
+use ruma::api::{
+	Metadata,
+	client::{
+		account::{check_registration_token_validity, register},
+		knock::knock_room,
+		media::{create_content, create_content_async, create_mxc_uri},
+		membership::{invite_user, join_room_by_id, join_room_by_id_or_alias},
+		message::send_message_event,
+		redact::redact_event,
+		session::login,
+		state::send_state_event,
+	},
+	federation::{
+		backfill::get_backfill,
+		event::{get_missing_events, get_room_state, get_room_state_ids},
+	},
+};
+use service::{
+	Services,
+	ratelimit::{Class, Key},
+};
+
+use super::{auth::Auth, request::Request};
+
+/// Counts the request against each of the requester's buckets for its kind of
+/// request, if it has any: the device's and the user's for logged in users, or
+/// the IP address's otherwise.
+///
+/// Requests from other servers are delayed for a while before being rejected.
+pub(super) async fn check(
+	services: &Services,
+	request: &mut Request,
+	auth: &Auth,
+	metadata: &Metadata,
+) -> Result {
+	let Some(class) = class(metadata) else {
+		return Ok(());
+	};
+
+	if let Some(origin) = &auth.origin {
+		return services.ratelimit.throttle_request(origin).await;
+	}
+
+	// Appservices are exempt unless their registration says otherwise
+	if auth
+		.appservice_info
+		.as_ref()
+		.is_some_and(|info| info.registration.rate_limited != Some(true))
+	{
+		return Ok(());
+	}
+
+	// Logged in users usually share the address of a reverse proxy, so only
+	// anonymous requests are limited by address
+	let mut keys = Vec::with_capacity(2);
+	match &auth.sender_user {
+		| Some(user_id) => {
+			if let Some(device_id) = &auth.sender_device {
+				keys.push(Key::Device(user_id.clone(), device_id.clone()));
+			}
+
+			keys.push(Key::User(user_id.clone()));
+		},
+		| None => match request.parts.extract::<ClientIp>().await {
+			| Ok(ClientIp(ip)) => keys.push(Key::Ip(ip)),
+			| Err(_) => return Ok(()),
+		},
+	}
+
+	services.ratelimit.check(class, &keys).await
+}
+
+fn class(metadata: &Metadata) -> Option<Class> {
+	match metadata {
+		| &send_message_event::v3::Request::METADATA
+		| &send_state_event::v3::Request::METADATA
+		| &redact_event::v3::Request::METADATA => Some(Class::Message),
+		| &login::v3::Request::METADATA => Some(Class::Login),
+		| &register::v3::Request::METADATA
+		| &check_registration_token_validity::v1::Request::METADATA => Some(Class::Registration),
+		| &join_room_by_id::v3::Request::METADATA
+		| &join_room_by_id_or_alias::v3::Request::METADATA
+		| &knock_room::v3::Request::METADATA => Some(Class::Join),
+		| &invite_user::v3::Request::METADATA => Some(Class::Invite),
+		| &create_content::v3::Request::METADATA
+		| &create_content_async::v3::Request::METADATA
+		| &create_mxc_uri::v1::Request::METADATA => Some(Class::Media),
+		| &get_backfill::v1::Request::METADATA
+		| &get_missing_events::v1::Request::METADATA
+		| &get_room_state::v1::Request::METADATA
+		| &get_room_state_ids::v1::Request::METADATA => Some(Class::FederationRequest),
+		| _ => None,
+	}
+}
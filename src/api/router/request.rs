@@ -1,6 +1,6 @@
 use std::str;
 
-use axum::{RequestExt, RequestPartsExt, extract::Path};
+use axum::{RequestExt, RequestPartsExt, body::Body, extract::Path};
 use bytes::Bytes;
 use conduwuit::{Result, err};
 use http::request::Parts;
@@ -26,10 +26,23 @@ pub(super) struct Request {
 	pub(super) parts: Parts,
 }
 
-pub(super) async fn from(
+pub(super) async fn from(services: &Services, request: hyper::Request<Body>) -> Result<Request> {
+	let (mut request, body) = from_streamed(services, request).await?;
+
+	let max_body_size = services.server.config.max_request_size;
+	request.body = axum::body::to_bytes(body, max_body_size)
+		.await
+		.map_err(|e| err!(Request(TooLarge("Request body too large: {e}"))))?;
+
+	Ok(request)
+}
+
+/// Like `from`, but leaves the body unread for the caller to stream. The
+/// request's `body` is empty.
+pub(super) async fn from_streamed(
 	services: &Services,
-	request: hyper::Request<axum::body::Body>,
-) -> Result<Request> {
+	request: hyper::Request<Body>,
+) -> Result<(Request, Body)> {
 	let limited = request.with_limited_body();
 	let (mut parts, body) = limited.into_parts();
 
@@ -53,9 +66,5 @@ pub(super) async fn from(
 		}
 	}
 
-	let body = axum::body::to_bytes(body, max_body_size)
-		.await
-		.map_err(|e| err!(Request(TooLarge("Request body too large: {e}"))))?;
-
-	Ok(Request { path, query, body, parts })
+	Ok((Request { path, query, body: Bytes::new(), parts }, body))
 }
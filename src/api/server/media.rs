@@ -1,7 +1,9 @@
-use axum::extract::State;
+use axum::{body::Body, extract::State, response::Response};
 use axum_client_ip::ClientIp;
 use conduwuit::{Err, Result, utils::content_disposition::make_content_disposition};
-use conduwuit_service::media::{Dim, FileMeta};
+use conduwuit_service::media::{Dim, FileMeta, FileStream, multipart};
+use futures::TryStreamExt;
+use http::header::CONTENT_TYPE;
 use ruma::{
 	Mxc,
 	api::federation::authenticated_media::{
@@ -13,7 +15,7 @@
 
 /// # `GET /_matrix/federation/v1/media/download/{mediaId}`
 ///
-/// Load media from our server.
+/// Load media from our server, streamed from storage.
 #[tracing::instrument(
 	name = "media_get",
 	level = "debug",
@@ -24,33 +26,32 @@ pub(crate) async fn get_content_route(
 	State(services): State<crate::State>,
 	ClientIp(client): ClientIp,
 	body: Ruma<get_content::v1::Request>,
-) -> Result<get_content::v1::Response> {
+) -> Result<Response> {
 	let mxc = Mxc {
 		server_name: services.globals.server_name(),
 		media_id: &body.media_id,
 	};
 
-	let Some(FileMeta {
+	let Some(FileStream {
 		content,
 		content_type,
 		content_disposition,
-	}) = services.media.get(&mxc).await?
+		..
+	}) = services.media.get_stream(&mxc, None).await?
 	else {
 		return Err!(Request(NotFound("Media not found.")));
 	};
 
 	let content_disposition =
 		make_content_disposition(content_disposition.as_ref(), content_type.as_deref(), None);
-	let content = Content {
-		file: content.expect("entire file contents"),
-		content_type: content_type.map(Into::into),
-		content_disposition: Some(content_disposition),
-	};
+	let (content_type, body) =
+		multipart::encode(content_type.as_deref(), &content_disposition, content);
 
-	Ok(get_content::v1::Response {
-		content: FileOrLocation::File(content),
-		metadata: ContentMetadata::new(),
-	})
+	let body = Body::from_stream(body.map_err(|e| std::io::Error::other(e.to_string())));
+
+	Ok(Response::builder()
+		.header(CONTENT_TYPE, content_type)
+		.body(body)?)
 }
 
 /// # `GET /_matrix/federation/v1/media/thumbnail/{mediaId}`
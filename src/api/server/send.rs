@@ -146,6 +146,18 @@ async fn process_inbound_transaction(
 	txn_key: TxnKey,
 	sender: Sender<WrappedTransactionResponse>,
 ) {
+	// Servers sending more PDUs than their quota allows are held back here,
+	// which also keeps them from sending their next transaction
+	services
+		.ratelimit
+		.throttle_pdus(body.origin(), body.pdus.len())
+		.await;
+
+	services
+		.sending
+		.stats
+		.record_received(body.origin(), body.pdus.len(), body.edus.len());
+
 	let txn_start_time = Instant::now();
 	let pdus = body
 		.pdus
@@ -171,6 +171,101 @@ pub fn check(config: &Config) -> Result {
 		));
 	}
 
+	let captcha = &config.captcha;
+	match captcha.provider.as_str() {
+		| "pow" if !(1..=32).contains(&captcha.pow_difficulty) => {
+			return Err!(Config(
+				"captcha.pow_difficulty",
+				"Proof-of-work difficulty must be between 1 and 32 bits."
+			));
+		},
+		| provider @ ("hcaptcha" | "turnstile")
+			if captcha.site_key.is_none() || captcha.secret_key.is_none() =>
+		{
+			return Err!(Config(
+				"captcha.secret_key",
+				"A site key and secret key are required for {provider}."
+			));
+		},
+		| "recaptcha" if captcha.site_key.is_some() != captcha.secret_key.is_some() => {
+			return Err!(Config(
+				"captcha.secret_key",
+				"A captcha site key and secret key must be set together."
+			));
+		},
+		| "pow" | "recaptcha" | "hcaptcha" | "turnstile" => {},
+		| provider => {
+			return Err!(Config("captcha.provider", "Unknown captcha provider {provider:?}."));
+		},
+	}
+
+	if let Some(sms) = &config.sms {
+		if sms.gateway_url.is_some() == !sms.command.is_empty() {
+			return Err!(Config(
+				"sms.gateway_url",
+				"Exactly one of an SMS gateway URL or command must be set."
+			));
+		}
+
+		if !matches!(sms.gateway_format.as_str(), "json" | "form" | "query") {
+			return Err!(Config(
+				"sms.gateway_format",
+				"Must be one of \"json\", \"form\" or \"query\"."
+			));
+		}
+
+		if http::Method::from_bytes(sms.gateway_method.as_bytes()).is_err() {
+			return Err!(Config("sms.gateway_method", "Invalid HTTP method."));
+		}
+	}
+
+	match config.media_storage.backend.as_str() {
+		| "filesystem" => {},
+		| "s3" =>
+			if config.media_storage.s3.is_none() {
+				return Err!(Config(
+					"media_storage.backend",
+					"The S3 media storage backend requires [global.media_storage.s3] to be \
+					 configured."
+				));
+			},
+		| backend => {
+			return Err!(Config(
+				"media_storage.backend",
+				"Unknown media storage backend {backend:?}."
+			));
+		},
+	}
+
+	let ldap = &config.ldap;
+	if ldap.sync_interval > 0 {
+		if !ldap.enable {
+			return Err!(Config(
+				"ldap.sync_interval",
+				"LDAP synchronisation requires LDAP to be enabled."
+			));
+		}
+
+		if ldap
+			.bind_dn
+			.as_ref()
+			.is_some_and(|bind_dn| bind_dn.contains("{username}"))
+		{
+			return Err!(Config(
+				"ldap.bind_dn",
+				"LDAP synchronisation cannot bind as the user logging in. Set a bind DN and \
+				 password file it may search with."
+			));
+		}
+
+		if !matches!(ldap.removed_users.as_str(), "lock" | "deactivate") {
+			return Err!(Config(
+				"ldap.removed_users",
+				"Must be either \"lock\" or \"deactivate\"."
+			));
+		}
+	}
+
 	if config.allow_registration
 		&& config.yes_i_am_very_very_sure_i_want_an_open_registration_server_prone_to_abuse
 		&& config.registration_token.is_none()
@@ -254,6 +349,64 @@ pub fn check(config: &Config) -> Result {
 		));
 	}
 
+	if let Some(id) = config.sso.providers.keys().find(|id| {
+		id.is_empty()
+			|| !id
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '~' | '-'))
+	}) {
+		return Err!(Config(
+			"sso.providers",
+			"Identity provider ID {id:?} may only contain letters, digits and the characters \
+			 '.', '_', '~' and '-'."
+		));
+	}
+
+	if config.jwt.enable && config.jwt.jwks_file.is_none() {
+		let algorithm = config.jwt.algorithm.as_str();
+		if !matches!(algorithm, "HS256" | "RS256" | "ES256") {
+			return Err!(Config(
+				"jwt.algorithm",
+				"Unsupported algorithm {algorithm:?}, expected \"HS256\", \"RS256\" or \
+				 \"ES256\"."
+			));
+		}
+
+		if algorithm == "HS256" && config.jwt.secret.is_none() {
+			return Err!(Config("jwt.secret", "A secret is required for HS256."));
+		}
+
+		if algorithm != "HS256" && config.jwt.public_key_file.is_none() {
+			return Err!(Config(
+				"jwt.public_key_file",
+				"A public key is required for {algorithm}, or set jwt.jwks_file."
+			));
+		}
+	}
+
+	if config.oauth.enable {
+		if config.oauth.issuer.is_none() {
+			return Err!(Config(
+				"oauth.issuer",
+				"The authorization server's issuer is required."
+			));
+		}
+
+		if config.oauth.client_id.is_none() {
+			return Err!(Config(
+				"oauth.client_id",
+				"A client ID is required to introspect access tokens."
+			));
+		}
+
+		if !config.sso.providers.is_empty() || config.jwt.enable {
+			return Err!(Config(
+				"oauth.enable",
+				"Single sign-on and JWT login cannot be used when authentication is delegated."
+			));
+		}
+	}
+
 	Ok(())
 }
 
@@ -1,4 +1,10 @@
-use std::sync::atomic::AtomicU32;
+pub mod prometheus;
+mod request;
+
+use std::{
+	collections::BTreeMap,
+	sync::atomic::{AtomicU32, AtomicU64},
+};
 
 use tokio::runtime;
 #[cfg(feature = "tokio_metrics")]
@@ -6,6 +12,9 @@
 #[cfg(all(tokio_unstable, feature = "tokio_metrics"))]
 use tokio_metrics::{RuntimeIntervals, RuntimeMonitor};
 
+pub use self::request::{LATENCY_BUCKETS, LongPoll, RouteStats};
+use crate::SyncMutex;
+
 pub struct Metrics {
 	_runtime: Option<runtime::Handle>,
 
@@ -24,6 +33,10 @@ pub struct Metrics {
 	pub requests_handle_active: AtomicU32,
 	pub requests_handle_finished: AtomicU32,
 	pub requests_panic: AtomicU32,
+
+	routes: SyncMutex<BTreeMap<(String, String), RouteStats>>,
+	pub sync_longpoll_active: AtomicU64,
+	pub sync_longpoll_total: AtomicU64,
 }
 
 impl Metrics {
@@ -52,6 +65,10 @@ pub fn new(runtime: Option<&runtime::Handle>) -> Self {
 			requests_handle_active: AtomicU32::new(0),
 			requests_handle_finished: AtomicU32::new(0),
 			requests_panic: AtomicU32::new(0),
+
+			routes: SyncMutex::new(BTreeMap::new()),
+			sync_longpoll_active: AtomicU64::new(0),
+			sync_longpoll_total: AtomicU64::new(0),
 		}
 	}
 
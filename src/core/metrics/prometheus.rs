@@ -0,0 +1,67 @@
+use std::fmt::{Display, Write};
+
+/// Metric types of the Prometheus text exposition format.
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+	Counter,
+	Gauge,
+	Histogram,
+}
+
+/// Writer for the Prometheus text exposition format. Each family is declared
+/// once with its help text and type, followed by its samples.
+#[derive(Debug, Default)]
+pub struct Text {
+	out: String,
+}
+
+impl Text {
+	#[must_use]
+	pub fn new() -> Self { Self::default() }
+
+	pub fn family(&mut self, name: &str, kind: Kind, help: &str) -> &mut Self {
+		let kind = match kind {
+			| Kind::Counter => "counter",
+			| Kind::Gauge => "gauge",
+			| Kind::Histogram => "histogram",
+		};
+
+		let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+		let _ = writeln!(self.out, "# HELP {name} {help}");
+		let _ = writeln!(self.out, "# TYPE {name} {kind}");
+		self
+	}
+
+	pub fn sample<V: Display>(
+		&mut self,
+		name: &str,
+		labels: &[(&str, &str)],
+		value: V,
+	) -> &mut Self {
+		self.out.push_str(name);
+		if !labels.is_empty() {
+			self.out.push('{');
+			for (i, (label, value)) in labels.iter().enumerate() {
+				if i > 0 {
+					self.out.push(',');
+				}
+
+				let _ = write!(self.out, "{label}=\"{}\"", escape(value));
+			}
+			self.out.push('}');
+		}
+
+		let _ = writeln!(self.out, " {value}");
+		self
+	}
+
+	#[must_use]
+	pub fn finish(self) -> String { self.out }
+}
+
+fn escape(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
@@ -0,0 +1,74 @@
+use std::{collections::BTreeMap, sync::atomic::Ordering, time::Duration};
+
+use http::{Method, StatusCode};
+
+use super::Metrics;
+
+/// Upper bounds in seconds of the request latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 12] =
+	[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Requests handled on one route, keyed in [`Metrics`] by method and the
+/// route's path template.
+#[derive(Clone, Debug, Default)]
+pub struct RouteStats {
+	/// Number of responses by status code.
+	pub responses: BTreeMap<u16, u64>,
+
+	/// Number of requests which took no longer than the corresponding bound
+	/// in [`LATENCY_BUCKETS`] but longer than the one before it.
+	pub buckets: [u64; LATENCY_BUCKETS.len()],
+
+	/// Total time spent handling requests, in seconds.
+	pub seconds: f64,
+
+	pub count: u64,
+}
+
+/// Counts a sync request as long-polling until dropped.
+pub struct LongPoll<'a>(&'a Metrics);
+
+impl Metrics {
+	pub fn record_request(
+		&self,
+		method: &Method,
+		route: Option<&str>,
+		status: StatusCode,
+		elapsed: Duration,
+	) {
+		let route = route.unwrap_or("unmatched");
+		let mut routes = self.routes.lock();
+		let stats = routes
+			.entry((method.as_str().to_owned(), route.to_owned()))
+			.or_default();
+
+		let seconds = elapsed.as_secs_f64();
+		if let Some(bucket) = LATENCY_BUCKETS
+			.iter()
+			.position(|bound| seconds <= *bound)
+			.and_then(|i| stats.buckets.get_mut(i))
+		{
+			*bucket = bucket.saturating_add(1);
+		}
+
+		let responses = stats.responses.entry(status.as_u16()).or_default();
+		*responses = responses.saturating_add(1);
+		stats.seconds += seconds;
+		stats.count = stats.count.saturating_add(1);
+	}
+
+	/// Snapshot of the requests handled so far on each route.
+	#[must_use]
+	pub fn routes(&self) -> BTreeMap<(String, String), RouteStats> { self.routes.lock().clone() }
+
+	#[must_use]
+	pub fn sync_longpoll(&self) -> LongPoll<'_> {
+		self.sync_longpoll_active.fetch_add(1, Ordering::Relaxed);
+		self.sync_longpoll_total.fetch_add(1, Ordering::Relaxed);
+		LongPoll(self)
+	}
+}
+
+impl Drop for LongPoll<'_> {
+	fn drop(&mut self) { self.0.sync_longpoll_active.fetch_sub(1, Ordering::Relaxed); }
+}
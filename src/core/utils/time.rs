@@ -48,6 +48,13 @@ pub fn rfc2822_from_seconds(epoch: i64) -> String {
 		.to_rfc2822()
 }
 
+#[inline]
+pub fn parse_rfc2822(date: &str) -> Result<SystemTime> {
+	chrono::DateTime::parse_from_rfc2822(date)
+		.map(Into::into)
+		.map_err(|error| err!("{date:?} is not a valid RFC 2822 date: {error}"))
+}
+
 #[must_use]
 pub fn format(ts: SystemTime, str: &str) -> String {
 	use chrono::{DateTime, Utc};
@@ -8,6 +8,7 @@
 mod memory_usage;
 mod open;
 mod repair;
+mod stats;
 
 use std::{
 	ffi::CStr,
@@ -23,6 +24,7 @@
 	WaitForCompactOptions,
 };
 
+pub use self::stats::Stats;
 use crate::{
 	Context,
 	pool::Pool,
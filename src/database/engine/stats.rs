@@ -0,0 +1,52 @@
+use std::ffi::CStr;
+
+use conduwuit::{Result, implement};
+use rocksdb::perf::get_memory_usage_stats;
+
+use super::Engine;
+use crate::{or_else, util::result};
+
+/// Point-in-time figures about the memory used by the database and the work
+/// it is doing in the background. Sizes are in bytes.
+#[derive(Debug, Default)]
+pub struct Stats {
+	pub mem_table_total: u64,
+	pub mem_table_unflushed: u64,
+	pub table_readers: u64,
+	pub row_cache: u64,
+	pub col_cache: Vec<(String, u64)>,
+	pub running_compactions: u64,
+	pub running_flushes: u64,
+	pub background_errors: u64,
+}
+
+#[implement(Engine)]
+pub fn stats(&self) -> Result<Stats> {
+	let row_cache = self.ctx.row_cache.lock();
+	let usage =
+		get_memory_usage_stats(Some(&[&self.db]), Some(&[&*row_cache])).or_else(or_else)?;
+
+	let col_cache = self
+		.ctx
+		.col_cache
+		.lock()
+		.iter()
+		.map(|(name, cache)| Ok((name.clone(), u64::try_from(cache.get_usage())?)))
+		.collect::<Result<_>>()?;
+
+	Ok(Stats {
+		mem_table_total: usage.mem_table_total,
+		mem_table_unflushed: usage.mem_table_unflushed,
+		table_readers: usage.mem_table_readers_total,
+		row_cache: u64::try_from(row_cache.get_usage())?,
+		col_cache,
+		running_compactions: self.property_global(c"rocksdb.num-running-compactions")?,
+		running_flushes: self.property_global(c"rocksdb.num-running-flushes")?,
+		background_errors: self.property_global(c"rocksdb.background-errors")?,
+	})
+}
+
+#[implement(Engine)]
+fn property_global(&self, name: &CStr) -> Result<u64> {
+	result(self.db.property_int_value(name)).map(Option::unwrap_or_default)
+}
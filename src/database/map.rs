@@ -30,7 +30,10 @@
 	fmt::{Debug, Display},
 	future::Future,
 	pin::Pin,
-	sync::Arc,
+	sync::{
+		Arc,
+		atomic::{AtomicU64, Ordering},
+	},
 };
 
 use conduwuit::Result;
@@ -51,6 +54,8 @@ pub struct Map {
 	read_options: ReadOptions,
 	cache_read_options: ReadOptions,
 	write_options: WriteOptions,
+	cache_hits: AtomicU64,
+	cache_misses: AtomicU64,
 }
 
 impl Map {
@@ -63,6 +68,8 @@ pub(crate) fn open(db: &Arc<Engine>, name: &'static str) -> Result<Arc<Self>> {
 			read_options: read_options_default(db),
 			cache_read_options: cache_read_options_default(db),
 			write_options: write_options_default(db),
+			cache_hits: AtomicU64::new(0),
+			cache_misses: AtomicU64::new(0),
 		}))
 	}
 
@@ -85,6 +92,16 @@ pub fn property_integer(&self, name: &CStr) -> Result<u64> {
 	#[inline]
 	pub fn property(&self, name: &str) -> Result<String> { self.db.property(&self.cf(), name) }
 
+	/// Number of point lookups answered from the cache and the number which
+	/// had to go to disk, since startup.
+	#[inline]
+	pub fn cache_stats(&self) -> (u64, u64) {
+		(
+			self.cache_hits.load(Ordering::Relaxed),
+			self.cache_misses.load(Ordering::Relaxed),
+		)
+	}
+
 	#[inline]
 	pub fn name(&self) -> &str { self.name }
 
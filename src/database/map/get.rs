@@ -1,4 +1,8 @@
-use std::{convert::AsRef, fmt::Debug, sync::Arc};
+use std::{
+	convert::AsRef,
+	fmt::Debug,
+	sync::{Arc, atomic::Ordering},
+};
 
 use conduwuit::{Err, Result, err, implement, utils::result::MapExpect};
 use futures::{Future, FutureExt, TryFutureExt, future::ready};
@@ -52,6 +56,12 @@ pub(crate) fn get_cached<K>(&self, key: &K) -> Result<Option<Handle<'_>>>
 	K: AsRef<[u8]> + Debug + ?Sized,
 {
 	let res = self.get_blocking_opts(key, &self.cache_read_options);
+	let counter = match &res {
+		| Err(error) if is_incomplete(error) => &self.cache_misses,
+		| _ => &self.cache_hits,
+	};
+
+	counter.fetch_add(1, Ordering::Relaxed);
 	cached_handle_from(res)
 }
 
@@ -108,14 +108,34 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "localpart_email",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "localpart_msisdn",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "mediaid_eventid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "mediaid_file",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "mediaid_pending",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "mediaid_quarantine",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "mediaid_user",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "msisdn_localpart",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "onetimekeyid_onetimekeys",
 		..descriptor::RANDOM_SMALL
@@ -153,10 +173,22 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "referencedevents",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "refreshtoken_info",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "registrationtoken_info",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "reportertarget_reportid",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "reportid_report",
+		..descriptor::SEQUENTIAL_SMALL
+	},
 	Descriptor {
 		name: "roomid_invitedcount",
 		..descriptor::RANDOM_SMALL
@@ -173,6 +205,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomid_pduleaves",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomid_purgejob",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomid_roomversion",
 		..descriptor::RANDOM_SMALL
@@ -311,6 +347,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		key_size_hint: Some(48),
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "ssoidpsub_userid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "statehash_shortstatehash",
 		val_size_hint: Some(8),
@@ -331,6 +371,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "todeviceid_events",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "termuserid_directory",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "tofrom_relation",
 		key_size_hint: Some(8),
@@ -354,10 +398,18 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userdeviceid_metadata",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userdeviceid_refreshtoken",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdeviceid_token",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userdeviceid_tokenexpiresat",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdevicesessionid_uiaainfo",
 		..descriptor::RANDOM_SMALL
@@ -382,6 +434,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_dehydrateddevice",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_directoryprofile",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_devicelistversion",
 		..descriptor::RANDOM_SMALL
@@ -398,6 +454,18 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_masterkeyid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_mediadaily",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userid_mediaquota",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userid_mediausage",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_origin",
 		..descriptor::RANDOM
@@ -422,6 +490,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_presenceid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_ratelimitoverride",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_selfsigningkeyid",
 		..descriptor::RANDOM_SMALL
@@ -430,6 +502,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userid_usersigningkeyid",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "useridmediaid_pending",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "useridprofilekey_value",
 		..descriptor::RANDOM_SMALL
@@ -462,6 +538,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userroomid_knockedstate",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userroomid_public",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userroomid_notificationcount",
 		..descriptor::RANDOM
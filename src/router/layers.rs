@@ -151,18 +151,25 @@ fn cors_layer(_server: &Server) -> CorsLayer {
 		Method::OPTIONS,
 	];
 
-	let headers: [HeaderName; 5] = [
+	let headers: [HeaderName; 7] = [
 		header::ORIGIN,
 		HeaderName::from_lowercase(b"x-requested-with").unwrap(),
 		header::CONTENT_TYPE,
 		header::ACCEPT,
 		header::AUTHORIZATION,
+		header::IF_MATCH,
+		header::IF_NONE_MATCH,
 	];
 
+	// Read by clients of the MSC4108 rendezvous endpoints
+	let exposed_headers: [HeaderName; 4] =
+		[header::ETAG, header::EXPIRES, header::LAST_MODIFIED, header::LOCATION];
+
 	CorsLayer::new()
 		.allow_origin(cors::Any)
 		.allow_methods(METHODS)
 		.allow_headers(headers)
+		.expose_headers(exposed_headers)
 		.max_age(Duration::from_secs(86400))
 }
 
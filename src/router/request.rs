@@ -1,11 +1,11 @@
 use std::{
 	fmt::Debug,
 	sync::{Arc, atomic::Ordering},
-	time::Duration,
+	time::{Duration, Instant},
 };
 
 use axum::{
-	extract::State,
+	extract::{MatchedPath, State},
 	response::{IntoResponse, Response},
 };
 use conduwuit::{Result, debug, debug_error, debug_warn, err, error, trace};
@@ -33,6 +33,13 @@ pub(crate) async fn handle(
 
 	let uri = req.uri().clone();
 	let method = req.method().clone();
+	let route = services.server.config.metrics.enabled.then(|| {
+		req.extensions()
+			.get::<MatchedPath>()
+			.map(|path| path.as_str().to_owned())
+	});
+
+	let started = Instant::now();
 	let services_ = services.clone();
 	let parent = Span::current();
 	let task = services.server.runtime().spawn(async move {
@@ -49,9 +56,25 @@ pub(crate) async fn handle(
 		}
 	});
 
-	task.await
+	let result = task
+		.await
 		.map_err(unhandled)
-		.and_then(move |result| handle_result(&method, &uri, result))
+		.and_then(|result| handle_result(&method, &uri, result));
+
+	if let Some(route) = route {
+		let status = result
+			.as_ref()
+			.map_or_else(|status| *status, Response::status);
+
+		services.server.metrics.record_request(
+			&method,
+			route.as_deref(),
+			status,
+			started.elapsed(),
+		);
+	}
+
+	result
 }
 
 #[tracing::instrument(
@@ -0,0 +1,22 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum_server::{Handle as ServerHandle, bind};
+use conduwuit::{Result, info};
+use conduwuit_service::Services;
+
+use crate::router;
+
+/// Serve the metrics on their own listener until the server shuts down.
+pub(super) async fn serve(
+	services: Arc<Services>,
+	handle: ServerHandle<SocketAddr>,
+	addr: SocketAddr,
+) -> Result {
+	let (app, _guard) = router::build_metrics(&services);
+	let app = app.into_make_service_with_connect_info::<SocketAddr>();
+
+	info!("Serving metrics on {addr}");
+	bind(addr).handle(handle).serve(app).await?;
+
+	Ok(())
+}
@@ -1,3 +1,4 @@
+mod metrics;
 mod plain;
 #[cfg(feature = "direct_tls")]
 mod tls;
@@ -6,7 +7,7 @@
 use std::sync::Arc;
 
 use axum_server::Handle as ServerHandle;
-use conduwuit::{Result, err};
+use conduwuit::{Result, err, error};
 use conduwuit_service::Services;
 use tokio::sync::broadcast;
 
@@ -27,6 +28,15 @@ pub(super) async fn serve(
 			.map_err(|e| err!(error!("channel error: {e}")));
 	}
 
+	if let Some(addr) = config.metrics.address.filter(|_| config.metrics.enabled) {
+		let listener = metrics::serve(services.clone(), handle.clone(), addr);
+		server.runtime().spawn(async move {
+			if let Err(e) = listener.await {
+				error!("Metrics listener failed: {e}");
+			}
+		});
+	}
+
 	let addrs = config.get_bind_addrs();
 	let (app, _guard) = layers::build(&services)?;
 	if cfg!(unix) && config.unix_socket_path.is_some() {
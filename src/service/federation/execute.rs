@@ -45,6 +45,34 @@ pub async fn execute_synapse<T>(
 	self.execute_on(client, dest, request).await
 }
 
+/// Like execute() but returns the response once its headers have arrived, so
+/// that its body can be streamed. Fails if it was not successful.
+#[implement(super::Service)]
+#[tracing::instrument(skip_all, name = "stream", level = "debug")]
+pub async fn execute_stream<T>(&self, dest: &ServerName, request: T) -> Result<Response>
+where
+	T: OutgoingRequest + Debug + Send,
+{
+	let client = &self.services.client.federation;
+	let (actual, request) = self.build_request(dest, request).await?;
+	let url = request.url().clone();
+	let method = request.method().clone();
+
+	debug!(%method, %url, "Sending request");
+	match client.execute(request).await {
+		| Ok(response) if response.status().is_success() => Ok(response),
+		| Ok(response) => {
+			let max_size = self.services.server.config.max_request_size.try_into()?;
+			Err(into_http_response(dest, &actual, &method, &url, response, max_size)
+				.await
+				.expect_err("response was not successful"))
+		},
+		| Err(error) =>
+			Err(handle_error(dest, &actual, &method, &url, error)
+				.expect_err("always returns error")),
+	}
+}
+
 #[implement(super::Service)]
 #[tracing::instrument(
 		name = "fed",
@@ -57,6 +85,15 @@ pub async fn execute_on<T>(
 	dest: &ServerName,
 	request: T,
 ) -> Result<T::IncomingResponse>
+where
+	T: OutgoingRequest + Send,
+{
+	let (actual, request) = self.build_request(dest, request).await?;
+	self.perform::<T>(dest, &actual, request, client).await
+}
+
+#[implement(super::Service)]
+async fn build_request<T>(&self, dest: &ServerName, request: T) -> Result<(ActualDest, Request)>
 where
 	T: OutgoingRequest + Send,
 {
@@ -71,7 +108,8 @@ pub async fn execute_on<T>(
 	let actual = self.services.resolver.get_actual_dest(dest).await?;
 	let request = into_http_request::<T>(&actual, request)?;
 	let request = self.prepare(dest, request)?;
-	self.perform::<T>(dest, &actual, request, client).await
+
+	Ok((actual, request))
 }
 
 #[implement(super::Service)]
@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+use conduwuit::{Result, debug, err, error, implement, result::LogErr};
+use ldap3::{
+	Ldap, Scope, SearchEntry,
+	adapters::{Adapter, EntriesOnly, PagedResults},
+};
+
+use super::{Entry, Service};
+use crate::users;
+
+const PAGE_SIZE: i32 = 500;
+
+/// Looks up every user matched by `ldap.filter`, keyed by their lowercased
+/// `ldap.uid_attribute`.
+#[implement(Service)]
+pub(super) async fn directory(&self) -> Result<HashMap<String, Entry>> {
+	let config = &self.services.server.config.ldap;
+	let uri = config
+		.uri
+		.as_ref()
+		.ok_or_else(|| err!(Ldap(error!("LDAP URI is not configured."))))?;
+
+	debug!(?uri, "LDAP creating connection...");
+	let (conn, mut ldap) = users::Service::create_ldap_connection(config, uri.as_str())
+		.await
+		.map_err(|e| err!(Ldap(error!("LDAP connection setup error: {e}"))))?;
+
+	let driver = self.services.server.runtime().spawn(async move {
+		match conn.drive().await {
+			| Err(e) => error!("LDAP connection error: {e}"),
+			| Ok(()) => debug!("LDAP connection completed."),
+		}
+	});
+
+	if let (Some(bind_dn), Some(bind_password_file)) =
+		(&config.bind_dn, &config.bind_password_file)
+	{
+		let bind_pw = String::from_utf8(std::fs::read(bind_password_file)?)?;
+		ldap.simple_bind(bind_dn, bind_pw.trim())
+			.await
+			.and_then(ldap3::LdapResult::success)
+			.map_err(|e| err!(Ldap(error!("LDAP bind error: {e}"))))?;
+	}
+
+	let uid = config.uid_attribute.as_str();
+	let attrs = [
+		uid,
+		config.name_attribute.as_str(),
+		config.mail_attribute.as_str(),
+		config.group_attribute.as_str(),
+	];
+
+	let filter = config.filter.replace("{username}", "*");
+	let mut users: HashMap<String, Entry> = search(&mut ldap, &config.base_dn, &filter, &attrs)
+		.await?
+		.into_iter()
+		.filter_map(|entry| {
+			let id = first(&entry, uid)?.to_lowercase();
+			let groups = entry
+				.attrs
+				.get(&config.group_attribute)
+				.into_iter()
+				.flatten()
+				.map(|group| group.to_lowercase())
+				.collect();
+
+			Some((id, Entry {
+				displayname: first(&entry, &config.name_attribute),
+				email: first(&entry, &config.mail_attribute),
+				groups,
+				..Entry::default()
+			}))
+		})
+		.collect();
+
+	if !config.admin_filter.is_empty() {
+		let admin_base_dn = if config.admin_base_dn.is_empty() {
+			&config.base_dn
+		} else {
+			&config.admin_base_dn
+		};
+
+		let admin_filter = config.admin_filter.replace("{username}", "*");
+		let admins = uids(search(&mut ldap, admin_base_dn, &admin_filter, &[uid]).await?, uid);
+		for (id, entry) in &mut users {
+			entry.admin = Some(admins.contains(id));
+		}
+	}
+
+	if !config.disabled_filter.is_empty() {
+		let disabled_filter = config.disabled_filter.replace("{username}", "*");
+		let disabled =
+			uids(search(&mut ldap, &config.base_dn, &disabled_filter, &[uid]).await?, uid);
+		for (id, entry) in &mut users {
+			entry.disabled = disabled.contains(id);
+		}
+	}
+
+	ldap.unbind()
+		.await
+		.map_err(|e| err!(Ldap(error!("LDAP unbind error: {e}"))))?;
+
+	driver.await.log_err().ok();
+
+	Ok(users)
+}
+
+/// Searches the whole subtree below `base`, a page at a time so that servers
+/// limiting the size of results return every entry.
+async fn search(
+	ldap: &mut Ldap,
+	base: &str,
+	filter: &str,
+	attrs: &[&str],
+) -> Result<Vec<SearchEntry>> {
+	let adapters: Vec<Box<dyn Adapter<'_, &str, Vec<&str>>>> =
+		vec![Box::new(EntriesOnly::new()), Box::new(PagedResults::new(PAGE_SIZE))];
+
+	let search_error =
+		|e: ldap3::LdapError| err!(Ldap(error!(?filter, "LDAP search error: {e}")));
+	let mut search = ldap
+		.streaming_search_with(adapters, base, Scope::Subtree, filter, attrs.to_vec())
+		.await
+		.map_err(search_error)?;
+
+	let mut entries = Vec::new();
+	while let Some(entry) = search.next().await.map_err(search_error)? {
+		entries.push(SearchEntry::construct(entry));
+	}
+
+	search.finish().await.success().map_err(search_error)?;
+
+	Ok(entries)
+}
+
+fn first(entry: &SearchEntry, attr: &str) -> Option<String> {
+	entry.attrs.get(attr)?.first().cloned()
+}
+
+fn uids(entries: Vec<SearchEntry>, uid: &str) -> HashSet<String> {
+	entries
+		.into_iter()
+		.filter_map(|entry| first(&entry, uid))
+		.map(|id| id.to_lowercase())
+		.collect()
+}
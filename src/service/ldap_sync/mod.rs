@@ -0,0 +1,570 @@
+//! Synchronises users who log in through LDAP with their directory entries.
+//!
+//! A worker periodically looks every such user up. Their display name, email
+//! and admin status follow their entry, they are joined to the rooms mapped
+//! to their groups and removed from those of groups they left, and users
+//! whose entry was removed or disabled are locked or deactivated, ending
+//! their sessions.
+
+#[cfg(feature = "ldap")]
+mod directory;
+#[cfg(test)]
+mod tests;
+
+use std::{
+	collections::{HashMap, HashSet},
+	mem,
+	sync::Arc,
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Result, Server, debug, debug_warn, err, implement, info, is_equal_to,
+	matrix::pdu::PduBuilder, warn,
+};
+use futures::{FutureExt, StreamExt};
+use lettre::Address;
+use ruma::{
+	Int, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	events::{
+		StateEventType,
+		room::{
+			member::{MembershipState, RoomMemberEventContent},
+			power_levels::RoomPowerLevelsEventContent,
+		},
+	},
+};
+use tokio::{
+	sync::{Mutex, Notify},
+	time::{MissedTickBehavior, interval},
+};
+
+use crate::{
+	Dep, admin, globals, rooms, rooms::state::RoomMutexGuard, threepid, user_directory, users,
+};
+
+pub struct Service {
+	interrupt: Notify,
+	syncing: Mutex<()>,
+	services: Services,
+}
+
+struct Services {
+	server: Arc<Server>,
+	admin: Dep<admin::Service>,
+	alias: Dep<rooms::alias::Service>,
+	globals: Dep<globals::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+	threepid: Dep<threepid::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+	user_directory: Dep<user_directory::Service>,
+	users: Dep<users::Service>,
+}
+
+/// What a synchronisation changed.
+#[derive(Debug, Default)]
+pub struct Synced {
+	/// Users who log in through LDAP.
+	pub users: usize,
+	/// Users whose profile or admin status was updated.
+	pub updated: usize,
+	/// Users who were locked or deactivated.
+	pub removed: usize,
+	/// Users missing from LDAP who were left alone, as too many were missing.
+	pub withheld: usize,
+	/// Users joined to the rooms of their groups.
+	pub joined: usize,
+	/// Users removed from the rooms of groups they left.
+	pub left: usize,
+}
+
+/// A user's entry in the directory.
+#[cfg_attr(not(feature = "ldap"), allow(dead_code))]
+#[derive(Debug, Default)]
+struct Entry {
+	displayname: Option<String>,
+	email: Option<String>,
+	/// Lowercased DNs of the groups the user is a member of.
+	groups: HashSet<String>,
+	/// Whether the user is matched by `ldap.admin_filter`, if one is set.
+	admin: Option<bool>,
+	disabled: bool,
+}
+
+/// What a synchronisation does with each user who logs in through LDAP.
+#[derive(Debug, Default)]
+struct Plan<'a> {
+	/// Users to update from their entry.
+	update: Vec<(OwnedUserId, &'a Entry)>,
+	/// Users whose entry was removed or disabled.
+	remove: Vec<OwnedUserId>,
+	/// Users whose entry is missing but who are not removed.
+	withheld: Vec<OwnedUserId>,
+}
+
+/// The rooms mapped to groups, with the lowercased DNs of their groups and the
+/// power levels those give.
+type GroupRooms = HashMap<OwnedRoomId, Vec<(String, Option<i64>)>>;
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			interrupt: Notify::new(),
+			syncing: Mutex::new(()),
+			services: Services {
+				server: args.server.clone(),
+				admin: args.depend::<admin::Service>("admin"),
+				alias: args.depend::<rooms::alias::Service>("rooms::alias"),
+				globals: args.depend::<globals::Service>("globals"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				threepid: args.depend::<threepid::Service>("threepid"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+				user_directory: args.depend::<user_directory::Service>("user_directory"),
+				users: args.depend::<users::Service>("users"),
+			},
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "ldap_sync", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result {
+		let config = &self.services.server.config.ldap;
+		if !cfg!(feature = "ldap") || !config.enable || config.sync_interval == 0 {
+			debug!("LDAP synchronisation is disabled");
+			return Ok(());
+		}
+
+		let mut i = interval(Duration::from_secs(config.sync_interval));
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			match self.sync().await {
+				| Ok(synced) => debug!(?synced, "Synchronised users with LDAP"),
+				| Err(e) => warn!("Failed to synchronise users with LDAP: {e}"),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Synchronises every user who logs in through LDAP with their entry.
+#[implement(Service)]
+pub async fn sync(&self) -> Result<Synced> {
+	let _syncing = self.syncing.lock().await;
+
+	let directory = self.directory().await?;
+	let rooms = self.group_rooms().await;
+
+	let users: Vec<OwnedUserId> = self
+		.services
+		.users
+		.list_local_users()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let mut ldap_users = Vec::new();
+	for user_id in users {
+		if self
+			.services
+			.users
+			.origin(&user_id)
+			.await
+			.is_ok_and(is_equal_to!("ldap"))
+			&& !self
+				.services
+				.users
+				.is_deactivated(&user_id)
+				.await
+				.unwrap_or(true)
+		{
+			ldap_users.push(user_id);
+		}
+	}
+
+	let mut synced = Synced {
+		users: ldap_users.len(),
+		..Synced::default()
+	};
+
+	let plan = plan(ldap_users, &directory, self.services.server.config.ldap.max_removed_users);
+	if !plan.withheld.is_empty() {
+		synced.withheld = plan.withheld.len();
+		warn!(
+			count = synced.withheld,
+			entries = directory.len(),
+			"Not removing users missing from LDAP, as too many are; check the LDAP search or \
+			 raise `max_removed_users`"
+		);
+	}
+
+	let mut ldap_users = HashMap::new();
+	for (user_id, entry) in plan.update {
+		match self.update_user(&user_id, entry).await {
+			| Ok(true) => synced.updated = synced.updated.saturating_add(1),
+			| Ok(false) => {},
+			| Err(e) => warn!(%user_id, "Failed to update user from LDAP: {e}"),
+		}
+
+		ldap_users.insert(user_id, Some(entry));
+	}
+
+	for user_id in plan.remove {
+		match self.remove_user(&user_id).await {
+			| Ok(true) => synced.removed = synced.removed.saturating_add(1),
+			| Ok(false) => {},
+			| Err(e) => warn!(%user_id, "Failed to remove user gone from LDAP: {e}"),
+		}
+
+		ldap_users.insert(user_id, None);
+	}
+
+	for (room_id, groups) in &rooms {
+		if let Err(e) = self
+			.sync_room(room_id, groups, &ldap_users, &mut synced)
+			.await
+		{
+			warn!(%room_id, "Failed to synchronise room with LDAP groups: {e}");
+		}
+	}
+
+	Ok(synced)
+}
+
+/// Sorts users by whether they still have an enabled entry. None are removed
+/// if the directory is empty or more than `max_removed` would be, as a failed
+/// or misconfigured search looks just like that.
+fn plan(
+	users: Vec<OwnedUserId>,
+	directory: &HashMap<String, Entry>,
+	max_removed: usize,
+) -> Plan<'_> {
+	let mut plan = Plan::default();
+	for user_id in users {
+		match directory
+			.get(&user_id.localpart().to_lowercase())
+			.filter(|entry| !entry.disabled)
+		{
+			| Some(entry) => plan.update.push((user_id, entry)),
+			| None => plan.remove.push(user_id),
+		}
+	}
+
+	if directory.is_empty() || (max_removed != 0 && plan.remove.len() > max_removed) {
+		plan.withheld = mem::take(&mut plan.remove);
+	}
+
+	plan
+}
+
+#[cfg(not(feature = "ldap"))]
+#[implement(Service)]
+async fn directory(&self) -> Result<HashMap<String, Entry>> { Err!(FeatureDisabled("ldap")) }
+
+/// Brings a user's profile and admin status in line with their entry,
+/// returning whether anything changed.
+#[implement(Service)]
+async fn update_user(&self, user_id: &UserId, entry: &Entry) -> Result<bool> {
+	let mut updated = false;
+
+	if let Some(displayname) = &entry.displayname
+		&& self.services.users.displayname(user_id).await.ok().as_ref() != Some(displayname)
+	{
+		self.set_displayname(user_id, displayname).await;
+		updated = true;
+	}
+
+	if let Some(email) = &entry.email
+		&& !self
+			.services
+			.threepid
+			.get_email_for_localpart(user_id.localpart())
+			.await
+			.is_some_and(|current| current.to_string().eq_ignore_ascii_case(email))
+	{
+		let email = Address::try_from(email.clone())
+			.map_err(|e| err!("Email {email:?} in LDAP is invalid: {e}"))?;
+
+		self.services
+			.threepid
+			.associate_localpart_email(user_id.localpart(), &email)
+			.await?;
+
+		updated = true;
+	}
+
+	if let Some(admin) = entry.admin {
+		let is_admin = self.services.admin.user_is_admin(user_id).await;
+		if admin && !is_admin {
+			self.services.admin.make_user_admin(user_id).await?;
+			updated = true;
+		} else if !admin && is_admin {
+			self.services.admin.revoke_admin(user_id).await?;
+			updated = true;
+		}
+	}
+
+	Ok(updated)
+}
+
+/// Locks or deactivates a user whose entry was removed or disabled, returning
+/// whether anything changed.
+#[implement(Service)]
+async fn remove_user(&self, user_id: &UserId) -> Result<bool> {
+	let users = &self.services.users;
+	if self.services.server.config.ldap.removed_users == "deactivate" {
+		info!(%user_id, "Deactivating user removed from LDAP");
+		let rooms: Vec<OwnedRoomId> = self
+			.services
+			.state_cache
+			.rooms_joined(user_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		users.full_deactivate(user_id, &rooms).boxed().await?;
+	} else {
+		if users.is_locked(user_id).await? {
+			return Ok(false);
+		}
+
+		info!(%user_id, "Locking user removed from LDAP");
+		users
+			.lock_account(user_id, &self.services.globals.server_user)
+			.await;
+
+		users
+			.all_device_ids(user_id)
+			.for_each(|device_id| users.remove_device(user_id, device_id))
+			.await;
+	}
+
+	if self.services.admin.user_is_admin(user_id).await {
+		self.services.admin.revoke_admin(user_id).await?;
+	}
+
+	Ok(true)
+}
+
+/// Sets a user's display name and announces it in the rooms they are in.
+#[implement(Service)]
+async fn set_displayname(&self, user_id: &UserId, displayname: &str) {
+	self.services
+		.users
+		.set_displayname(user_id, Some(displayname.to_owned()));
+
+	self.services
+		.user_directory
+		.update_local_profile(user_id)
+		.await;
+
+	let rooms: Vec<OwnedRoomId> = self
+		.services
+		.state_cache
+		.rooms_joined(user_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for room_id in rooms {
+		let Ok(mut content) = self
+			.services
+			.state_accessor
+			.get_member(&room_id, user_id)
+			.await
+		else {
+			continue;
+		};
+
+		content.displayname = Some(displayname.to_owned());
+		content.join_authorized_via_users_server = None;
+		content.reason = None;
+
+		let state_lock = self.services.state.mutex.lock(&room_id).await;
+		if let Err(e) = self
+			.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder::state(user_id.to_string(), &content),
+				user_id,
+				Some(&room_id),
+				&state_lock,
+			)
+			.await
+		{
+			debug_warn!(%room_id, %user_id, "Failed to update display name in room: {e}");
+		}
+	}
+}
+
+#[implement(Service)]
+async fn group_rooms(&self) -> GroupRooms {
+	let mut rooms = GroupRooms::new();
+	for (group, config) in &self.services.server.config.ldap.groups {
+		for room in &config.rooms {
+			match self.services.alias.resolve(room).await {
+				| Ok(room_id) => rooms
+					.entry(room_id)
+					.or_default()
+					.push((group.to_lowercase(), config.power_level)),
+				| Err(e) => warn!(%room, %group, "Failed to resolve room of LDAP group: {e}"),
+			}
+		}
+	}
+
+	rooms
+}
+
+/// Joins the members of a room's groups to it and removes other users who log
+/// in through LDAP, giving members the power level of their groups.
+#[implement(Service)]
+async fn sync_room(
+	&self,
+	room_id: &RoomId,
+	groups: &[(String, Option<i64>)],
+	ldap_users: &HashMap<OwnedUserId, Option<&Entry>>,
+	synced: &mut Synced,
+) -> Result {
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+	let mut power_levels = self
+		.services
+		.state_accessor
+		.room_state_get_content::<RoomPowerLevelsEventContent>(
+			room_id,
+			&StateEventType::RoomPowerLevels,
+			"",
+		)
+		.await
+		.unwrap_or_default();
+
+	let mut power_levels_changed = false;
+	for (user_id, entry) in ldap_users {
+		let level = group_level(groups, *entry);
+		let member = level.is_some();
+		let joined = self.services.state_cache.is_joined(user_id, room_id).await;
+		if member && !joined {
+			match self.join(user_id, room_id, &state_lock).await {
+				| Ok(()) => synced.joined = synced.joined.saturating_add(1),
+				| Err(e) => {
+					warn!(%room_id, %user_id, "Failed to join user to room of LDAP group: {e}");
+					continue;
+				},
+			}
+		} else if !member && joined {
+			match self.kick(user_id, room_id, &state_lock).await {
+				| Ok(()) => synced.left = synced.left.saturating_add(1),
+				| Err(e) => {
+					warn!(%room_id, %user_id, "Failed to remove user from room of LDAP group: {e}");
+					continue;
+				},
+			}
+		}
+
+		let current = power_levels.users.get(user_id).copied();
+		if !member {
+			power_levels_changed |= power_levels.users.remove(user_id).is_some();
+		} else if let Some(level) = level.flatten()
+			&& current != Some(level)
+		{
+			power_levels.users.insert(user_id.clone(), level);
+			power_levels_changed = true;
+		}
+	}
+
+	if power_levels_changed {
+		self.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder::state(String::new(), &power_levels),
+				&self.services.globals.server_user,
+				Some(room_id),
+				&state_lock,
+			)
+			.await?;
+	}
+
+	Ok(())
+}
+
+/// The power level a user's entry gives them in a room mapped to `groups`:
+/// `None` if they are in none of the groups, and `Some(None)` if their groups
+/// give no power level.
+fn group_level(groups: &[(String, Option<i64>)], entry: Option<&Entry>) -> Option<Option<Int>> {
+	let entry = entry?;
+	let mut levels = groups
+		.iter()
+		.filter(|(group, _)| entry.groups.contains(group))
+		.map(|(_, level)| *level)
+		.peekable();
+
+	levels.peek()?;
+	Some(levels.flatten().max().map(Int::new_saturating))
+}
+
+/// Has the server user invite a user to a room, and joins them to it.
+#[implement(Service)]
+async fn join(&self, user_id: &UserId, room_id: &RoomId, state_lock: &RoomMutexGuard) -> Result {
+	if !self.services.state_cache.is_invited(user_id, room_id).await {
+		self.services
+			.timeline
+			.build_and_append_pdu(
+				PduBuilder::state(
+					user_id.to_string(),
+					&RoomMemberEventContent::new(MembershipState::Invite),
+				),
+				&self.services.globals.server_user,
+				Some(room_id),
+				state_lock,
+			)
+			.await?;
+	}
+
+	let mut content = RoomMemberEventContent::new(MembershipState::Join);
+	content.displayname = self.services.users.displayname(user_id).await.ok();
+	content.avatar_url = self.services.users.avatar_url(user_id).await.ok();
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(user_id.to_string(), &content),
+			user_id,
+			Some(room_id),
+			state_lock,
+		)
+		.await?;
+
+	Ok(())
+}
+
+#[implement(Service)]
+async fn kick(&self, user_id: &UserId, room_id: &RoomId, state_lock: &RoomMutexGuard) -> Result {
+	let mut content = RoomMemberEventContent::new(MembershipState::Leave);
+	content.reason = Some("No longer in an LDAP group of this room".to_owned());
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(user_id.to_string(), &content),
+			&self.services.globals.server_user,
+			Some(room_id),
+			state_lock,
+		)
+		.await?;
+
+	Ok(())
+}
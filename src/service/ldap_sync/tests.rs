@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use ruma::{Int, OwnedUserId, owned_user_id};
+
+use super::{Entry, group_level, plan};
+
+fn entry(groups: &[&str]) -> Entry {
+	Entry {
+		groups: groups.iter().map(|&group| group.to_owned()).collect(),
+		..Entry::default()
+	}
+}
+
+fn users() -> Vec<OwnedUserId> {
+	vec![
+		owned_user_id!("@alice:example.com"),
+		owned_user_id!("@bob:example.com"),
+		owned_user_id!("@carol:example.com"),
+	]
+}
+
+#[test]
+fn plan_updates_users_with_entries() {
+	let directory = HashMap::from([
+		("alice".to_owned(), entry(&[])),
+		("bob".to_owned(), entry(&[])),
+		("carol".to_owned(), entry(&[])),
+	]);
+
+	let plan = plan(users(), &directory, 10);
+	assert_eq!(plan.update.len(), 3);
+	assert!(plan.remove.is_empty());
+	assert!(plan.withheld.is_empty());
+}
+
+#[test]
+fn plan_removes_missing_and_disabled_users() {
+	let directory = HashMap::from([
+		("alice".to_owned(), entry(&[])),
+		("bob".to_owned(), Entry { disabled: true, ..Entry::default() }),
+	]);
+
+	let plan = plan(users(), &directory, 10);
+	assert_eq!(plan.update.len(), 1);
+	assert_eq!(plan.remove, [
+		owned_user_id!("@bob:example.com"),
+		owned_user_id!("@carol:example.com")
+	]);
+	assert!(plan.withheld.is_empty());
+}
+
+#[test]
+fn plan_withholds_removals_from_empty_directory() {
+	let plan = plan(users(), &HashMap::new(), 0);
+	assert!(plan.update.is_empty());
+	assert!(plan.remove.is_empty());
+	assert_eq!(plan.withheld.len(), 3);
+}
+
+#[test]
+fn plan_withholds_too_many_removals() {
+	let directory = HashMap::from([("alice".to_owned(), entry(&[]))]);
+
+	let plan = plan(users(), &directory, 1);
+	assert_eq!(plan.update.len(), 1);
+	assert!(plan.remove.is_empty());
+	assert_eq!(plan.withheld.len(), 2);
+
+	let plan = super::plan(users(), &directory, 0);
+	assert_eq!(plan.remove.len(), 2);
+}
+
+#[test]
+fn group_level_joins_members_only() {
+	let groups = [
+		("cn=staff".to_owned(), Some(10)),
+		("cn=mods".to_owned(), Some(50)),
+		("cn=guests".to_owned(), None),
+	];
+
+	assert_eq!(group_level(&groups, None), None);
+	assert_eq!(group_level(&groups, Some(&entry(&["cn=other"]))), None);
+	assert_eq!(group_level(&groups, Some(&entry(&["cn=guests"]))), Some(None));
+	assert_eq!(
+		group_level(&groups, Some(&entry(&["cn=staff", "cn=mods"]))),
+		Some(Some(Int::from(50)))
+	);
+}
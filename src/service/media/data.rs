@@ -4,16 +4,25 @@
 	Err, Result, debug, debug_info, err,
 	utils::{ReadyExt, str_from_bytes, stream::TryIgnore, string_from_bytes},
 };
-use database::{Database, Interfix, Map};
-use futures::StreamExt;
-use ruma::{Mxc, OwnedMxcUri, UserId, http_headers::ContentDisposition};
+use database::{Database, Deserialized, Ignore, Interfix, Json, Map};
+use futures::{Stream, StreamExt};
+use ruma::{EventId, Mxc, OwnedMxcUri, OwnedUserId, UserId, http_headers::ContentDisposition};
 
-use super::{preview::UrlPreviewData, thumbnail::Dim};
+use super::{
+	pending::PendingUpload, preview::UrlPreviewData, quota::DailyUpload, thumbnail::Dim,
+};
 
 pub(crate) struct Data {
+	mediaid_eventid: Arc<Map>,
 	mediaid_file: Arc<Map>,
+	mediaid_pending: Arc<Map>,
+	mediaid_quarantine: Arc<Map>,
 	mediaid_user: Arc<Map>,
 	url_previews: Arc<Map>,
+	userid_mediadaily: Arc<Map>,
+	userid_mediaquota: Arc<Map>,
+	userid_mediausage: Arc<Map>,
+	useridmediaid_pending: Arc<Map>,
 }
 
 #[derive(Debug)]
@@ -26,9 +35,16 @@ pub(super) struct Metadata {
 impl Data {
 	pub(super) fn new(db: &Arc<Database>) -> Self {
 		Self {
+			mediaid_eventid: db["mediaid_eventid"].clone(),
 			mediaid_file: db["mediaid_file"].clone(),
+			mediaid_pending: db["mediaid_pending"].clone(),
+			mediaid_quarantine: db["mediaid_quarantine"].clone(),
 			mediaid_user: db["mediaid_user"].clone(),
 			url_previews: db["url_previews"].clone(),
+			userid_mediadaily: db["userid_mediadaily"].clone(),
+			userid_mediaquota: db["userid_mediaquota"].clone(),
+			userid_mediausage: db["userid_mediausage"].clone(),
+			useridmediaid_pending: db["useridmediaid_pending"].clone(),
 		}
 	}
 
@@ -40,16 +56,28 @@ pub(super) fn create_file_metadata(
 		content_disposition: Option<&ContentDisposition>,
 		content_type: Option<&str>,
 	) -> Result<Vec<u8>> {
-		let dim: &[u32] = &[dim.width, dim.height];
-		let key = (mxc, dim, content_disposition, content_type);
-		let key = database::serialize_key(key)?;
+		let key = Self::file_metadata_key(mxc, dim, content_disposition, content_type)?;
 		self.mediaid_file.insert(&key, []);
 		if let Some(user) = user {
 			let key = (mxc, user);
 			self.mediaid_user.put_raw(key, user);
 		}
 
-		Ok(key.to_vec())
+		Ok(key)
+	}
+
+	/// Key `create_file_metadata` will store the metadata under, which also
+	/// names the file.
+	pub(super) fn file_metadata_key(
+		mxc: &Mxc<'_>,
+		dim: &Dim,
+		content_disposition: Option<&ContentDisposition>,
+		content_type: Option<&str>,
+	) -> Result<Vec<u8>> {
+		let dim: &[u32] = &[dim.width, dim.height];
+		let key = (mxc, dim, content_disposition, content_type);
+
+		Ok(database::serialize_key(key)?.to_vec())
 	}
 
 	pub(super) async fn delete_file_mxc(&self, mxc: &Mxc<'_>) {
@@ -77,6 +105,12 @@ pub(super) async fn delete_file_mxc(&self, mxc: &Mxc<'_>) {
 				self.mediaid_user.remove(key);
 			})
 			.await;
+
+		self.mediaid_eventid
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| self.mediaid_eventid.remove(key))
+			.await;
 	}
 
 	/// Searches for all files with the given MXC
@@ -141,6 +175,17 @@ pub(super) async fn search_file_metadata(
 		Ok(Metadata { content_disposition, content_type, key })
 	}
 
+	/// Gets the user who uploaded an MXC, if it was uploaded by a user
+	pub(super) async fn get_uploader(&self, mxc: &Mxc<'_>) -> Option<OwnedUserId> {
+		let prefix = (mxc, Interfix);
+		self.mediaid_user
+			.stream_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_filter_map(|(_, user)| str_from_bytes(user).ok()?.try_into().ok())
+			.next()
+			.await
+	}
+
 	/// Gets all the MXCs associated with a user
 	pub(super) async fn get_all_user_mxcs(&self, user_id: &UserId) -> Vec<OwnedMxcUri> {
 		self.mediaid_user
@@ -153,6 +198,134 @@ pub(super) async fn get_all_user_mxcs(&self, user_id: &UserId) -> Vec<OwnedMxcUr
 			.await
 	}
 
+	/// Resolves once metadata is created for `mxc`.
+	pub(super) fn watch_file_metadata(
+		&self,
+		mxc: &Mxc<'_>,
+	) -> Result<impl Future<Output = ()> + Send + '_> {
+		let prefix = database::serialize_key((mxc, Interfix))?;
+
+		Ok(self.mediaid_file.watch_prefix(&prefix))
+	}
+
+	pub(super) fn add_reference(&self, mxc: &Mxc<'_>, event_id: &EventId) {
+		let key = (mxc, event_id);
+		self.mediaid_eventid.put_raw(key, []);
+	}
+
+	pub(super) fn referencing_events<'a>(
+		&'a self,
+		mxc: &'a Mxc<'_>,
+	) -> impl Stream<Item = &'a EventId> + Send + 'a {
+		let prefix = (mxc, Interfix);
+		self.mediaid_eventid
+			.keys_prefix(&prefix)
+			.ignore_err()
+			.map(|(_, event_id): (Ignore, &EventId)| event_id)
+	}
+
+	pub(super) fn remove_reference(&self, mxc: &Mxc<'_>, event_id: &EventId) {
+		let key = (mxc, event_id);
+		self.mediaid_eventid.del(key);
+	}
+
+	pub(super) fn put_pending_upload(&self, mxc: &str, upload: &PendingUpload) {
+		self.mediaid_pending.raw_put(mxc, Json(upload));
+		self.useridmediaid_pending
+			.put((&upload.user_id, mxc), upload.expires_at);
+	}
+
+	pub(super) async fn get_pending_upload(&self, mxc: &Mxc<'_>) -> Result<PendingUpload> {
+		self.mediaid_pending
+			.get(&mxc.to_string())
+			.await
+			.deserialized()
+	}
+
+	pub(super) fn remove_pending_upload(&self, mxc: &str, user_id: &UserId) {
+		self.mediaid_pending.remove(mxc);
+		self.useridmediaid_pending.del((user_id, mxc));
+	}
+
+	/// When each upload `user_id` has reserved expires, in milliseconds since
+	/// the unix epoch.
+	pub(super) fn user_pending_uploads<'a>(
+		&'a self,
+		user_id: &'a UserId,
+	) -> impl Stream<Item = u64> + Send + 'a {
+		let prefix = (user_id, Interfix);
+		self.useridmediaid_pending
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.map(|(_, expires_at): ((Ignore, Ignore), u64)| expires_at)
+	}
+
+	pub(super) fn pending_uploads(
+		&self,
+	) -> impl Stream<Item = (&str, PendingUpload)> + Send + '_ {
+		self.mediaid_pending.stream().ignore_err()
+	}
+
+	pub(super) async fn media_usage(&self, user_id: &UserId) -> u64 {
+		self.userid_mediausage
+			.get(user_id)
+			.await
+			.deserialized()
+			.unwrap_or(0)
+	}
+
+	pub(super) fn set_media_usage(&self, user_id: &UserId, bytes: u64) {
+		self.userid_mediausage.raw_put(user_id, bytes);
+	}
+
+	pub(super) fn all_media_usage(&self) -> impl Stream<Item = (&UserId, u64)> + Send + '_ {
+		self.userid_mediausage.stream().ignore_err()
+	}
+
+	pub(super) async fn media_quota(&self, user_id: &UserId) -> Option<u64> {
+		self.userid_mediaquota
+			.get(user_id)
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	pub(super) fn set_media_quota(&self, user_id: &UserId, quota: Option<u64>) {
+		match quota {
+			| Some(quota) => self.userid_mediaquota.raw_put(user_id, quota),
+			| None => self.userid_mediaquota.remove(user_id),
+		}
+	}
+
+	pub(super) async fn daily_upload(&self, user_id: &UserId) -> Option<DailyUpload> {
+		self.userid_mediadaily
+			.get(user_id)
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	pub(super) fn set_daily_upload(&self, user_id: &UserId, daily: &DailyUpload) {
+		self.userid_mediadaily.raw_put(user_id, Json(daily));
+	}
+
+	pub(super) fn quarantine_mxc(&self, mxc: &Mxc<'_>, quarantined: bool) {
+		let mxc = mxc.to_string();
+		if quarantined {
+			self.mediaid_quarantine.insert(&mxc, []);
+		} else {
+			self.mediaid_quarantine.remove(&mxc);
+		}
+	}
+
+	pub(super) async fn is_quarantined(&self, mxc: &Mxc<'_>) -> bool {
+		self.mediaid_quarantine.get(&mxc.to_string()).await.is_ok()
+	}
+
+	pub(super) fn quarantined_mxcs(&self) -> impl Stream<Item = &str> + Send + '_ {
+		self.mediaid_quarantine.keys().ignore_err()
+	}
+
 	/// Gets all the media keys in our database (this includes all the metadata
 	/// associated with it such as width, height, content-type, etc)
 	pub(crate) async fn get_all_media_keys(&self) -> Vec<Vec<u8>> {
@@ -1,5 +1,5 @@
 use std::{
-	collections::HashSet,
+	collections::{HashMap, HashSet},
 	ffi::{OsStr, OsString},
 	fs::{self},
 	path::PathBuf,
@@ -12,7 +12,12 @@
 	utils::{ReadyExt, stream::TryIgnore},
 	warn,
 };
+use futures::StreamExt;
+use ruma::{Mxc, OwnedEventId, OwnedUserId, UserId};
+use serde::Deserialize;
+use serde_json::value::RawValue;
 
+use super::{Dim, data::Metadata};
 use crate::Services;
 
 /// Migrates a media directory from legacy base64 file names to sha2 file names.
@@ -53,6 +58,53 @@ pub(crate) async fn migrate_sha256_media(services: &Services) -> Result<()> {
 	Ok(())
 }
 
+/// Counts the media local users uploaded before usage was recorded towards
+/// their quotas, replacing what has been recorded since. Upon success the
+/// database is keyed to not perform this again.
+pub(crate) async fn backfill_media_usage(services: &Services) -> Result<()> {
+	let media = &services.media;
+	let uploads: Vec<(String, OwnedUserId)> = services.db["mediaid_user"]
+		.keys()
+		.ignore_err()
+		.ready_filter(|(_, user): &(&str, &UserId)| services.globals.user_is_local(user))
+		.map(|(mxc, user): (&str, &UserId)| (mxc.to_owned(), user.to_owned()))
+		.collect()
+		.await;
+
+	let mut usage = HashMap::<OwnedUserId, u64>::new();
+	for (mxc, user) in uploads {
+		let Ok(mxc) = Mxc::try_from(mxc.as_str()) else {
+			debug_warn!(%mxc, "Failed to parse MXC URI from database");
+			continue;
+		};
+
+		if !services.globals.server_is_ours(mxc.server_name) {
+			continue;
+		}
+
+		let Ok(Metadata { key, .. }) = media.db.search_file_metadata(&mxc, &Dim::default()).await
+		else {
+			continue;
+		};
+
+		match media.file_size(&key).await {
+			| Ok(size) => {
+				let used = usage.entry(user).or_default();
+				*used = used.saturating_add(size);
+			},
+			| Err(e) => debug_warn!(%mxc, "Failed to get the size of media: {e}"),
+		}
+	}
+
+	for (user, used) in &usage {
+		media.db.set_media_usage(user, *used);
+	}
+
+	services.db["global"].insert(b"backfill_media_usage", []);
+	info!(users = usage.len(), "Finished backfilling media usage");
+	Ok(())
+}
+
 /// Check is run on startup for prior-migrated media directories. This handles:
 /// - Going back and forth to non-sha256 legacy binaries (e.g. upstream).
 /// - Deletion of artifacts in the media directory which will then fall out of
@@ -164,3 +216,33 @@ async fn handle_media_check(
 
 	Ok(())
 }
+
+/// Records which events refer to which local media for the events stored
+/// before references were recorded. Upon success the database is keyed to not
+/// perform this again.
+pub(crate) async fn index_media_references(services: &Services) -> Result<()> {
+	#[derive(Deserialize)]
+	struct ExtractContent<'a> {
+		event_id: OwnedEventId,
+		#[serde(borrow)]
+		content: &'a RawValue,
+	}
+
+	let mut events: usize = 0;
+	for map in ["pduid_pdu", "eventid_outlierpdu"] {
+		services.db[map]
+			.raw_stream()
+			.ignore_err()
+			.ready_for_each(|(_, pdu)| {
+				if let Ok(ExtractContent { event_id, content }) = serde_json::from_slice(pdu) {
+					services.media.add_references(&event_id, content.get());
+					events = events.saturating_add(1);
+				}
+			})
+			.await;
+	}
+
+	services.db["global"].insert(b"index_media_references", []);
+	info!(events, "Finished indexing media references");
+	Ok(())
+}
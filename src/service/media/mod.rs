@@ -1,11 +1,22 @@
 pub mod blurhash;
 mod data;
 pub(super) mod migrations;
+pub mod multipart;
+mod pending;
 mod preview;
+mod quarantine;
+mod quota;
+mod references;
 mod remote;
+mod storage;
+mod stream;
 mod tests;
 mod thumbnail;
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+	path::PathBuf,
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
 
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose};
@@ -20,11 +31,18 @@
 use ruma::{Mxc, OwnedMxcUri, UserId, http_headers::ContentDisposition};
 use tokio::{
 	fs,
-	io::{AsyncReadExt, AsyncWriteExt, BufReader},
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
 };
 
 use self::data::{Data, Metadata};
-pub use self::{preview::parse_preview_url, thumbnail::Dim};
+pub use self::{
+	preview::parse_preview_url,
+	quota::MediaUsage,
+	storage::{Backend, ByteStream, StoredFile},
+	stream::{FileStream, parse_range},
+	thumbnail::Dim,
+};
 use crate::{Dep, client, globals, moderation, sending};
 
 #[derive(Debug)]
@@ -36,6 +54,12 @@ pub struct FileMeta {
 
 pub struct Service {
 	url_preview_mutex: MutexMap<String, ()>,
+	upload_mutex: MutexMap<String, ()>,
+	pending_mutex: MutexMap<String, ()>,
+	usage_mutex: MutexMap<String, ()>,
+	reserved_usage: quota::Reserved,
+	storage: Backend,
+	interrupt: Notify,
 	pub(super) db: Data,
 	services: Services,
 }
@@ -62,6 +86,12 @@ impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			url_preview_mutex: MutexMap::new(),
+			upload_mutex: MutexMap::new(),
+			pending_mutex: MutexMap::new(),
+			usage_mutex: MutexMap::new(),
+			reserved_usage: quota::Reserved::default(),
+			storage: args.server.config.media_storage.backend.parse()?,
+			interrupt: Notify::new(),
 			db: Data::new(args.db),
 			services: Services {
 				server: args.server.clone(),
@@ -74,11 +104,26 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 	}
 
 	async fn worker(self: Arc<Self>) -> Result<()> {
-		self.create_media_dir().await?;
+		if self.storage == Backend::Filesystem {
+			self.create_media_dir().await?;
+		}
+
+		let mut i = interval(Duration::from_secs(60 * 60));
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.remove_expired_pending().await;
+		}
 
 		Ok(())
 	}
 
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -101,27 +146,24 @@ pub async fn create(
 			})?;
 
 		//TODO: Dangling metadata in database if creation fails
-		let mut f = self.create_media_file(&key).await.map_err(|e| {
-			err!(Database(error!(
-				"Failed to create media file for MXC {mxc} at key {key:?}: {e}"
-			)))
-		})?;
-		f.write_all(file).await.map_err(|e| {
+		self.write_file(&key, file).await.map_err(|e| {
 			err!(Database(error!("Failed to write media file for MXC {mxc} at key {key:?}: {e}")))
 		})?;
 
 		Ok(())
 	}
 
-	/// Deletes a file in the database and from the media directory via an MXC
+	/// Deletes a file in the database and from media storage via an MXC
 	pub async fn delete(&self, mxc: &Mxc<'_>) -> Result<()> {
 		match self.db.search_mxc_metadata_prefix(mxc).await {
 			| Ok(keys) => {
+				self.record_deletion(mxc).await;
+
 				for key in keys {
 					trace!(%mxc, "MXC Key: {key:?}");
-					debug_info!(%mxc, "Deleting from filesystem");
+					debug_info!(%mxc, "Deleting from storage");
 
-					if let Err(e) = self.remove_media_file(&key).await {
+					if let Err(e) = self.remove_file(&key).await {
 						debug_error!(%mxc, "Failed to remove media file: {e}");
 					}
 
@@ -169,13 +211,13 @@ pub async fn delete_from_user(&self, user: &UserId) -> Result<usize> {
 
 	/// Downloads a file.
 	pub async fn get(&self, mxc: &Mxc<'_>) -> Result<Option<FileMeta>> {
+		if self.is_quarantined(mxc).await {
+			return Ok(None);
+		}
+
 		match self.db.search_file_metadata(mxc, &Dim::default()).await {
 			| Ok(Metadata { content_disposition, content_type, key }) => {
-				let mut content = Vec::with_capacity(8192);
-				let path = self.get_media_file(&key);
-				BufReader::new(fs::File::open(path).await?)
-					.read_to_end(&mut content)
-					.await?;
+				let content = self.read_file(&key).await?;
 
 				Ok(Some(FileMeta {
 					content: Some(content),
@@ -272,29 +314,10 @@ pub async fn delete_all_media_within_timeframe(
 				continue;
 			}
 
-			let path = self.get_media_file(&key);
-
-			let file_metadata = match fs::metadata(path.clone()).await {
-				| Ok(file_metadata) => file_metadata,
-				| Err(e) => {
-					error!(
-						"Failed to obtain file metadata for MXC {mxc} at file path \
-						 \"{path:?}\", skipping: {e}"
-					);
-					continue;
-				},
-			};
-
-			trace!(%mxc, ?path, "File metadata: {file_metadata:?}");
-
-			let file_created_at = match file_metadata.created() {
+			let file_created_at = match self.file_created(&key).await {
 				| Ok(value) => value,
-				| Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
-					debug!("btime is unsupported, using mtime instead");
-					file_metadata.modified()?
-				},
-				| Err(err) => {
-					error!("Could not delete MXC {mxc} at path {path:?}: {err:?}. Skipping...");
+				| Err(e) => {
+					error!("Failed to obtain file metadata for MXC {mxc}, skipping: {e}");
 					continue;
 				},
 			};
@@ -407,9 +430,7 @@ pub fn get_media_file_sha256(&self, key: &[u8]) -> PathBuf {
 		// Using the hash of the base64 key as the filename
 		// This is to prevent the total length of the path from exceeding the maximum
 		// length in most filesystems
-		let digest = <sha2::Sha256 as sha2::Digest>::digest(key);
-		let encoded = encode_key(&digest);
-		r.push(encoded);
+		r.push(media_file_name(key));
 		r
 	}
 
@@ -436,3 +457,19 @@ pub fn get_media_dir(&self) -> PathBuf {
 #[inline]
 #[must_use]
 pub fn encode_key(key: &[u8]) -> String { general_purpose::URL_SAFE_NO_PAD.encode(key) }
+
+/// Finds every MXC URI mentioned in a JSON document, which covers `url`,
+/// `thumbnail_url` and encrypted `file` attachments alike.
+pub fn referenced_mxcs(json: &str) -> impl Iterator<Item = &str> + '_ {
+	json.match_indices("mxc://").filter_map(|(start, _)| {
+		let len = json[start..].find('"')?;
+		Some(&json[start..start.saturating_add(len)])
+	})
+}
+
+/// Name media files are stored under: the SHA256 hash of their key.
+#[must_use]
+fn media_file_name(key: &[u8]) -> String {
+	let digest = <sha2::Sha256 as sha2::Digest>::digest(key);
+	encode_key(&digest)
+}
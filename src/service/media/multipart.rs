@@ -0,0 +1,175 @@
+//! Federation Media Responses
+//!
+//! Federation serves media as `multipart/mixed`: a part of JSON metadata
+//! followed by a part holding either the file or the `Location` to fetch it
+//! from. Both are written and read here as streams, so that files are never
+//! held in memory whole.
+
+use bytes::{Buf, Bytes, BytesMut};
+use conduwuit::{Err, Result, err, utils};
+use futures::{Stream, StreamExt, stream};
+use ruma::http_headers::ContentDisposition;
+
+use super::ByteStream;
+
+/// Length of the boundaries of written responses.
+const BOUNDARY_LENGTH: usize = 30;
+
+/// Most bytes read looking for the headers of the file part.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// The file part of a federation media response.
+pub(super) enum RemoteContent {
+	File {
+		content_type: Option<String>,
+		content_disposition: Option<ContentDisposition>,
+		content: ByteStream,
+	},
+	Location(String),
+}
+
+/// Writes a federation media response with empty metadata around `content`,
+/// returning its `Content-Type` and body.
+pub fn encode(
+	content_type: Option<&str>,
+	content_disposition: &ContentDisposition,
+	content: ByteStream,
+) -> (String, impl Stream<Item = Result<Bytes>> + Send) {
+	let boundary = utils::random_string(BOUNDARY_LENGTH);
+	let content_type = content_type.unwrap_or("application/octet-stream");
+	let head = format!(
+		"\r\n--{boundary}\r\nContent-Type: \
+		 application/json\r\n\r\n{{}}\r\n--{boundary}\r\nContent-Type: \
+		 {content_type}\r\nContent-Disposition: {content_disposition}\r\n\r\n"
+	);
+	let tail = format!("\r\n--{boundary}--");
+
+	let body = stream::once(async move { Ok(Bytes::from(head)) })
+		.chain(content)
+		.chain(stream::once(async move { Ok(Bytes::from(tail)) }));
+
+	(format!("multipart/mixed; boundary={boundary}"), body)
+}
+
+/// Reads a federation media response with the `Content-Type` `content_type`.
+/// The content of a file is streamed from `body` as it arrives.
+pub(super) async fn decode<S>(content_type: &str, body: S) -> Result<RemoteContent>
+where
+	S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+	let boundary = boundary(content_type)
+		.ok_or_else(|| err!(BadServerResponse("Media response is not multipart/mixed.")))?;
+
+	// Every delimiter but one starting the body follows a line break
+	let delimiter = Bytes::from(format!("\r\n--{boundary}"));
+	let mut buf = BytesMut::from(&b"\r\n"[..]);
+	let mut body = body.boxed();
+
+	let (headers, content_start) = loop {
+		if let Some(head) = find_head(&buf, &delimiter) {
+			break head;
+		}
+
+		if buf.len() > MAX_HEAD_SIZE {
+			return Err!(BadServerResponse("Media response metadata is too large."));
+		}
+
+		match body.next().await {
+			| Some(chunk) => buf.extend_from_slice(&chunk?),
+			| None => return Err!(BadServerResponse("Media response ended early.")),
+		}
+	};
+
+	let mut content_type = None;
+	let mut content_disposition = None;
+	for (name, value) in headers {
+		if name.eq_ignore_ascii_case("location") {
+			return Ok(RemoteContent::Location(value));
+		} else if name.eq_ignore_ascii_case("content-type") {
+			content_type = Some(value);
+		} else if name.eq_ignore_ascii_case("content-disposition") {
+			content_disposition = value.as_bytes().try_into().ok();
+		}
+	}
+
+	buf.advance(content_start);
+	let content = stream::try_unfold(Some((body, buf, delimiter)), |state| async move {
+		let Some((mut body, mut buf, delimiter)) = state else {
+			return Ok(None);
+		};
+
+		loop {
+			if let Some(end) = find(&buf, &delimiter) {
+				return Ok(Some((buf.split_to(end).freeze(), None)));
+			}
+
+			// The end of what was read may be the start of the delimiter
+			let keep = delimiter.len().saturating_sub(1);
+			if buf.len() > keep {
+				let chunk = buf.split_to(buf.len().saturating_sub(keep)).freeze();
+				return Ok(Some((chunk, Some((body, buf, delimiter)))));
+			}
+
+			match body.next().await {
+				| Some(chunk) => buf.extend_from_slice(&chunk?),
+				| None => return Err!(BadServerResponse("Media response ended early.")),
+			}
+		}
+	});
+
+	Ok(RemoteContent::File {
+		content_type,
+		content_disposition,
+		content: content.boxed(),
+	})
+}
+
+/// Boundary of a `multipart/mixed` `Content-Type`.
+fn boundary(content_type: &str) -> Option<&str> {
+	let (mime, params) = content_type.split_once(';')?;
+	if !mime.trim().eq_ignore_ascii_case("multipart/mixed") {
+		return None;
+	}
+
+	params.split(';').find_map(|param| {
+		let (name, value) = param.split_once('=')?;
+		name.trim()
+			.eq_ignore_ascii_case("boundary")
+			.then(|| value.trim().trim_matches('"'))
+			.filter(|boundary| !boundary.is_empty())
+	})
+}
+
+/// Headers of the second part of `buf` and where its content starts, once
+/// they have been read.
+fn find_head(buf: &[u8], delimiter: &[u8]) -> Option<(Vec<(String, String)>, usize)> {
+	let metadata = find(buf, delimiter)?.saturating_add(delimiter.len());
+	let part = find(&buf[metadata..], delimiter)?
+		.saturating_add(metadata)
+		.saturating_add(delimiter.len());
+
+	// The delimiter line may end with whitespace before its line break
+	let line_end = find(&buf[part..], b"\r\n")?.saturating_add(part);
+	let headers_start = line_end.saturating_add(2);
+	let (headers, content_start) = if buf[headers_start..].starts_with(b"\r\n") {
+		("", headers_start.saturating_add(2))
+	} else {
+		let headers_end = find(&buf[headers_start..], b"\r\n\r\n")?.saturating_add(headers_start);
+		let headers = std::str::from_utf8(&buf[headers_start..headers_end]).ok()?;
+		(headers, headers_end.saturating_add(4))
+	};
+
+	let headers = headers
+		.split("\r\n")
+		.filter_map(|line| line.split_once(':'))
+		.map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+		.collect();
+
+	Some((headers, content_start))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack
+		.windows(needle.len())
+		.position(|window| window == needle)
+}
@@ -0,0 +1,174 @@
+//! Asynchronous Uploads
+//!
+//! An MXC can be reserved with `/create` before its content is uploaded, so
+//! that it can be sent in a message while the upload is still in progress.
+//! Reservations expire if their content is not uploaded in time.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use conduwuit::{
+	Err, Error, Result, debug, debug_info, err, implement,
+	utils::{self, ReadyExt},
+};
+use futures::{Stream, StreamExt};
+use http::StatusCode;
+use ruma::{
+	Mxc, OwnedMxcUri, OwnedUserId, UserId, api::client::error::ErrorKind,
+	http_headers::ContentDisposition,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{Dim, MXC_LENGTH, StoredFile};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct PendingUpload {
+	pub(super) user_id: OwnedUserId,
+	/// When the reservation expires, in milliseconds since the unix epoch.
+	pub(super) expires_at: u64,
+}
+
+impl PendingUpload {
+	fn is_expired(&self) -> bool { self.expires_at <= utils::millis_since_unix_epoch() }
+}
+
+/// Reserves a new MXC for `user` to upload content to later. Returns the MXC
+/// and when the reservation expires, in milliseconds since the unix epoch.
+#[implement(super::Service)]
+pub async fn create_pending(&self, user: &UserId) -> Result<(OwnedMxcUri, u64)> {
+	let config = &self.services.server.config;
+
+	// Concurrent reservations must not together exceed the limit
+	let _lock = self.pending_mutex.lock(user.as_str()).await;
+
+	let now = utils::millis_since_unix_epoch();
+	let pending = self
+		.db
+		.user_pending_uploads(user)
+		.ready_filter(|&expires_at| expires_at > now)
+		.count()
+		.await;
+
+	if pending >= config.max_pending_media_uploads {
+		return Err(Error::Request(
+			ErrorKind::LimitExceeded { retry_after: None },
+			"Too many pending media uploads.".into(),
+			StatusCode::TOO_MANY_REQUESTS,
+		));
+	}
+
+	let mxc = Mxc {
+		server_name: self.services.globals.server_name(),
+		media_id: &utils::random_string(MXC_LENGTH),
+	};
+
+	let timeout = config.pending_media_upload_timeout.saturating_mul(1000);
+	let expires_at = utils::millis_since_unix_epoch().saturating_add(timeout);
+
+	let mxc = mxc.to_string();
+	self.db
+		.put_pending_upload(&mxc, &PendingUpload { user_id: user.to_owned(), expires_at });
+
+	debug!(%mxc, %user, expires_at, "Reserved media upload");
+
+	Ok((mxc.into(), expires_at))
+}
+
+/// Uploads the content of an MXC `user` reserved with `create_pending`, which
+/// is expected to be `size` bytes if known.
+#[implement(super::Service)]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_pending<S>(
+	&self,
+	mxc: &Mxc<'_>,
+	user: &UserId,
+	content_disposition: Option<&ContentDisposition>,
+	content_type: Option<&str>,
+	content: S,
+	size: Option<u64>,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	let _lock = self.upload_mutex.lock(mxc.media_id).await;
+
+	if self
+		.db
+		.search_file_metadata(mxc, &Dim::default())
+		.await
+		.is_ok()
+	{
+		return Err!(Request(CannotOverwriteMedia("Media has already been uploaded.")));
+	}
+
+	let Ok(pending) = self.db.get_pending_upload(mxc).await else {
+		return Err!(Request(NotFound("Unknown media ID.")));
+	};
+
+	if pending.is_expired() {
+		return Err!(Request(NotFound("Media upload reservation has expired.")));
+	}
+
+	if pending.user_id != *user {
+		return Err!(Request(Forbidden("Media was reserved by another user.")));
+	}
+
+	let stored = self
+		.create_stream(mxc, Some(user), content_disposition, content_type, content, size)
+		.await?;
+
+	self.db.remove_pending_upload(&mxc.to_string(), user);
+
+	Ok(stored)
+}
+
+/// Waits up to `timeout` for the content of a reserved MXC to be uploaded.
+/// Fails with `M_NOT_YET_UPLOADED` if it is not, and with `M_NOT_FOUND` if
+/// `mxc` was never reserved or its reservation expired.
+#[implement(super::Service)]
+pub async fn wait_for_upload(&self, mxc: &Mxc<'_>, timeout: Duration) -> Result {
+	let uploaded = self.db.watch_file_metadata(mxc)?;
+
+	// The upload may have completed since the caller last looked
+	if self
+		.db
+		.search_file_metadata(mxc, &Dim::default())
+		.await
+		.is_ok()
+	{
+		return Ok(());
+	}
+
+	if !self
+		.db
+		.get_pending_upload(mxc)
+		.await
+		.is_ok_and(|pending| !pending.is_expired())
+	{
+		return Err!(Request(NotFound("Media not found.")));
+	}
+
+	tokio::time::timeout(timeout, uploaded)
+		.await
+		.map_err(|_| err!(Request(NotYetUploaded("Media has not been uploaded yet."))))
+}
+
+/// Removes the reservations whose content was not uploaded in time.
+#[implement(super::Service)]
+pub(super) async fn remove_expired_pending(&self) {
+	let expired: Vec<(String, OwnedUserId)> = self
+		.db
+		.pending_uploads()
+		.ready_filter(|(_, upload)| upload.is_expired())
+		.map(|(mxc, upload)| (mxc.to_owned(), upload.user_id))
+		.collect()
+		.await;
+
+	for (mxc, user) in &expired {
+		self.db.remove_pending_upload(mxc, user);
+	}
+
+	if !expired.is_empty() {
+		debug_info!(count = expired.len(), "Removed expired media upload reservations");
+	}
+}
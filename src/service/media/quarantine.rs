@@ -0,0 +1,44 @@
+//! Media Quarantine
+//!
+//! Quarantined media is kept in storage but served to neither clients nor
+//! federation, as if it did not exist. Quarantine is recorded by MXC, so remote
+//! media can be quarantined before it is ever fetched and is not fetched again
+//! once quarantined.
+
+use conduwuit::{debug_error, debug_info, implement};
+use futures::Stream;
+use ruma::{Mxc, UserId};
+
+#[implement(super::Service)]
+#[inline]
+pub fn quarantine(&self, mxc: &Mxc<'_>, quarantined: bool) {
+	debug_info!(%mxc, quarantined, "Setting media quarantine");
+	self.db.quarantine_mxc(mxc, quarantined);
+}
+
+/// Quarantines or unquarantines all the media uploaded by `user`, returning
+/// how many files were affected.
+#[implement(super::Service)]
+pub async fn quarantine_from_user(&self, user: &UserId, quarantined: bool) -> usize {
+	let mut count: usize = 0;
+	for mxc in self.db.get_all_user_mxcs(user).await {
+		let Ok(mxc) = mxc.as_str().try_into() else {
+			debug_error!(%mxc, "Failed to parse MXC URI from database");
+			continue;
+		};
+
+		self.quarantine(&mxc, quarantined);
+		count = count.saturating_add(1);
+	}
+
+	count
+}
+
+#[implement(super::Service)]
+#[inline]
+pub async fn is_quarantined(&self, mxc: &Mxc<'_>) -> bool { self.db.is_quarantined(mxc).await }
+
+#[implement(super::Service)]
+pub fn list_quarantined(&self) -> impl Stream<Item = &str> + Send + '_ {
+	self.db.quarantined_mxcs()
+}
@@ -0,0 +1,276 @@
+//! Media Quotas
+//!
+//! The bytes of media each local user uploads are counted so that how much
+//! they may store, and upload each day, can be limited.
+
+use std::collections::HashMap;
+
+use conduwuit::{Error, Result, SyncMutex, debug, implement, utils};
+use futures::StreamExt;
+use http::StatusCode;
+use ruma::{Mxc, OwnedUserId, UserId, api::client::error::ErrorKind};
+use serde::{Deserialize, Serialize};
+
+use super::{Dim, data::Metadata};
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// Bytes a user uploaded on one day.
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct DailyUpload {
+	/// Days since the unix epoch.
+	pub(super) day: u64,
+	pub(super) bytes: u64,
+}
+
+/// Bytes of media each user has reserved for uploads in progress.
+pub(super) type Reserved = SyncMutex<HashMap<OwnedUserId, u64>>;
+
+/// Bytes of the allowance of a user held for an upload in progress, so that
+/// concurrent uploads cannot together exceed it. They are given back when this
+/// is dropped, whether or not the upload completed.
+pub(super) struct Reservation<'a> {
+	reserved: &'a Reserved,
+	user: OwnedUserId,
+	pub(super) size: u64,
+}
+
+impl Drop for Reservation<'_> {
+	fn drop(&mut self) {
+		let mut reserved = self.reserved.lock();
+		if let Some(bytes) = reserved.get_mut(&self.user) {
+			*bytes = bytes.saturating_sub(self.size);
+			if *bytes == 0 {
+				reserved.remove(&self.user);
+			}
+		}
+	}
+}
+
+/// Media a user has stored and how much more they may store.
+#[derive(Debug)]
+pub struct MediaUsage {
+	/// Bytes of media stored.
+	pub used: u64,
+	/// Bytes of media which may be stored, if limited.
+	pub quota: Option<u64>,
+	/// Whether `quota` was set for this user rather than by `media_quota`.
+	pub quota_overridden: bool,
+	/// Bytes of media uploaded today.
+	pub uploaded_today: u64,
+	/// Bytes of media which may be uploaded per day, if limited.
+	pub daily_limit: Option<u64>,
+}
+
+impl MediaUsage {
+	/// Bytes which may still be uploaded, or `None` if uploads are not
+	/// limited.
+	#[must_use]
+	pub fn allowance(&self) -> Option<u64> {
+		let quota = self.quota.map(|quota| quota.saturating_sub(self.used));
+		let daily = self
+			.daily_limit
+			.map(|limit| limit.saturating_sub(self.uploaded_today));
+
+		match (quota, daily) {
+			| (Some(quota), Some(daily)) => Some(quota.min(daily)),
+			| (quota, daily) => quota.or(daily),
+		}
+	}
+}
+
+#[implement(super::Service)]
+pub async fn media_usage(&self, user: &UserId) -> MediaUsage {
+	let config = &self.services.server.config;
+	let overridden = self.db.media_quota(user).await;
+	let uploaded_today = self
+		.db
+		.daily_upload(user)
+		.await
+		.filter(|daily| daily.day == today())
+		.map_or(0, |daily| daily.bytes);
+
+	MediaUsage {
+		used: self.db.media_usage(user).await,
+		quota: overridden
+			.or(config.media_quota)
+			.filter(|&quota| quota != u64::MAX),
+		quota_overridden: overridden.is_some(),
+		uploaded_today,
+		daily_limit: config.media_daily_upload_limit,
+	}
+}
+
+/// Sets the quota of `user`, where `u64::MAX` means no limit. `None` restores
+/// the `media_quota` default.
+#[implement(super::Service)]
+pub fn set_media_quota(&self, user: &UserId, quota: Option<u64>) {
+	self.db.set_media_quota(user, quota);
+}
+
+/// The local users who have stored the most media, with the bytes they have
+/// stored.
+#[implement(super::Service)]
+pub async fn top_uploaders(&self, limit: usize) -> Vec<(OwnedUserId, u64)> {
+	let mut uploaders: Vec<_> = self
+		.db
+		.all_media_usage()
+		.map(|(user, used)| (user.to_owned(), used))
+		.collect()
+		.await;
+
+	uploaders.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+	uploaders.truncate(limit);
+	uploaders
+}
+
+/// Largest file `user` may upload: `max_request_size`, or less if their quota
+/// does not allow for that much.
+#[implement(super::Service)]
+pub async fn max_upload_size(&self, user: &UserId) -> u64 {
+	let max_size = self
+		.services
+		.server
+		.config
+		.max_request_size
+		.try_into()
+		.unwrap_or(u64::MAX);
+
+	self.upload_allowance(user)
+		.await
+		.map_or(max_size, |allowance| allowance.min(max_size))
+}
+
+/// Bytes of media `user` may still upload, or `None` if they are not limited.
+/// Only local users are limited.
+#[implement(super::Service)]
+pub(super) async fn upload_allowance(&self, user: &UserId) -> Option<u64> {
+	if !self.services.globals.user_is_local(user) {
+		return None;
+	}
+
+	let held = self.reserved_usage.lock().get(user).copied().unwrap_or(0);
+
+	self.media_usage(user)
+		.await
+		.allowance()
+		.map(|allowance| allowance.saturating_sub(held))
+}
+
+/// Reserves the allowance of `user` for an upload of `size` bytes, or of as
+/// many as they may upload when its size is not known, up to `max_size`. Fails
+/// if the upload would take them over their quota. Gives `None` when they are
+/// not limited.
+#[implement(super::Service)]
+pub(super) async fn reserve_upload(
+	&self,
+	user: &UserId,
+	size: Option<u64>,
+	max_size: u64,
+) -> Result<Option<Reservation<'_>>> {
+	if !self.services.globals.user_is_local(user) {
+		return Ok(None);
+	}
+
+	let _lock = self.usage_mutex.lock(user.as_str()).await;
+
+	let Some(allowance) = self.media_usage(user).await.allowance() else {
+		return Ok(None);
+	};
+
+	let mut reserved = self.reserved_usage.lock();
+	let held = reserved.get(user).copied().unwrap_or(0);
+	let available = allowance.saturating_sub(held);
+	let wanted = size.unwrap_or(max_size).min(max_size);
+	if available == 0 || (size.is_some() && wanted > available) {
+		return Err(self.quota_exceeded());
+	}
+
+	let size = wanted.min(available);
+	reserved.insert(user.to_owned(), held.saturating_add(size));
+
+	Ok(Some(Reservation {
+		reserved: &self.reserved_usage,
+		user: user.to_owned(),
+		size,
+	}))
+}
+
+#[implement(super::Service)]
+pub(super) async fn record_upload(&self, user: &UserId, size: u64) {
+	if !self.services.globals.user_is_local(user) {
+		return;
+	}
+
+	let _lock = self.usage_mutex.lock(user.as_str()).await;
+
+	let used = self.db.media_usage(user).await;
+	self.db.set_media_usage(user, used.saturating_add(size));
+
+	let day = today();
+	let uploaded_today = self
+		.db
+		.daily_upload(user)
+		.await
+		.filter(|daily| daily.day == day)
+		.map_or(0, |daily| daily.bytes);
+
+	self.db.set_daily_upload(user, &DailyUpload {
+		day,
+		bytes: uploaded_today.saturating_add(size),
+	});
+}
+
+/// Gives the bytes of a local upload back to its uploader before it is
+/// deleted.
+#[implement(super::Service)]
+pub(super) async fn record_deletion(&self, mxc: &Mxc<'_>) {
+	if !self.services.globals.server_is_ours(mxc.server_name) {
+		return;
+	}
+
+	let Some(user) = self.db.get_uploader(mxc).await else {
+		return;
+	};
+
+	let Ok(Metadata { key, .. }) = self.db.search_file_metadata(mxc, &Dim::default()).await
+	else {
+		return;
+	};
+
+	let Ok(size) = self.file_size(&key).await else {
+		return;
+	};
+
+	let _lock = self.usage_mutex.lock(user.as_str()).await;
+
+	let used = self.db.media_usage(&user).await;
+	self.db.set_media_usage(&user, used.saturating_sub(size));
+
+	debug!(%mxc, %user, size, "Released media quota");
+}
+
+/// Error for an upload which would take a user over their quota.
+#[implement(super::Service)]
+pub(super) fn quota_exceeded(&self) -> Error {
+	let well_known = &self.services.server.config.well_known;
+	let admin_contact = well_known
+		.support_page
+		.as_ref()
+		.map(ToString::to_string)
+		.or_else(|| {
+			well_known
+				.support_email
+				.as_ref()
+				.map(|email| format!("mailto:{email}"))
+		})
+		.unwrap_or_default();
+
+	Error::Request(
+		ErrorKind::ResourceLimitExceeded { admin_contact },
+		"Media upload quota exceeded.".into(),
+		StatusCode::FORBIDDEN,
+	)
+}
+
+fn today() -> u64 { utils::millis_since_unix_epoch() / DAY_MILLIS }
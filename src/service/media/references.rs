@@ -0,0 +1,41 @@
+//! Media References
+//!
+//! Records which events refer to which local media, so that purging a room's
+//! history only has to check whether the media referred to by the purged events
+//! is still referred to elsewhere, rather than scanning every stored event.
+
+use conduwuit::implement;
+use futures::Stream;
+use ruma::{EventId, Mxc};
+
+use super::referenced_mxcs;
+
+/// Records the local media referred to by the event with JSON `content`.
+#[implement(super::Service)]
+pub fn add_references(&self, event_id: &EventId, content: &str) {
+	for mxc in referenced_mxcs(content) {
+		let Ok(mxc) = Mxc::try_from(mxc) else {
+			continue;
+		};
+
+		if self.services.globals.server_is_ours(mxc.server_name) {
+			self.db.add_reference(&mxc, event_id);
+		}
+	}
+}
+
+/// Events recorded as referring to `mxc`. These may include events which have
+/// since been purged; see [`remove_reference`](Self::remove_reference).
+#[implement(super::Service)]
+pub fn referencing_events<'a>(
+	&'a self,
+	mxc: &'a Mxc<'_>,
+) -> impl Stream<Item = &'a EventId> + Send + 'a {
+	self.db.referencing_events(mxc)
+}
+
+#[implement(super::Service)]
+#[inline]
+pub fn remove_reference(&self, mxc: &Mxc<'_>, event_id: &EventId) {
+	self.db.remove_reference(mxc, event_id);
+}
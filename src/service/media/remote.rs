@@ -1,12 +1,14 @@
 use std::{fmt::Debug, time::Duration};
 
+use bytes::Bytes;
 use conduwuit::{
 	Err, Error, Result, debug_warn, err, implement,
 	utils::{content_disposition::make_content_disposition, response::LimitReadExt},
 };
+use futures::{Stream, TryStreamExt};
 use http::{
 	StatusCode,
-	header::{CONTENT_DISPOSITION, CONTENT_TYPE, HeaderValue},
+	header::{CONTENT_DISPOSITION, CONTENT_TYPE, HeaderName, HeaderValue},
 };
 use ruma::{
 	Mxc, ServerName, UserId,
@@ -19,9 +21,13 @@
 		federation,
 		federation::authenticated_media::{Content, FileOrLocation},
 	},
+	http_headers::ContentDisposition,
 };
 
-use super::{Dim, FileMeta};
+use super::{
+	Dim, FileMeta, StoredFile,
+	multipart::{self, RemoteContent},
+};
 
 #[implement(super::Service)]
 pub async fn fetch_remote_thumbnail(
@@ -32,7 +38,7 @@ pub async fn fetch_remote_thumbnail(
 	timeout_ms: Duration,
 	dim: &Dim,
 ) -> Result<FileMeta> {
-	self.check_fetch_authorized(mxc)?;
+	self.check_fetch_authorized(mxc).await?;
 
 	let result = self
 		.fetch_thumbnail_authenticated(mxc, user, server, timeout_ms, dim)
@@ -47,6 +53,7 @@ pub async fn fetch_remote_thumbnail(
 	result
 }
 
+/// Fetches remote media into storage, streaming it from the remote server.
 #[implement(super::Service)]
 pub async fn fetch_remote_content(
 	&self,
@@ -54,8 +61,8 @@ pub async fn fetch_remote_content(
 	user: Option<&UserId>,
 	server: Option<&ServerName>,
 	timeout_ms: Duration,
-) -> Result<FileMeta> {
-	self.check_fetch_authorized(mxc)?;
+) -> Result<StoredFile> {
+	self.check_fetch_authorized(mxc).await?;
 
 	let result = self
 		.fetch_content_authenticated(mxc, user, server, timeout_ms)
@@ -80,7 +87,7 @@ pub async fn fetch_remote_content(
 }
 
 fn should_fallback_to_unauthenticated(
-	result: &Result<FileMeta>,
+	result: &Result<StoredFile>,
 	allow_broad_fallback: bool,
 ) -> bool {
 	match result {
@@ -137,19 +144,38 @@ async fn fetch_content_authenticated(
 	user: Option<&UserId>,
 	server: Option<&ServerName>,
 	timeout_ms: Duration,
-) -> Result<FileMeta> {
-	use federation::authenticated_media::get_content::v1::{Request, Response};
+) -> Result<StoredFile> {
+	use federation::authenticated_media::get_content::v1::Request;
 
 	let request = Request {
 		media_id: mxc.media_id.into(),
 		timeout_ms,
 	};
 
-	let Response { content, .. } = self.federation_request(mxc, server, request).await?;
+	let response = self
+		.services
+		.sending
+		.send_federation_stream(server.unwrap_or(mxc.server_name), request)
+		.await?;
 
-	match content {
-		| FileOrLocation::File(content) => self.handle_content_file(mxc, user, content).await,
-		| FileOrLocation::Location(location) => self.handle_location(mxc, user, &location).await,
+	let content_type = header(&response, CONTENT_TYPE).unwrap_or_default();
+	let body = response.bytes_stream().map_err(Into::into);
+	match multipart::decode(&content_type, body).await? {
+		| RemoteContent::File {
+			content_type,
+			content_disposition,
+			content,
+		} =>
+			self.store_remote_content(
+				mxc,
+				user,
+				content_type.as_deref(),
+				content_disposition.as_ref(),
+				content,
+			)
+			.await,
+		| RemoteContent::Location(location) =>
+			self.handle_content_location(mxc, user, &location).await,
 	}
 }
 
@@ -194,8 +220,8 @@ async fn fetch_content_unauthenticated(
 	user: Option<&UserId>,
 	server: Option<&ServerName>,
 	timeout_ms: Duration,
-) -> Result<FileMeta> {
-	use media::get_content::v3::{Request, Response};
+) -> Result<StoredFile> {
+	use media::get_content::v3::Request;
 
 	let request = Request {
 		allow_remote: true,
@@ -205,13 +231,13 @@ async fn fetch_content_unauthenticated(
 		timeout_ms,
 	};
 
-	let Response {
-		file, content_type, content_disposition, ..
-	} = self.federation_request(mxc, server, request).await?;
-
-	let content = Content { file, content_type, content_disposition };
+	let response = self
+		.services
+		.sending
+		.send_federation_stream(server.unwrap_or(mxc.server_name), request)
+		.await?;
 
-	self.handle_content_file(mxc, user, content).await
+	self.store_remote_response(mxc, user, response).await
 }
 
 #[implement(super::Service)]
@@ -245,31 +271,86 @@ async fn handle_thumbnail_file(
 }
 
 #[implement(super::Service)]
-async fn handle_content_file(
+async fn handle_content_location(
 	&self,
 	mxc: &Mxc<'_>,
 	user: Option<&UserId>,
-	content: Content,
-) -> Result<FileMeta> {
-	let content_disposition = make_content_disposition(
-		content.content_disposition.as_ref(),
-		content.content_type.as_deref(),
-		None,
-	);
+	location: &str,
+) -> Result<StoredFile> {
+	self.content_location_request(mxc, user, location)
+		.await
+		.map_err(|error| {
+			err!(Request(NotFound(
+				debug_warn!(%mxc, user = user.map(tracing::field::display), ?location, ?error, "Fetching media from location failed")
+			)))
+		})
+}
 
-	self.create(
+#[implement(super::Service)]
+async fn content_location_request(
+	&self,
+	mxc: &Mxc<'_>,
+	user: Option<&UserId>,
+	location: &str,
+) -> Result<StoredFile> {
+	let response = self
+		.services
+		.client
+		.extern_media
+		.get(location)
+		.send()
+		.await?
+		.error_for_status()?;
+
+	self.store_remote_response(mxc, user, response).await
+}
+
+/// Stores remote media sent as the whole body of `response`.
+#[implement(super::Service)]
+async fn store_remote_response(
+	&self,
+	mxc: &Mxc<'_>,
+	user: Option<&UserId>,
+	response: reqwest::Response,
+) -> Result<StoredFile> {
+	let content_type = header(&response, CONTENT_TYPE);
+	let content_disposition = response
+		.headers()
+		.get(CONTENT_DISPOSITION)
+		.map(HeaderValue::as_bytes)
+		.map(TryFrom::try_from)
+		.and_then(Result::ok);
+
+	let content = response.bytes_stream().map_err(Into::into);
+	self.store_remote_content(
 		mxc,
 		user,
-		Some(&content_disposition),
-		content.content_type.as_deref(),
-		&content.file,
+		content_type.as_deref(),
+		content_disposition.as_ref(),
+		content,
 	)
 	.await
-	.map(|()| FileMeta {
-		content: Some(content.file),
-		content_type: content.content_type.map(Into::into),
-		content_disposition: Some(content_disposition),
-	})
+}
+
+/// Stores remote media as it is received, so that it can be streamed from
+/// storage like any other. It does not count against the quota of `user`.
+#[implement(super::Service)]
+async fn store_remote_content<S>(
+	&self,
+	mxc: &Mxc<'_>,
+	user: Option<&UserId>,
+	content_type: Option<&str>,
+	content_disposition: Option<&ContentDisposition>,
+	content: S,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	let content_disposition = make_content_disposition(content_disposition, content_type, None);
+	let max_size = self.services.server.config.max_request_size.try_into()?;
+
+	self.store_stream(mxc, user, Some(&content_disposition), content_type, content, max_size)
+		.await
 }
 
 #[implement(super::Service)]
@@ -348,7 +429,7 @@ async fn federation_request<Request>(
 }
 
 #[implement(super::Service)]
-fn check_fetch_authorized(&self, mxc: &Mxc<'_>) -> Result<()> {
+async fn check_fetch_authorized(&self, mxc: &Mxc<'_>) -> Result<()> {
 	if self
 		.services
 		.moderation
@@ -360,6 +441,11 @@ fn check_fetch_authorized(&self, mxc: &Mxc<'_>) -> Result<()> {
 		return Err!(Request(NotFound("Media not found.")));
 	}
 
+	if self.is_quarantined(mxc).await {
+		debug_warn!(%mxc, "Received request for quarantined remote media");
+		return Err!(Request(NotFound("Media not found.")));
+	}
+
 	Ok(())
 }
 
@@ -369,3 +455,12 @@ pub fn check_legacy_freeze(&self) -> Result<()> {
 		.then_some(())
 		.ok_or(err!(Request(NotFound("Remote media is frozen."))))
 }
+
+fn header(response: &reqwest::Response, name: HeaderName) -> Option<String> {
+	response
+		.headers()
+		.get(name)
+		.map(HeaderValue::to_str)
+		.and_then(Result::ok)
+		.map(str::to_owned)
+}
@@ -0,0 +1,343 @@
+//! Media Storage
+//!
+//! Media files are stored under the name `media_file_name` gives their key,
+//! either in the media directory or in an S3-compatible bucket.
+
+mod s3;
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, io::SeekFrom, ops::Range, pin::Pin, str::FromStr, time::SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use conduwuit::{Err, Error, Result, debug, debug_error, err, implement, warn};
+use futures::{Stream, StreamExt, TryStreamExt, pin_mut, stream};
+use sha2::{Digest, Sha256};
+use tokio::{
+	fs,
+	io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+use self::s3::{Bucket, Upload};
+use super::{encode_key, media_file_name};
+
+/// Contents of a media file, read in chunks.
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+/// Size of the chunks media files are read from the filesystem in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file written from a stream.
+#[derive(Debug)]
+pub struct StoredFile {
+	pub size: u64,
+	pub sha256: [u8; 32],
+}
+
+enum Writer {
+	File(fs::File),
+	S3(Upload),
+}
+
+/// Backend media files are stored in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backend {
+	Filesystem,
+	S3,
+}
+
+impl FromStr for Backend {
+	type Err = Error;
+
+	fn from_str(backend: &str) -> Result<Self> {
+		match backend {
+			| "filesystem" => Ok(Self::Filesystem),
+			| "s3" => Ok(Self::S3),
+			| _ => Err!("Unknown media storage backend {backend:?}."),
+		}
+	}
+}
+
+impl fmt::Display for Backend {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			| Self::Filesystem => "filesystem",
+			| Self::S3 => "s3",
+		})
+	}
+}
+
+/// Backend new media is stored in.
+#[implement(super::Service)]
+#[must_use]
+pub fn storage_backend(&self) -> Backend { self.storage }
+
+#[implement(super::Service)]
+pub(super) async fn write_file(&self, key: &[u8], content: &[u8]) -> Result {
+	self.write_file_to(self.storage, key, content).await
+}
+
+#[implement(super::Service)]
+pub(super) async fn read_file(&self, key: &[u8]) -> Result<Vec<u8>> {
+	self.read_file_from(self.storage, key).await
+}
+
+/// Writes the file `key` from `content` as it arrives, failing once more
+/// than `max_size` bytes have been received. Nothing is left behind if
+/// writing fails.
+#[implement(super::Service)]
+pub(super) async fn write_stream<S>(
+	&self,
+	key: &[u8],
+	content: S,
+	max_size: u64,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	self.write_stream_to(self.storage, key, content, max_size)
+		.await
+}
+
+/// Streams the file `key`, or the part of it in `range`.
+#[implement(super::Service)]
+pub(super) async fn read_stream(
+	&self,
+	key: &[u8],
+	range: Option<Range<u64>>,
+) -> Result<ByteStream> {
+	self.read_stream_from(self.storage, key, range).await
+}
+
+#[implement(super::Service)]
+async fn write_stream_to<S>(
+	&self,
+	backend: Backend,
+	key: &[u8],
+	content: S,
+	max_size: u64,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	let mut writer = match backend {
+		| Backend::Filesystem => Writer::File(self.create_media_file(key).await?),
+		| Backend::S3 => Writer::S3(Bucket::upload(media_file_name(key))),
+	};
+
+	let mut hasher = Sha256::new();
+	let mut size: u64 = 0;
+
+	pin_mut!(content);
+	let written: Result = async {
+		while let Some(chunk) = content.try_next().await? {
+			size = size.saturating_add(chunk.len().try_into()?);
+			if size > max_size {
+				return Err!(Request(TooLarge("File is larger than {max_size} bytes.")));
+			}
+
+			hasher.update(&chunk);
+			match &mut writer {
+				| Writer::File(file) => file.write_all(&chunk).await?,
+				| Writer::S3(upload) => self.bucket()?.upload_chunk(upload, &chunk).await?,
+			}
+		}
+
+		Ok(())
+	}
+	.await;
+
+	let finished = match (written, writer) {
+		| (Ok(()), Writer::File(mut file)) => file.flush().await.map_err(Into::into),
+		| (Ok(()), Writer::S3(upload)) => self.bucket()?.complete_upload(upload).await,
+		| (Err(e), Writer::S3(upload)) => {
+			if let Err(e) = self.bucket()?.abort_upload(upload).await {
+				debug_error!(key = encode_key(key), "Failed to abort S3 upload: {e}");
+			}
+
+			return Err(e);
+		},
+		| (Err(e), Writer::File(_)) => Err(e),
+	};
+
+	if let Err(e) = finished {
+		if backend == Backend::Filesystem {
+			if let Err(e) = self.remove_media_file(key).await {
+				debug_error!(key = encode_key(key), "Failed to remove partial media file: {e}");
+			}
+		}
+
+		return Err(e);
+	}
+
+	Ok(StoredFile { size, sha256: hasher.finalize().into() })
+}
+
+#[implement(super::Service)]
+async fn read_stream_from(
+	&self,
+	backend: Backend,
+	key: &[u8],
+	range: Option<Range<u64>>,
+) -> Result<ByteStream> {
+	match backend {
+		| Backend::Filesystem => {
+			let mut file = fs::File::open(self.get_media_file(key)).await?;
+			let length = match range {
+				| Some(range) => {
+					file.seek(SeekFrom::Start(range.start)).await?;
+					range.end.saturating_sub(range.start)
+				},
+				| None => u64::MAX,
+			};
+
+			let chunks = stream::try_unfold(file.take(length), |mut reader| async move {
+				let mut chunk = BytesMut::with_capacity(CHUNK_SIZE);
+				let read = reader.read_buf(&mut chunk).await?;
+
+				Ok::<_, Error>((read > 0).then(|| (chunk.freeze(), reader)))
+			});
+
+			Ok(chunks.boxed())
+		},
+		| Backend::S3 =>
+			self.bucket()?
+				.get_stream(&media_file_name(key), range)
+				.await,
+	}
+}
+
+#[implement(super::Service)]
+pub(super) async fn file_size(&self, key: &[u8]) -> Result<u64> {
+	match self.storage {
+		| Backend::Filesystem => Ok(fs::metadata(self.get_media_file(key)).await?.len()),
+		| Backend::S3 => self.bucket()?.size(&media_file_name(key)).await,
+	}
+}
+
+#[implement(super::Service)]
+pub(super) async fn remove_file(&self, key: &[u8]) -> Result {
+	self.remove_file_from(self.storage, key).await
+}
+
+/// When the file was stored, falling back to when it was last modified where
+/// the creation time is unknown.
+#[implement(super::Service)]
+pub(super) async fn file_created(&self, key: &[u8]) -> Result<SystemTime> {
+	match self.storage {
+		| Backend::Filesystem => {
+			let metadata = fs::metadata(self.get_media_file(key)).await?;
+			match metadata.created() {
+				| Ok(created) => Ok(created),
+				| Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+					debug!("btime is unsupported, using mtime instead");
+					Ok(metadata.modified()?)
+				},
+				| Err(e) => Err(e.into()),
+			}
+		},
+		| Backend::S3 => self.bucket()?.last_modified(&media_file_name(key)).await,
+	}
+}
+
+/// Copies every media file from the `from` backend to the `to` backend,
+/// optionally deleting it from `from` afterwards. Files which fail to migrate
+/// are skipped. Returns the numbers of files migrated and skipped.
+#[implement(super::Service)]
+pub async fn migrate_storage(
+	&self,
+	from: Backend,
+	to: Backend,
+	delete_source: bool,
+) -> Result<(usize, usize)> {
+	if from == to {
+		return Err!("Cannot migrate media from the {from} backend to itself.");
+	}
+
+	if to == Backend::Filesystem {
+		self.create_media_dir().await?;
+	}
+
+	let (mut migrated, mut failed) = (0_usize, 0_usize);
+	for key in self.db.get_all_media_keys().await {
+		match self.migrate_file(from, to, &key, delete_source).await {
+			| Ok(()) => migrated = migrated.saturating_add(1),
+			| Err(e) => {
+				warn!(
+					key = encode_key(&key),
+					"Failed to migrate media file from {from} to {to}: {e}"
+				);
+				failed = failed.saturating_add(1);
+			},
+		}
+	}
+
+	Ok((migrated, failed))
+}
+
+#[implement(super::Service)]
+async fn migrate_file(
+	&self,
+	from: Backend,
+	to: Backend,
+	key: &[u8],
+	delete_source: bool,
+) -> Result {
+	let content = self.read_stream_from(from, key, None).await?;
+	self.write_stream_to(to, key, content, u64::MAX).await?;
+
+	if delete_source {
+		self.remove_file_from(from, key).await?;
+	}
+
+	Ok(())
+}
+
+#[implement(super::Service)]
+async fn write_file_to(&self, backend: Backend, key: &[u8], content: &[u8]) -> Result {
+	match backend {
+		| Backend::Filesystem => {
+			let mut file = self.create_media_file(key).await?;
+			file.write_all(content).await?;
+
+			Ok(())
+		},
+		| Backend::S3 =>
+			self.bucket()?
+				.put(&media_file_name(key), content.to_vec())
+				.await,
+	}
+}
+
+#[implement(super::Service)]
+async fn read_file_from(&self, backend: Backend, key: &[u8]) -> Result<Vec<u8>> {
+	match backend {
+		| Backend::Filesystem => Ok(fs::read(self.get_media_file(key)).await?),
+		| Backend::S3 => self.bucket()?.get(&media_file_name(key)).await,
+	}
+}
+
+#[implement(super::Service)]
+async fn remove_file_from(&self, backend: Backend, key: &[u8]) -> Result {
+	match backend {
+		| Backend::Filesystem => self.remove_media_file(key).await,
+		| Backend::S3 => self.bucket()?.delete(&media_file_name(key)).await,
+	}
+}
+
+#[implement(super::Service)]
+fn bucket(&self) -> Result<Bucket<'_>> {
+	let config = self
+		.services
+		.server
+		.config
+		.media_storage
+		.s3
+		.as_ref()
+		.ok_or_else(|| err!(Config("media_storage.s3", "S3 media storage is not configured.")))?;
+
+	Ok(Bucket {
+		client: &self.services.client.default,
+		config,
+	})
+}
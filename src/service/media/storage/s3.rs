@@ -0,0 +1,412 @@
+//! Minimal client for S3-compatible object stores, signing requests with AWS
+//! Signature Version 4.
+
+use std::{fmt::Write as _, mem, ops::Range, time::SystemTime};
+
+use conduwuit::{
+	Err, Result,
+	config::S3StorageConfig,
+	err,
+	utils::{response::LimitReadExt, time},
+};
+use futures::TryStreamExt;
+use http::{
+	HeaderMap, Method,
+	header::{AUTHORIZATION, CONTENT_LENGTH, ETAG, LAST_MODIFIED, RANGE},
+};
+use ring::hmac;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::ByteStream;
+
+const MAX_ERROR_SIZE: u64 = 64 * 1024;
+
+/// Size of the parts streamed uploads are sent in. Uploads smaller than this
+/// are sent in a single request.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+pub(super) struct Bucket<'a> {
+	pub(super) client: &'a reqwest::Client,
+	pub(super) config: &'a S3StorageConfig,
+}
+
+/// Object being uploaded in parts, as a multipart upload once it outgrows a
+/// single part.
+pub(super) struct Upload {
+	name: String,
+	buffer: Vec<u8>,
+	upload_id: Option<String>,
+	etags: Vec<String>,
+}
+
+impl Bucket<'_> {
+	pub(super) async fn put(&self, name: &str, content: Vec<u8>) -> Result {
+		self.send(Method::PUT, name, &[], content).await?;
+
+		Ok(())
+	}
+
+	pub(super) async fn get(&self, name: &str) -> Result<Vec<u8>> {
+		let response = self.send(Method::GET, name, &[], Vec::new()).await?;
+
+		Ok(response.bytes().await?.into())
+	}
+
+	/// Streams the object, or the part of it in `range`.
+	pub(super) async fn get_stream(
+		&self,
+		name: &str,
+		range: Option<Range<u64>>,
+	) -> Result<ByteStream> {
+		let mut request = self.request(Method::GET, name, &[], Vec::new())?;
+		if let Some(range) = range {
+			let last = range.end.saturating_sub(1);
+			request = request.header(RANGE, format!("bytes={}-{last}", range.start));
+		}
+
+		let response = check(name, request.send().await?).await?;
+
+		Ok(Box::pin(response.bytes_stream().map_err(Into::into)))
+	}
+
+	pub(super) async fn delete(&self, name: &str) -> Result {
+		self.send(Method::DELETE, name, &[], Vec::new()).await?;
+
+		Ok(())
+	}
+
+	pub(super) async fn last_modified(&self, name: &str) -> Result<SystemTime> {
+		let headers = self.head(name).await?;
+		let Some(last_modified) = headers.get(LAST_MODIFIED) else {
+			return Err!(BadServerResponse("S3 response has no Last-Modified header"));
+		};
+
+		let last_modified = last_modified
+			.to_str()
+			.map_err(|e| err!(BadServerResponse("Invalid Last-Modified header: {e}")))?;
+
+		time::parse_rfc2822(last_modified)
+	}
+
+	pub(super) async fn size(&self, name: &str) -> Result<u64> {
+		let headers = self.head(name).await?;
+
+		headers
+			.get(CONTENT_LENGTH)
+			.and_then(|length| length.to_str().ok())
+			.and_then(|length| length.parse().ok())
+			.ok_or_else(|| err!(BadServerResponse("S3 response has no valid Content-Length")))
+	}
+
+	async fn head(&self, name: &str) -> Result<HeaderMap> {
+		let response = self.send(Method::HEAD, name, &[], Vec::new()).await?;
+
+		Ok(response.headers().clone())
+	}
+
+	#[must_use]
+	pub(super) fn upload(name: String) -> Upload {
+		Upload {
+			name,
+			buffer: Vec::new(),
+			upload_id: None,
+			etags: Vec::new(),
+		}
+	}
+
+	/// Adds `chunk` to the upload, sending a part whenever enough has been
+	/// buffered.
+	pub(super) async fn upload_chunk(&self, upload: &mut Upload, chunk: &[u8]) -> Result {
+		upload.buffer.extend_from_slice(chunk);
+		if upload.buffer.len() >= PART_SIZE {
+			self.upload_part(upload).await?;
+		}
+
+		Ok(())
+	}
+
+	pub(super) async fn complete_upload(&self, mut upload: Upload) -> Result {
+		let Some(upload_id) = upload.upload_id.clone() else {
+			return self.put(&upload.name, upload.buffer).await;
+		};
+
+		if !upload.buffer.is_empty() {
+			self.upload_part(&mut upload).await?;
+		}
+
+		let parts: String = upload
+			.etags
+			.iter()
+			.enumerate()
+			.map(|(i, etag)| {
+				format!(
+					"<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+					i.saturating_add(1)
+				)
+			})
+			.collect();
+
+		let body = format!("<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>");
+		let response = self
+			.send(Method::POST, &upload.name, &[("uploadId", &upload_id)], body.into_bytes())
+			.await?;
+
+		// Errors completing an upload may come with a successful status
+		let body = response.limit_read(MAX_ERROR_SIZE).await?;
+		if String::from_utf8_lossy(&body).contains("<Error>") {
+			return Err!(BadServerResponse(
+				"S3 failed to complete upload of {:?}: {}",
+				upload.name,
+				String::from_utf8_lossy(&body)
+			));
+		}
+
+		Ok(())
+	}
+
+	pub(super) async fn abort_upload(&self, upload: Upload) -> Result {
+		let Some(upload_id) = upload.upload_id else {
+			return Ok(());
+		};
+
+		self.send(Method::DELETE, &upload.name, &[("uploadId", &upload_id)], Vec::new())
+			.await?;
+
+		Ok(())
+	}
+
+	async fn upload_part(&self, upload: &mut Upload) -> Result {
+		let upload_id = match &upload.upload_id {
+			| Some(upload_id) => upload_id.clone(),
+			| None => {
+				let response = self
+					.send(Method::POST, &upload.name, &[("uploads", "")], Vec::new())
+					.await?;
+				let body = response.limit_read(MAX_ERROR_SIZE).await?;
+				let upload_id = xml_element(&String::from_utf8_lossy(&body), "UploadId")
+					.ok_or_else(|| err!(BadServerResponse("S3 did not return an upload ID")))?;
+
+				upload.upload_id.insert(upload_id).clone()
+			},
+		};
+
+		let part_number = upload.etags.len().saturating_add(1).to_string();
+		let response = self
+			.send(
+				Method::PUT,
+				&upload.name,
+				&[("partNumber", &part_number), ("uploadId", &upload_id)],
+				mem::take(&mut upload.buffer),
+			)
+			.await?;
+
+		let etag = response
+			.headers()
+			.get(ETAG)
+			.and_then(|etag| etag.to_str().ok())
+			.ok_or_else(|| err!(BadServerResponse("S3 did not return an ETag for part")))?;
+
+		upload.etags.push(etag.to_owned());
+
+		Ok(())
+	}
+
+	async fn send(
+		&self,
+		method: Method,
+		name: &str,
+		query: &[(&str, &str)],
+		body: Vec<u8>,
+	) -> Result<reqwest::Response> {
+		let request = self.request(method, name, query, body)?;
+
+		check(name, request.send().await?).await
+	}
+
+	/// Builds a signed request for the object `name`. `query` must be sorted
+	/// by name.
+	fn request(
+		&self,
+		method: Method,
+		name: &str,
+		query: &[(&str, &str)],
+		body: Vec<u8>,
+	) -> Result<reqwest::RequestBuilder> {
+		let mut url = self.object_url(name)?;
+		if !query.is_empty() {
+			let query: Vec<_> = query
+				.iter()
+				.map(|(name, value)| {
+					format!("{}={}", uri_encode(name, true), uri_encode(value, true))
+				})
+				.collect();
+
+			url.set_query(Some(&query.join("&")));
+		}
+
+		let host = host_header(&url)?;
+		let payload_hash = hex(&Sha256::digest(&body));
+		let datetime = time::format(SystemTime::now(), "%Y%m%dT%H%M%SZ");
+
+		let headers = [
+			("host", host.as_str()),
+			("x-amz-content-sha256", payload_hash.as_str()),
+			("x-amz-date", datetime.as_str()),
+		];
+		let authorization = authorization(self.config, &method, &url, &headers, &datetime);
+
+		Ok(self
+			.client
+			.request(method, url)
+			.header(AUTHORIZATION, authorization)
+			.header("x-amz-content-sha256", &payload_hash)
+			.header("x-amz-date", &datetime)
+			.body(body))
+	}
+
+	/// URL of the object `name`, under the configured prefix.
+	pub(super) fn object_url(&self, name: &str) -> Result<Url> {
+		let mut url = self.config.endpoint.clone();
+		let mut path = url.path().trim_end_matches('/').to_owned();
+
+		if self.config.path_style {
+			path.push('/');
+			path.push_str(&uri_encode(&self.config.bucket, true));
+		} else {
+			let Some(host) = url.host_str() else {
+				return Err!(Config("media_storage.s3.endpoint", "Endpoint has no host."));
+			};
+
+			let host = format!("{}.{host}", self.config.bucket);
+			url.set_host(Some(&host)).map_err(|e| {
+				err!(Config("media_storage.s3.bucket", "Invalid bucket name for its host: {e}"))
+			})?;
+		}
+
+		path.push('/');
+		path.push_str(&uri_encode(&format!("{}{name}", self.config.prefix), false));
+		url.set_path(&path);
+
+		Ok(url)
+	}
+}
+
+/// Value of the `Authorization` header of a request. `headers` are the signed
+/// headers, sorted by their lowercase names, and must include
+/// `x-amz-content-sha256`, the hash of the payload, and `x-amz-date`, which
+/// is `datetime`.
+pub(super) fn authorization(
+	config: &S3StorageConfig,
+	method: &Method,
+	url: &Url,
+	headers: &[(&str, &str)],
+	datetime: &str,
+) -> String {
+	let (date, _) = datetime.split_once('T').unwrap_or((datetime, ""));
+	let payload_hash = headers
+		.iter()
+		.find_map(|(name, value)| (*name == "x-amz-content-sha256").then_some(*value))
+		.unwrap_or_default();
+
+	let canonical_headers: String = headers
+		.iter()
+		.map(|(name, value)| format!("{name}:{}\n", value.trim()))
+		.collect();
+
+	let signed_headers = headers
+		.iter()
+		.map(|(name, _)| *name)
+		.collect::<Vec<_>>()
+		.join(";");
+
+	let canonical_request = format!(
+		"{method}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+		url.path(),
+		url.query().unwrap_or_default(),
+	);
+
+	let scope = format!("{date}/{}/s3/aws4_request", config.region);
+	let string_to_sign = format!(
+		"AWS4-HMAC-SHA256\n{datetime}\n{scope}\n{}",
+		hex(&Sha256::digest(canonical_request.as_bytes()))
+	);
+
+	let signing_key = [config.region.as_str(), "s3", "aws4_request"]
+		.into_iter()
+		.fold(
+			hmac_sha256(format!("AWS4{}", config.secret_access_key).as_bytes(), date),
+			|key, data| hmac_sha256(&key, data),
+		);
+	let signature = hex(&hmac_sha256(&signing_key, &string_to_sign));
+
+	format!(
+		"AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, \
+		 Signature={signature}",
+		config.access_key_id
+	)
+}
+
+async fn check(name: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+	let status = response.status();
+	if !status.is_success() {
+		let body = response.limit_read(MAX_ERROR_SIZE).await?;
+		return Err!(BadServerResponse(
+			"S3 responded to request for {name:?} with {status}: {}",
+			String::from_utf8_lossy(&body)
+		));
+	}
+
+	Ok(response)
+}
+
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+	let (_, rest) = xml.split_once(&format!("<{name}>"))?;
+	let (value, _) = rest.split_once(&format!("</{name}>"))?;
+
+	Some(value.to_owned())
+}
+
+fn host_header(url: &Url) -> Result<String> {
+	let Some(host) = url.host_str() else {
+		return Err!(Config("media_storage.s3.endpoint", "Endpoint has no host."));
+	};
+
+	Ok(match url.port() {
+		| Some(port) => format!("{host}:{port}"),
+		| None => host.to_owned(),
+	})
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+	let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+	hmac::sign(&key, data.as_bytes()).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes
+		.iter()
+		.fold(String::with_capacity(bytes.len().saturating_mul(2)), |mut hex, byte| {
+			write!(hex, "{byte:02x}").expect("writing to a String cannot fail");
+			hex
+		})
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` unless
+/// `encode_slash` is set, as object names and query parameters are in
+/// canonical requests.
+fn uri_encode(name: &str, encode_slash: bool) -> String {
+	name.bytes()
+		.fold(String::with_capacity(name.len()), |mut encoded, byte| {
+			if byte.is_ascii_alphanumeric()
+				|| matches!(byte, b'-' | b'.' | b'_' | b'~')
+				|| (byte == b'/' && !encode_slash)
+			{
+				encoded.push(char::from(byte));
+			} else {
+				write!(encoded, "%{byte:02X}").expect("writing to a String cannot fail");
+			}
+
+			encoded
+		})
+}
@@ -0,0 +1,154 @@
+//! Streamed Media
+//!
+//! Uploads are written to storage as they arrive and downloads are read from
+//! it as they are sent, so neither is ever held in memory whole.
+
+use std::ops::Range;
+
+use bytes::Bytes;
+use conduwuit::{Result, debug, implement};
+use futures::Stream;
+use ruma::{Mxc, UserId, api::client::error::ErrorKind, http_headers::ContentDisposition};
+
+use super::{
+	Dim,
+	data::{Data, Metadata},
+	encode_key,
+	storage::{ByteStream, StoredFile},
+};
+
+/// A file being downloaded.
+pub struct FileStream {
+	pub content: ByteStream,
+	/// Size of the whole file.
+	pub size: u64,
+	/// Part of the file `content` holds, if not all of it.
+	pub range: Option<Range<u64>>,
+	pub content_type: Option<String>,
+	pub content_disposition: Option<ContentDisposition>,
+}
+
+/// Uploads a file streamed from `content`, which may be at most
+/// `max_request_size` bytes and must fit in the media quota of `user`. The
+/// `size` the upload is expected to be, if known, is reserved from that quota
+/// before any of it is stored. The media only becomes visible once all of it
+/// has been stored.
+#[implement(super::Service)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_stream<S>(
+	&self,
+	mxc: &Mxc<'_>,
+	user: Option<&UserId>,
+	content_disposition: Option<&ContentDisposition>,
+	content_type: Option<&str>,
+	content: S,
+	size: Option<u64>,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	let max_size: u64 = self.services.server.config.max_request_size.try_into()?;
+	let reservation = match user {
+		| Some(user) => self.reserve_upload(user, size, max_size).await?,
+		| None => None,
+	};
+
+	let limit = reservation
+		.as_ref()
+		.map_or(max_size, |reservation| reservation.size);
+
+	let stored = match self
+		.store_stream(mxc, user, content_disposition, content_type, content, limit)
+		.await
+	{
+		| Err(e) if limit < max_size && matches!(e.kind(), ErrorKind::TooLarge) =>
+			return Err(self.quota_exceeded()),
+		| stored => stored?,
+	};
+
+	if let Some(user) = user {
+		self.record_upload(user, stored.size).await;
+	}
+
+	// Only given back once the upload has been counted
+	drop(reservation);
+
+	Ok(stored)
+}
+
+/// Stores a file streamed from `content`, which may be at most `max_size`
+/// bytes, without counting it against anyone's quota.
+#[implement(super::Service)]
+pub(super) async fn store_stream<S>(
+	&self,
+	mxc: &Mxc<'_>,
+	user: Option<&UserId>,
+	content_disposition: Option<&ContentDisposition>,
+	content_type: Option<&str>,
+	content: S,
+	max_size: u64,
+) -> Result<StoredFile>
+where
+	S: Stream<Item = Result<Bytes>> + Send,
+{
+	let dim = Dim::default();
+	let key = Data::file_metadata_key(mxc, &dim, content_disposition, content_type)?;
+	let stored = self.write_stream(&key, content, max_size).await?;
+
+	self.db
+		.create_file_metadata(mxc, user, &dim, content_disposition, content_type)?;
+
+	debug!(%mxc, size = stored.size, sha256 = encode_key(&stored.sha256), "Stored media");
+
+	Ok(stored)
+}
+
+/// Downloads a file as a stream, limited to the part of it the `Range` header
+/// `range` asks for when that is satisfiable.
+#[implement(super::Service)]
+pub async fn get_stream(&self, mxc: &Mxc<'_>, range: Option<&str>) -> Result<Option<FileStream>> {
+	if self.is_quarantined(mxc).await {
+		return Ok(None);
+	}
+
+	let Ok(Metadata { content_disposition, content_type, key }) =
+		self.db.search_file_metadata(mxc, &Dim::default()).await
+	else {
+		return Ok(None);
+	};
+
+	let size = self.file_size(&key).await?;
+	let range = range.and_then(|range| parse_range(range, size));
+	let content = self.read_stream(&key, range.clone()).await?;
+
+	Ok(Some(FileStream {
+		content,
+		size,
+		range,
+		content_type,
+		content_disposition,
+	}))
+}
+
+/// Byte range of a file of `size` bytes a `Range` header asks for. Invalid,
+/// unsatisfiable and multiple ranges give `None`, so that the whole file is
+/// sent, which servers are allowed to do for any range request.
+#[must_use]
+pub fn parse_range(range: &str, size: u64) -> Option<Range<u64>> {
+	let spec = range.trim().strip_prefix("bytes=")?;
+	if spec.contains(',') {
+		return None;
+	}
+
+	let (start, end) = spec.split_once('-')?;
+	let (start, end) = match (start.trim(), end.trim()) {
+		| ("", suffix) => (size.saturating_sub(suffix.parse().ok()?), size),
+		| (start, "") => (start.parse().ok()?, size),
+		| (start, end) => {
+			let end: u64 = end.parse().ok()?;
+			(start.parse().ok()?, end.saturating_add(1).min(size))
+		},
+	};
+
+	(start < end).then_some(start..end)
+}
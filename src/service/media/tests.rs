@@ -237,3 +237,106 @@ fn test_apply_opengraph_dimensions_propagation() {
 		assert_eq!(result.image_height, Some(1080));
 	}
 }
+
+#[test]
+fn parses_byte_ranges() {
+	use super::parse_range;
+
+	assert_eq!(parse_range("bytes=0-499", 1000), Some(0..500));
+	assert_eq!(parse_range("bytes=500-", 1000), Some(500..1000));
+	assert_eq!(parse_range("bytes=-200", 1000), Some(800..1000));
+	assert_eq!(parse_range("bytes=900-2000", 1000), Some(900..1000));
+	assert_eq!(parse_range("bytes=1000-", 1000), None);
+	assert_eq!(parse_range("bytes=0-1,5-9", 1000), None);
+	assert_eq!(parse_range("items=0-1", 1000), None);
+}
+
+#[test]
+fn media_allowance() {
+	use super::MediaUsage;
+
+	let usage = MediaUsage {
+		used: 700,
+		quota: Some(1000),
+		quota_overridden: false,
+		uploaded_today: 100,
+		daily_limit: None,
+	};
+	assert_eq!(usage.allowance(), Some(300));
+
+	let usage = MediaUsage { daily_limit: Some(150), ..usage };
+	assert_eq!(usage.allowance(), Some(50));
+
+	let usage = MediaUsage { used: 1200, ..usage };
+	assert_eq!(usage.allowance(), Some(0));
+
+	let usage = MediaUsage { quota: None, daily_limit: None, ..usage };
+	assert_eq!(usage.allowance(), None);
+}
+
+#[tokio::test]
+async fn multipart_round_trip() {
+	use bytes::Bytes;
+	use futures::{StreamExt, TryStreamExt, stream};
+	use ruma::http_headers::{ContentDisposition, ContentDispositionType};
+
+	use super::multipart::{RemoteContent, decode, encode};
+
+	let file: Vec<u8> = (0..10_000_u32).flat_map(u32::to_le_bytes).collect();
+	let content_disposition = ContentDisposition::new(ContentDispositionType::Inline)
+		.with_filename(Some("file.bin".to_owned()));
+
+	let content = stream::iter(
+		file.chunks(777)
+			.map(Bytes::copy_from_slice)
+			.map(Ok::<_, conduwuit::Error>)
+			.collect::<Vec<_>>(),
+	);
+	let (content_type, body) =
+		encode(Some("application/x-test"), &content_disposition, content.boxed());
+
+	// Rechunk so that delimiters are split across reads
+	let body: Vec<Bytes> = body.try_collect().await.unwrap();
+	let body = body.concat();
+	let body = stream::iter(
+		body.chunks(13)
+			.map(Bytes::copy_from_slice)
+			.map(Ok::<_, conduwuit::Error>)
+			.collect::<Vec<_>>(),
+	);
+
+	let RemoteContent::File {
+		content_type,
+		content_disposition,
+		content,
+	} = decode(&content_type, body).await.unwrap()
+	else {
+		panic!("expected a file");
+	};
+
+	assert_eq!(content_type.as_deref(), Some("application/x-test"));
+	assert_eq!(content_disposition.and_then(|cd| cd.filename).as_deref(), Some("file.bin"));
+	let content: Vec<Bytes> = content.try_collect().await.unwrap();
+	assert_eq!(content.concat(), file);
+}
+
+#[tokio::test]
+async fn multipart_location() {
+	use bytes::Bytes;
+	use futures::stream;
+
+	use super::multipart::{RemoteContent, decode};
+
+	let body = "--b\r\nContent-Type: application/json\r\n\r\n{}\r\n--b\r\nLocation: \
+	            https://example.com/file\r\n\r\n\r\n--b--";
+	let body = stream::iter([Ok::<_, conduwuit::Error>(Bytes::from(body))]);
+
+	let RemoteContent::Location(location) = decode("multipart/mixed; boundary=\"b\"", body)
+		.await
+		.unwrap()
+	else {
+		panic!("expected a location");
+	};
+
+	assert_eq!(location, "https://example.com/file");
+}
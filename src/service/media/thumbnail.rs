@@ -9,10 +9,6 @@
 
 use conduwuit::{Result, checked, err, implement};
 use ruma::{Mxc, UInt, UserId, http_headers::ContentDisposition, media::Method};
-use tokio::{
-	fs,
-	io::{AsyncReadExt, AsyncWriteExt},
-};
 
 use super::{FileMeta, data::Metadata};
 
@@ -41,8 +37,7 @@ pub async fn upload_thumbnail(
 				.create_file_metadata(mxc, user, dim, content_disposition, content_type)?;
 
 		//TODO: Dangling metadata in database if creation fails
-		let mut f = self.create_media_file(&key).await?;
-		f.write_all(file).await?;
+		self.write_file(&key, file).await?;
 
 		Ok(())
 	}
@@ -62,6 +57,10 @@ pub async fn upload_thumbnail(
 	/// which crops the image afterwards.
 	#[tracing::instrument(skip(self), name = "thumbnail", level = "debug")]
 	pub async fn get_thumbnail(&self, mxc: &Mxc<'_>, dim: &Dim) -> Result<Option<FileMeta>> {
+		if self.is_quarantined(mxc).await {
+			return Ok(None);
+		}
+
 		// 0, 0 because that's the original file
 		let dim = dim.normalized();
 
@@ -79,12 +78,7 @@ pub async fn get_thumbnail(&self, mxc: &Mxc<'_>, dim: &Dim) -> Result<Option<Fil
 #[implement(super::Service)]
 #[tracing::instrument(name = "saved", level = "debug", skip(self, data))]
 async fn get_thumbnail_saved(&self, data: Metadata) -> Result<Option<FileMeta>> {
-	let mut content = Vec::new();
-	let path = self.get_media_file(&data.key);
-	fs::File::open(path)
-		.await?
-		.read_to_end(&mut content)
-		.await?;
+	let content = self.read_file(&data.key).await?;
 
 	Ok(Some(into_filemeta(data, content)))
 }
@@ -99,12 +93,7 @@ async fn get_thumbnail_generate(
 	dim: &Dim,
 	data: Metadata,
 ) -> Result<Option<FileMeta>> {
-	let mut content = Vec::new();
-	let path = self.get_media_file(&data.key);
-	fs::File::open(path)
-		.await?
-		.read_to_end(&mut content)
-		.await?;
+	let content = self.read_file(&data.key).await?;
 
 	let Ok(image) = image::load_from_memory(&content) else {
 		// Couldn't parse file to generate thumbnail, send original
@@ -131,8 +120,7 @@ async fn get_thumbnail_generate(
 		data.content_type.as_deref(),
 	)?;
 
-	let mut f = self.create_media_file(&thumbnail_key).await?;
-	f.write_all(&thumbnail_bytes).await?;
+	self.write_file(&thumbnail_key, &thumbnail_bytes).await?;
 
 	Ok(Some(into_filemeta(data, thumbnail_bytes)))
 }
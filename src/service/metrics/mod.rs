@@ -0,0 +1,343 @@
+//! Metrics in the Prometheus text exposition format.
+//!
+//! Everything is gathered when the metrics are scraped; the counters behind
+//! them are kept by the request router, the sending service, the auth chain
+//! cache and the database.
+
+use std::{
+	ffi::CStr,
+	sync::{Arc, atomic::Ordering},
+};
+
+use conduwuit::{
+	Result, Server, implement,
+	metrics::{
+		LATENCY_BUCKETS,
+		prometheus::{Kind, Text},
+	},
+};
+use database::Database;
+
+use crate::{Dep, rooms, sending, sending::stats::DestinationStats};
+
+pub struct Service {
+	services: Services,
+	server: Arc<Server>,
+	db: Arc<Database>,
+}
+
+struct Services {
+	auth_chain: Dep<rooms::auth_chain::Service>,
+	sending: Dep<sending::Service>,
+}
+
+const PENDING_COMPACTION_BYTES: &CStr = c"rocksdb.estimate-pending-compaction-bytes";
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			services: Services {
+				auth_chain: args.depend::<rooms::auth_chain::Service>("rooms::auth_chain"),
+				sending: args.depend::<sending::Service>("sending"),
+			},
+			server: args.server.clone(),
+			db: args.db.clone(),
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Whether `token` may scrape the metrics. Without a configured token only
+/// the separate metrics listener serves them, so any request reaching it is
+/// allowed.
+#[implement(Service)]
+#[must_use]
+pub fn is_authorized(&self, token: Option<&str>) -> bool {
+	match &self.server.config.metrics.token {
+		| Some(expected) => token.is_some_and(|token| token == expected),
+		| None => true,
+	}
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+#[implement(Service)]
+pub fn render(&self) -> Result<String> {
+	let mut text = Text::new();
+	self.render_requests(&mut text);
+	self.render_federation(&mut text);
+	self.render_caches(&mut text);
+	self.render_database(&mut text)?;
+
+	Ok(text.finish())
+}
+
+#[implement(Service)]
+fn render_requests(&self, text: &mut Text) {
+	let metrics = &self.server.metrics;
+	let routes = metrics.routes();
+
+	text.family(
+		"continuwuity_http_requests_total",
+		Kind::Counter,
+		"Requests handled, by route and response status.",
+	);
+	for ((method, route), stats) in &routes {
+		for (status, count) in &stats.responses {
+			let status = status.to_string();
+			text.sample(
+				"continuwuity_http_requests_total",
+				&[("method", method.as_str()), ("route", route.as_str()), ("status", &status)],
+				count,
+			);
+		}
+	}
+
+	text.family(
+		"continuwuity_http_request_duration_seconds",
+		Kind::Histogram,
+		"Time taken to handle requests, by route.",
+	);
+	for ((method, route), stats) in &routes {
+		let labels = [("method", method.as_str()), ("route", route.as_str())];
+		let mut cumulative = 0_u64;
+		for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+			cumulative = cumulative.saturating_add(count);
+			let le = bound.to_string();
+			text.sample(
+				"continuwuity_http_request_duration_seconds_bucket",
+				&[labels[0], labels[1], ("le", &le)],
+				cumulative,
+			);
+		}
+
+		text.sample(
+			"continuwuity_http_request_duration_seconds_bucket",
+			&[labels[0], labels[1], ("le", "+Inf")],
+			stats.count,
+		)
+		.sample("continuwuity_http_request_duration_seconds_sum", &labels, stats.seconds)
+		.sample("continuwuity_http_request_duration_seconds_count", &labels, stats.count);
+	}
+
+	text.family(
+		"continuwuity_http_requests_active",
+		Kind::Gauge,
+		"Requests currently being handled.",
+	)
+	.sample(
+		"continuwuity_http_requests_active",
+		&[],
+		metrics.requests_handle_active.load(Ordering::Relaxed),
+	)
+	.family(
+		"continuwuity_http_request_panics_total",
+		Kind::Counter,
+		"Requests whose handler panicked.",
+	)
+	.sample(
+		"continuwuity_http_request_panics_total",
+		&[],
+		metrics.requests_panic.load(Ordering::Relaxed),
+	)
+	.family(
+		"continuwuity_sync_longpolls_active",
+		Kind::Gauge,
+		"Sync requests currently waiting for new data.",
+	)
+	.sample(
+		"continuwuity_sync_longpolls_active",
+		&[],
+		metrics.sync_longpoll_active.load(Ordering::Relaxed),
+	)
+	.family(
+		"continuwuity_sync_longpolls_total",
+		Kind::Counter,
+		"Sync requests which had to wait for new data.",
+	)
+	.sample(
+		"continuwuity_sync_longpolls_total",
+		&[],
+		metrics.sync_longpoll_total.load(Ordering::Relaxed),
+	);
+}
+
+#[implement(Service)]
+fn render_federation(&self, text: &mut Text) {
+	let sending = &self.services.sending;
+	let destinations = sending.stats.destinations();
+
+	let families: [(&str, Kind, &str, fn(&DestinationStats) -> u64); 7] = [
+		(
+			"continuwuity_federation_sent_transactions_total",
+			Kind::Counter,
+			"Transactions sent, by destination server.",
+			|stats| stats.sent_txns,
+		),
+		(
+			"continuwuity_federation_sent_pdus_total",
+			Kind::Counter,
+			"PDUs sent, by destination server.",
+			|stats| stats.sent_pdus,
+		),
+		(
+			"continuwuity_federation_sent_edus_total",
+			Kind::Counter,
+			"EDUs sent, by destination server.",
+			|stats| stats.sent_edus,
+		),
+		(
+			"continuwuity_federation_send_errors_total",
+			Kind::Counter,
+			"Transactions which failed to send, by destination server.",
+			|stats| stats.send_errors,
+		),
+		(
+			"continuwuity_federation_received_transactions_total",
+			Kind::Counter,
+			"Transactions received, by origin server.",
+			|stats| stats.received_txns,
+		),
+		(
+			"continuwuity_federation_received_pdus_total",
+			Kind::Counter,
+			"PDUs received, by origin server.",
+			|stats| stats.received_pdus,
+		),
+		(
+			"continuwuity_federation_received_edus_total",
+			Kind::Counter,
+			"EDUs received, by origin server.",
+			|stats| stats.received_edus,
+		),
+	];
+
+	for (name, kind, help, value) in families {
+		text.family(name, kind, help);
+		for (server, stats) in &destinations {
+			text.sample(name, &[("server", server.as_str())], value(stats));
+		}
+	}
+
+	text.family(
+		"continuwuity_sending_queue_depth",
+		Kind::Gauge,
+		"Messages waiting to be picked up, by sender worker.",
+	);
+	for (worker, depth) in sending.queue_depths().into_iter().enumerate() {
+		let worker = worker.to_string();
+		text.sample("continuwuity_sending_queue_depth", &[("worker", &worker)], depth);
+	}
+}
+
+#[implement(Service)]
+fn render_caches(&self, text: &mut Text) {
+	let (hits, misses) = self.services.auth_chain.get_cache_stats();
+	let (len, capacity) = self.services.auth_chain.get_cache_usage();
+	text.family(
+		"continuwuity_auth_chain_cache_hits_total",
+		Kind::Counter,
+		"Auth chain lookups answered from memory.",
+	)
+	.sample("continuwuity_auth_chain_cache_hits_total", &[], hits)
+	.family(
+		"continuwuity_auth_chain_cache_misses_total",
+		Kind::Counter,
+		"Auth chain lookups not found in memory.",
+	)
+	.sample("continuwuity_auth_chain_cache_misses_total", &[], misses)
+	.family(
+		"continuwuity_auth_chain_cache_entries",
+		Kind::Gauge,
+		"Auth chains held in memory.",
+	)
+	.sample("continuwuity_auth_chain_cache_entries", &[], len)
+	.family(
+		"continuwuity_auth_chain_cache_capacity",
+		Kind::Gauge,
+		"Auth chains which can be held in memory.",
+	)
+	.sample("continuwuity_auth_chain_cache_capacity", &[], capacity);
+
+	// The PDU caches are the block caches of the columns holding PDUs, so the
+	// same counters are kept for every column.
+	let columns: Vec<_> = self
+		.db
+		.iter()
+		.map(|(name, map)| (*name, map.cache_stats()))
+		.collect();
+
+	text.family(
+		"continuwuity_db_cache_hits_total",
+		Kind::Counter,
+		"Point lookups answered from the cache, by column. The pduid_pdu and eventid_outlierpdu \
+		 columns are the PDU cache.",
+	);
+	for (name, (hits, _)) in &columns {
+		text.sample("continuwuity_db_cache_hits_total", &[("column", name)], hits);
+	}
+
+	text.family(
+		"continuwuity_db_cache_misses_total",
+		Kind::Counter,
+		"Point lookups which had to read from disk, by column.",
+	);
+	for (name, (_, misses)) in &columns {
+		text.sample("continuwuity_db_cache_misses_total", &[("column", name)], misses);
+	}
+}
+
+#[implement(Service)]
+fn render_database(&self, text: &mut Text) -> Result {
+	let stats = self.db.db.stats()?;
+	text.family(
+		"continuwuity_db_memtable_bytes",
+		Kind::Gauge,
+		"Memory used by write buffers, including those not yet flushed.",
+	)
+	.sample("continuwuity_db_memtable_bytes", &[], stats.mem_table_total)
+	.family(
+		"continuwuity_db_memtable_unflushed_bytes",
+		Kind::Gauge,
+		"Memory used by write buffers not yet flushed.",
+	)
+	.sample("continuwuity_db_memtable_unflushed_bytes", &[], stats.mem_table_unflushed)
+	.family(
+		"continuwuity_db_table_readers_bytes",
+		Kind::Gauge,
+		"Memory used by table readers, including indexes and filters.",
+	)
+	.sample("continuwuity_db_table_readers_bytes", &[], stats.table_readers)
+	.family("continuwuity_db_cache_bytes", Kind::Gauge, "Memory used by each cache.")
+	.sample("continuwuity_db_cache_bytes", &[("cache", "row")], stats.row_cache);
+	for (name, usage) in &stats.col_cache {
+		text.sample("continuwuity_db_cache_bytes", &[("cache", name)], usage);
+	}
+
+	text.family(
+		"continuwuity_db_running_compactions",
+		Kind::Gauge,
+		"Compactions currently running.",
+	)
+	.sample("continuwuity_db_running_compactions", &[], stats.running_compactions)
+	.family("continuwuity_db_running_flushes", Kind::Gauge, "Flushes currently running.")
+	.sample("continuwuity_db_running_flushes", &[], stats.running_flushes)
+	.family(
+		"continuwuity_db_background_errors_total",
+		Kind::Counter,
+		"Errors in compactions and flushes since startup.",
+	)
+	.sample("continuwuity_db_background_errors_total", &[], stats.background_errors)
+	.family(
+		"continuwuity_db_pending_compaction_bytes",
+		Kind::Gauge,
+		"Estimated bytes compaction has to rewrite, by column.",
+	);
+	for (name, map) in self.db.iter() {
+		if let Ok(bytes) = map.property_integer(PENDING_COMPACTION_BYTES) {
+			text.sample("continuwuity_db_pending_compaction_bytes", &[("column", name)], bytes);
+		}
+	}
+
+	Ok(())
+}
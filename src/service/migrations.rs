@@ -10,7 +10,7 @@
 	},
 	warn,
 };
-use database::Json;
+use database::{Deserialized, Json};
 use futures::{FutureExt, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use ruma::{
@@ -71,6 +71,9 @@ async fn fresh(services: &Services) -> Result<()> {
 	db["global"].insert(b"fix_corrupt_msc4133_fields", []);
 	db["global"].insert(b"populate_userroomid_leftstate_table", []);
 	db["global"].insert(b"fix_local_invite_state", []);
+	db["global"].insert(b"lowercase_email_localpart", []);
+	db["global"].insert(b"backfill_media_usage", []);
+	db["global"].insert(b"index_media_references", []);
 
 	// Create the admin room and server user on first run
 	info!("Creating admin room and server user");
@@ -114,7 +117,9 @@ async fn migrate(services: &Services) -> Result<()> {
 		media::migrations::migrate_sha256_media(services)
 			.await
 			.map_err(|e| err!("Failed to run SHA256 media migration: {e}"))?;
-	} else if config.media_startup_check {
+	} else if config.media_startup_check
+		&& services.media.storage_backend() == media::Backend::Filesystem
+	{
 		info!("Starting media startup integrity check.");
 		let now = std::time::Instant::now();
 		media::migrations::checkup_sha256_media(services)
@@ -228,6 +233,39 @@ async fn migrate(services: &Services) -> Result<()> {
 			.map_err(|e| err!("Failed to run 'fix_local_invite_state' migration': {e}"))?;
 	}
 
+	if db["global"]
+		.get(LOWERCASED_EMAIL_LOCALPART_MARKER)
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'lowercase_email_localpart'");
+		lowercase_email_localpart(services)
+			.await
+			.map_err(|e| err!("Failed to run 'lowercase_email_localpart' migration': {e}"))?;
+	}
+
+	if db["global"]
+		.get(b"backfill_media_usage")
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'backfill_media_usage'");
+		media::migrations::backfill_media_usage(services)
+			.await
+			.map_err(|e| err!("Failed to run 'backfill_media_usage' migration': {e}"))?;
+	}
+
+	if db["global"]
+		.get(b"index_media_references")
+		.await
+		.is_not_found()
+	{
+		info!("Running migration 'index_media_references'");
+		media::migrations::index_media_references(services)
+			.await
+			.map_err(|e| err!("Failed to run 'index_media_references' migration': {e}"))?;
+	}
+
 	assert_eq!(
 		services.globals.db.database_version().await,
 		DATABASE_VERSION,
@@ -841,3 +879,48 @@ async fn fix_local_invite_state(services: &Services) -> Result {
 	db.db.sort()?;
 	Ok(())
 }
+
+const LOWERCASED_EMAIL_LOCALPART_MARKER: &str = "lowercase_email_localpart";
+async fn lowercase_email_localpart(services: &Services) -> Result {
+	// Emails are looked up by their lowercased address so that users can log in
+	// with any case
+	let db = &services.db;
+	let email_localpart = db["email_localpart"].clone();
+
+	let emails: Vec<(String, String)> = email_localpart
+		.stream()
+		.ignore_err()
+		.ready_filter(|(email, _): &(&str, &str)| email.chars().any(char::is_uppercase))
+		.map(|(email, localpart): (&str, &str)| (email.to_owned(), localpart.to_owned()))
+		.collect()
+		.await;
+
+	let mut fixed = 0_usize;
+	for (email, localpart) in emails {
+		let lowercased = email.to_lowercase();
+		if let Ok(other) = email_localpart
+			.get(&lowercased)
+			.await
+			.deserialized::<String>()
+			&& other != localpart
+		{
+			warn!(
+				%email,
+				%localpart,
+				%other,
+				"Another user has this email in a different case, so it remains theirs"
+			);
+			continue;
+		}
+
+		email_localpart.remove(&email);
+		email_localpart.insert(&lowercased, &localpart);
+		fixed = fixed.saturating_add(1);
+	}
+
+	info!(?fixed, "Lowercased email addresses.");
+
+	db["global"].insert(LOWERCASED_EMAIL_LOCALPART_MARKER, []);
+	db.db.sort()?;
+	Ok(())
+}
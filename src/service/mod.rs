@@ -25,21 +25,29 @@
 pub mod firstrun;
 pub mod globals;
 pub mod key_backups;
+pub mod ldap_sync;
 pub mod mailer;
 pub mod media;
+pub mod metrics;
 pub mod moderation;
 pub mod password_reset;
 pub mod presence;
 pub mod pusher;
+pub mod ratelimit;
 pub mod registration_tokens;
+pub mod rendezvous;
+pub mod reports;
 pub mod resolver;
 pub mod rooms;
 pub mod sending;
 pub mod server_keys;
+pub mod sms;
+pub mod sso;
 pub mod sync;
 pub mod threepid;
 pub mod transactions;
 pub mod uiaa;
+pub mod user_directory;
 pub mod users;
 
 pub(crate) use service::{Args, Dep, Service};
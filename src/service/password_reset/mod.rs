@@ -101,13 +101,18 @@ pub async fn check_token(&self, token: &str) -> Option<ValidResetToken> {
 	}
 
 	/// Consume the supplied valid token, using it to change its user's password
-	/// to `new_password`.
+	/// to `new_password`. The token is kept if `new_password` does not meet the
+	/// password policy.
 	pub async fn consume_token(
 		&self,
 		ValidResetToken { token, info }: ValidResetToken,
 		new_password: &str,
 	) -> Result<()> {
 		if info.is_valid() {
+			self.services
+				.users
+				.check_password_policy(Some(info.user.localpart()), new_password)?;
+
 			self.db.remove_token(&token);
 			self.services
 				.users
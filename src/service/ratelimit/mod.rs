@@ -0,0 +1,423 @@
+//! Rate limiting of client and federation requests.
+//!
+//! Each kind of request which is cheap to send but expensive or disruptive to
+//! handle has a bucket per requester, refilled at the configured rate. Admins
+//! can give users limits of their own, or exempt bots from rate limiting
+//! entirely.
+//!
+//! Clients over their quota are rejected straight away. Servers over their
+//! quota are slowed down first, so that a busy server is deprioritised rather
+//! than cut off, and the servers which were throttled are remembered for
+//! admins to look at.
+
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	num::NonZeroU32,
+	sync::Arc,
+	time::{Duration, Instant, SystemTime},
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Error, Result, Server, SyncMutex, config::RatelimitConfig, debug, debug_warn, implement,
+	utils::stream::TryIgnore,
+};
+use database::{Deserialized, Json, Map};
+use futures::{Stream, StreamExt};
+use governor::{
+	DefaultDirectRateLimiter, DefaultKeyedRateLimiter, InsufficientCapacity, NotUntil, Quota,
+	RateLimiter,
+	clock::{Clock, DefaultClock},
+};
+use http::StatusCode;
+use ruma::{
+	OwnedDeviceId, OwnedServerName, OwnedUserId, ServerName, UserId,
+	api::client::error::{ErrorKind, RetryAfter},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
+
+pub struct Service {
+	db: Data,
+	server: Arc<Server>,
+	interrupt: Notify,
+	limiters: HashMap<Class, DefaultKeyedRateLimiter<Key>>,
+	overridden: SyncMutex<HashMap<(Class, OwnedUserId), DefaultDirectRateLimiter>>,
+	throttled: SyncMutex<HashMap<OwnedServerName, Throttled>>,
+}
+
+struct Data {
+	userid_ratelimitoverride: Arc<Map>,
+}
+
+/// The kinds of requests which are rate limited separately.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Class {
+	/// Sending messages and state events, and redacting events.
+	Message,
+	Login,
+	Registration,
+	/// Joining and knocking on rooms.
+	Join,
+	Invite,
+	/// Uploading media.
+	Media,
+	/// Creating and updating rendezvous sessions.
+	Rendezvous,
+	/// PDUs in transactions sent to us.
+	FederationPdu,
+	/// Backfill, missing events and room state requests from other servers.
+	FederationRequest,
+}
+
+/// Who a request counts against.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+	Device(OwnedUserId, OwnedDeviceId),
+	User(OwnedUserId),
+	Ip(IpAddr),
+	Origin(OwnedServerName),
+}
+
+/// Limits set by an admin for a user in place of the configured ones.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Override {
+	/// The user is never rate limited.
+	Exempt,
+
+	/// The user gets this quota for every kind of request, shared between all
+	/// of their devices.
+	Quota {
+		per_second: f64,
+		burst_count: u32,
+	},
+}
+
+/// How often a server was held back for going over its quota.
+#[derive(Clone, Copy, Debug)]
+pub struct Throttled {
+	/// Requests and transactions which were delayed.
+	pub delayed: u64,
+
+	/// Requests which were rejected after being delayed for too long.
+	pub rejected: u64,
+
+	pub last_throttled: SystemTime,
+}
+
+/// How long throttled servers are remembered for.
+const THROTTLED_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		let config = &args.server.config.ratelimit;
+		let limiters = Class::ALL
+			.into_iter()
+			.filter_map(|class| {
+				let (per_second, burst_count) = class.config(config)?;
+				Some((class, RateLimiter::keyed(quota(per_second, burst_count)?)))
+			})
+			.collect();
+
+		Ok(Arc::new(Self {
+			db: Data {
+				userid_ratelimitoverride: args.db["userid_ratelimitoverride"].clone(),
+			},
+			server: args.server.clone(),
+			interrupt: Notify::new(),
+			limiters,
+			overridden: SyncMutex::default(),
+			throttled: SyncMutex::default(),
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "ratelimit", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result {
+		if self.limiters.is_empty() {
+			return Ok(());
+		}
+
+		// Forget requesters whose buckets have refilled so the limiters don't
+		// grow without bound
+		let mut i = interval(Duration::from_secs(60));
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			for limiter in self.limiters.values() {
+				limiter.retain_recent();
+				limiter.shrink_to_fit();
+			}
+
+			self.throttled.lock().retain(|_, throttled| {
+				throttled
+					.last_throttled
+					.elapsed()
+					.is_ok_and(|elapsed| elapsed < THROTTLED_TTL)
+			});
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Class {
+	const ALL: [Self; 9] = [
+		Self::Message,
+		Self::Login,
+		Self::Registration,
+		Self::Join,
+		Self::Invite,
+		Self::Media,
+		Self::Rendezvous,
+		Self::FederationPdu,
+		Self::FederationRequest,
+	];
+
+	/// The configured rate and burst for the class, or None if requests of
+	/// this class are not rate limited.
+	fn config(self, config: &RatelimitConfig) -> Option<(f64, u32)> {
+		let enabled = match self {
+			| Self::FederationPdu | Self::FederationRequest => config.federation_enabled,
+			| _ => config.enabled,
+		};
+
+		enabled.then_some(match self {
+			| Self::Message => (config.message_per_second, config.message_burst_count),
+			| Self::Login => (config.login_per_second, config.login_burst_count),
+			| Self::Registration =>
+				(config.registration_per_second, config.registration_burst_count),
+			| Self::Join => (config.join_per_second, config.join_burst_count),
+			| Self::Invite => (config.invite_per_second, config.invite_burst_count),
+			| Self::Media => (config.media_per_second, config.media_burst_count),
+			| Self::Rendezvous => (config.rendezvous_per_second, config.rendezvous_burst_count),
+			| Self::FederationPdu =>
+				(config.federation_pdus_per_second, config.federation_pdus_burst_count),
+			| Self::FederationRequest =>
+				(config.federation_requests_per_second, config.federation_requests_burst_count),
+		})
+	}
+}
+
+/// Takes a request of the given class from each of the requester's buckets,
+/// such as those of their device, their account and their IP address, failing
+/// with `M_LIMIT_EXCEEDED` if any of them is empty. A user with limits of their
+/// own is held to those instead.
+#[implement(Service)]
+pub async fn check(&self, class: Class, keys: &[Key]) -> Result {
+	let Some(limiter) = self.limiters.get(&class) else {
+		return Ok(());
+	};
+
+	let user = keys.iter().find_map(|key| match key {
+		| Key::Device(user_id, _) | Key::User(user_id) => Some((key, user_id)),
+		| _ => None,
+	});
+
+	if let Some((key, user_id)) = user {
+		if let Ok(limits) = self.get_override(user_id).await {
+			let Override::Quota { per_second, burst_count } = limits else {
+				return Ok(());
+			};
+
+			let Some(quota) = quota(per_second, burst_count) else {
+				return Ok(());
+			};
+
+			return self
+				.overridden
+				.lock()
+				.entry((class, user_id.clone()))
+				.or_insert_with(|| RateLimiter::direct(quota))
+				.check()
+				.map_err(|not_until| limit_exceeded(class, key, wait_time(&not_until)));
+		}
+	}
+
+	for key in keys {
+		limiter
+			.check_key(key)
+			.map_err(|not_until| limit_exceeded(class, key, wait_time(&not_until)))?;
+	}
+
+	Ok(())
+}
+
+#[implement(Service)]
+pub async fn get_override(&self, user_id: &UserId) -> Result<Override> {
+	self.db
+		.userid_ratelimitoverride
+		.get(user_id)
+		.await
+		.deserialized()
+}
+
+#[implement(Service)]
+pub fn set_override(&self, user_id: &UserId, limits: Override) {
+	self.db
+		.userid_ratelimitoverride
+		.raw_put(user_id, Json(limits));
+
+	self.forget_overridden(user_id);
+}
+
+#[implement(Service)]
+pub fn remove_override(&self, user_id: &UserId) {
+	self.db.userid_ratelimitoverride.remove(user_id);
+	self.forget_overridden(user_id);
+}
+
+/// Returns every user with limits of their own.
+#[implement(Service)]
+pub fn overrides(&self) -> impl Stream<Item = (OwnedUserId, Override)> + Send + '_ {
+	self.db
+		.userid_ratelimitoverride
+		.stream()
+		.ignore_err()
+		.map(|(user_id, limits): (&UserId, Override)| (user_id.to_owned(), limits))
+}
+
+/// Holds back a transaction from `origin` until its quota allows `pdus` more
+/// PDUs.
+#[implement(Service)]
+pub async fn throttle_pdus(&self, origin: &ServerName, pdus: usize) {
+	self.wait_for_quota(Class::FederationPdu, origin, pdus, None)
+		.await;
+}
+
+/// Delays a request from `origin` until its quota allows it, failing with
+/// `M_LIMIT_EXCEEDED` if that would take longer than
+/// `federation_max_delay_ms`.
+#[implement(Service)]
+pub async fn throttle_request(&self, origin: &ServerName) -> Result {
+	let max_delay = Duration::from_millis(self.server.config.ratelimit.federation_max_delay_ms);
+	match self
+		.wait_for_quota(Class::FederationRequest, origin, 1, Some(max_delay))
+		.await
+	{
+		| Some(retry_after) => Err(limit_exceeded(
+			Class::FederationRequest,
+			&Key::Origin(origin.to_owned()),
+			retry_after,
+		)),
+		| None => Ok(()),
+	}
+}
+
+/// Waits until the origin's bucket for the class has room for `n` more, or
+/// returns how much longer it would have had to wait if that is more than
+/// `max_delay`.
+#[implement(Service)]
+async fn wait_for_quota(
+	&self,
+	class: Class,
+	origin: &ServerName,
+	n: usize,
+	max_delay: Option<Duration>,
+) -> Option<Duration> {
+	let limiter = self.limiters.get(&class)?;
+	let mut n = u32::try_from(n).ok().and_then(NonZeroU32::new)?;
+	let key = Key::Origin(origin.to_owned());
+	let started = Instant::now();
+	let mut delayed = false;
+	loop {
+		let wait = match limiter.check_key_n(&key, n) {
+			| Ok(Ok(())) => return None,
+			| Ok(Err(not_until)) => wait_time(&not_until),
+			| Err(InsufficientCapacity(burst_count)) => {
+				// More than fits in a burst; take the whole burst instead
+				n = NonZeroU32::new(burst_count).unwrap_or(NonZeroU32::MIN);
+				continue;
+			},
+		};
+
+		if max_delay.is_some_and(|max_delay| started.elapsed().saturating_add(wait) > max_delay) {
+			self.record_throttled(origin, false, true);
+			return Some(wait);
+		}
+
+		if !delayed {
+			debug_warn!(%origin, ?class, ?wait, "Delaying server over its quota");
+			self.record_throttled(origin, true, false);
+			delayed = true;
+		}
+
+		tokio::time::sleep(wait).await;
+	}
+}
+
+/// Returns the servers which were throttled in the last day, most recently
+/// throttled first.
+#[implement(Service)]
+pub fn throttled_origins(&self) -> Vec<(OwnedServerName, Throttled)> {
+	let mut throttled: Vec<_> = self
+		.throttled
+		.lock()
+		.iter()
+		.map(|(origin, throttled)| (origin.clone(), *throttled))
+		.collect();
+
+	throttled.sort_by(|(_, a), (_, b)| b.last_throttled.cmp(&a.last_throttled));
+	throttled
+}
+
+#[implement(Service)]
+fn record_throttled(&self, origin: &ServerName, delayed: bool, rejected: bool) {
+	let mut throttled = self.throttled.lock();
+	let throttled = throttled.entry(origin.to_owned()).or_insert(Throttled {
+		delayed: 0,
+		rejected: 0,
+		last_throttled: SystemTime::now(),
+	});
+
+	throttled.delayed = throttled.delayed.saturating_add(delayed.into());
+	throttled.rejected = throttled.rejected.saturating_add(rejected.into());
+	throttled.last_throttled = SystemTime::now();
+}
+
+/// Drops the buckets made for a user's previous override so that a changed
+/// quota applies straight away.
+#[implement(Service)]
+fn forget_overridden(&self, user_id: &UserId) {
+	self.overridden
+		.lock()
+		.retain(|(_, overridden), _| overridden != user_id);
+}
+
+/// Builds the quota refilling `per_second` requests every second, or None if
+/// the rate disables rate limiting.
+fn quota(per_second: f64, burst_count: u32) -> Option<Quota> {
+	let period = Duration::try_from_secs_f64(per_second.recip()).ok()?;
+	let burst_count = NonZeroU32::new(burst_count).unwrap_or(NonZeroU32::MIN);
+
+	Quota::with_period(period).map(|quota| quota.allow_burst(burst_count))
+}
+
+fn wait_time(not_until: &NotUntil<<DefaultClock as Clock>::Instant>) -> Duration {
+	not_until.wait_time_from(DefaultClock::default().now())
+}
+
+fn limit_exceeded(class: Class, key: &Key, retry_after: Duration) -> Error {
+	debug!(?class, ?key, ?retry_after, "Rate limited");
+
+	Error::Request(
+		ErrorKind::LimitExceeded {
+			retry_after: Some(RetryAfter::Delay(retry_after)),
+		},
+		"Too many requests, try again later.".into(),
+		StatusCode::TOO_MANY_REQUESTS,
+	)
+}
@@ -0,0 +1,142 @@
+//! Rendezvous sessions (MSC4108): short-lived blobs through which a device
+//! being signed in with a QR code and a device already signed in exchange
+//! messages. Sessions are kept in memory only, as they last for minutes.
+
+use std::{
+	collections::HashMap,
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use conduwuit::{Err, Error, Result, Server, SyncMutex, err, implement, utils};
+use ruma::api::client::error::ErrorKind;
+
+pub struct Service {
+	server: Arc<Server>,
+	sessions: SyncMutex<HashMap<String, Session>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+	pub data: Bytes,
+	pub content_type: String,
+
+	/// Changes on every update, so that a device can tell whether the other
+	/// has written to the session since it last looked.
+	pub etag: String,
+	pub expires_at: SystemTime,
+	pub last_modified: SystemTime,
+}
+
+const SESSION_ID_LENGTH: usize = 32;
+const ETAG_LENGTH: usize = 16;
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			server: args.server.clone(),
+			sessions: SyncMutex::new(HashMap::new()),
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Opens a session holding `data`, returning its ID.
+#[implement(Service)]
+pub fn create(&self, content_type: String, data: Bytes) -> Result<(String, Session)> {
+	self.check_size(&data)?;
+
+	let config = &self.server.config.rendezvous;
+	let now = SystemTime::now();
+	let session = Session {
+		data,
+		content_type,
+		etag: utils::random_string(ETAG_LENGTH),
+		expires_at: now
+			.checked_add(Duration::from_secs(config.ttl))
+			.unwrap_or(now),
+		last_modified: now,
+	};
+
+	let mut sessions = self.sessions.lock();
+	sessions.retain(|_, session| session.expires_at > now);
+	if sessions.len() >= config.max_sessions {
+		return Err(Error::BadRequest(
+			ErrorKind::LimitExceeded { retry_after: None },
+			"Too many rendezvous sessions are open.",
+		));
+	}
+
+	let id = utils::random_string(SESSION_ID_LENGTH);
+	sessions.insert(id.clone(), session.clone());
+
+	Ok((id, session))
+}
+
+/// The session with the given ID, unless it has expired.
+#[implement(Service)]
+pub fn get(&self, id: &str) -> Result<Session> {
+	let mut sessions = self.sessions.lock();
+	match sessions.get(id) {
+		| Some(session) if session.expires_at > SystemTime::now() => Ok(session.clone()),
+		| Some(_) => {
+			sessions.remove(id);
+			Err!(Request(NotFound("Rendezvous session has expired.")))
+		},
+		| None => Err!(Request(NotFound("Unknown rendezvous session."))),
+	}
+}
+
+/// Replaces the data of a session, provided it still has the ETag the
+/// writer last saw. The session keeps its original expiry.
+#[implement(Service)]
+pub fn update(
+	&self,
+	id: &str,
+	if_match: impl FnOnce(&str) -> bool,
+	content_type: String,
+	data: Bytes,
+) -> Result<Session> {
+	self.check_size(&data)?;
+
+	let mut sessions = self.sessions.lock();
+	let session = sessions
+		.get_mut(id)
+		.filter(|session| session.expires_at > SystemTime::now())
+		.ok_or_else(|| err!(Request(NotFound("Unknown rendezvous session."))))?;
+
+	if !if_match(&session.etag) {
+		return Err!(Request(
+			Unknown("The session was updated by the other device."),
+			PRECONDITION_FAILED
+		));
+	}
+
+	session.data = data;
+	session.content_type = content_type;
+	session.etag = utils::random_string(ETAG_LENGTH);
+	session.last_modified = SystemTime::now();
+
+	Ok(session.clone())
+}
+
+/// Closes a session.
+#[implement(Service)]
+pub fn delete(&self, id: &str) -> Result {
+	match self.sessions.lock().remove(id) {
+		| Some(_) => Ok(()),
+		| None => Err!(Request(NotFound("Unknown rendezvous session."))),
+	}
+}
+
+#[implement(Service)]
+fn check_size(&self, data: &Bytes) -> Result {
+	let max_size = self.server.config.rendezvous.max_payload_size;
+	if data.len() > max_size {
+		return Err!(Request(TooLarge("Rendezvous payloads may be at most {max_size} bytes.")));
+	}
+
+	Ok(())
+}
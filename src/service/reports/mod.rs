@@ -0,0 +1,323 @@
+//! Abuse reports submitted by local users.
+//!
+//! Reports are kept until an admin resolves them so that they can be triaged
+//! from the admin room or the admin API. A user reporting the same thing again
+//! while their earlier report is still open updates that report instead of
+//! creating a new one.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use conduwuit::{Err, Error, Result, implement, utils, utils::stream::TryIgnore};
+use database::{Deserialized, Json, Map};
+use futures::Stream;
+use ruma::{OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{Dep, globals};
+
+pub struct Service {
+	db: Data,
+	services: Services,
+	writing: Mutex<()>,
+}
+
+struct Data {
+	/// The unresolved report of each reporter about each target.
+	reportertarget_reportid: Arc<Map>,
+	reportid_report: Arc<Map>,
+}
+
+struct Services {
+	globals: Dep<globals::Service>,
+}
+
+/// An abuse report and its triage state.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Report {
+	/// The local user who made the report.
+	pub reporter: OwnedUserId,
+
+	/// What was reported.
+	pub target: ReportTarget,
+
+	/// The reason given by the reporter.
+	pub reason: Option<String>,
+
+	/// How offensive the reporter rated the content, from -100 (most
+	/// offensive) to 0.
+	pub score: Option<i64>,
+
+	pub status: ReportStatus,
+
+	/// The admin who claimed or resolved the report.
+	pub assignee: Option<OwnedUserId>,
+
+	/// How the report was resolved.
+	pub resolution: Option<String>,
+
+	/// When the report was first received, in milliseconds since the unix
+	/// epoch.
+	pub received_ts: u64,
+
+	/// How many more times the reporter reported the same thing while this
+	/// report was open.
+	pub duplicates: u64,
+}
+
+/// The event, room or user a report is about.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReportTarget {
+	Event {
+		room_id: OwnedRoomId,
+		event_id: OwnedEventId,
+		sender: OwnedUserId,
+	},
+	Room {
+		room_id: OwnedRoomId,
+	},
+	User {
+		user_id: OwnedUserId,
+	},
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+	/// Nobody has looked at the report yet.
+	Open,
+
+	/// An admin is looking into the report.
+	Claimed,
+
+	/// The report has been dealt with.
+	Resolved,
+}
+
+/// Criteria which listed reports must all match.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ReportFilter {
+	pub status: Option<ReportStatus>,
+
+	/// The reported room, or the room of the reported event.
+	pub room_id: Option<OwnedRoomId>,
+
+	/// The reporter, the reported user or the sender of the reported event.
+	pub user_id: Option<OwnedUserId>,
+
+	pub assignee: Option<OwnedUserId>,
+}
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				reportertarget_reportid: args.db["reportertarget_reportid"].clone(),
+				reportid_report: args.db["reportid_report"].clone(),
+			},
+			services: Services {
+				globals: args.depend::<globals::Service>("globals"),
+			},
+			writing: Mutex::new(()),
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl ReportTarget {
+	/// The room the report is about, if any.
+	#[must_use]
+	pub fn room_id(&self) -> Option<&RoomId> {
+		match self {
+			| Self::Event { room_id, .. } | Self::Room { room_id } => Some(room_id),
+			| Self::User { .. } => None,
+		}
+	}
+
+	/// The user whose behaviour the report is about, if any.
+	#[must_use]
+	pub fn user_id(&self) -> Option<&UserId> {
+		match self {
+			| Self::Event { sender, .. } => Some(sender),
+			| Self::User { user_id } => Some(user_id),
+			| Self::Room { .. } => None,
+		}
+	}
+
+	/// Identifies the target in the index of unresolved reports.
+	fn key(&self) -> String {
+		match self {
+			| Self::Event { room_id, event_id, .. } => format!("event {room_id} {event_id}"),
+			| Self::Room { room_id } => format!("room {room_id}"),
+			| Self::User { user_id } => format!("user {user_id}"),
+		}
+	}
+}
+
+impl fmt::Display for ReportStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			| Self::Open => write!(f, "open"),
+			| Self::Claimed => write!(f, "claimed"),
+			| Self::Resolved => write!(f, "resolved"),
+		}
+	}
+}
+
+impl FromStr for ReportStatus {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		match s {
+			| "open" => Ok(Self::Open),
+			| "claimed" => Ok(Self::Claimed),
+			| "resolved" => Ok(Self::Resolved),
+			| _ => Err!("Unknown report status {s:?}, expected open, claimed or resolved."),
+		}
+	}
+}
+
+impl ReportFilter {
+	#[must_use]
+	pub fn matches(&self, report: &Report) -> bool {
+		self.status.is_none_or(|status| status == report.status)
+			&& self
+				.room_id
+				.as_deref()
+				.is_none_or(|room_id| report.target.room_id() == Some(room_id))
+			&& self.user_id.as_deref().is_none_or(|user_id| {
+				report.reporter == user_id || report.target.user_id() == Some(user_id)
+			}) && self
+			.assignee
+			.as_ref()
+			.is_none_or(|assignee| report.assignee.as_ref() == Some(assignee))
+	}
+}
+
+/// Stores a report, returning its ID and whether it is new. A report from the
+/// same reporter about the same target which is still open is updated instead.
+#[implement(Service)]
+pub async fn add(
+	&self,
+	reporter: &UserId,
+	target: ReportTarget,
+	reason: Option<String>,
+	score: Option<i64>,
+) -> Result<(u64, bool)> {
+	let _writing = self.writing.lock().await;
+
+	let key = (reporter, target.key());
+	let existing = match self
+		.db
+		.reportertarget_reportid
+		.qry(&key)
+		.await
+		.deserialized()
+	{
+		| Ok(id) => self.get(id).await.ok().map(|report| (id, report)),
+		| Err(_) => None,
+	};
+
+	if let Some((id, mut report)) =
+		existing.filter(|(_, report)| report.status != ReportStatus::Resolved)
+	{
+		report.duplicates = report.duplicates.saturating_add(1);
+		report.reason = reason.or(report.reason);
+		report.score = score.or(report.score);
+		self.save(id, &report);
+		return Ok((id, false));
+	}
+
+	let id = self.services.globals.next_count()?;
+	let report = Report {
+		reporter: reporter.to_owned(),
+		target,
+		reason,
+		score,
+		status: ReportStatus::Open,
+		assignee: None,
+		resolution: None,
+		received_ts: utils::millis_since_unix_epoch(),
+		duplicates: 0,
+	};
+
+	self.save(id, &report);
+	self.db.reportertarget_reportid.put(key, id);
+
+	Ok((id, true))
+}
+
+#[implement(Service)]
+pub async fn get(&self, id: u64) -> Result<Report> {
+	self.db.reportid_report.qry(&id).await.deserialized()
+}
+
+/// Returns all reports, most recent first.
+#[implement(Service)]
+pub fn reports(&self) -> impl Stream<Item = (u64, Report)> + Send + '_ {
+	self.db.reportid_report.rev_stream().ignore_err()
+}
+
+/// Assigns an unresolved report to `admin`.
+#[implement(Service)]
+pub async fn claim(&self, id: u64, admin: &UserId) -> Result<Report> {
+	let _writing = self.writing.lock().await;
+	let Ok(mut report) = self.get(id).await else {
+		return Err!(Request(NotFound("Report {id} does not exist.")));
+	};
+
+	if report.status == ReportStatus::Resolved {
+		return Err!(Request(InvalidParam("Report {id} has already been resolved.")));
+	}
+
+	if let Some(assignee) = report
+		.assignee
+		.as_deref()
+		.filter(|assignee| *assignee != admin)
+	{
+		return Err!(Request(InvalidParam(
+			"Report {id} has already been claimed by {assignee}."
+		)));
+	}
+
+	report.status = ReportStatus::Claimed;
+	report.assignee = Some(admin.to_owned());
+	self.save(id, &report);
+
+	Ok(report)
+}
+
+/// Closes a report, recording which admin closed it and why.
+#[implement(Service)]
+pub async fn resolve(
+	&self,
+	id: u64,
+	admin: &UserId,
+	resolution: Option<String>,
+) -> Result<Report> {
+	let _writing = self.writing.lock().await;
+	let Ok(mut report) = self.get(id).await else {
+		return Err!(Request(NotFound("Report {id} does not exist.")));
+	};
+
+	if report.status == ReportStatus::Resolved {
+		return Err!(Request(InvalidParam("Report {id} has already been resolved.")));
+	}
+
+	report.status = ReportStatus::Resolved;
+	report.assignee = Some(admin.to_owned());
+	report.resolution = resolution;
+	self.save(id, &report);
+
+	// Reporting the target again opens a new report
+	self.db
+		.reportertarget_reportid
+		.del((&report.reporter, report.target.key()));
+
+	Ok(report)
+}
+
+#[implement(Service)]
+fn save(&self, id: u64, report: &Report) { self.db.reportid_report.put(id, Json(report)); }
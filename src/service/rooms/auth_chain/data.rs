@@ -1,4 +1,10 @@
-use std::{mem::size_of, sync::Arc};
+use std::{
+	mem::size_of,
+	sync::{
+		Arc,
+		atomic::{AtomicU64, Ordering},
+	},
+};
 
 use conduwuit::{Err, Result, SyncMutex, err, utils, utils::math::usize_from_f64};
 use database::Map;
@@ -9,6 +15,8 @@
 pub(super) struct Data {
 	shorteventid_authchain: Arc<Map>,
 	pub(super) auth_chain_cache: SyncMutex<LruCache<Vec<u64>, Arc<[ShortEventId]>>>,
+	pub(super) cache_hits: AtomicU64,
+	pub(super) cache_misses: AtomicU64,
 }
 
 impl Data {
@@ -21,6 +29,8 @@ pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		Self {
 			shorteventid_authchain: db["shorteventid_authchain"].clone(),
 			auth_chain_cache: SyncMutex::new(LruCache::new(cache_size)),
+			cache_hits: AtomicU64::new(0),
+			cache_misses: AtomicU64::new(0),
 		}
 	}
 
@@ -32,9 +42,12 @@ pub(super) async fn get_cached_eventid_authchain(
 
 		// Check RAM cache
 		if let Some(result) = self.auth_chain_cache.lock().get_mut(key) {
+			self.cache_hits.fetch_add(1, Ordering::Relaxed);
 			return Ok(Arc::clone(result));
 		}
 
+		self.cache_misses.fetch_add(1, Ordering::Relaxed);
+
 		// We only save auth chains for single events in the db
 		if key.len() != 1 {
 			return Err!(Request(NotFound("auth_chain not cached")));
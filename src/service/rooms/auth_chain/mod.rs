@@ -3,7 +3,7 @@
 use std::{
 	collections::{BTreeSet, HashSet, VecDeque},
 	fmt::Debug,
-	sync::Arc,
+	sync::{Arc, atomic::Ordering},
 	time::Instant,
 };
 
@@ -260,5 +260,15 @@ pub fn get_cache_usage(&self) -> (usize, usize) {
 	(cache.len(), cache.capacity())
 }
 
+/// Number of lookups answered from the in-memory cache and the number which
+/// were not, since startup.
+#[implement(Service)]
+pub fn get_cache_stats(&self) -> (u64, u64) {
+	(
+		self.db.cache_hits.load(Ordering::Relaxed),
+		self.db.cache_misses.load(Ordering::Relaxed),
+	)
+}
+
 #[implement(Service)]
 pub fn clear_cache(&self) { self.db.auth_chain_cache.lock().clear(); }
@@ -7,7 +7,9 @@
 pub mod metadata;
 pub mod outlier;
 pub mod pdu_metadata;
+pub mod purge;
 pub mod read_receipt;
+pub mod retention;
 pub mod search;
 pub mod short;
 pub mod spaces;
@@ -32,7 +34,9 @@ pub struct Service {
 	pub metadata: Arc<metadata::Service>,
 	pub outlier: Arc<outlier::Service>,
 	pub pdu_metadata: Arc<pdu_metadata::Service>,
+	pub purge: Arc<purge::Service>,
 	pub read_receipt: Arc<read_receipt::Service>,
+	pub retention: Arc<retention::Service>,
 	pub search: Arc<search::Service>,
 	pub short: Arc<short::Service>,
 	pub spaces: Arc<spaces::Service>,
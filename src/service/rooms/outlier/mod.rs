@@ -4,20 +4,30 @@
 use database::{Deserialized, Json, Map};
 use ruma::{CanonicalJsonObject, EventId};
 
+use crate::{Dep, media};
+
 pub struct Service {
 	db: Data,
+	services: Services,
 }
 
 struct Data {
 	eventid_outlierpdu: Arc<Map>,
 }
 
+struct Services {
+	media: Dep<media::Service>,
+}
+
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
 				eventid_outlierpdu: args.db["eventid_outlierpdu"].clone(),
 			},
+			services: Services {
+				media: args.depend::<media::Service>("media"),
+			},
 		}))
 	}
 
@@ -49,6 +59,9 @@ pub async fn get_pdu_outlier(&self, event_id: &EventId) -> Result<PduEvent> {
 #[tracing::instrument(skip(self, pdu), level = "debug")]
 pub fn add_pdu_outlier(&self, event_id: &EventId, pdu: &CanonicalJsonObject) {
 	self.db.eventid_outlierpdu.raw_put(event_id, Json(pdu));
+	if let Some(Ok(content)) = pdu.get("content").map(serde_json::to_string) {
+		self.services.media.add_references(event_id, &content);
+	}
 }
 
 /// Clear all outlier PDUs.
@@ -0,0 +1,223 @@
+//! Removes the older part of a room's timeline while keeping the room itself.
+//!
+//! State events are never removed since they are needed to authorise new
+//! events and to answer federation requests, and neither are the room's
+//! forward extremities since new events will reference them.
+
+use std::collections::HashSet;
+
+use conduwuit::{
+	Err, Result, debug, debug_warn, implement,
+	matrix::{PduCount, PduEvent},
+	utils::{ReadyExt, stream::TryIgnore},
+};
+use futures::StreamExt;
+use ruma::{EventId, Mxc, OwnedEventId, RoomId};
+use serde::Serialize;
+
+use super::BATCH_SIZE;
+use crate::{
+	media::referenced_mxcs,
+	rooms::timeline::{PduId, RawPduId},
+};
+
+/// Where a history purge stops. Only events before this point are removed.
+#[derive(Clone, Debug)]
+pub enum HistoryBound {
+	/// Events which precede this event in the room's timeline.
+	Event(OwnedEventId),
+
+	/// Events sent before this time, in milliseconds since the unix epoch.
+	Timestamp(u64),
+}
+
+/// Outcome of a history purge.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HistoryPurge {
+	/// Number of timeline events removed.
+	pub events: usize,
+
+	/// Number of local media files deleted.
+	pub media: usize,
+}
+
+/// Removes the room's timeline events before `bound`, along with their search
+/// index entries and relations. With `delete_media`, local media which was
+/// referenced by the removed events and by no other event is deleted too.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn purge_history(
+	&self,
+	room_id: &RoomId,
+	bound: &HistoryBound,
+	delete_media: bool,
+) -> Result<HistoryPurge> {
+	if self.job(room_id).await.is_ok() {
+		return Err!("{room_id} is already being purged entirely.");
+	}
+
+	let shortroomid = self.services.short.get_shortroomid(room_id).await?;
+	let until = match bound {
+		| HistoryBound::Event(event_id) => {
+			let Ok(pdu_id) = self.services.timeline.get_pdu_id(event_id).await else {
+				return Err!("{event_id} is not in the timeline of any room.");
+			};
+
+			if pdu_id.shortroomid() != shortroomid.to_be_bytes() {
+				return Err!("{event_id} is not in {room_id}.");
+			}
+
+			Some(pdu_id.pdu_count())
+		},
+		| HistoryBound::Timestamp(_) => None,
+	};
+
+	let before = |count: PduCount, pdu: &PduEvent| match bound {
+		| HistoryBound::Event(_) => until.is_some_and(|until| count < until),
+		| HistoryBound::Timestamp(ts) => u64::from(pdu.origin_server_ts) < *ts,
+	};
+
+	let mut purged = HistoryPurge::default();
+	let mut mxcs: HashSet<String> = HashSet::new();
+	let mut from: Option<PduCount> = None;
+	loop {
+		if !self.services.server.running() {
+			return Err!("The server is shutting down.");
+		}
+
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+		let leaves: HashSet<OwnedEventId> = self
+			.services
+			.state
+			.get_forward_extremities(room_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		let batch: Vec<(PduCount, PduEvent)> = self
+			.services
+			.timeline
+			.pdus(room_id, from)
+			.ignore_err()
+			.ready_take_while(|(count, pdu)| before(*count, pdu))
+			.take(BATCH_SIZE)
+			.collect()
+			.await;
+
+		let Some((last, _)) = batch.last() else {
+			break;
+		};
+
+		from = Some(*last);
+
+		let _cork = self.db.db.cork();
+		for (count, pdu) in &batch {
+			if pdu.state_key.is_some() || leaves.contains(&pdu.event_id) {
+				continue;
+			}
+
+			if delete_media {
+				mxcs.extend(
+					referenced_mxcs(pdu.content.get())
+						.filter(|mxc| self.is_local_mxc(mxc))
+						.map(ToOwned::to_owned),
+				);
+			}
+
+			let pdu_id: RawPduId = PduId { shortroomid, shorteventid: *count }.into();
+			self.purge_timeline_event(shortroomid, &pdu_id, pdu).await;
+
+			purged.events = purged.events.saturating_add(1);
+		}
+
+		drop(state_lock);
+		if batch.len() < BATCH_SIZE {
+			break;
+		}
+	}
+
+	if purged.events > 0 {
+		self.services
+			.timeline
+			.next_shortstatehash_cache
+			.lock()
+			.clear();
+		self.services
+			.timeline
+			.prev_shortstatehash_cache
+			.lock()
+			.clear();
+	}
+
+	if !mxcs.is_empty() {
+		purged.media = self.delete_unreferenced_media(mxcs).await;
+	}
+
+	debug!(%room_id, ?purged, "Purged room history");
+	Ok(purged)
+}
+
+/// Deletes the media which no remaining event refers to, returning how many
+/// files were deleted. Only the events recorded as referring to each file are
+/// checked, since they are what would keep it.
+#[implement(super::Service)]
+async fn delete_unreferenced_media(&self, mxcs: HashSet<String>) -> usize {
+	let mut deleted: usize = 0;
+	for mxc in &mxcs {
+		let Ok(mxc) = Mxc::try_from(mxc.as_str()) else {
+			continue;
+		};
+
+		let events: Vec<OwnedEventId> = self
+			.services
+			.media
+			.referencing_events(&mxc)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		let mut referenced = false;
+		for event_id in events {
+			if self.still_references(&event_id, &mxc).await {
+				referenced = true;
+				break;
+			}
+
+			// The event has been purged or redacted since it was recorded
+			self.services.media.remove_reference(&mxc, &event_id);
+		}
+
+		if referenced {
+			continue;
+		}
+
+		match self.services.media.delete(&mxc).await {
+			| Ok(()) => deleted = deleted.saturating_add(1),
+			| Err(e) => debug_warn!(%mxc, "Failed to delete media: {e}"),
+		}
+	}
+
+	deleted
+}
+
+/// Whether the stored event, in the timeline or as an outlier, still refers to
+/// `mxc`.
+#[implement(super::Service)]
+async fn still_references(&self, event_id: &EventId, mxc: &Mxc<'_>) -> bool {
+	let pdu = match self.db.eventid_pduid.get(event_id).await {
+		| Ok(pdu_id) => self.db.pduid_pdu.get(&*pdu_id).await,
+		| Err(_) => self.db.eventid_outlierpdu.get(event_id).await,
+	};
+
+	let Ok(pdu) = pdu else {
+		return false;
+	};
+
+	let mxc = mxc.to_string();
+	std::str::from_utf8(&pdu).is_ok_and(|pdu| referenced_mxcs(pdu).any(|found| found == mxc))
+}
+
+#[implement(super::Service)]
+fn is_local_mxc(&self, mxc: &str) -> bool {
+	Mxc::try_from(mxc).is_ok_and(|mxc| self.services.globals.server_is_ours(mxc.server_name))
+}
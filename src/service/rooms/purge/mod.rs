@@ -0,0 +1,736 @@
+//! Removes everything the server stores about a room.
+//!
+//! A purge runs as a background job whose progress is persisted after every
+//! stage and timeline batch. Each stage only deletes data and can safely be
+//! repeated, so a purge interrupted by a restart resumes where it left off.
+//! Purging only the older part of a room's timeline is handled in [`history`].
+
+mod history;
+
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt,
+	sync::Arc,
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Result, Server, error, implement, info,
+	matrix::{Event, PduEvent},
+	utils::{self, ReadyExt, stream::TryIgnore},
+};
+use database::{Database, Deserialized, Interfix, Json, Map};
+use futures::{Stream, StreamExt, pin_mut};
+use ruma::{EventId, OwnedEventId, OwnedRoomAliasId, OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+pub use self::history::{HistoryBound, HistoryPurge};
+use crate::{
+	Dep, admin, globals, media,
+	rooms::{
+		self,
+		short::{ShortEventId, ShortRoomId, ShortStateHash},
+		state_compressor::{StateDiff, parse_compressed_state_event},
+		timeline::RawPduId,
+	},
+};
+
+pub struct Service {
+	interrupt: Notify,
+	queued: Notify,
+	db: Data,
+	services: Services,
+}
+
+struct Services {
+	server: Arc<Server>,
+	admin: Dep<admin::Service>,
+	alias: Dep<rooms::alias::Service>,
+	auth_chain: Dep<rooms::auth_chain::Service>,
+	directory: Dep<rooms::directory::Service>,
+	globals: Dep<globals::Service>,
+	media: Dep<media::Service>,
+	search: Dep<rooms::search::Service>,
+	short: Dep<rooms::short::Service>,
+	state: Dep<rooms::state::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+	state_compressor: Dep<rooms::state_compressor::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+}
+
+struct Data {
+	roomid_purgejob: Arc<Map>,
+	eventid_outlierpdu: Arc<Map>,
+	eventid_pduid: Arc<Map>,
+	eventid_shorteventid: Arc<Map>,
+	pduid_pdu: Arc<Map>,
+	readreceiptid_readreceipt: Arc<Map>,
+	referencedevents: Arc<Map>,
+	roomid_pduleaves: Arc<Map>,
+	roomid_roomversion: Arc<Map>,
+	roomid_shortroomid: Arc<Map>,
+	roomid_shortstatehash: Arc<Map>,
+	roomid_timestamp_pducount: Arc<Map>,
+	roomuserid_lastprivatereadupdate: Arc<Map>,
+	roomuserid_privateread: Arc<Map>,
+	shorteventid_authchain: Arc<Map>,
+	shorteventid_eventid: Arc<Map>,
+	shorteventid_shortstatehash: Arc<Map>,
+	softfailedeventids: Arc<Map>,
+	statehash_shortstatehash: Arc<Map>,
+	threadid_userids: Arc<Map>,
+	tofrom_relation: Arc<Map>,
+	tokenids: Arc<Map>,
+	userroomid_highlightcount: Arc<Map>,
+	userroomid_notificationcount: Arc<Map>,
+	db: Arc<Database>,
+}
+
+/// Persisted progress of a room purge.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PurgeJob {
+	/// The next stage to run.
+	pub stage: Stage,
+
+	/// When the purge was queued, in milliseconds since the unix epoch.
+	pub started: u64,
+
+	/// Number of timeline events removed.
+	#[serde(default)]
+	pub events: u64,
+
+	/// Number of state and auth events removed which were not part of the
+	/// timeline.
+	#[serde(default)]
+	pub outliers: u64,
+
+	/// Number of state diff layers removed.
+	#[serde(default)]
+	pub state_layers: u64,
+
+	/// Why the last attempt failed. Failed purges are not retried until they
+	/// are queued again.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+	/// Joined, invited, knocked and left members, and the servers in the room.
+	Members,
+	/// Public and private read receipts.
+	Receipts,
+	/// State diff layers and the state and auth events only they refer to.
+	State,
+	/// Timeline events, their relations and the search index.
+	Timeline,
+	/// Aliases, the directory entry and the room's short ID and version.
+	Room,
+}
+
+#[derive(Deserialize)]
+struct ExtractEventId {
+	event_id: OwnedEventId,
+}
+
+/// Number of timeline events removed between progress saves.
+const BATCH_SIZE: usize = 1024;
+
+/// Number of timeline events between progress notices in the admin room.
+const NOTICE_INTERVAL: u64 = 50_000;
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		let db = &args.db;
+		Ok(Arc::new(Self {
+			interrupt: Notify::new(),
+			queued: Notify::new(),
+			db: Data {
+				roomid_purgejob: db["roomid_purgejob"].clone(),
+				eventid_outlierpdu: db["eventid_outlierpdu"].clone(),
+				eventid_pduid: db["eventid_pduid"].clone(),
+				eventid_shorteventid: db["eventid_shorteventid"].clone(),
+				pduid_pdu: db["pduid_pdu"].clone(),
+				readreceiptid_readreceipt: db["readreceiptid_readreceipt"].clone(),
+				referencedevents: db["referencedevents"].clone(),
+				roomid_pduleaves: db["roomid_pduleaves"].clone(),
+				roomid_roomversion: db["roomid_roomversion"].clone(),
+				roomid_shortroomid: db["roomid_shortroomid"].clone(),
+				roomid_shortstatehash: db["roomid_shortstatehash"].clone(),
+				roomid_timestamp_pducount: db["roomid_timestamp_pducount"].clone(),
+				roomuserid_lastprivatereadupdate: db["roomuserid_lastprivatereadupdate"].clone(),
+				roomuserid_privateread: db["roomuserid_privateread"].clone(),
+				shorteventid_authchain: db["shorteventid_authchain"].clone(),
+				shorteventid_eventid: db["shorteventid_eventid"].clone(),
+				shorteventid_shortstatehash: db["shorteventid_shortstatehash"].clone(),
+				softfailedeventids: db["softfailedeventids"].clone(),
+				statehash_shortstatehash: db["statehash_shortstatehash"].clone(),
+				threadid_userids: db["threadid_userids"].clone(),
+				tofrom_relation: db["tofrom_relation"].clone(),
+				tokenids: db["tokenids"].clone(),
+				userroomid_highlightcount: db["userroomid_highlightcount"].clone(),
+				userroomid_notificationcount: db["userroomid_notificationcount"].clone(),
+				db: args.db.clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				admin: args.depend::<admin::Service>("admin"),
+				alias: args.depend::<rooms::alias::Service>("rooms::alias"),
+				auth_chain: args.depend::<rooms::auth_chain::Service>("rooms::auth_chain"),
+				directory: args.depend::<rooms::directory::Service>("rooms::directory"),
+				globals: args.depend::<globals::Service>("globals"),
+				media: args.depend::<media::Service>("media"),
+				search: args.depend::<rooms::search::Service>("rooms::search"),
+				short: args.depend::<rooms::short::Service>("rooms::short"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				state_compressor: args
+					.depend::<rooms::state_compressor::Service>("rooms::state_compressor"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+			},
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "purge", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result {
+		loop {
+			for room_id in self.pending().await {
+				if !self.services.server.running() {
+					return Ok(());
+				}
+
+				if let Err(e) = self.run(&room_id).await {
+					error!(%room_id, "Failed to purge room: {e}");
+					self.fail(&room_id, &e.to_string()).await;
+				}
+			}
+
+			if !self.services.server.running() {
+				break;
+			}
+
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				() = self.queued.notified() => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Queues a room to be purged. A failed purge can be queued again and
+/// continues from the stage it failed in.
+#[implement(Service)]
+pub async fn queue(&self, room_id: &RoomId) -> Result<PurgeJob> {
+	let job = match self.job(room_id).await {
+		| Ok(job) if job.error.is_none() =>
+			return Err!("A purge of {room_id} is already in progress."),
+		| Ok(job) => PurgeJob { error: None, ..job },
+		| Err(_) => {
+			if self.services.short.get_shortroomid(room_id).await.is_err() {
+				return Err!("{room_id} is not known to this server.");
+			}
+
+			PurgeJob {
+				stage: Stage::Members,
+				started: utils::millis_since_unix_epoch(),
+				events: 0,
+				outliers: 0,
+				state_layers: 0,
+				error: None,
+			}
+		},
+	};
+
+	self.save(room_id, &job);
+	self.queued.notify_one();
+
+	Ok(job)
+}
+
+/// Returns the progress of a queued, running or failed purge.
+#[implement(Service)]
+pub async fn job(&self, room_id: &RoomId) -> Result<PurgeJob> {
+	self.db.roomid_purgejob.get(room_id).await.deserialized()
+}
+
+/// Returns all queued, running and failed purges.
+#[implement(Service)]
+pub fn jobs(&self) -> impl Stream<Item = (OwnedRoomId, PurgeJob)> + Send + '_ {
+	self.db
+		.roomid_purgejob
+		.stream()
+		.ignore_err()
+		.map(|(room_id, job): (&RoomId, PurgeJob)| (room_id.to_owned(), job))
+}
+
+#[implement(Service)]
+async fn pending(&self) -> Vec<OwnedRoomId> {
+	self.jobs()
+		.ready_filter_map(|(room_id, job)| job.error.is_none().then_some(room_id))
+		.collect()
+		.await
+}
+
+#[implement(Service)]
+fn save(&self, room_id: &RoomId, job: &PurgeJob) {
+	self.db.roomid_purgejob.raw_put(room_id, Json(job));
+}
+
+#[implement(Service)]
+async fn fail(&self, room_id: &RoomId, error: &str) {
+	if let Ok(mut job) = self.job(room_id).await {
+		job.error = Some(error.to_owned());
+		self.save(room_id, &job);
+	}
+
+	self.services
+		.admin
+		.notice(&format!(
+			"Failed to purge {room_id}: {error}\n\nRun the purge again to retry from where it \
+			 stopped."
+		))
+		.await;
+}
+
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "info")]
+async fn run(&self, room_id: &RoomId) -> Result {
+	let mut job = self.job(room_id).await?;
+	let shortroomid = self.services.short.get_shortroomid(room_id).await.ok();
+
+	info!(%room_id, stage = %job.stage, "Purging room");
+	self.services
+		.admin
+		.notice(&format!("Purging {room_id}, starting with {}.", job.stage))
+		.await;
+
+	loop {
+		if !self.services.server.running() {
+			return Ok(());
+		}
+
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+		let completed = job.stage;
+		job.stage = match (job.stage, shortroomid) {
+			| (Stage::Members, _) => {
+				self.purge_members(room_id).await;
+				Stage::Receipts
+			},
+			| (Stage::Receipts, _) => {
+				self.purge_receipts(room_id).await;
+				Stage::State
+			},
+			| (Stage::State, Some(shortroomid)) => {
+				if !self.purge_state(room_id, shortroomid, &mut job).await {
+					return Ok(());
+				}
+
+				Stage::Timeline
+			},
+			| (Stage::Timeline, Some(shortroomid)) => {
+				let removed = self.purge_timeline_batch(shortroomid).await;
+				if removed > 0 {
+					let before = job.events;
+					job.events = job
+						.events
+						.saturating_add(u64::try_from(removed).unwrap_or(u64::MAX));
+					self.save(room_id, &job);
+					drop(state_lock);
+
+					if before / NOTICE_INTERVAL != job.events / NOTICE_INTERVAL {
+						self.services
+							.admin
+							.notice(&format!(
+								"Purging {room_id}: removed {} timeline events so far.",
+								job.events
+							))
+							.await;
+					}
+
+					continue;
+				}
+
+				self.purge_timeline_indexes(room_id, shortroomid).await;
+				Stage::Room
+			},
+			| (Stage::State | Stage::Timeline, None) => Stage::Room,
+			| (Stage::Room, _) => {
+				self.purge_metadata(room_id).await?;
+				break;
+			},
+		};
+
+		self.save(room_id, &job);
+		drop(state_lock);
+
+		self.services
+			.admin
+			.notice(&format!("Purging {room_id}: finished {completed}."))
+			.await;
+	}
+
+	self.db.roomid_purgejob.remove(room_id);
+
+	let elapsed = utils::millis_since_unix_epoch().saturating_sub(job.started);
+	let elapsed = utils::time::pretty(Duration::from_millis(elapsed));
+	info!(%room_id, ?job, "Finished purging room");
+	self.services
+		.admin
+		.notice(&format!(
+			"Finished purging {room_id} after {elapsed}: removed {} timeline events, {} other \
+			 state and auth events, and {} state layers.",
+			job.events, job.outliers, job.state_layers
+		))
+		.await;
+
+	Ok(())
+}
+
+#[implement(Service)]
+async fn purge_members(&self, room_id: &RoomId) {
+	let users = self.services.state_cache.purge_room(room_id).await;
+	for user_id in &users {
+		let userroom_id = (user_id, room_id);
+		self.db.userroomid_notificationcount.del(userroom_id);
+		self.db.userroomid_highlightcount.del(userroom_id);
+	}
+}
+
+#[implement(Service)]
+async fn purge_receipts(&self, room_id: &RoomId) {
+	let prefix = (room_id, Interfix);
+	for map in [
+		&self.db.readreceiptid_readreceipt,
+		&self.db.roomuserid_privateread,
+		&self.db.roomuserid_lastprivatereadupdate,
+	] {
+		map.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| map.remove(key))
+			.await;
+	}
+}
+
+/// Removes every state layer the room was ever in, along with the state and
+/// auth events which are not part of the timeline. Timeline events are left
+/// for the timeline stage, since their state is how the layers are found.
+/// Returns false if the server started shutting down before this completed.
+#[implement(Service)]
+async fn purge_state(
+	&self,
+	room_id: &RoomId,
+	shortroomid: ShortRoomId,
+	job: &mut PurgeJob,
+) -> bool {
+	let mut pending: Vec<ShortStateHash> = self
+		.services
+		.state
+		.get_room_shortstatehash(room_id)
+		.await
+		.into_iter()
+		.collect();
+
+	let events = self.timeline_events(shortroomid);
+	pin_mut!(events);
+	while let Some((_, event_id)) = events.next().await {
+		if let Ok(shorteventid) = self.services.short.get_shorteventid(&event_id).await {
+			if let Ok(shortstatehash) = self.services.state.get_shortstatehash(shorteventid).await
+			{
+				pending.push(shortstatehash);
+			}
+		}
+	}
+
+	let mut layers: BTreeMap<ShortStateHash, StateDiff> = BTreeMap::new();
+	while let Some(shortstatehash) = pending.pop() {
+		if layers.contains_key(&shortstatehash) {
+			continue;
+		}
+
+		// Layers which were already removed by an interrupted attempt end the walk
+		let Ok(diff) = self
+			.services
+			.state_compressor
+			.get_statediff(shortstatehash)
+			.await
+		else {
+			continue;
+		};
+
+		// The empty state is shared by every room
+		if diff.parent.is_none() && diff.added.is_empty() {
+			continue;
+		}
+
+		pending.extend(diff.parent);
+		layers.insert(shortstatehash, diff);
+	}
+
+	let mut state_events: HashSet<ShortEventId> = layers
+		.values()
+		.flat_map(|diff| diff.added.iter().chain(diff.removed.iter()))
+		.map(|compressed| parse_compressed_state_event(*compressed).1)
+		.collect();
+
+	// Auth events we only know as outliers are not referenced by any state
+	let auth_events: Vec<ShortEventId> = state_events.iter().copied().collect();
+	for shorteventid in auth_events {
+		if let Ok(chain) = self
+			.services
+			.auth_chain
+			.get_cached_eventid_authchain(&[shorteventid])
+			.await
+		{
+			state_events.extend(chain.iter().copied());
+		}
+	}
+
+	for (i, shorteventid) in state_events.into_iter().enumerate() {
+		if i % BATCH_SIZE == 0 && !self.services.server.running() {
+			return false;
+		}
+
+		let Ok(event_id) = self
+			.services
+			.short
+			.get_eventid_from_short::<OwnedEventId>(shorteventid)
+			.await
+		else {
+			continue;
+		};
+
+		if self.services.timeline.get_pdu_id(&event_id).await.is_ok() {
+			continue;
+		}
+
+		self.purge_event(&event_id, Some(shorteventid));
+		job.outliers = job.outliers.saturating_add(1);
+	}
+
+	for shortstatehash in layers.keys() {
+		self.services
+			.state_compressor
+			.delete_statediff(*shortstatehash);
+	}
+
+	self.db
+		.statehash_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(state_hash, shortstatehash)| {
+			let shortstatehash = utils::u64_from_bytes(shortstatehash).ok()?;
+			layers.contains_key(&shortstatehash).then_some(state_hash)
+		})
+		.ready_for_each(|state_hash| self.db.statehash_shortstatehash.remove(state_hash))
+		.await;
+
+	self.db.roomid_shortstatehash.remove(room_id);
+	self.services
+		.state_compressor
+		.stateinfo_cache
+		.lock()
+		.clear();
+	self.services.auth_chain.clear_cache();
+
+	job.state_layers = job
+		.state_layers
+		.saturating_add(u64::try_from(layers.len()).unwrap_or(u64::MAX));
+
+	true
+}
+
+/// Removes up to `BATCH_SIZE` timeline events from the start of the room's
+/// timeline, returning how many were removed.
+#[implement(Service)]
+async fn purge_timeline_batch(&self, shortroomid: ShortRoomId) -> usize {
+	let batch: Vec<(RawPduId, OwnedEventId)> = self
+		.timeline_events(shortroomid)
+		.take(BATCH_SIZE)
+		.collect()
+		.await;
+
+	let _cork = self.db.db.cork();
+	for (pdu_id, event_id) in &batch {
+		let shorteventid = self.services.short.get_shorteventid(event_id).await.ok();
+		self.purge_event(event_id, shorteventid);
+
+		let count = pdu_id.pdu_count().into_unsigned();
+		self.db
+			.tofrom_relation
+			.keys_prefix_raw(&count)
+			.ignore_err()
+			.ready_for_each(|key| self.db.tofrom_relation.remove(key))
+			.await;
+
+		self.db.eventid_pduid.remove(event_id.as_bytes());
+		self.db.pduid_pdu.remove(pdu_id);
+	}
+
+	batch.len()
+}
+
+/// Removes a single event from its room's timeline, along with its search
+/// index entries, its relations and everything else keyed by the event.
+#[implement(Service)]
+pub async fn purge_timeline_event(
+	&self,
+	shortroomid: ShortRoomId,
+	pdu_id: &RawPduId,
+	pdu: &PduEvent,
+) {
+	#[derive(Deserialize)]
+	struct ExtractBody {
+		body: Option<String>,
+	}
+
+	#[derive(Deserialize)]
+	struct ExtractRelatesTo {
+		#[serde(rename = "m.relates_to")]
+		relates_to: ExtractEventId,
+	}
+
+	if let Ok(ExtractBody { body: Some(body) }) = pdu.get_content() {
+		self.services.search.deindex_pdu(shortroomid, pdu_id, &body);
+	}
+
+	let count = pdu_id.pdu_count().into_unsigned();
+	if let Ok(ExtractRelatesTo { relates_to }) = pdu.get_content() {
+		if let Ok(target) = self
+			.services
+			.timeline
+			.get_pdu_count(&relates_to.event_id)
+			.await
+		{
+			let key = [target.into_unsigned().to_be_bytes(), count.to_be_bytes()].concat();
+			self.db.tofrom_relation.remove(&key);
+		}
+	}
+
+	self.db
+		.tofrom_relation
+		.keys_prefix_raw(&count)
+		.ignore_err()
+		.ready_for_each(|key| self.db.tofrom_relation.remove(key))
+		.await;
+
+	self.db.threadid_userids.remove(pdu_id);
+
+	let shorteventid = self
+		.services
+		.short
+		.get_shorteventid(&pdu.event_id)
+		.await
+		.ok();
+
+	self.purge_event(&pdu.event_id, shorteventid);
+	self.services.timeline.remove_pdu(pdu_id, pdu);
+}
+
+/// Removes what is indexed by the room rather than by its events, once the
+/// timeline itself is gone.
+#[implement(Service)]
+async fn purge_timeline_indexes(&self, room_id: &RoomId, shortroomid: ShortRoomId) {
+	for map in [&self.db.tokenids, &self.db.roomid_timestamp_pducount, &self.db.threadid_userids]
+	{
+		map.keys_prefix_raw(&shortroomid)
+			.ignore_err()
+			.ready_for_each(|key| map.remove(key))
+			.await;
+	}
+
+	let prefix = (room_id, Interfix);
+	for map in [&self.db.referencedevents, &self.db.roomid_pduleaves] {
+		map.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| map.remove(key))
+			.await;
+	}
+
+	self.services
+		.timeline
+		.next_shortstatehash_cache
+		.lock()
+		.clear();
+	self.services
+		.timeline
+		.prev_shortstatehash_cache
+		.lock()
+		.clear();
+}
+
+#[implement(Service)]
+async fn purge_metadata(&self, room_id: &RoomId) -> Result {
+	let aliases: Vec<OwnedRoomAliasId> = self
+		.services
+		.alias
+		.local_aliases_for_room(room_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for alias in &aliases {
+		self.services
+			.alias
+			.remove_alias(alias, &self.services.globals.server_user)
+			.await?;
+	}
+
+	self.services.directory.set_not_public(room_id);
+	self.db.roomid_roomversion.remove(room_id);
+	self.db.roomid_shortroomid.remove(room_id);
+
+	Ok(())
+}
+
+/// Removes everything keyed by an event other than its timeline entry.
+#[implement(Service)]
+fn purge_event(&self, event_id: &EventId, shorteventid: Option<ShortEventId>) {
+	if let Some(shorteventid) = shorteventid {
+		let key = shorteventid.to_be_bytes();
+		self.db.shorteventid_authchain.remove(&key);
+		self.db.shorteventid_shortstatehash.remove(&key);
+		self.db.shorteventid_eventid.remove(&key);
+	}
+
+	self.db.eventid_shorteventid.remove(event_id);
+	self.db.eventid_outlierpdu.remove(event_id);
+	self.db.softfailedeventids.remove(event_id);
+}
+
+/// Streams the ID of every event in the room's timeline, oldest first.
+#[implement(Service)]
+fn timeline_events(
+	&self,
+	shortroomid: ShortRoomId,
+) -> impl Stream<Item = (RawPduId, OwnedEventId)> + Send + '_ {
+	self.db
+		.pduid_pdu
+		.stream_prefix_raw(&shortroomid)
+		.ignore_err()
+		.ready_filter_map(|(pdu_id, pdu)| {
+			let ExtractEventId { event_id } = serde_json::from_slice(pdu).ok()?;
+			Some((RawPduId::from(pdu_id), event_id))
+		})
+}
+
+impl fmt::Display for Stage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			| Self::Members => "members",
+			| Self::Receipts => "receipts",
+			| Self::State => "state",
+			| Self::Timeline => "timeline",
+			| Self::Room => "room metadata",
+		})
+	}
+}
@@ -0,0 +1,207 @@
+//! Enforces message retention policies (MSC1763).
+//!
+//! Rooms choose how long their messages are kept with an `m.room.retention`
+//! state event, within bounds set by the server. A worker periodically removes
+//! timeline events which have outlived their room's policy, in the same way
+//! as purging a room's history before a point in time.
+
+#[cfg(test)]
+mod tests;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use conduwuit::{Result, Server, config::RetentionConfig, debug, implement, info, utils, warn};
+use futures::StreamExt;
+use ruma::{OwnedRoomId, RoomId, events::StateEventType};
+use serde::Deserialize;
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
+
+use crate::{Dep, rooms, rooms::purge::HistoryBound};
+
+pub struct Service {
+	interrupt: Notify,
+	services: Services,
+}
+
+struct Services {
+	server: Arc<Server>,
+	metadata: Dep<rooms::metadata::Service>,
+	purge: Dep<rooms::purge::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+}
+
+/// Content of an `m.room.retention` state event.
+#[derive(Debug, Default, Deserialize)]
+struct RetentionEventContent {
+	/// How long events must be kept for at least, in milliseconds.
+	min_lifetime: Option<u64>,
+
+	/// How long events should be kept for, in milliseconds.
+	max_lifetime: Option<u64>,
+}
+
+const RETENTION_EVENT_TYPE: &str = "m.room.retention";
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			interrupt: Notify::new(),
+			services: Services {
+				server: args.server.clone(),
+				metadata: args.depend::<rooms::metadata::Service>("rooms::metadata"),
+				purge: args.depend::<rooms::purge::Service>("rooms::purge"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+			},
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "retention", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result {
+		let config = &self.services.server.config.retention;
+		if !config.enabled {
+			debug!("Message retention is disabled");
+			return Ok(());
+		}
+
+		let mut i = interval(Duration::from_secs(config.purge_interval_s.max(1)));
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.purge_expired().await;
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Returns how long events in the room are kept for after applying the
+/// server's bounds, or None if they are kept forever.
+#[implement(Service)]
+pub async fn max_lifetime(&self, room_id: &RoomId) -> Option<Duration> {
+	let config = &self.services.server.config.retention;
+	if !config.enabled {
+		return None;
+	}
+
+	let content = self
+		.services
+		.state_accessor
+		.room_state_get_content::<RetentionEventContent>(
+			room_id,
+			&StateEventType::from(RETENTION_EVENT_TYPE),
+			"",
+		)
+		.await
+		.ok();
+
+	effective_lifetime(config, content.as_ref())
+}
+
+/// How long events are kept for under the room's policy, or the server's
+/// default where the policy sets no `max_lifetime`. The server's upper bound
+/// caps the lifetime and stands in for it where there is none, but the result
+/// is never shorter than the server's lower bound nor the policy's own
+/// `min_lifetime`.
+fn effective_lifetime(
+	config: &RetentionConfig,
+	content: Option<&RetentionEventContent>,
+) -> Option<Duration> {
+	let max = config.allowed_lifetime_max_s.map(Duration::from_secs);
+	let lifetime = content
+		.and_then(|content| content.max_lifetime)
+		.map(Duration::from_millis)
+		.or_else(|| config.default_max_lifetime_s.map(Duration::from_secs))
+		.map(|lifetime| lifetime.min(max.unwrap_or(Duration::MAX)))
+		.or_else(|| content.and(max))?;
+
+	let min = config
+		.allowed_lifetime_min_s
+		.map(Duration::from_secs)
+		.unwrap_or_default();
+	let min_lifetime = content
+		.and_then(|content| content.min_lifetime)
+		.map(Duration::from_millis)
+		.unwrap_or_default();
+
+	Some(lifetime.max(min).max(min_lifetime))
+}
+
+/// Whether an event sent at `origin_server_ts` has outlived the room's
+/// retention policy.
+#[implement(Service)]
+pub async fn is_expired(&self, room_id: &RoomId, origin_server_ts: u64) -> bool {
+	self.max_lifetime(room_id)
+		.await
+		.is_some_and(|lifetime| origin_server_ts < cutoff(lifetime))
+}
+
+#[implement(Service)]
+async fn purge_expired(&self) {
+	let rooms: Vec<OwnedRoomId> = self
+		.services
+		.metadata
+		.iter_ids()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let mut removed: usize = 0;
+	for room_id in &rooms {
+		if !self.services.server.running() {
+			return;
+		}
+
+		match self.purge_room(room_id).await {
+			| Ok(count) => removed = removed.saturating_add(count),
+			| Err(e) => warn!(%room_id, "Failed to apply retention policy: {e}"),
+		}
+	}
+
+	if removed > 0 {
+		info!("Removed {removed} events which outlived their room's retention policy");
+	}
+}
+
+/// Removes the room's expired timeline events, returning how many were
+/// removed.
+#[implement(Service)]
+async fn purge_room(&self, room_id: &RoomId) -> Result<usize> {
+	let Some(lifetime) = self.max_lifetime(room_id).await else {
+		return Ok(0);
+	};
+
+	// Rooms being purged entirely are left to the purge
+	if self.services.purge.job(room_id).await.is_ok() {
+		return Ok(0);
+	}
+
+	let bound = HistoryBound::Timestamp(cutoff(lifetime));
+	let purged = self
+		.services
+		.purge
+		.purge_history(room_id, &bound, false)
+		.await?;
+
+	Ok(purged.events)
+}
+
+/// Events sent before the returned time, in milliseconds since the unix
+/// epoch, have outlived `lifetime`.
+fn cutoff(lifetime: Duration) -> u64 {
+	let lifetime = u64::try_from(lifetime.as_millis()).unwrap_or(u64::MAX);
+	utils::millis_since_unix_epoch().saturating_sub(lifetime)
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use conduwuit::config::RetentionConfig;
+
+use super::{RetentionEventContent, effective_lifetime};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Server bounds, in days.
+fn config(default: Option<u64>, min: Option<u64>, max: Option<u64>) -> RetentionConfig {
+	let secs = |days: u64| days.saturating_mul(DAY);
+	RetentionConfig {
+		enabled: true,
+		default_max_lifetime_s: default.map(secs),
+		allowed_lifetime_min_s: min.map(secs),
+		allowed_lifetime_max_s: max.map(secs),
+		..RetentionConfig::default()
+	}
+}
+
+/// A room's policy, in days.
+fn policy(min_lifetime: Option<u64>, max_lifetime: Option<u64>) -> RetentionEventContent {
+	let millis = |days: u64| days.saturating_mul(DAY).saturating_mul(1000);
+	RetentionEventContent {
+		min_lifetime: min_lifetime.map(millis),
+		max_lifetime: max_lifetime.map(millis),
+	}
+}
+
+fn days(days: u64) -> Option<Duration> { Some(Duration::from_secs(days.saturating_mul(DAY))) }
+
+#[test]
+fn rooms_without_policy_use_default() {
+	assert_eq!(effective_lifetime(&config(Some(90), None, None), None), days(90));
+	assert_eq!(effective_lifetime(&config(None, None, Some(365)), None), None);
+}
+
+#[test]
+fn default_is_clamped_to_server_bounds() {
+	assert_eq!(effective_lifetime(&config(Some(1), Some(7), None), None), days(7));
+	assert_eq!(effective_lifetime(&config(Some(90), None, Some(30)), None), days(30));
+}
+
+#[test]
+fn policy_is_clamped_to_server_bounds() {
+	let config = config(Some(21), Some(7), Some(30));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, Some(1)))), days(7));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, Some(14)))), days(14));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, Some(90)))), days(30));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, None))), days(21));
+}
+
+#[test]
+fn policy_without_max_lifetime_uses_default() {
+	let config = config(Some(90), None, None);
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, None))), days(90));
+	assert_eq!(effective_lifetime(&config, Some(&policy(Some(10), None))), days(90));
+	assert_eq!(effective_lifetime(&config, Some(&policy(Some(120), None))), days(120));
+}
+
+#[test]
+fn policy_without_any_limit_gets_server_max() {
+	let config = config(None, None, Some(30));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, None))), days(30));
+	assert_eq!(effective_lifetime(&config(None, None, None), Some(&policy(None, None))), None);
+}
+
+#[test]
+fn lifetime_is_at_least_policy_min_lifetime() {
+	let config = config(None, None, Some(30));
+	assert_eq!(effective_lifetime(&config, Some(&policy(Some(10), Some(5)))), days(10));
+	assert_eq!(effective_lifetime(&config, Some(&policy(Some(60), Some(90)))), days(60));
+}
+
+#[test]
+fn min_wins_over_max_when_bounds_conflict() {
+	let config = config(None, Some(30), Some(7));
+	assert_eq!(effective_lifetime(&config, Some(&policy(None, Some(14)))), days(30));
+}
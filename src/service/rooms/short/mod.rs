@@ -1,5 +1,6 @@
 use std::{
 	borrow::Borrow,
+	collections::HashSet,
 	fmt::Debug,
 	mem::{size_of, size_of_val},
 	sync::Arc,
@@ -7,7 +8,7 @@
 
 pub use conduwuit::matrix::pdu::{ShortEventId, ShortId, ShortRoomId, ShortStateKey};
 use conduwuit::{
-	Result, err, implement,
+	Err, Result, SyncMutex, err, implement,
 	matrix::StateKey,
 	pair_of,
 	utils::{self, IterStream, ReadyExt},
@@ -19,14 +20,30 @@
 };
 use ruma::{EventId, OwnedEventId, RoomId, RoomVersionId, events::StateEventType};
 use serde::Deserialize;
+use tokio::sync::RwLock;
 
 use crate::{Dep, globals};
 
 pub struct Service {
 	db: Data,
 	services: Services,
+	/// Held shared while short IDs are looked up for reuse, and exclusively
+	/// while state garbage collection deletes them.
+	pub(crate) gc_lock: RwLock<()>,
+	reused: SyncMutex<Option<Reused>>,
 }
 
+/// Short IDs which were reused while a state garbage collection ran, and
+/// which it must therefore keep.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Reused {
+	pub(crate) statehashes: HashSet<ShortStateHash>,
+	pub(crate) statekeys: HashSet<ShortStateKey>,
+}
+
+/// Records reused short IDs for as long as it is held.
+pub(crate) struct ReuseTracking<'a>(&'a Service);
+
 struct Data {
 	eventid_shorteventid: Arc<Map>,
 	shorteventid_eventid: Arc<Map>,
@@ -58,6 +75,8 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			services: Services {
 				globals: args.depend::<globals::Service>("globals"),
 			},
+			gc_lock: RwLock::new(()),
+			reused: SyncMutex::new(None),
 		}))
 	}
 
@@ -127,7 +146,12 @@ pub async fn get_or_create_shortstatekey(
 ) -> ShortStateKey {
 	const BUFSIZE: usize = size_of::<ShortStateKey>();
 
+	let _lock = self.gc_lock.read().await;
 	if let Ok(shortstatekey) = self.get_shortstatekey(event_type, state_key).await {
+		if let Some(reused) = self.reused.lock().as_mut() {
+			reused.statekeys.insert(shortstatekey);
+		}
+
 		return shortstatekey;
 	}
 
@@ -228,6 +252,7 @@ pub fn multi_get_statekey_from_short<'a, S>(
 pub async fn get_or_create_shortstatehash(&self, state_hash: &[u8]) -> (ShortStateHash, bool) {
 	const BUFSIZE: usize = size_of::<ShortStateHash>();
 
+	let _lock = self.gc_lock.read().await;
 	if let Ok(shortstatehash) = self
 		.db
 		.statehash_shortstatehash
@@ -235,6 +260,10 @@ pub async fn get_or_create_shortstatehash(&self, state_hash: &[u8]) -> (ShortSta
 		.await
 		.deserialized()
 	{
+		if let Some(reused) = self.reused.lock().as_mut() {
+			reused.statehashes.insert(shortstatehash);
+		}
+
 		return (shortstatehash, true);
 	}
 
@@ -303,3 +332,27 @@ pub async fn get_room_version(&self, room_id: &RoomId) -> Result<RoomVersionId>
 pub fn set_room_version(&self, room_id: &RoomId, version: &RoomVersionId) {
 	self.db.roomid_roomversion.insert(room_id, version);
 }
+
+/// Starts recording the short IDs which are reused, until the returned guard
+/// is dropped. Only one state garbage collection may do so at a time.
+#[implement(Service)]
+pub(crate) async fn track_reuse(&self) -> Result<ReuseTracking<'_>> {
+	let _lock = self.gc_lock.write().await;
+	let mut reused = self.reused.lock();
+	if reused.is_some() {
+		return Err!("State garbage collection is already running.");
+	}
+
+	*reused = Some(Reused::default());
+	Ok(ReuseTracking(self))
+}
+
+impl ReuseTracking<'_> {
+	/// The short IDs reused so far. Only stable while `gc_lock` is held
+	/// exclusively.
+	pub(crate) fn reused(&self) -> Reused { self.0.reused.lock().clone().unwrap_or_default() }
+}
+
+impl Drop for ReuseTracking<'_> {
+	fn drop(&mut self) { *self.0.reused.lock() = None; }
+}
@@ -1,10 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-use conduwuit::{Err, Event, Pdu, Result, implement, is_not_empty, utils::ReadyExt, warn};
-use database::{Json, serialize_key};
+use conduwuit::{
+	Err, Event, Pdu, Result, implement, is_not_empty,
+	utils::{ReadyExt, stream::TryIgnore},
+	warn,
+};
+use database::{Ignore, Interfix, Json, serialize_key};
 use futures::StreamExt;
 use ruma::{
-	OwnedServerName, RoomId, UserId,
+	OwnedServerName, OwnedUserId, RoomId, UserId,
 	events::{
 		AnyStrippedStateEvent, GlobalAccountDataEventType, RoomAccountDataEventType,
 		StateEventType,
@@ -132,6 +136,11 @@ pub async fn update_membership(
 		| _ => {},
 	}
 
+	self.services
+		.user_directory
+		.update_membership(room_id, user_id, &membership)
+		.await;
+
 	if update_joined_count {
 		self.update_joined_count(room_id).await;
 	}
@@ -310,6 +319,62 @@ pub fn forget(&self, room_id: &RoomId, user_id: &UserId) {
 	self.db.roomuserid_leftcount.del(roomuser_id);
 }
 
+/// Removes all membership data of a room, returning every user who had any
+/// membership in it. Used when a room is purged from the database.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn purge_room(&self, room_id: &RoomId) -> Vec<OwnedUserId> {
+	let mut users = BTreeSet::new();
+	let prefix = (room_id, Interfix);
+	for map in [
+		&self.db.roomuserid_joined,
+		&self.db.roomuserid_invitecount,
+		&self.db.roomuserid_leftcount,
+		&self.db.roomuserid_knockedcount,
+	] {
+		map.keys_prefix(&prefix)
+			.ignore_err()
+			.ready_for_each(|(_, user_id): (Ignore, &UserId)| {
+				users.insert(user_id.to_owned());
+			})
+			.await;
+	}
+
+	for user_id in &users {
+		let userroom_id = (user_id, room_id);
+		let roomuser_id = (room_id, user_id);
+
+		self.db.userroomid_joined.del(userroom_id);
+		self.db.roomuserid_joined.del(roomuser_id);
+
+		self.db.userroomid_invitestate.del(userroom_id);
+		self.db.roomuserid_invitecount.del(roomuser_id);
+		self.db.userroomid_invitesender.del(userroom_id);
+
+		self.db.userroomid_leftstate.del(userroom_id);
+		self.db.roomuserid_leftcount.del(roomuser_id);
+
+		self.db.userroomid_knockedstate.del(userroom_id);
+		self.db.roomuserid_knockedcount.del(roomuser_id);
+
+		self.db.roomuseroncejoinedids.del(userroom_id);
+	}
+
+	self.room_servers(room_id)
+		.ready_for_each(|server| {
+			self.db.roomserverids.del((room_id, server));
+			self.db.serverroomids.del((server, room_id));
+		})
+		.await;
+
+	self.db.roomid_joinedcount.remove(room_id);
+	self.db.roomid_invitedcount.remove(room_id);
+	self.db.roomid_inviteviaservers.remove(room_id);
+	self.appservice_in_room_cache.write().remove(room_id);
+
+	users.into_iter().collect()
+}
+
 #[implement(super::Service)]
 #[tracing::instrument(level = "debug", skip(self))]
 fn mark_as_once_joined(&self, user_id: &UserId, room_id: &RoomId) {
@@ -0,0 +1,447 @@
+//! Garbage collection of state diff layers which nothing refers to any more.
+//!
+//! Layers are referenced by the state of each event and the current state of
+//! each room. Layers which neither these nor their descendants refer to are
+//! deleted. Unreferenced layers in the middle of a chain are folded into
+//! their only child, unless new state may still be built on top of them.
+//! Short state keys which no remaining layer uses are deleted as well.
+//!
+//! Layers and short state keys allocated after the collection started are
+//! never touched. Those which new state reuses while it runs are recorded, and
+//! deleting takes `gc_lock`, which lookups for reuse share: if state created
+//! meanwhile reuses anything to be deleted, builds on it or became a room's
+//! current state, the collection fails without deleting anything and can be
+//! run again.
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+	collections::{HashMap, HashSet},
+	mem::size_of,
+	sync::Arc,
+};
+
+use conduwuit::{
+	Err, Result, debug, implement, info,
+	utils::{self, ReadyExt, stream::TryIgnore},
+};
+
+use super::{CompressedStateEvent, StateDiff, parse_compressed_state_event, parse_statediff};
+use crate::rooms::short::{ReuseTracking, ShortStateHash, ShortStateKey};
+
+/// What a state garbage collection removed, or would remove in a dry run.
+#[derive(Clone, Debug, Default)]
+pub struct GarbageReport {
+	/// Number of diff layers which nothing refers to.
+	pub layers: usize,
+
+	/// Number of unreferenced diff layers folded into their only child.
+	pub folded: usize,
+
+	/// Number of short state keys which no remaining layer uses.
+	pub statekeys: usize,
+
+	/// Approximate number of bytes freed, before compression.
+	pub bytes: usize,
+}
+
+struct Layer {
+	parent: Option<ShortStateHash>,
+	size: usize,
+}
+
+/// Finds state diff layers and short state keys which are no longer needed
+/// and deletes them, unless `dry_run` is set.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "info")]
+pub async fn collect_garbage(&self, dry_run: bool) -> Result<GarbageReport> {
+	let tracking = self.services.short.track_reuse().await?;
+	let horizon = self.services.globals.current_count()?;
+
+	let layers: HashMap<ShortStateHash, Layer> = self
+		.db
+		.shortstatehash_statediff
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(key, value)| parse_layer(key, value))
+		.collect()
+		.await;
+
+	// Layers created after we started may not have been referenced yet
+	let mut referenced: HashSet<ShortStateHash> = layers
+		.keys()
+		.copied()
+		.filter(|shortstatehash| *shortstatehash >= horizon)
+		.collect();
+
+	self.db
+		.shorteventid_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(_, shortstatehash)| utils::u64_from_bytes(shortstatehash).ok())
+		.ready_for_each(|shortstatehash| {
+			referenced.insert(shortstatehash);
+		})
+		.await;
+
+	if !self.services.server.running() {
+		return Err!("The server is shutting down.");
+	}
+
+	// New state is always built on top of a room's current state, so neither
+	// it nor its ancestors may be folded away
+	let mut pinned: HashSet<ShortStateHash> = HashSet::new();
+	self.db
+		.roomid_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(_, shortstatehash)| utils::u64_from_bytes(shortstatehash).ok())
+		.ready_for_each(|shortstatehash| {
+			referenced.insert(shortstatehash);
+			mark_ancestors(&layers, &mut pinned, shortstatehash);
+		})
+		.await;
+
+	let mut reachable: HashSet<ShortStateHash> = HashSet::new();
+	for shortstatehash in &referenced {
+		mark_ancestors(&layers, &mut reachable, *shortstatehash);
+	}
+
+	let mut children: HashMap<ShortStateHash, Vec<ShortStateHash>> = HashMap::new();
+	for shortstatehash in &reachable {
+		if let Some(parent) = layers.get(shortstatehash).and_then(|layer| layer.parent) {
+			children.entry(parent).or_default().push(*shortstatehash);
+		}
+	}
+
+	let unreachable: Vec<ShortStateHash> = layers
+		.keys()
+		.copied()
+		.filter(|shortstatehash| !reachable.contains(shortstatehash))
+		.collect();
+
+	let foldable: Vec<ShortStateHash> = reachable
+		.iter()
+		.copied()
+		.filter(|shortstatehash| {
+			!referenced.contains(shortstatehash)
+				&& !pinned.contains(shortstatehash)
+				&& children
+					.get(shortstatehash)
+					.is_some_and(|children| children.len() == 1)
+		})
+		.collect();
+
+	let mut report = GarbageReport::default();
+	let mut removed: HashSet<ShortStateHash> = HashSet::new();
+	for shortstatehash in &unreachable {
+		report.layers = report.layers.saturating_add(1);
+		report.bytes = report.bytes.saturating_add(layers[shortstatehash].size);
+		removed.insert(*shortstatehash);
+	}
+
+	// Fold each layer into its child, keeping the rewritten children in memory
+	// so that chains of foldable layers collapse into a single layer
+	let mut only_child: HashMap<ShortStateHash, ShortStateHash> = children
+		.into_iter()
+		.filter_map(|(parent, children)| (children.len() == 1).then(|| (parent, children[0])))
+		.collect();
+
+	let mut rewritten: HashMap<ShortStateHash, StateDiff> = HashMap::new();
+	let mut growth: usize = 0;
+	for shortstatehash in &foldable {
+		let Some(child) = only_child.get(shortstatehash).copied() else {
+			continue;
+		};
+
+		let Ok(diff) = self.load_statediff(&rewritten, *shortstatehash).await else {
+			continue;
+		};
+
+		let Ok(child_diff) = self.load_statediff(&rewritten, child).await else {
+			continue;
+		};
+
+		let folded = fold(&diff, &child_diff);
+		growth = growth
+			.saturating_add(statediff_size(&folded).saturating_sub(statediff_size(&child_diff)));
+
+		if let Some(parent) = folded.parent {
+			only_child.insert(parent, child);
+		}
+
+		rewritten.insert(child, folded);
+		rewritten.remove(shortstatehash);
+		report.folded = report.folded.saturating_add(1);
+		report.bytes = report.bytes.saturating_add(layers[shortstatehash].size);
+		removed.insert(*shortstatehash);
+	}
+
+	report.bytes = report.bytes.saturating_sub(growth);
+
+	if !self.services.server.running() {
+		return Err!("The server is shutting down.");
+	}
+
+	let mut state_hashes: Vec<Vec<u8>> = Vec::new();
+	self.db
+		.statehash_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(state_hash, shortstatehash)| {
+			let shortstatehash = utils::u64_from_bytes(shortstatehash).ok()?;
+			removed
+				.contains(&shortstatehash)
+				.then(|| state_hash.to_vec())
+		})
+		.ready_for_each(|state_hash| {
+			report.bytes = report
+				.bytes
+				.saturating_add(state_hash.len().saturating_add(size_of::<ShortStateHash>()));
+
+			state_hashes.push(state_hash);
+		})
+		.await;
+
+	let mut statekeys: HashSet<ShortStateKey> = HashSet::new();
+	self.db
+		.shortstatehash_statediff
+		.raw_stream()
+		.ignore_err()
+		.ready_for_each(|(key, value)| {
+			let Ok(shortstatehash) = utils::u64_from_bytes(key) else {
+				return;
+			};
+
+			if removed.contains(&shortstatehash) || rewritten.contains_key(&shortstatehash) {
+				return;
+			}
+
+			if let Ok(diff) = parse_statediff(value) {
+				statekeys.extend(used_statekeys(&diff));
+			}
+		})
+		.await;
+
+	for diff in rewritten.values() {
+		statekeys.extend(used_statekeys(diff));
+	}
+
+	let mut unused_statekeys: Vec<(ShortStateKey, Vec<u8>, Vec<u8>)> = Vec::new();
+	self.db
+		.shortstatekey_statekey
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(shortstatekey, statekey)| {
+			let id = utils::u64_from_bytes(shortstatekey).ok()?;
+			(id < horizon && !statekeys.contains(&id))
+				.then(|| (id, shortstatekey.to_vec(), statekey.to_vec()))
+		})
+		.ready_for_each(|unused| unused_statekeys.push(unused))
+		.await;
+
+	if dry_run {
+		report.statekeys = unused_statekeys.len();
+		report.bytes = report
+			.bytes
+			.saturating_add(statekeys_size(&unused_statekeys));
+		debug!(?report, "State garbage collection dry run");
+		return Ok(report);
+	}
+
+	if !self.services.server.running() {
+		return Err!("The server is shutting down.");
+	}
+
+	let _lock = self.services.short.gc_lock.write().await;
+	let reused = self
+		.check_unchanged(&tracking, &layers, horizon, &removed)
+		.await?;
+
+	unused_statekeys.retain(|(id, ..)| !reused.contains(id));
+	report.statekeys = unused_statekeys.len();
+	report.bytes = report
+		.bytes
+		.saturating_add(statekeys_size(&unused_statekeys));
+
+	// Mappings to removed layers must go first, or new state with the same
+	// hash would be assumed to exist already
+	let _cork = self.db.db.cork();
+	for state_hash in &state_hashes {
+		self.db.statehash_shortstatehash.remove(state_hash);
+	}
+
+	for (shortstatehash, diff) in &rewritten {
+		self.save_statediff(*shortstatehash, diff);
+	}
+
+	for shortstatehash in &removed {
+		self.delete_statediff(*shortstatehash);
+	}
+
+	for (_, shortstatekey, statekey) in &unused_statekeys {
+		self.db.statekey_shortstatekey.remove(statekey);
+		self.db.shortstatekey_statekey.remove(shortstatekey);
+	}
+
+	self.stateinfo_cache.lock().clear();
+
+	info!(?report, "Collected state garbage");
+
+	Ok(report)
+}
+
+/// Checks that no state created since the collection started depends on
+/// layers it is about to delete, returning the short state keys which were
+/// reused meanwhile. Must be called with `gc_lock` held exclusively.
+#[implement(super::Service)]
+async fn check_unchanged(
+	&self,
+	tracking: &ReuseTracking<'_>,
+	layers: &HashMap<ShortStateHash, Layer>,
+	horizon: u64,
+	removed: &HashSet<ShortStateHash>,
+) -> Result<HashSet<ShortStateKey>> {
+	let reused = tracking.reused();
+	let mut needed = reused.statehashes;
+
+	self.db
+		.roomid_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(_, shortstatehash)| utils::u64_from_bytes(shortstatehash).ok())
+		.ready_for_each(|shortstatehash| {
+			needed.insert(shortstatehash);
+		})
+		.await;
+
+	// Layers created since the collection started may be built on old ones
+	self.db
+		.shortstatehash_statediff
+		.raw_stream_from(&horizon.to_be_bytes())
+		.ignore_err()
+		.ready_filter_map(|(key, value)| parse_layer(key, value))
+		.ready_for_each(|(_, layer)| needed.extend(layer.parent))
+		.await;
+
+	let mut kept: HashSet<ShortStateHash> = HashSet::new();
+	for shortstatehash in needed {
+		mark_ancestors(layers, &mut kept, shortstatehash);
+	}
+
+	if kept
+		.iter()
+		.any(|shortstatehash| removed.contains(shortstatehash))
+	{
+		return Err!(
+			"State was reused while collecting garbage; nothing was deleted, try again."
+		);
+	}
+
+	Ok(reused.statekeys)
+}
+
+#[implement(super::Service)]
+async fn load_statediff(
+	&self,
+	rewritten: &HashMap<ShortStateHash, StateDiff>,
+	shortstatehash: ShortStateHash,
+) -> Result<StateDiff> {
+	match rewritten.get(&shortstatehash) {
+		| Some(diff) => Ok(diff.clone()),
+		| None => self.get_statediff(shortstatehash).await,
+	}
+}
+
+fn parse_layer(key: &[u8], value: &[u8]) -> Option<(ShortStateHash, Layer)> {
+	let shortstatehash = utils::u64_from_bytes(key).ok()?;
+	let parent = value
+		.get(..size_of::<ShortStateHash>())
+		.and_then(|parent| utils::u64_from_bytes(parent).ok())
+		.take_if(|parent| *parent != 0);
+
+	let size = key.len().saturating_add(value.len());
+	Some((shortstatehash, Layer { parent, size }))
+}
+
+fn mark_ancestors(
+	layers: &HashMap<ShortStateHash, Layer>,
+	marked: &mut HashSet<ShortStateHash>,
+	shortstatehash: ShortStateHash,
+) {
+	let mut next = Some(shortstatehash);
+	while let Some(shortstatehash) = next {
+		if !layers.contains_key(&shortstatehash) || !marked.insert(shortstatehash) {
+			break;
+		}
+
+		next = layers[&shortstatehash].parent;
+	}
+}
+
+/// Combines a layer with its child into a single layer based on the layer's
+/// parent.
+fn fold(diff: &StateDiff, child: &StateDiff) -> StateDiff {
+	let mut added = (*diff.added).clone();
+	let mut removed = (*diff.removed).clone();
+
+	for r in child.removed.iter() {
+		if !added.remove(r) {
+			removed.insert(*r);
+		}
+	}
+
+	for new in child.added.iter() {
+		if !removed.remove(new) {
+			added.insert(*new);
+		}
+	}
+
+	// The bottom layer holds the full state and nothing can be removed from it
+	if diff.parent.is_none() {
+		removed.clear();
+	}
+
+	StateDiff {
+		parent: diff.parent,
+		added: Arc::new(added),
+		removed: Arc::new(removed),
+	}
+}
+
+/// Size of a layer as written by `save_statediff`.
+fn statediff_size(diff: &StateDiff) -> usize {
+	const ENTRY: usize = size_of::<CompressedStateEvent>();
+	const SEPARATOR: usize = size_of::<u64>();
+
+	let removed = if diff.removed.is_empty() {
+		0
+	} else {
+		SEPARATOR.saturating_add(diff.removed.len().saturating_mul(ENTRY))
+	};
+
+	size_of::<ShortStateHash>()
+		.saturating_add(diff.added.len().saturating_mul(ENTRY))
+		.saturating_add(removed)
+}
+
+/// Bytes taken by short state keys in both directions.
+fn statekeys_size(statekeys: &[(ShortStateKey, Vec<u8>, Vec<u8>)]) -> usize {
+	statekeys
+		.iter()
+		.map(|(_, shortstatekey, statekey)| {
+			shortstatekey
+				.len()
+				.saturating_add(statekey.len())
+				.saturating_mul(2)
+		})
+		.fold(0, usize::saturating_add)
+}
+
+fn used_statekeys(diff: &StateDiff) -> impl Iterator<Item = ShortStateKey> + '_ {
+	diff.added
+		.iter()
+		.chain(diff.removed.iter())
+		.map(|compressed| parse_compressed_state_event(*compressed).0)
+}
@@ -0,0 +1,61 @@
+use std::{collections::HashSet, sync::Arc};
+
+use super::{fold, statediff_size, used_statekeys};
+use crate::rooms::state_compressor::{CompressedState, StateDiff, compress_state_event};
+
+fn diff(parent: Option<u64>, added: &[(u64, u64)], removed: &[(u64, u64)]) -> StateDiff {
+	let compress = |events: &[(u64, u64)]| -> CompressedState {
+		events
+			.iter()
+			.map(|&(shortstatekey, shorteventid)| {
+				compress_state_event(shortstatekey, shorteventid)
+			})
+			.collect()
+	};
+
+	StateDiff {
+		parent,
+		added: Arc::new(compress(added)),
+		removed: Arc::new(compress(removed)),
+	}
+}
+
+#[test]
+fn fold_combines_layers() {
+	let layer = diff(Some(1), &[(10, 100), (11, 101)], &[(12, 102)]);
+	let child = diff(Some(2), &[(10, 103), (12, 102), (13, 104)], &[(10, 100)]);
+
+	let folded = fold(&layer, &child);
+	let expected = diff(Some(1), &[(10, 103), (11, 101), (13, 104)], &[]);
+
+	assert_eq!(folded.parent, expected.parent);
+	assert_eq!(folded.added, expected.added);
+	assert_eq!(folded.removed, expected.removed);
+}
+
+#[test]
+fn fold_into_bottom_layer_removes_nothing() {
+	let layer = diff(None, &[(10, 100)], &[]);
+	let child = diff(Some(2), &[], &[(10, 100), (11, 101)]);
+
+	let folded = fold(&layer, &child);
+
+	assert_eq!(folded.parent, None);
+	assert!(folded.added.is_empty());
+	assert!(folded.removed.is_empty());
+}
+
+#[test]
+fn statediff_size_matches_saved_layout() {
+	assert_eq!(statediff_size(&diff(None, &[], &[])), 8);
+	assert_eq!(statediff_size(&diff(Some(1), &[(10, 100), (11, 101)], &[])), 8 + 2 * 16);
+	assert_eq!(statediff_size(&diff(Some(1), &[(10, 100)], &[(11, 101)])), 8 + 16 + 8 + 16);
+}
+
+#[test]
+fn used_statekeys_covers_added_and_removed() {
+	let layer = diff(Some(1), &[(10, 100), (11, 101)], &[(12, 102), (10, 99)]);
+	let used: HashSet<_> = used_statekeys(&layer).collect();
+
+	assert_eq!(used, HashSet::from([10, 11, 12]));
+}
@@ -1,3 +1,5 @@
+mod gc;
+
 use std::{
 	collections::{BTreeSet, HashMap},
 	fmt::{Debug, Write},
@@ -7,18 +9,19 @@
 
 use async_trait::async_trait;
 use conduwuit::{
-	Result, SyncMutex,
+	Result, Server, SyncMutex,
 	arrayvec::ArrayVec,
 	at, checked, err, expected, implement, utils,
 	utils::{bytes, math::usize_from_f64, stream::IterStream},
 };
-use database::Map;
+use database::{Database, Map};
 use futures::{Stream, StreamExt};
 use lru_cache::LruCache;
 use ruma::{EventId, RoomId};
 
+pub use self::gc::GarbageReport;
 use crate::{
-	Dep, rooms,
+	Dep, globals, rooms,
 	rooms::short::{ShortEventId, ShortId, ShortStateHash, ShortStateKey},
 };
 
@@ -29,19 +32,27 @@ pub struct Service {
 }
 
 struct Services {
+	server: Arc<Server>,
+	globals: Dep<globals::Service>,
 	short: Dep<rooms::short::Service>,
 	state: Dep<rooms::state::Service>,
 }
 
 struct Data {
 	shortstatehash_statediff: Arc<Map>,
+	roomid_shortstatehash: Arc<Map>,
+	shorteventid_shortstatehash: Arc<Map>,
+	shortstatekey_statekey: Arc<Map>,
+	statehash_shortstatehash: Arc<Map>,
+	statekey_shortstatekey: Arc<Map>,
+	db: Arc<Database>,
 }
 
 #[derive(Clone)]
-struct StateDiff {
-	parent: Option<ShortStateHash>,
-	added: Arc<CompressedState>,
-	removed: Arc<CompressedState>,
+pub(crate) struct StateDiff {
+	pub(crate) parent: Option<ShortStateHash>,
+	pub(crate) added: Arc<CompressedState>,
+	pub(crate) removed: Arc<CompressedState>,
 }
 
 #[derive(Clone, Default)]
@@ -76,8 +87,16 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			stateinfo_cache: LruCache::new(usize_from_f64(cache_capacity)?).into(),
 			db: Data {
 				shortstatehash_statediff: args.db["shortstatehash_statediff"].clone(),
+				roomid_shortstatehash: args.db["roomid_shortstatehash"].clone(),
+				shorteventid_shortstatehash: args.db["shorteventid_shortstatehash"].clone(),
+				shortstatekey_statekey: args.db["shortstatekey_statekey"].clone(),
+				statehash_shortstatehash: args.db["statehash_shortstatehash"].clone(),
+				statekey_shortstatekey: args.db["statekey_shortstatekey"].clone(),
+				db: args.db.clone(),
 			},
 			services: Services {
+				server: args.server.clone(),
+				globals: args.depend::<globals::Service>("globals"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				state: args.depend::<rooms::state::Service>("rooms::state"),
 			},
@@ -435,9 +454,8 @@ pub async fn save_state(
 
 #[implement(Service)]
 #[tracing::instrument(skip(self), level = "debug", name = "get")]
-async fn get_statediff(&self, shortstatehash: ShortStateHash) -> Result<StateDiff> {
+pub(crate) async fn get_statediff(&self, shortstatehash: ShortStateHash) -> Result<StateDiff> {
 	const BUFSIZE: usize = size_of::<ShortStateHash>();
-	const STRIDE: usize = size_of::<ShortStateHash>();
 
 	let value = self
 		.db
@@ -448,6 +466,13 @@ async fn get_statediff(&self, shortstatehash: ShortStateHash) -> Result<StateDif
 			err!(Database("Failed to find StateDiff from short {shortstatehash:?}: {e}"))
 		})?;
 
+	parse_statediff(&value)
+}
+
+/// Decodes a diff layer as stored in `shortstatehash_statediff`.
+fn parse_statediff(value: &[u8]) -> Result<StateDiff> {
+	const STRIDE: usize = size_of::<ShortStateHash>();
+
 	let parent = utils::u64_from_bytes(&value[0..size_of::<u64>()])
 		.ok()
 		.take_if(|parent| *parent != 0);
@@ -508,6 +533,17 @@ fn save_statediff(&self, shortstatehash: ShortStateHash, diff: &StateDiff) {
 		.insert(&shortstatehash.to_be_bytes(), &value);
 }
 
+/// Removes a diff layer. Layers built on top of it can no longer be loaded
+/// afterwards, so this must only be used when they are removed as well.
+#[implement(Service)]
+pub(crate) fn delete_statediff(&self, shortstatehash: ShortStateHash) {
+	self.db
+		.shortstatehash_statediff
+		.remove(&shortstatehash.to_be_bytes());
+
+	self.stateinfo_cache.lock().remove(&shortstatehash);
+}
+
 #[inline]
 #[must_use]
 pub(crate) fn compress_state_event(
@@ -20,8 +20,8 @@
 		GlobalAccountDataEventType, StateEventType, TimelineEventType,
 		push_rules::PushRulesEvent,
 		room::{
-			encrypted::Relation, power_levels::RoomPowerLevelsEventContent,
-			redaction::RoomRedactionEventContent,
+			encrypted::Relation, join_rules::RoomJoinRulesEventContent,
+			power_levels::RoomPowerLevelsEventContent, redaction::RoomRedactionEventContent,
 		},
 	},
 	push::{Action, Ruleset, Tweak},
@@ -201,6 +201,9 @@ pub async fn append_pdu<'a, Leaves>(
 
 	// Insert pdu
 	self.db.append_pdu(&pdu_id, pdu, &pdu_json, count2).await;
+	self.services
+		.media
+		.add_references(&pdu.event_id, pdu.content.get());
 
 	drop(insert_lock);
 
@@ -354,6 +357,14 @@ pub async fn append_pdu<'a, Leaves>(
 					.await?;
 			}
 		},
+		| TimelineEventType::RoomJoinRules =>
+			if pdu.state_key().is_some_and(str::is_empty) {
+				let content: RoomJoinRulesEventContent = pdu.get_content()?;
+				self.services
+					.user_directory
+					.update_join_rule(room_id, &content.join_rule)
+					.await;
+			},
 		| TimelineEventType::RoomMessage => {
 			let content: ExtractBody = pdu.get_content()?;
 			if let Some(body) = content.body {
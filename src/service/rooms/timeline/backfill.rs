@@ -12,7 +12,7 @@
 };
 use futures::{FutureExt, StreamExt};
 use ruma::{
-	CanonicalJsonObject, EventId, Int, RoomId, ServerName,
+	CanonicalJsonObject, CanonicalJsonValue, EventId, Int, RoomId, ServerName,
 	api::federation,
 	events::{
 		StateEventType, TimelineEventType,
@@ -315,6 +315,20 @@ pub async fn backfill_pdu(&self, origin: &ServerName, pdu: Box<RawJsonValue>) ->
 		return Ok(());
 	}
 
+	// Skip message events which were removed by the room's retention policy
+	if let Some(CanonicalJsonValue::Integer(ts)) = value.get("origin_server_ts") {
+		if !value.contains_key("state_key")
+			&& self
+				.services
+				.retention
+				.is_expired(&room_id, u64::try_from(i64::from(*ts)).unwrap_or_default())
+				.await
+		{
+			debug!("Not backfilling {event_id} as it has expired");
+			return Ok(());
+		}
+	}
+
 	self.services
 		.event_handler
 		.handle_incoming_pdu(origin, &room_id, &event_id, value, false)
@@ -339,6 +353,9 @@ pub async fn backfill_pdu(&self, origin: &ServerName, pdu: Box<RawJsonValue>) ->
 
 	// Insert pdu
 	self.db.prepend_backfill_pdu(&pdu_id, &event_id, &value);
+	self.services
+		.media
+		.add_references(&event_id, pdu.content.get());
 
 	drop(insert_lock);
 
@@ -293,6 +293,19 @@ pub(super) async fn replace_pdu(
 		Ok(())
 	}
 
+	/// Removes a pdu from the timeline and the timestamp index.
+	pub(super) fn remove_pdu(&self, pdu_id: &RawPduId, pdu: &PduEvent) {
+		let key = pack_timestamp_key(
+			pdu_id.shortroomid(),
+			u64::from(pdu.origin_server_ts),
+			pdu_id.pdu_count(),
+		);
+
+		self.db["roomid_timestamp_pducount"].remove(&key);
+		self.eventid_pduid.remove(pdu.event_id.as_bytes());
+		self.pduid_pdu.remove(pdu_id);
+	}
+
 	/// Returns an iterator over all events and their tokens in a room that
 	/// happened before the event with id `until` in reverse-chronological
 	/// order.
@@ -33,9 +33,9 @@
 use self::data::Data;
 pub use self::{create::pdu_fits, data::PdusIterItem};
 use crate::{
-	Dep, account_data, admin, appservice, config, globals, pusher, rooms,
+	Dep, account_data, admin, appservice, config, globals, media, pusher, rooms,
 	rooms::short::{ShortEventId, ShortRoomId, ShortStateHash},
-	sending, server_keys, users,
+	sending, server_keys, user_directory, users,
 };
 
 // Update Relationships
@@ -77,15 +77,18 @@ struct Services {
 	config: Dep<config::Service>,
 	directory: Dep<rooms::directory::Service>,
 	globals: Dep<globals::Service>,
+	media: Dep<media::Service>,
 	short: Dep<rooms::short::Service>,
 	state: Dep<rooms::state::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	pdu_metadata: Dep<rooms::pdu_metadata::Service>,
 	read_receipt: Dep<rooms::read_receipt::Service>,
+	retention: Dep<rooms::retention::Service>,
 	sending: Dep<sending::Service>,
 	server_keys: Dep<server_keys::Service>,
 	user: Dep<rooms::user::Service>,
+	user_directory: Dep<user_directory::Service>,
 	users: Dep<users::Service>,
 	pusher: Dep<pusher::Service>,
 	threads: Dep<rooms::threads::Service>,
@@ -118,6 +121,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				config: args.depend::<config::Service>("config"),
 				directory: args.depend::<rooms::directory::Service>("rooms::directory"),
 				globals: args.depend::<globals::Service>("globals"),
+				media: args.depend::<media::Service>("media"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				state: args.depend::<rooms::state::Service>("rooms::state"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
@@ -125,9 +129,11 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				pdu_metadata: args.depend::<rooms::pdu_metadata::Service>("rooms::pdu_metadata"),
 				read_receipt: args.depend::<rooms::read_receipt::Service>("rooms::read_receipt"),
+				retention: args.depend::<rooms::retention::Service>("rooms::retention"),
 				sending: args.depend::<sending::Service>("sending"),
 				server_keys: args.depend::<server_keys::Service>("server_keys"),
 				user: args.depend::<rooms::user::Service>("rooms::user"),
+				user_directory: args.depend::<user_directory::Service>("user_directory"),
 				users: args.depend::<users::Service>("users"),
 				pusher: args.depend::<pusher::Service>("pusher"),
 				threads: args.depend::<rooms::threads::Service>("rooms::threads"),
@@ -435,6 +441,13 @@ pub async fn replace_pdu(&self, pdu_id: &RawPduId, pdu_json: &CanonicalJsonObjec
 		self.db.replace_pdu(pdu_id, pdu_json).await
 	}
 
+	/// Removes a pdu from the timeline. Its relations, search index entries
+	/// and state are left for the caller to clean up.
+	#[tracing::instrument(skip(self, pdu), level = "debug")]
+	pub fn remove_pdu(&self, pdu_id: &RawPduId, pdu: &PduEvent) {
+		self.db.remove_pdu(pdu_id, pdu);
+	}
+
 	/// Returns an iterator over all PDUs in a room. Unknown rooms produce no
 	/// items.
 	#[inline]
@@ -332,6 +332,20 @@ pub async fn send_federation_request<T>(
 		self.services.federation.execute(dest, request).await
 	}
 
+	/// Like send_federation_request() but returns the response as soon as its
+	/// headers arrive, for its body to be streamed.
+	#[inline]
+	pub async fn send_federation_stream<T>(
+		&self,
+		dest: &ServerName,
+		request: T,
+	) -> Result<reqwest::Response>
+	where
+		T: OutgoingRequest + Debug + Send,
+	{
+		self.services.federation.execute_stream(dest, request).await
+	}
+
 	/// Like send_federation_request() but with a very large timeout
 	#[inline]
 	pub async fn send_synapse_request<T>(
@@ -384,6 +398,15 @@ pub async fn cleanup_events(
 		}
 	}
 
+	/// Number of messages waiting to be picked up by each sender worker.
+	#[must_use]
+	pub fn queue_depths(&self) -> Vec<usize> {
+		self.channels
+			.iter()
+			.map(|(sender, _)| sender.len())
+			.collect()
+	}
+
 	fn dispatch(&self, msg: Msg) -> Result {
 		let shard = self.shard_id(&msg.dest);
 		let sender = &self
@@ -996,6 +996,7 @@ async fn send_events_dest_federation(
 			.outgoing_pdus
 			.fetch_add(pdus.len().try_into().unwrap_or(u64::MAX), Ordering::Relaxed);
 		self.stats.outgoing_txns.fetch_add(1, Ordering::Relaxed);
+		self.stats.record_sent(&server, pdus.len(), edus.len());
 
 		let counter_bytes;
 		let preimage: Vec<&[u8]> = if pdus.is_empty() {
@@ -1044,6 +1045,7 @@ async fn send_events_dest_federation(
 		match result {
 			| Err(error) => {
 				self.stats.outgoing_errors.fetch_add(1, Ordering::Relaxed);
+				self.stats.record_send_error(&server);
 				Err((Destination::Federation(server), error))
 			},
 			| Ok(_) => {
@@ -1,11 +1,18 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::{
+	collections::HashMap,
+	sync::atomic::{AtomicU64, Ordering},
+};
 
-use conduwuit::warn;
+use conduwuit::{SyncMutex, warn};
+use ruma::{OwnedServerName, ServerName};
 
 /// Lightweight atomic counters for federation activity.
-/// Logged periodically and reset after each report.
+/// Logged periodically and reset after each report. The per-server counters
+/// are never reset; they are served by the metrics endpoint.
 #[derive(Default)]
 pub struct FederationStats {
+	destinations: SyncMutex<HashMap<OwnedServerName, DestinationStats>>,
+
 	pub outgoing_txns: AtomicU64,
 	pub outgoing_pdus: AtomicU64,
 	pub outgoing_edus: AtomicU64, // Catch-all for unknown
@@ -17,7 +24,71 @@ pub struct FederationStats {
 	pub outgoing_errors: AtomicU64,
 }
 
+/// Transactions exchanged with one server since startup.
+#[derive(Clone, Debug, Default)]
+pub struct DestinationStats {
+	pub sent_txns: u64,
+	pub sent_pdus: u64,
+	pub sent_edus: u64,
+	pub send_errors: u64,
+	pub received_txns: u64,
+	pub received_pdus: u64,
+	pub received_edus: u64,
+}
+
 impl FederationStats {
+	pub fn record_sent(&self, destination: &ServerName, pdus: usize, edus: usize) {
+		self.update(destination, |stats| {
+			stats.sent_txns = stats.sent_txns.saturating_add(1);
+			stats.sent_pdus = stats
+				.sent_pdus
+				.saturating_add(pdus.try_into().unwrap_or(u64::MAX));
+			stats.sent_edus = stats
+				.sent_edus
+				.saturating_add(edus.try_into().unwrap_or(u64::MAX));
+		});
+	}
+
+	pub fn record_send_error(&self, destination: &ServerName) {
+		self.update(destination, |stats| {
+			stats.send_errors = stats.send_errors.saturating_add(1);
+		});
+	}
+
+	pub fn record_received(&self, origin: &ServerName, pdus: usize, edus: usize) {
+		self.update(origin, |stats| {
+			stats.received_txns = stats.received_txns.saturating_add(1);
+			stats.received_pdus = stats
+				.received_pdus
+				.saturating_add(pdus.try_into().unwrap_or(u64::MAX));
+			stats.received_edus = stats
+				.received_edus
+				.saturating_add(edus.try_into().unwrap_or(u64::MAX));
+		});
+	}
+
+	/// Snapshot of the per-server counters.
+	#[must_use]
+	pub fn destinations(&self) -> Vec<(OwnedServerName, DestinationStats)> {
+		self.destinations
+			.lock()
+			.iter()
+			.map(|(server, stats)| (server.clone(), stats.clone()))
+			.collect()
+	}
+
+	fn update<F>(&self, server: &ServerName, f: F)
+	where
+		F: FnOnce(&mut DestinationStats),
+	{
+		let mut destinations = self.destinations.lock();
+		if let Some(stats) = destinations.get_mut(server) {
+			f(stats);
+		} else {
+			f(destinations.entry(server.to_owned()).or_default());
+		}
+	}
+
 	/// Log a summary and reset all counters. Returns true if any activity
 	/// occurred.
 	pub fn report_and_reset(&self) -> bool {
@@ -11,12 +11,12 @@
 
 use crate::{
 	account_data, admin, announcements, antispam, appservice, client, config, emergency,
-	federation, firstrun, globals, key_backups, mailer,
+	federation, firstrun, globals, key_backups, ldap_sync, mailer,
 	manager::Manager,
-	media, moderation, password_reset, presence, pusher, registration_tokens, resolver, rooms,
-	sending, server_keys,
+	media, metrics, moderation, password_reset, presence, pusher, ratelimit, registration_tokens,
+	rendezvous, reports, resolver, rooms, sending, server_keys,
 	service::{self, Args, Map, Service},
-	sync, threepid, transactions, uiaa, users,
+	sms, sso, sync, threepid, transactions, uiaa, user_directory, users,
 };
 
 pub struct Services {
@@ -28,22 +28,30 @@ pub struct Services {
 	pub emergency: Arc<emergency::Service>,
 	pub globals: Arc<globals::Service>,
 	pub key_backups: Arc<key_backups::Service>,
+	pub ldap_sync: Arc<ldap_sync::Service>,
 	pub media: Arc<media::Service>,
+	pub metrics: Arc<metrics::Service>,
 	pub password_reset: Arc<password_reset::Service>,
 	pub mailer: Arc<mailer::Service>,
 	pub presence: Arc<presence::Service>,
 	pub pusher: Arc<pusher::Service>,
+	pub ratelimit: Arc<ratelimit::Service>,
 	pub registration_tokens: Arc<registration_tokens::Service>,
+	pub rendezvous: Arc<rendezvous::Service>,
+	pub reports: Arc<reports::Service>,
 	pub resolver: Arc<resolver::Service>,
 	pub rooms: rooms::Service,
 	pub federation: Arc<federation::Service>,
 	pub firstrun: Arc<firstrun::Service>,
 	pub sending: Arc<sending::Service>,
 	pub server_keys: Arc<server_keys::Service>,
+	pub sms: Arc<sms::Service>,
+	pub sso: Arc<sso::Service>,
 	pub sync: Arc<sync::Service>,
 	pub transactions: Arc<transactions::Service>,
 	pub threepid: Arc<threepid::Service>,
 	pub uiaa: Arc<uiaa::Service>,
+	pub user_directory: Arc<user_directory::Service>,
 	pub users: Arc<users::Service>,
 	pub moderation: Arc<moderation::Service>,
 	pub announcements: Arc<announcements::Service>,
@@ -85,12 +93,17 @@ macro_rules! build {
 			emergency: build!(emergency::Service),
 			globals: build!(globals::Service),
 			key_backups: build!(key_backups::Service),
+			ldap_sync: build!(ldap_sync::Service),
 			media: build!(media::Service),
+			metrics: build!(metrics::Service),
 			password_reset: build!(password_reset::Service),
 			mailer: build!(mailer::Service),
 			presence: build!(presence::Service),
 			pusher: build!(pusher::Service),
+			ratelimit: build!(ratelimit::Service),
 			registration_tokens: build!(registration_tokens::Service),
+			rendezvous: build!(rendezvous::Service),
+			reports: build!(reports::Service),
 			rooms: rooms::Service {
 				alias: build!(rooms::alias::Service),
 				auth_chain: build!(rooms::auth_chain::Service),
@@ -101,7 +114,9 @@ macro_rules! build {
 				metadata: build!(rooms::metadata::Service),
 				outlier: build!(rooms::outlier::Service),
 				pdu_metadata: build!(rooms::pdu_metadata::Service),
+				purge: build!(rooms::purge::Service),
 				read_receipt: build!(rooms::read_receipt::Service),
+				retention: build!(rooms::retention::Service),
 				search: build!(rooms::search::Service),
 				short: build!(rooms::short::Service),
 				spaces: build!(rooms::spaces::Service),
@@ -117,10 +132,13 @@ macro_rules! build {
 			federation: build!(federation::Service),
 			sending: build!(sending::Service),
 			server_keys: build!(server_keys::Service),
+			sms: build!(sms::Service),
+			sso: build!(sso::Service),
 			sync: build!(sync::Service),
 			threepid: build!(threepid::Service),
 			transactions: build!(transactions::Service),
 			uiaa: build!(uiaa::Service),
+			user_directory: build!(user_directory::Service),
 			users: build!(users::Service),
 			moderation: build!(moderation::Service),
 			announcements: build!(announcements::Service),
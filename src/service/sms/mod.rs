@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, process::Stdio, sync::Arc};
+
+use conduwuit::{Err, Result, config::SmsConfig, err, info, utils::response::LimitReadExt};
+use http::Method;
+use reqwest::header::CONTENT_TYPE;
+use tokio::process::Command;
+use url::form_urlencoded;
+
+use crate::{Args, Dep, client, config};
+
+const MAX_RESPONSE_SIZE: u64 = 64 * 1024;
+
+pub struct Service {
+	services: Services,
+}
+
+struct Services {
+	client: Dep<client::Service>,
+	config: Dep<config::Service>,
+}
+
+#[async_trait::async_trait]
+impl crate::Service for Service {
+	fn build(args: Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			services: Services {
+				client: args.depend::<client::Service>("client"),
+				config: args.depend::<config::Service>("config"),
+			},
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		if self.services.config.sms.is_none() {
+			info!("SMS is not configured, phone number functionality will be unavailable");
+		}
+
+		Ok(())
+	}
+}
+
+impl Service {
+	/// Whether text messages can be sent.
+	#[must_use]
+	pub fn is_configured(&self) -> bool { self.services.config.sms.is_some() }
+
+	/// Sends a text message to `msisdn`, a phone number in international
+	/// format without a leading `+`.
+	pub async fn send(&self, msisdn: &str, message: &str) -> Result {
+		let Some(sms) = &self.services.config.sms else {
+			return Err!(Request(FeatureDisabled(
+				"This homeserver is not configured to send text messages."
+			)));
+		};
+
+		let expand = |template: &str| {
+			template
+				.replace("{to}", msisdn)
+				.replace("{message}", message)
+		};
+
+		if let Some(program) = sms.command.first() {
+			return run_command(program, sms.command.iter().skip(1).map(|arg| expand(arg))).await;
+		}
+
+		self.send_to_gateway(sms, expand).await
+	}
+
+	async fn send_to_gateway<F>(&self, sms: &SmsConfig, expand: F) -> Result
+	where
+		F: Fn(&str) -> String,
+	{
+		let Some(url) = &sms.gateway_url else {
+			return Err!(Config("sms.gateway_url", "No SMS gateway is configured."));
+		};
+
+		let method = Method::from_bytes(sms.gateway_method.as_bytes())
+			.map_err(|e| err!(Config("sms.gateway_method", "Invalid HTTP method: {e}")))?;
+
+		let params: BTreeMap<&str, String> = sms
+			.gateway_params
+			.iter()
+			.map(|(key, value)| (key.as_str(), expand(value)))
+			.collect();
+
+		let mut url = url.clone();
+		if sms.gateway_format == "query" {
+			url.query_pairs_mut().extend_pairs(&params);
+		}
+
+		let mut request = self.services.client.default.request(method, url);
+		for (name, value) in &sms.gateway_headers {
+			request = request.header(name, value);
+		}
+
+		request = match sms.gateway_format.as_str() {
+			| "form" => request
+				.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+				.body(
+					form_urlencoded::Serializer::new(String::new())
+						.extend_pairs(&params)
+						.finish(),
+				),
+			| "json" => request
+				.header(CONTENT_TYPE, "application/json")
+				.body(serde_json::to_vec(&params)?),
+			| _ => request,
+		};
+
+		let response = request.send().await?;
+		let status = response.status();
+		if !status.is_success() {
+			let body = response.limit_read(MAX_RESPONSE_SIZE).await?;
+			return Err!(BadServerResponse(
+				"SMS gateway responded with {status}: {}",
+				String::from_utf8_lossy(&body)
+			));
+		}
+
+		Ok(())
+	}
+}
+
+async fn run_command<I>(program: &str, args: I) -> Result
+where
+	I: Iterator<Item = String>,
+{
+	let output = Command::new(program)
+		.args(args)
+		.stdin(Stdio::null())
+		.kill_on_drop(true)
+		.output()
+		.await
+		.map_err(|e| err!("Failed to run SMS command {program:?}: {e}"))?;
+
+	if !output.status.success() {
+		return Err!(
+			"SMS command {program:?} failed with {}: {}",
+			output.status,
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+
+	Ok(())
+}
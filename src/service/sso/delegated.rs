@@ -0,0 +1,229 @@
+use std::time::{Duration, Instant};
+
+use conduwuit::{Err, Result, debug_warn, err, implement, info, utils, warn};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use ruma::{OwnedDeviceId, OwnedUserId, UserId};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::{Claims, Service, client_auth, read_json};
+
+/// The outcome of introspecting an access token, remembered for
+/// `oauth.introspection_cache_ttl` seconds.
+pub(super) struct Introspection {
+	user: Option<(OwnedUserId, OwnedDeviceId)>,
+	checked: Instant,
+}
+
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+	active: bool,
+	scope: Option<String>,
+	username: Option<String>,
+}
+
+const API_SCOPES: [&str; 2] =
+	["urn:matrix:client:api:*", "urn:matrix:org.matrix.msc2967.client:api:*"];
+const DEVICE_SCOPE_PREFIXES: [&str; 2] =
+	["urn:matrix:client:device:", "urn:matrix:org.matrix.msc2967.client:device:"];
+
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+const MAX_INTROSPECTIONS: usize = 16384;
+const DEVICE_TOKEN_LENGTH: usize = 32;
+
+/// Whether authentication is delegated to an OAuth 2.0 authorization server.
+#[implement(Service)]
+#[must_use]
+pub fn is_delegated(&self) -> bool { self.services.config.oauth.enable }
+
+/// Metadata of the authorization server, discovered from its issuer.
+#[implement(Service)]
+pub async fn auth_metadata(&self) -> Result<Claims> {
+	let Some(issuer) = &self.services.config.oauth.issuer else {
+		return Err!(Request(NotFound("Authentication is not delegated.")));
+	};
+
+	if let Some((fetched, metadata)) = &*self.auth_metadata.lock()
+		&& fetched.elapsed() < METADATA_TTL
+	{
+		return Ok(metadata.clone());
+	}
+
+	let discovery =
+		format!("{}/.well-known/openid-configuration", issuer.as_str().trim_end_matches('/'));
+
+	let response = self
+		.services
+		.client
+		.default
+		.get(discovery)
+		.header(ACCEPT, "application/json")
+		.send()
+		.await?;
+
+	let metadata: Claims = read_json(response).await?;
+	*self.auth_metadata.lock() = Some((Instant::now(), metadata.clone()));
+
+	Ok(metadata)
+}
+
+/// The user and device an access token was issued to by the authorization
+/// server, or `None` if it is not active. Users and devices the
+/// authorization server knows of but we do not are created.
+#[implement(Service)]
+pub async fn introspect(&self, token: &str) -> Result<Option<(OwnedUserId, OwnedDeviceId)>> {
+	let config = &self.services.config.oauth;
+	let ttl = Duration::from_secs(config.introspection_cache_ttl);
+	let key: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+
+	if let Some(introspection) = self.introspections.lock().get(&key)
+		&& introspection.checked.elapsed() < ttl
+	{
+		return Ok(introspection.user.clone());
+	}
+
+	let response = self.introspection_response(token).await?;
+	let user = match response {
+		| Some(response) => self.user_for_introspection(response).await?,
+		| None => None,
+	};
+
+	let mut introspections = self.introspections.lock();
+	if introspections.len() >= MAX_INTROSPECTIONS {
+		introspections.retain(|_, introspection| introspection.checked.elapsed() < ttl);
+		if introspections.len() >= MAX_INTROSPECTIONS {
+			introspections.clear();
+		}
+	}
+
+	introspections.insert(key, Introspection {
+		user: user.clone(),
+		checked: Instant::now(),
+	});
+
+	Ok(user)
+}
+
+/// Asks the authorization server about an access token as described in RFC
+/// 7662, returning `None` if it is not active.
+#[implement(Service)]
+async fn introspection_response(&self, token: &str) -> Result<Option<IntrospectionResponse>> {
+	let config = &self.services.config.oauth;
+	let Some(client_id) = &config.client_id else {
+		return Err!(Config("oauth.client_id", "A client ID is required."));
+	};
+
+	let endpoint = match &config.introspection_endpoint {
+		| Some(endpoint) => endpoint.clone(),
+		| None => self
+			.auth_metadata()
+			.await?
+			.get("introspection_endpoint")
+			.and_then(Value::as_str)
+			.and_then(|endpoint| Url::parse(endpoint).ok())
+			.ok_or_else(|| {
+				err!(BadServerResponse(
+					"Authorization server does not advertise an introspection endpoint."
+				))
+			})?,
+	};
+
+	let mut form = url::form_urlencoded::Serializer::new(String::new());
+	form.append_pair("token", token)
+		.append_pair("token_type_hint", "access_token");
+
+	let request = self
+		.services
+		.client
+		.default
+		.post(endpoint)
+		.header(ACCEPT, "application/json")
+		.header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+	let request = client_auth(
+		request,
+		&mut form,
+		&config.client_auth_method,
+		client_id,
+		config.client_secret.as_deref(),
+	);
+
+	let response = request.body(form.finish()).send().await?;
+	let response: IntrospectionResponse = read_json(response).await?;
+
+	Ok(response.active.then_some(response))
+}
+
+/// The user and device an active access token belongs to, creating them if
+/// this is the first we hear of them.
+#[implement(Service)]
+async fn user_for_introspection(
+	&self,
+	response: IntrospectionResponse,
+) -> Result<Option<(OwnedUserId, OwnedDeviceId)>> {
+	let scope = response.scope.unwrap_or_default();
+	let scopes: Vec<_> = scope.split_whitespace().collect();
+
+	if !scopes.iter().any(|scope| API_SCOPES.contains(scope)) {
+		debug_warn!("Access token was not granted access to the client-server API");
+		return Ok(None);
+	}
+
+	let Some(device_id) = scopes.iter().find_map(|scope| {
+		DEVICE_SCOPE_PREFIXES
+			.iter()
+			.find_map(|prefix| scope.strip_prefix(prefix))
+	}) else {
+		debug_warn!("Access token was not granted a device scope");
+		return Ok(None);
+	};
+
+	let Some(username) = response.username else {
+		return Err!(BadServerResponse(warn!(
+			"Authorization server did not return the username of an active token."
+		)));
+	};
+
+	let server_name = self.services.globals.server_name();
+	let user_id = if username.starts_with('@') {
+		UserId::parse(username)
+	} else {
+		UserId::parse_with_server_name(username, server_name)
+	}
+	.map_err(|e| err!(BadServerResponse("Authorization server returned an invalid user: {e}")))?;
+
+	if !self.services.globals.user_is_local(&user_id) {
+		return Err!(BadServerResponse("Authorization server returned a remote user."));
+	}
+
+	let device_id: OwnedDeviceId = device_id.into();
+
+	let _provisioning = self.provisioning.lock().await;
+	if !self.services.users.exists(&user_id).await {
+		self.provision(&user_id, "oauth", "the authorization server", None)
+			.await?;
+	}
+
+	if self.services.users.is_deactivated(&user_id).await? {
+		return Ok(None);
+	}
+
+	if self
+		.services
+		.users
+		.get_device_metadata(&user_id, &device_id)
+		.await
+		.is_err()
+	{
+		info!(%user_id, %device_id, "Creating device issued by the authorization server");
+		let token = utils::random_string(DEVICE_TOKEN_LENGTH);
+		self.services
+			.users
+			.create_device(&user_id, &device_id, &token, None, None)
+			.await?;
+	}
+
+	Ok(Some((user_id, device_id)))
+}
@@ -0,0 +1,219 @@
+use std::fs;
+
+use conduwuit::{Err, Result, config::JwtConfig, debug_warn, err, implement, utils, warn};
+use jsonwebtoken::{
+	Algorithm, DecodingKey, Validation, decode, decode_header,
+	errors::ErrorKind,
+	jwk::{AlgorithmParameters, EllipticCurve, Jwk},
+};
+use ruma::{OwnedUserId, UserId};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Claims, Service, claim};
+
+/// A key JSON Web Tokens may be signed with.
+pub(super) struct Key {
+	kid: Option<String>,
+	algorithm: Algorithm,
+	key: DecodingKey,
+}
+
+#[derive(Deserialize)]
+struct KeySet {
+	keys: Vec<Value>,
+}
+
+/// Logs in the user a JSON Web Token was issued for, creating their account
+/// if allowed.
+#[implement(Service)]
+pub async fn login_with_jwt(&self, token: &str) -> Result<OwnedUserId> {
+	let config = &self.services.config.jwt;
+	if !config.enable {
+		return Err!(Request(Unknown("JWT login is not enabled.")));
+	}
+
+	let claims = verify(&self.jwt_keys, config, token, utils::millis_since_unix_epoch())?;
+	let subject_claim = &config.subject_claim;
+	let Some(subject) = claim(&claims, subject_claim) else {
+		return Err!(Request(Forbidden("JWT has no {subject_claim:?} claim.")));
+	};
+
+	let server_name = self.services.globals.server_name();
+	let user_id = if subject.starts_with('@') {
+		UserId::parse(subject)
+	} else {
+		UserId::parse_with_server_name(subject, server_name)
+	}
+	.map_err(|e| err!(Request(InvalidUsername("User ID in JWT is invalid: {e}"))))?;
+
+	if !self.services.globals.user_is_local(&user_id) {
+		return Err!(Request(Forbidden("User ID in JWT does not belong to this homeserver.")));
+	}
+
+	let provisioning = self.provisioning.lock().await;
+	if !self.services.users.exists(&user_id).await {
+		if !config.register_user {
+			return Err!(Request(Forbidden("User does not exist.")));
+		}
+
+		if self
+			.services
+			.appservice
+			.is_exclusive_user_id(&user_id)
+			.await
+		{
+			return Err!(Request(Exclusive("Username is reserved by an appservice.")));
+		}
+
+		let displayname = claim(&claims, &config.displayname_claim);
+		self.provision(&user_id, "jwt", "JWT login", displayname)
+			.await?;
+	}
+
+	drop(provisioning);
+
+	self.check_login(&user_id).await?;
+
+	Ok(user_id)
+}
+
+/// Loads the keys configured for JWT login.
+pub(super) fn load_keys(config: &JwtConfig) -> Result<Vec<Key>> {
+	if !config.enable {
+		return Ok(Vec::new());
+	}
+
+	if let Some(path) = &config.jwks_file {
+		let keys: KeySet = serde_json::from_slice(&fs::read(path)?)
+			.map_err(|e| err!(Config("jwt.jwks_file", "Invalid JSON Web Key Set: {e}")))?;
+
+		// Keys of types which cannot sign tokens we accept are skipped
+		return keys
+			.keys
+			.into_iter()
+			.filter_map(|jwk| serde_json::from_value(jwk).ok())
+			.filter_map(|jwk| jwk_key(&jwk).transpose())
+			.collect();
+	}
+
+	let (algorithm, key) = match config.algorithm.as_str() {
+		| "HS256" => {
+			let Some(secret) = &config.secret else {
+				return Err!(Config("jwt.secret", "A secret is required for HS256."));
+			};
+
+			(Algorithm::HS256, DecodingKey::from_secret(secret.as_bytes()))
+		},
+		| algorithm => {
+			let Some(path) = &config.public_key_file else {
+				return Err!(Config("jwt.public_key_file", "A public key is required."));
+			};
+
+			let pem = fs::read(path)?;
+			let key = match algorithm {
+				| "RS256" => DecodingKey::from_rsa_pem(&pem).map(|key| (Algorithm::RS256, key)),
+				| "ES256" => DecodingKey::from_ec_pem(&pem).map(|key| (Algorithm::ES256, key)),
+				| _ => Err(ErrorKind::InvalidAlgorithm.into()),
+			};
+
+			key.map_err(|e| {
+				err!(Config(
+					"jwt.public_key_file",
+					"Invalid {algorithm} public key in {path:?}: {e}"
+				))
+			})?
+		},
+	};
+
+	Ok(vec![Key { kid: None, algorithm, key }])
+}
+
+/// Checks the signature and claims of a JSON Web Token, returning its claims.
+pub(super) fn verify(keys: &[Key], config: &JwtConfig, token: &str, now: u64) -> Result<Claims> {
+	let header = decode_header(token).map_err(|_| err!(Request(Forbidden("Malformed JWT."))))?;
+
+	// Times are checked against `now` below rather than the system clock
+	let mut validation = Validation::new(header.alg);
+	validation.validate_exp = false;
+	validation.validate_nbf = false;
+	validation.set_required_spec_claims(&["exp"]);
+	validation.validate_aud = config.audience.is_some();
+	if let Some(audience) = &config.audience {
+		validation.set_audience(&[audience]);
+	}
+
+	if let Some(issuer) = &config.issuer {
+		validation.set_issuer(&[issuer]);
+	}
+
+	let verified = keys
+		.iter()
+		.filter(|key| key.algorithm == header.alg)
+		.filter(|key| header.kid.is_none() || key.kid.is_none() || key.kid == header.kid)
+		.map(|key| decode::<Claims>(token, &key.key, &validation))
+		.find(|result| {
+			!result
+				.as_ref()
+				.is_err_and(|e| matches!(e.kind(), ErrorKind::InvalidSignature))
+		});
+
+	let claims = match verified {
+		| Some(Ok(data)) => data.claims,
+		| Some(Err(e)) => {
+			return match e.kind() {
+				| ErrorKind::MissingRequiredClaim(_) =>
+					Err!(Request(Forbidden("JWT has no expiry."))),
+				| ErrorKind::InvalidIssuer =>
+					Err!(Request(Forbidden(warn!("JWT was issued by an untrusted issuer.")))),
+				| ErrorKind::InvalidAudience =>
+					Err!(Request(Forbidden("JWT was issued for another audience."))),
+				| _ => Err!(Request(Forbidden(debug_warn!("Invalid JWT: {e}")))),
+			};
+		},
+		| None => {
+			return Err!(Request(Forbidden(debug_warn!(
+				alg = ?header.alg,
+				kid = ?header.kid,
+				"JWT signature could not be verified."
+			))));
+		},
+	};
+
+	let Some(expires_at) = claims.get("exp").and_then(Value::as_u64) else {
+		return Err!(Request(Forbidden("JWT has no expiry.")));
+	};
+
+	if expires_at.saturating_mul(1000) <= now {
+		return Err!(Request(Forbidden("JWT has expired.")));
+	}
+
+	if claims
+		.get("nbf")
+		.and_then(Value::as_u64)
+		.is_some_and(|not_before| not_before.saturating_mul(1000) > now)
+	{
+		return Err!(Request(Forbidden("JWT is not valid yet.")));
+	}
+
+	Ok(claims)
+}
+
+/// The key described by a JSON Web Key, or `None` for types of key which
+/// cannot sign tokens we accept.
+fn jwk_key(jwk: &Jwk) -> Result<Option<Key>> {
+	let algorithm = match &jwk.algorithm {
+		| AlgorithmParameters::OctetKey(_) => Algorithm::HS256,
+		| AlgorithmParameters::RSA(_) => Algorithm::RS256,
+		| AlgorithmParameters::EllipticCurve(params)
+			if matches!(params.curve, EllipticCurve::P256) =>
+			Algorithm::ES256,
+		| _ => return Ok(None),
+	};
+
+	let kid = jwk.common.key_id.clone();
+	let key = DecodingKey::from_jwk(jwk)
+		.map_err(|e| err!(Config("jwt.jwks_file", "Key {kid:?} is invalid: {e}")))?;
+
+	Ok(Some(Key { kid, algorithm, key }))
+}
@@ -0,0 +1,653 @@
+//! Single sign-on through OpenID Connect identity providers, and logins with
+//! JSON Web Tokens issued by trusted services.
+//!
+//! An SSO login starts at the client's SSO redirect endpoint, which sends the
+//! user to the identity provider. The provider sends them back to our
+//! callback page with an authorization code, which is exchanged for their
+//! claims. These are mapped to a local user, who is handed to the client as a
+//! login token appended to its redirect URL.
+//!
+//! Authentication can instead be delegated to an OAuth 2.0 authorization
+//! server, in which case access tokens are introspected there.
+
+mod delegated;
+mod jwt;
+#[cfg(test)]
+mod tests;
+
+use std::{
+	collections::HashMap,
+	fmt::Write,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use conduwuit::{
+	Err, Result, SyncMutex,
+	config::OidcProviderConfig,
+	debug_warn, err, implement, info,
+	utils::{self, response::LimitReadExt},
+	warn,
+};
+use database::{Deserialized, Map};
+use reqwest::{
+	RequestBuilder,
+	header::{ACCEPT, CONTENT_TYPE},
+};
+use ruma::{
+	OwnedUserId, UserId,
+	events::{
+		GlobalAccountDataEventType,
+		push_rules::{PushRulesEvent, PushRulesEventContent},
+	},
+	push::Ruleset,
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use url::{Url, form_urlencoded};
+
+use crate::{
+	Dep, account_data, admin, appservice, client, config, firstrun, globals, user_directory,
+	users,
+};
+
+pub struct Service {
+	services: Services,
+	sessions: SyncMutex<HashMap<String, Session>>,
+	endpoints: SyncMutex<HashMap<String, Endpoints>>,
+	jwt_keys: Vec<jwt::Key>,
+	auth_metadata: SyncMutex<Option<(Instant, Claims)>>,
+	introspections: SyncMutex<HashMap<[u8; 32], delegated::Introspection>>,
+	provisioning: Mutex<()>,
+	db: Data,
+}
+
+struct Services {
+	account_data: Dep<account_data::Service>,
+	admin: Dep<admin::Service>,
+	appservice: Dep<appservice::Service>,
+	client: Dep<client::Service>,
+	config: Dep<config::Service>,
+	firstrun: Dep<firstrun::Service>,
+	globals: Dep<globals::Service>,
+	user_directory: Dep<user_directory::Service>,
+	users: Dep<users::Service>,
+}
+
+struct Data {
+	ssoidpsub_userid: Arc<Map>,
+}
+
+/// A login waiting for the user to come back from the identity provider,
+/// keyed by the `state` parameter sent along with them.
+struct Session {
+	idp_id: String,
+	redirect_url: Url,
+	code_verifier: String,
+	nonce: String,
+	started: Instant,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Endpoints {
+	authorization_endpoint: Url,
+	token_endpoint: Url,
+	userinfo_endpoint: Option<Url>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+	access_token: String,
+	id_token: Option<String>,
+}
+
+/// A user who signed in with an identity provider.
+#[derive(Debug)]
+pub struct Login {
+	pub user_id: OwnedUserId,
+
+	/// The client's redirect URL with the login token appended.
+	pub redirect_url: Url,
+
+	/// Whether the client is in `sso.client_allowlist`, and so may be
+	/// redirected to without asking the user first.
+	pub trusted: bool,
+}
+
+type Claims = serde_json::Map<String, Value>;
+
+/// Path of the page identity providers send users back to.
+pub const CALLBACK_PATH: &str = "/_continuwuity/sso/callback";
+
+/// Path of the page listing the identity providers to choose from, for
+/// clients which did not pick one.
+pub const SELECT_PATH: &str = "/_continuwuity/sso/select";
+
+const SESSION_TTL: Duration = Duration::from_secs(600);
+const MAX_SESSIONS: usize = 4096;
+const STATE_LENGTH: usize = 32;
+const CODE_VERIFIER_LENGTH: usize = 64;
+const LOGIN_TOKEN_LENGTH: usize = 32;
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			services: Services {
+				account_data: args.depend::<account_data::Service>("account_data"),
+				admin: args.depend::<admin::Service>("admin"),
+				appservice: args.depend::<appservice::Service>("appservice"),
+				client: args.depend::<client::Service>("client"),
+				config: args.depend::<config::Service>("config"),
+				firstrun: args.depend::<firstrun::Service>("firstrun"),
+				globals: args.depend::<globals::Service>("globals"),
+				user_directory: args.depend::<user_directory::Service>("user_directory"),
+				users: args.depend::<users::Service>("users"),
+			},
+			sessions: SyncMutex::new(HashMap::new()),
+			endpoints: SyncMutex::new(HashMap::new()),
+			jwt_keys: jwt::load_keys(&args.server.config.jwt)?,
+			auth_metadata: SyncMutex::new(None),
+			introspections: SyncMutex::new(HashMap::new()),
+			provisioning: Mutex::new(()),
+			db: Data {
+				ssoidpsub_userid: args.db["ssoidpsub_userid"].clone(),
+			},
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Whether any identity providers are configured.
+#[implement(Service)]
+#[must_use]
+pub fn is_enabled(&self) -> bool { !self.services.config.sso.providers.is_empty() }
+
+/// Starts a login with the identity provider `idp_id`, returning the URL to
+/// send the user to. Once they have signed in they will be sent on to
+/// `redirect_url` with a login token.
+#[implement(Service)]
+pub async fn start(&self, idp_id: &str, redirect_url: &str) -> Result<Url> {
+	let Some((idp_id, provider)) = self.services.config.sso.providers.get_key_value(idp_id)
+	else {
+		return Err!(Request(NotFound("Unknown identity provider.")));
+	};
+
+	let redirect_url = Url::parse(redirect_url)
+		.map_err(|e| err!(Request(InvalidParam("Invalid redirect URL: {e}"))))?;
+
+	let endpoints = self.endpoints(idp_id, provider).await?;
+
+	let state = utils::random_string(STATE_LENGTH);
+	let nonce = utils::random_string(STATE_LENGTH);
+	let code_verifier = utils::random_string(CODE_VERIFIER_LENGTH);
+	let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+	let mut location = endpoints.authorization_endpoint;
+	location
+		.query_pairs_mut()
+		.append_pair("response_type", "code")
+		.append_pair("client_id", &provider.client_id)
+		.append_pair("redirect_uri", self.callback_url().as_str())
+		.append_pair("scope", &provider.scopes.join(" "))
+		.append_pair("state", &state)
+		.append_pair("nonce", &nonce)
+		.append_pair("code_challenge", &code_challenge)
+		.append_pair("code_challenge_method", "S256");
+
+	let mut sessions = self.sessions.lock();
+	sessions.retain(|_, session| session.started.elapsed() < SESSION_TTL);
+	if sessions.len() >= MAX_SESSIONS {
+		let oldest = sessions
+			.iter()
+			.min_by_key(|(_, session)| session.started)
+			.map(|(state, _)| state.clone());
+
+		if let Some(oldest) = oldest {
+			sessions.remove(&oldest);
+		}
+	}
+
+	sessions.insert(state, Session {
+		idp_id: idp_id.clone(),
+		redirect_url,
+		code_verifier,
+		nonce,
+		started: Instant::now(),
+	});
+
+	Ok(location)
+}
+
+/// Completes the login started with `state` using the authorization code the
+/// identity provider sent the user back with.
+#[implement(Service)]
+pub async fn complete(&self, state: &str, code: &str) -> Result<Login> {
+	let session = self
+		.sessions
+		.lock()
+		.remove(state)
+		.filter(|session| session.started.elapsed() < SESSION_TTL)
+		.ok_or_else(|| err!(Request(Forbidden("Unknown or expired single sign-on session."))))?;
+
+	let Some(provider) = self.services.config.sso.providers.get(&session.idp_id) else {
+		return Err!(Request(NotFound("Unknown identity provider.")));
+	};
+
+	let claims = self.claims(&session, provider, code).await?;
+	let Some(subject) = claim(&claims, &provider.subject_claim) else {
+		return Err!(BadServerResponse(warn!(
+			idp_id = %session.idp_id,
+			"Identity provider did not return the {:?} claim",
+			provider.subject_claim
+		)));
+	};
+
+	let user_id = self
+		.user_for_subject(&session.idp_id, provider, &subject, &claims)
+		.await?;
+
+	self.check_login(&user_id).await?;
+
+	let token = utils::random_string(LOGIN_TOKEN_LENGTH);
+	self.services.users.create_login_token(&user_id, &token);
+
+	let trusted =
+		is_allowlisted(&self.services.config.sso.client_allowlist, &session.redirect_url);
+
+	let mut redirect_url = session.redirect_url;
+	redirect_url
+		.query_pairs_mut()
+		.append_pair("loginToken", &token);
+
+	Ok(Login { user_id, redirect_url, trusted })
+}
+
+#[implement(Service)]
+fn callback_url(&self) -> Url {
+	let mut url = self.services.config.get_client_domain();
+	url.set_path(CALLBACK_PATH);
+	url
+}
+
+/// Endpoints of the identity provider, discovered from its issuer unless all
+/// of them are configured.
+#[implement(Service)]
+async fn endpoints(&self, idp_id: &str, provider: &OidcProviderConfig) -> Result<Endpoints> {
+	if let (Some(authorization_endpoint), Some(token_endpoint), Some(userinfo_endpoint)) = (
+		&provider.authorization_endpoint,
+		&provider.token_endpoint,
+		&provider.userinfo_endpoint,
+	) {
+		return Ok(Endpoints {
+			authorization_endpoint: authorization_endpoint.clone(),
+			token_endpoint: token_endpoint.clone(),
+			userinfo_endpoint: Some(userinfo_endpoint.clone()),
+		});
+	}
+
+	if let Some(endpoints) = self.endpoints.lock().get(idp_id) {
+		return Ok(endpoints.clone());
+	}
+
+	let discovery = format!(
+		"{}/.well-known/openid-configuration",
+		provider.issuer.as_str().trim_end_matches('/')
+	);
+
+	let response = self
+		.services
+		.client
+		.default
+		.get(discovery)
+		.header(ACCEPT, "application/json")
+		.send()
+		.await?;
+
+	let mut endpoints: Endpoints = read_json(response).await?;
+	if let Some(url) = &provider.authorization_endpoint {
+		endpoints.authorization_endpoint = url.clone();
+	}
+	if let Some(url) = &provider.token_endpoint {
+		endpoints.token_endpoint = url.clone();
+	}
+	if let Some(url) = &provider.userinfo_endpoint {
+		endpoints.userinfo_endpoint = Some(url.clone());
+	}
+
+	self.endpoints
+		.lock()
+		.insert(idp_id.to_owned(), endpoints.clone());
+
+	Ok(endpoints)
+}
+
+/// Exchanges the authorization code for the user's claims, taken from the ID
+/// token and the userinfo endpoint.
+#[implement(Service)]
+async fn claims(
+	&self,
+	session: &Session,
+	provider: &OidcProviderConfig,
+	code: &str,
+) -> Result<Claims> {
+	let endpoints = self.endpoints(&session.idp_id, provider).await?;
+
+	let mut form = form_urlencoded::Serializer::new(String::new());
+	form.append_pair("grant_type", "authorization_code")
+		.append_pair("code", code)
+		.append_pair("redirect_uri", self.callback_url().as_str())
+		.append_pair("code_verifier", &session.code_verifier);
+
+	let request = self
+		.services
+		.client
+		.default
+		.post(endpoints.token_endpoint)
+		.header(ACCEPT, "application/json")
+		.header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+	let request = client_auth(
+		request,
+		&mut form,
+		&provider.client_auth_method,
+		&provider.client_id,
+		provider.client_secret.as_deref(),
+	);
+
+	let response = request.body(form.finish()).send().await?;
+	let tokens: TokenResponse = read_json(response).await?;
+
+	let mut claims = match &tokens.id_token {
+		| Some(id_token) => id_token_claims(id_token, provider, &session.nonce)?,
+		| None => Claims::new(),
+	};
+
+	if let Some(userinfo_endpoint) = endpoints.userinfo_endpoint {
+		let response = self
+			.services
+			.client
+			.default
+			.get(userinfo_endpoint)
+			.header(ACCEPT, "application/json")
+			.bearer_auth(&tokens.access_token)
+			.send()
+			.await?;
+
+		let userinfo: Claims = read_json(response).await?;
+		if tokens.id_token.is_some() && userinfo.get("sub") != claims.get("sub") {
+			return Err!(BadServerResponse(
+				"Subject of the userinfo response does not match the ID token."
+			));
+		}
+
+		for (name, value) in userinfo {
+			claims.entry(name).or_insert(value);
+		}
+	}
+
+	Ok(claims)
+}
+
+/// The user linked to `subject` at the identity provider, linking or
+/// creating one if this is their first login.
+#[implement(Service)]
+async fn user_for_subject(
+	&self,
+	idp_id: &str,
+	provider: &OidcProviderConfig,
+	subject: &str,
+	claims: &Claims,
+) -> Result<OwnedUserId> {
+	let key = (idp_id, subject);
+	if let Ok(user_id) = self.db.ssoidpsub_userid.qry(&key).await.deserialized() {
+		return Ok(user_id);
+	}
+
+	// Concurrent first logins must not both link or create an account
+	let _provisioning = self.provisioning.lock().await;
+	if let Ok(user_id) = self.db.ssoidpsub_userid.qry(&key).await.deserialized() {
+		return Ok(user_id);
+	}
+
+	let Some(localpart) = claim(claims, &provider.localpart_claim)
+		.map(|localpart| sanitize_localpart(&localpart))
+		.filter(|localpart| !localpart.is_empty())
+	else {
+		return Err!(Request(Forbidden(debug_warn!(
+			%idp_id,
+			"Identity provider did not return the {:?} claim",
+			provider.localpart_claim
+		))));
+	};
+
+	let user_id = UserId::parse_with_server_name(localpart, self.services.globals.server_name())
+		.map_err(|e| err!(Request(InvalidUsername("Username is invalid: {e}"))))?;
+
+	if self.services.users.exists(&user_id).await {
+		if !provider.link_existing_users {
+			let localpart = user_id.localpart();
+			return Err!(Request(UserInUse("The username {localpart} is already taken.")));
+		}
+
+		info!(%user_id, %idp_id, "Linking existing user to identity provider");
+	} else {
+		if !provider.provision_users {
+			return Err!(Request(Forbidden("No account is linked to this identity.")));
+		}
+
+		if self
+			.services
+			.appservice
+			.is_exclusive_user_id(&user_id)
+			.await
+		{
+			return Err!(Request(Exclusive("Username is reserved by an appservice.")));
+		}
+
+		let displayname = claim(claims, &provider.displayname_claim);
+		self.provision(&user_id, "sso", idp_id, displayname).await?;
+	}
+
+	self.db.ssoidpsub_userid.put(key, &*user_id);
+
+	Ok(user_id)
+}
+
+/// Checks that `user_id` may log in at all.
+#[implement(Service)]
+async fn check_login(&self, user_id: &UserId) -> Result {
+	if self.services.users.is_deactivated(user_id).await? {
+		return Err!(Request(UserDeactivated("This account has been deactivated.")));
+	}
+
+	if self.services.users.is_locked(user_id).await? {
+		return Err!(Request(UserLocked("This account has been locked.")));
+	}
+
+	if self.services.users.is_login_disabled(user_id).await {
+		return Err!(Request(Forbidden("This account is not permitted to log in.")));
+	}
+
+	Ok(())
+}
+
+/// Creates an account for a user logging in for the first time, as
+/// registration would. Its `origin` keeps it from logging in with a
+/// password.
+#[implement(Service)]
+async fn provision(
+	&self,
+	user_id: &UserId,
+	origin: &str,
+	via: &str,
+	displayname: Option<String>,
+) -> Result {
+	self.services
+		.users
+		.create(user_id, Some("*"), Some(origin))
+		.await?;
+
+	let mut displayname = displayname.unwrap_or_else(|| user_id.localpart().to_owned());
+	if !self
+		.services
+		.globals
+		.new_user_displayname_suffix()
+		.is_empty()
+	{
+		write!(displayname, " {}", self.services.globals.new_user_displayname_suffix())?;
+	}
+
+	self.services
+		.users
+		.set_displayname(user_id, Some(displayname));
+
+	self.services
+		.user_directory
+		.update_local_profile(user_id)
+		.await;
+
+	self.services
+		.account_data
+		.update(
+			None,
+			user_id,
+			GlobalAccountDataEventType::PushRules.to_string().into(),
+			&serde_json::to_value(PushRulesEvent {
+				content: PushRulesEventContent { global: Ruleset::server_default(user_id) },
+			})?,
+		)
+		.await?;
+
+	self.services.firstrun.empower_first_user(user_id).await?;
+
+	let notice = format!("New user \"{user_id}\" registered on this server through {via}.");
+	info!("{notice}");
+	if self.services.config.admin_room_notices {
+		self.services.admin.notice(&notice).await;
+	}
+
+	Ok(())
+}
+
+/// Claims of an ID token received straight from the token endpoint. Its
+/// signature is not checked, as the TLS connection it came over already
+/// authenticates the identity provider.
+fn id_token_claims(id_token: &str, provider: &OidcProviderConfig, nonce: &str) -> Result<Claims> {
+	let claims: Claims = id_token
+		.split('.')
+		.nth(1)
+		.and_then(|payload| URL_SAFE_NO_PAD.decode(payload).ok())
+		.and_then(|payload| serde_json::from_slice(&payload).ok())
+		.ok_or_else(|| {
+			err!(BadServerResponse("Identity provider returned a malformed ID token."))
+		})?;
+
+	let issuer = provider.issuer.as_str().trim_end_matches('/');
+	if claims
+		.get("iss")
+		.and_then(Value::as_str)
+		.is_none_or(|iss| iss.trim_end_matches('/') != issuer)
+	{
+		return Err!(BadServerResponse("ID token was issued by another identity provider."));
+	}
+
+	let audience = match claims.get("aud") {
+		| Some(Value::String(aud)) => *aud == provider.client_id,
+		| Some(Value::Array(aud)) => aud
+			.iter()
+			.any(|aud| aud.as_str() == Some(&provider.client_id)),
+		| _ => false,
+	};
+
+	if !audience {
+		return Err!(BadServerResponse("ID token was issued to another client."));
+	}
+
+	if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+		return Err!(BadServerResponse("ID token does not belong to this login."));
+	}
+
+	Ok(claims)
+}
+
+/// Authenticates a request to an authorization server's token or
+/// introspection endpoint with `method`, either "client_secret_basic" or
+/// "client_secret_post".
+fn client_auth(
+	request: RequestBuilder,
+	form: &mut form_urlencoded::Serializer<'_, String>,
+	method: &str,
+	client_id: &str,
+	secret: Option<&str>,
+) -> RequestBuilder {
+	match (method, secret) {
+		| ("client_secret_post", Some(secret)) => {
+			form.append_pair("client_id", client_id)
+				.append_pair("client_secret", secret);
+			request
+		},
+		| (_, Some(secret)) => request.basic_auth(client_id, Some(secret)),
+		| (_, None) => {
+			form.append_pair("client_id", client_id);
+			request
+		},
+	}
+}
+
+async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+	let status = response.status();
+	let body = response.limit_read(MAX_RESPONSE_SIZE).await?;
+	if !status.is_success() {
+		return Err!(BadServerResponse(warn!(
+			"Identity provider responded with {status}: {}",
+			String::from_utf8_lossy(&body)
+		)));
+	}
+
+	serde_json::from_slice(&body)
+		.map_err(|e| err!(BadServerResponse("Invalid response from identity provider: {e}")))
+}
+
+fn claim(claims: &Claims, name: &str) -> Option<String> {
+	match claims.get(name)? {
+		| Value::String(value) => Some(value.clone()),
+		| Value::Number(value) => Some(value.to_string()),
+		| _ => None,
+	}
+}
+
+/// Whether `redirect_url` has the scheme, host and port of an entry in
+/// `allowlist`, and a path at or below the entry's. Entries which are not
+/// valid URLs never match.
+fn is_allowlisted(allowlist: &[String], redirect_url: &Url) -> bool {
+	allowlist
+		.iter()
+		.filter_map(|entry| Url::parse(entry).ok())
+		.any(|entry| {
+			let prefix = entry.path().trim_end_matches('/');
+			entry.scheme() == redirect_url.scheme()
+				&& entry.host() == redirect_url.host()
+				&& entry.port_or_known_default() == redirect_url.port_or_known_default()
+				&& redirect_url
+					.path()
+					.strip_prefix(prefix)
+					.is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+		})
+}
+
+/// Lowercases `localpart` and replaces characters not allowed in user IDs.
+fn sanitize_localpart(localpart: &str) -> String {
+	localpart
+		.to_lowercase()
+		.chars()
+		.map(|c| match c {
+			| 'a'..='z' | '0'..='9' | '.' | '_' | '=' | '-' | '/' => c,
+			| _ => '_',
+		})
+		.collect()
+}
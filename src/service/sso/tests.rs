@@ -0,0 +1,149 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use conduwuit::config::{JwtConfig, OidcProviderConfig};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use serde_json::{Value, json};
+use url::Url;
+
+use super::{id_token_claims, is_allowlisted, jwt, sanitize_localpart};
+
+fn provider() -> OidcProviderConfig {
+	serde_json::from_value(json!({
+		"name": "Example",
+		"issuer": "https://idp.example.com",
+		"client_id": "continuwuity",
+	}))
+	.unwrap()
+}
+
+fn id_token(claims: &Value) -> String {
+	let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256"}"#);
+	let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+	format!("{header}.{payload}.signature")
+}
+
+#[test]
+fn id_token_is_accepted() {
+	let token = id_token(&json!({
+		"iss": "https://idp.example.com",
+		"aud": ["other", "continuwuity"],
+		"sub": "1234",
+		"nonce": "abc",
+	}));
+
+	let claims = id_token_claims(&token, &provider(), "abc").unwrap();
+	assert_eq!(claims.get("sub").and_then(Value::as_str), Some("1234"));
+}
+
+#[test]
+fn id_token_checks_nonce_issuer_and_audience() {
+	let claims = json!({
+		"iss": "https://idp.example.com/",
+		"aud": "continuwuity",
+		"sub": "1234",
+		"nonce": "abc",
+	});
+
+	assert!(id_token_claims(&id_token(&claims), &provider(), "abc").is_ok());
+	assert!(id_token_claims(&id_token(&claims), &provider(), "xyz").is_err());
+
+	let mut wrong_issuer = claims.clone();
+	wrong_issuer["iss"] = json!("https://evil.example.com");
+	assert!(id_token_claims(&id_token(&wrong_issuer), &provider(), "abc").is_err());
+
+	let mut wrong_audience = claims;
+	wrong_audience["aud"] = json!("someone-else");
+	assert!(id_token_claims(&id_token(&wrong_audience), &provider(), "abc").is_err());
+
+	assert!(id_token_claims("not-a-jwt", &provider(), "abc").is_err());
+}
+
+#[test]
+fn localparts_are_sanitized() {
+	assert_eq!(sanitize_localpart("Alice.Smith"), "alice.smith");
+	assert_eq!(sanitize_localpart("bob@example.com"), "bob_example.com");
+	assert_eq!(sanitize_localpart("zoë b"), "zo__b");
+}
+
+#[test]
+fn allowlist_matches_origin_and_path() {
+	let allowlist = ["https://app.element.io".to_owned(), "https://example.com/chat/".to_owned()];
+	let allowed = |url: &str| is_allowlisted(&allowlist, &Url::parse(url).unwrap());
+
+	assert!(allowed("https://app.element.io/"));
+	assert!(allowed("https://app.element.io/#/login?x=1"));
+	assert!(allowed("https://app.element.io:443/"));
+	assert!(allowed("https://example.com/chat"));
+	assert!(allowed("https://example.com/chat/login"));
+
+	assert!(!allowed("https://app.element.io.evil.com/"));
+	assert!(!allowed("https://app.element.io@evil.com/"));
+	assert!(!allowed("http://app.element.io/"));
+	assert!(!allowed("https://app.element.io:8443/"));
+	assert!(!allowed("https://example.com/chatroom"));
+	assert!(!allowed("https://example.com/"));
+}
+
+fn jwt_config() -> JwtConfig {
+	serde_json::from_value(json!({
+		"enable": true,
+		"secret": "hunter2",
+		"issuer": "https://portal.example.com",
+		"audience": "matrix",
+	}))
+	.unwrap()
+}
+
+fn hs256_token(claims: &Value, secret: &str) -> String {
+	let key = EncodingKey::from_secret(secret.as_bytes());
+	encode(&Header::new(Algorithm::HS256), claims, &key).unwrap()
+}
+
+#[test]
+fn jwt_is_verified() {
+	let config = jwt_config();
+	let keys = jwt::load_keys(&config).unwrap();
+	let claims = json!({
+		"iss": "https://portal.example.com",
+		"aud": "matrix",
+		"sub": "alice",
+		"exp": 2_000_000_000,
+	});
+
+	let now = 1_700_000_000_000;
+	let verified = jwt::verify(&keys, &config, &hs256_token(&claims, "hunter2"), now).unwrap();
+	assert_eq!(verified.get("sub").and_then(Value::as_str), Some("alice"));
+
+	assert!(jwt::verify(&keys, &config, &hs256_token(&claims, "wrong"), now).is_err());
+	assert!(
+		jwt::verify(&keys, &config, &hs256_token(&claims, "hunter2"), 2_000_000_000_000).is_err()
+	);
+}
+
+#[test]
+fn jwt_claims_are_checked() {
+	let config = jwt_config();
+	let keys = jwt::load_keys(&config).unwrap();
+	let now = 1_700_000_000_000;
+	let claims = json!({
+		"iss": "https://portal.example.com",
+		"aud": ["matrix"],
+		"sub": "alice",
+		"exp": 2_000_000_000,
+	});
+
+	let mut no_expiry = claims.clone();
+	no_expiry.as_object_mut().unwrap().remove("exp");
+	assert!(jwt::verify(&keys, &config, &hs256_token(&no_expiry, "hunter2"), now).is_err());
+
+	let mut wrong_issuer = claims.clone();
+	wrong_issuer["iss"] = json!("https://evil.example.com");
+	assert!(jwt::verify(&keys, &config, &hs256_token(&wrong_issuer, "hunter2"), now).is_err());
+
+	let mut wrong_audience = claims.clone();
+	wrong_audience["aud"] = json!(["other"]);
+	assert!(jwt::verify(&keys, &config, &hs256_token(&wrong_audience, "hunter2"), now).is_err());
+
+	let mut not_yet_valid = claims;
+	not_yet_valid["nbf"] = json!(1_900_000_000);
+	assert!(jwt::verify(&keys, &config, &hs256_token(&not_yet_valid, "hunter2"), now).is_err());
+}
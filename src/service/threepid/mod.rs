@@ -1,4 +1,4 @@
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
 
 use conduwuit::{Err, Error, Result, result::FlatOk};
 use database::{Deserialized, Map};
@@ -7,13 +7,18 @@
 use nonzero_ext::nonzero;
 use ruma::{
 	ClientSecret, OwnedClientSecret, OwnedSessionId, SessionId, api::client::error::ErrorKind,
+	thirdparty::Medium,
 };
 
+pub mod msisdn;
 mod session;
+#[cfg(test)]
+mod tests;
 
 use crate::{
 	Args, Dep, config,
 	mailer::{self, messages::MessageTemplate},
+	sms,
 	threepid::session::{ValidationSessions, ValidationState, ValidationToken},
 };
 
@@ -21,20 +26,48 @@ pub struct Service {
 	db: Data,
 	services: Services,
 	sessions: tokio::sync::Mutex<ValidationSessions>,
-	send_attempts: std::sync::Mutex<HashMap<(OwnedClientSecret, Address), usize>>,
-	ratelimiter: DefaultKeyedRateLimiter<Address>,
+	send_attempts: std::sync::Mutex<HashMap<(OwnedClientSecret, Threepid), usize>>,
+	ratelimiter: DefaultKeyedRateLimiter<Threepid>,
 }
 
-pub enum EmailRequirement {
-	/// Users may change their email, but cannot remove it entirely.
+/// A third-party identifier which may be associated with an account.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Threepid {
+	Email(Address),
+	/// A phone number in international format, without a leading `+`.
+	Msisdn(String),
+}
+
+impl Threepid {
+	#[must_use]
+	pub fn medium(&self) -> Medium {
+		match self {
+			| Self::Email(_) => Medium::Email,
+			| Self::Msisdn(_) => Medium::Msisdn,
+		}
+	}
+}
+
+impl fmt::Display for Threepid {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			| Self::Email(email) => write!(f, "{email}"),
+			| Self::Msisdn(msisdn) => write!(f, "{msisdn}"),
+		}
+	}
+}
+
+pub enum ThreepidRequirement {
+	/// Users may change their 3PID of this medium, but cannot remove it
+	/// entirely.
 	Required,
-	/// Users may change or remove their email.
+	/// Users may change or remove their 3PID of this medium.
 	Optional,
-	/// Users may not change their email at all.
+	/// Users may not change their 3PID of this medium at all.
 	Unavailable,
 }
 
-impl EmailRequirement {
+impl ThreepidRequirement {
 	#[must_use]
 	pub fn may_change(&self) -> bool { matches!(self, Self::Required | Self::Optional) }
 
@@ -45,11 +78,14 @@ pub fn may_remove(&self) -> bool { matches!(self, Self::Optional) }
 struct Data {
 	localpart_email: Arc<Map>,
 	email_localpart: Arc<Map>,
+	localpart_msisdn: Arc<Map>,
+	msisdn_localpart: Arc<Map>,
 }
 
 struct Services {
 	config: Dep<config::Service>,
 	mailer: Dep<mailer::Service>,
+	sms: Dep<sms::Service>,
 }
 
 impl crate::Service for Service {
@@ -58,14 +94,17 @@ fn build(args: Args<'_>) -> Result<Arc<Self>> {
 			db: Data {
 				email_localpart: args.db["email_localpart"].clone(),
 				localpart_email: args.db["localpart_email"].clone(),
+				localpart_msisdn: args.db["localpart_msisdn"].clone(),
+				msisdn_localpart: args.db["msisdn_localpart"].clone(),
 			},
 			services: Services {
 				config: args.depend("config"),
 				mailer: args.depend("mailer"),
+				sms: args.depend("sms"),
 			},
 			sessions: tokio::sync::Mutex::default(),
 			send_attempts: std::sync::Mutex::default(),
-			ratelimiter: RateLimiter::keyed(Self::EMAIL_RATELIMIT),
+			ratelimiter: RateLimiter::keyed(Self::SEND_RATELIMIT),
 		}))
 	}
 
@@ -73,24 +112,37 @@ fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 impl Service {
-	// Each address gets two tickets to send an email, which refill at a rate of one
-	// per ten minutes. This allows two emails to be sent at once without waiting
-	// (in case the first one gets eaten), but requires a wait of at least ten
-	// minutes before sending another.
-	const EMAIL_RATELIMIT: Quota =
+	// Each address gets two tickets to send a message, which refill at a rate of
+	// one per ten minutes. This allows two messages to be sent at once without
+	// waiting (in case the first one gets eaten), but requires a wait of at least
+	// ten minutes before sending another.
+	const SEND_RATELIMIT: Quota =
 		Quota::per_minute(nonzero!(10_u32)).allow_burst(nonzero!(2_u32));
 	const VALIDATION_URL_PATH: &str = "/_continuwuity/3pid/email/validate";
 
 	/// Check if users are required to have an email address.
-	pub fn email_requirement(&self) -> EmailRequirement {
+	pub fn email_requirement(&self) -> ThreepidRequirement {
 		if let Some(smtp) = &self.services.config.smtp {
 			if smtp.require_email_for_registration || smtp.require_email_for_token_registration {
-				EmailRequirement::Required
+				ThreepidRequirement::Required
+			} else {
+				ThreepidRequirement::Optional
+			}
+		} else {
+			ThreepidRequirement::Unavailable
+		}
+	}
+
+	/// Check if users are required to have a phone number.
+	pub fn msisdn_requirement(&self) -> ThreepidRequirement {
+		if let Some(sms) = &self.services.config.sms {
+			if sms.require_msisdn_for_registration || sms.require_msisdn_for_token_registration {
+				ThreepidRequirement::Required
 			} else {
-				EmailRequirement::Optional
+				ThreepidRequirement::Optional
 			}
 		} else {
-			EmailRequirement::Unavailable
+			ThreepidRequirement::Unavailable
 		}
 	}
 
@@ -106,23 +158,102 @@ pub async fn send_validation_email<Template: MessageTemplate>(
 		send_attempt: usize,
 	) -> Result<OwnedSessionId> {
 		let mailer = self.services.mailer.expect_mailer()?;
+		let threepid = Threepid::Email(recipient.email.clone());
+
+		let (session_id, Some(token)) = self
+			.start_validation(threepid, client_secret, send_attempt)
+			.await?
+		else {
+			return Ok(session_id);
+		};
+
+		let mut validation_url = self
+			.services
+			.config
+			.get_client_domain()
+			.join(Self::VALIDATION_URL_PATH)
+			.unwrap();
+
+		validation_url
+			.query_pairs_mut()
+			.append_pair("session", session_id.as_str())
+			.append_pair("token", &token);
+
+		let message = prepare_body(validation_url.to_string());
+
+		mailer.send(recipient, message).await?;
+
+		Ok(session_id)
+	}
+
+	/// Send a validation code to a phone number by text message. The code is
+	/// submitted with [`Self::submit_validation_token`].
+	///
+	/// Returns the validation session ID on success.
+	pub async fn send_validation_sms(
+		&self,
+		msisdn: &str,
+		client_secret: &ClientSecret,
+		send_attempt: usize,
+	) -> Result<OwnedSessionId> {
+		let Some(sms) = &self.services.config.sms else {
+			return Err!(Request(FeatureDisabled(
+				"This homeserver is not configured to send text messages."
+			)));
+		};
+
+		let threepid = Threepid::Msisdn(msisdn.to_owned());
+		let (session_id, Some(token)) = self
+			.start_validation(threepid, client_secret, send_attempt)
+			.await?
+		else {
+			return Ok(session_id);
+		};
+
+		let message = sms
+			.message
+			.replace("{server_name}", self.services.config.server_name.as_str())
+			.replace("{code}", &token);
+
+		self.services.sms.send(msisdn, &message).await?;
+
+		Ok(session_id)
+	}
+
+	/// Find or create the validation session of a client secret, returning its
+	/// ID along with a new token to send if one should be sent.
+	async fn start_validation(
+		&self,
+		threepid: Threepid,
+		client_secret: &ClientSecret,
+		send_attempt: usize,
+	) -> Result<(OwnedSessionId, Option<String>)> {
 		let mut sessions = self.sessions.lock().await;
 
+		// A client secret identifies a session together with the 3PID it validates,
+		// so a secret reused for another 3PID starts over.
+		if let Some(session) = sessions.get_session_by_client_secret(client_secret)
+			&& session.threepid != threepid
+		{
+			let session_id = session.session_id.clone();
+			sessions.remove_session(&session_id);
+		}
+
 		let session = match sessions.get_session_by_client_secret(client_secret) {
 			// If a validation session already exists for this client secret, we can either
 			// reuse it with a new token or return early because it's already valid.
 			| Some(session) => {
 				match session.validation_state {
 					| ValidationState::Validated => {
-						// If the existing session is already valid, don't send an email.
-						return Ok(session.session_id.clone());
+						// If the existing session is already valid, don't send a message.
+						return Ok((session.session_id.clone(), None));
 					},
 					| ValidationState::Pending(ref mut token) => {
 						// Check ratelimiting for the target address.
-						if self.ratelimiter.check_key(&recipient.email).is_err() {
+						if self.ratelimiter.check_key(&threepid).is_err() {
 							return Err(Error::BadRequest(
 								ErrorKind::LimitExceeded { retry_after: None },
-								"You're sending emails too fast, try again in a few minutes.",
+								"You're sending messages too fast, try again in a few minutes.",
 							));
 						}
 
@@ -130,13 +261,13 @@ pub async fn send_validation_email<Template: MessageTemplate>(
 						let mut send_attempts = self.send_attempts.lock().unwrap();
 
 						let last_send_attempt = send_attempts
-							.entry((session.client_secret.clone(), session.email.clone()))
+							.entry((session.client_secret.clone(), session.threepid.clone()))
 							.or_default();
 
 						if send_attempt <= *last_send_attempt {
 							// If the supplied send attempt isn't higher than the last
-							// one, don't send an email.
-							return Ok(session.session_id.clone());
+							// one, don't send a message.
+							return Ok((session.session_id.clone(), None));
 						}
 
 						// Save this send attempt.
@@ -144,44 +275,21 @@ pub async fn send_validation_email<Template: MessageTemplate>(
 						drop(send_attempts);
 
 						// Create a new token for the existing session.
-						*token = ValidationToken::new_random();
+						*token = ValidationToken::new(&session.threepid);
 
 						session
 					},
 				}
 			},
 			// If no session exists, create a new one.
-			| None => sessions.create_session(recipient.email.clone(), client_secret.to_owned()),
+			| None => sessions.create_session(threepid, client_secret.to_owned()),
 		};
 
-		// Clone this so it can outlive the lock we're holding on `sessions`
-		let session_id = session.session_id.clone();
-
 		let ValidationState::Pending(token) = &session.validation_state else {
 			unreachable!("session should be pending")
 		};
 
-		let mut validation_url = self
-			.services
-			.config
-			.get_client_domain()
-			.join(Self::VALIDATION_URL_PATH)
-			.unwrap();
-
-		validation_url
-			.query_pairs_mut()
-			.append_pair("session", session_id.as_str())
-			.append_pair("token", &token.token);
-
-		// Once the validation URL is built, we don't need any data borrowed from
-		// `sessions` anymore and can release our lock
-		drop(sessions);
-
-		let message = prepare_body(validation_url.to_string());
-
-		mailer.send(recipient, message).await?;
-
-		Ok(session_id)
+		Ok((session.session_id.clone(), Some(token.token.clone())))
 	}
 
 	/// Attempt to mark a validation session as valid using a validation token.
@@ -190,41 +298,42 @@ pub async fn try_validate_session(
 		session_id: &SessionId,
 		supplied_token: &str,
 	) -> Result<(), Cow<'static, str>> {
-		let mut sessions = self.sessions.lock().await;
-
-		let Some(session) = sessions.get_session(session_id) else {
-			return Err("Validation session does not exist".into());
-		};
-
-		session.validation_state = match &session.validation_state {
-			| ValidationState::Validated => {
-				// If the session is already validated, do nothing.
-
-				return Ok(());
-			},
-			| ValidationState::Pending(token) => {
-				// Otherwise check the token and mark the session as valid.
+		self.sessions
+			.lock()
+			.await
+			.validate_session(session_id, supplied_token)
+	}
 
-				if *token != *supplied_token || !token.is_valid() {
-					return Err("Validation token is invalid or expired, please request a new \
-					            one"
-					.into());
-				}
+	/// Attempt to mark a validation session as valid using a validation token
+	/// submitted by the client which started it, as is done for phone numbers.
+	pub async fn submit_validation_token(
+		&self,
+		session_id: &SessionId,
+		client_secret: &ClientSecret,
+		supplied_token: &str,
+	) -> Result<(), Cow<'static, str>> {
+		let is_owner = self
+			.sessions
+			.lock()
+			.await
+			.get_session(session_id)
+			.is_some_and(|session| session.client_secret == client_secret);
 
-				ValidationState::Validated
-			},
-		};
+		if !is_owner {
+			return Err("Validation session does not exist".into());
+		}
 
-		Ok(())
+		self.try_validate_session(session_id, supplied_token.trim())
+			.await
 	}
 
 	/// Consume a validated validation session, removing it from the database
-	/// and returning the newly validated email address.
+	/// and returning the newly validated email address or phone number.
 	pub async fn consume_valid_session(
 		&self,
 		session_id: &SessionId,
 		client_secret: &ClientSecret,
-	) -> Result<Address, Cow<'static, str>> {
+	) -> Result<Threepid, Cow<'static, str>> {
 		let mut sessions = self.sessions.lock().await;
 
 		let Some(session) = sessions.get_session(session_id) else {
@@ -236,11 +345,16 @@ pub async fn consume_valid_session(
 		{
 			let session = sessions.remove_session(session_id);
 
-			Ok(session.email)
+			Ok(session.threepid)
 		} else {
-			Err("This email address has not been validated. Did you use the link that was sent \
-			     to you?"
-				.into())
+			match session.threepid {
+				| Threepid::Email(_) => Err("This email address has not been validated. Did \
+				                             you use the link that was sent to you?"
+					.into()),
+				| Threepid::Msisdn(_) => Err("This phone number has not been validated. Did \
+				                              you enter the code that was sent to you?"
+					.into()),
+			}
 		}
 	}
 
@@ -267,9 +381,10 @@ pub async fn associate_localpart_email(
 				// Remove the user's existing email first.
 				let _ = self.disassociate_localpart_email(localpart).await;
 
-				let email: &str = email.as_ref();
-				self.db.localpart_email.insert(localpart, email);
-				self.db.email_localpart.insert(email, localpart);
+				self.db
+					.localpart_email
+					.insert(localpart, <Address as AsRef<str>>::as_ref(email));
+				self.db.email_localpart.insert(&email_key(email), localpart);
 				Ok(())
 			},
 		}
@@ -283,9 +398,9 @@ pub async fn disassociate_localpart_email(&self, localpart: &str) -> Option<Addr
 		let email = self.get_email_for_localpart(localpart).await?;
 
 		self.db.localpart_email.remove(localpart);
-		self.db
-			.email_localpart
-			.remove(<Address as AsRef<str>>::as_ref(&email));
+		if self.get_localpart_for_email(&email).await.as_deref() == Some(localpart) {
+			self.db.email_localpart.remove(&email_key(&email));
+		}
 
 		Some(email)
 	}
@@ -302,13 +417,69 @@ pub async fn get_email_for_localpart(&self, localpart: &str) -> Option<Address>
 			.flat_ok()
 	}
 
-	/// Get the localpart associated with an email, if one exists.
+	/// Get the localpart associated with an email, if one exists. Emails are
+	/// matched case-insensitively.
 	pub async fn get_localpart_for_email(&self, email: &Address) -> Option<String> {
 		self.db
 			.email_localpart
-			.get(<Address as AsRef<str>>::as_ref(email))
+			.get(&email_key(email))
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	/// Associate a localpart with a phone number.
+	pub async fn associate_localpart_msisdn(&self, localpart: &str, msisdn: &str) -> Result<()> {
+		match self.get_localpart_for_msisdn(msisdn).await {
+			| Some(existing_localpart) if existing_localpart != localpart => {
+				Err!(Request(ThreepidInUse("This phone number is already in use.")))
+			},
+			| Some(_) => Ok(()),
+			| None => {
+				// Remove the user's existing phone number first.
+				let _ = self.disassociate_localpart_msisdn(localpart).await;
+
+				self.db.localpart_msisdn.insert(localpart, msisdn);
+				self.db.msisdn_localpart.insert(msisdn, localpart);
+				Ok(())
+			},
+		}
+	}
+
+	/// Given a localpart, remove its corresponding phone number.
+	pub async fn disassociate_localpart_msisdn(&self, localpart: &str) -> Option<String> {
+		let msisdn = self.get_msisdn_for_localpart(localpart).await?;
+
+		self.db.localpart_msisdn.remove(localpart);
+		if self.get_localpart_for_msisdn(&msisdn).await.as_deref() == Some(localpart) {
+			self.db.msisdn_localpart.remove(&msisdn);
+		}
+
+		Some(msisdn)
+	}
+
+	/// Get the phone number associated with a localpart, if one exists.
+	pub async fn get_msisdn_for_localpart(&self, localpart: &str) -> Option<String> {
+		self.db
+			.localpart_msisdn
+			.get(localpart)
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	/// Get the localpart associated with a phone number, if one exists.
+	pub async fn get_localpart_for_msisdn(&self, msisdn: &str) -> Option<String> {
+		self.db
+			.msisdn_localpart
+			.get(msisdn)
 			.await
 			.deserialized()
 			.ok()
 	}
 }
+
+/// The key of an email in `email_localpart`. Nearly every mail server treats
+/// addresses case-insensitively, so users can't be expected to remember the
+/// case they registered with.
+fn email_key(email: &Address) -> String { <Address as AsRef<str>>::as_ref(email).to_lowercase() }
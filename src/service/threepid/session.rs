@@ -1,32 +1,42 @@
 use std::{
+	borrow::Cow,
 	collections::HashMap,
 	time::{Duration, SystemTime},
 };
 
 use conduwuit::utils;
-use lettre::Address;
 use ruma::{ClientSecret, OwnedClientSecret, OwnedSessionId, SessionId};
 
+use super::Threepid;
+
 #[derive(Default)]
 pub(super) struct ValidationSessions {
 	sessions: HashMap<OwnedSessionId, ValidationSession>,
 	client_secrets: HashMap<OwnedClientSecret, OwnedSessionId>,
 }
 
-/// A pending or completed email validation session.
+/// A pending or completed 3PID validation session.
 #[derive(Debug)]
 pub(crate) struct ValidationSession {
 	/// The session's ID
 	pub session_id: OwnedSessionId,
 	/// The client's supplied client secret
 	pub client_secret: OwnedClientSecret,
-	/// The email address which is being validated
-	pub email: Address,
+	/// The email address or phone number which is being validated
+	pub threepid: Threepid,
 	/// The session's validation state
 	pub validation_state: ValidationState,
+	/// How many times a wrong validation token was supplied
+	pub failed_attempts: usize,
+}
+
+impl ValidationSession {
+	/// Wrong validation tokens which may be supplied before the session is
+	/// removed, so that short codes cannot be guessed.
+	pub(super) const MAX_FAILED_ATTEMPTS: usize = 5;
 }
 
-/// The state of an email validation session.
+/// The state of a 3PID validation session.
 #[derive(Debug)]
 pub(crate) enum ValidationState {
 	/// The session is waiting for this validation token to be provided
@@ -44,8 +54,18 @@ pub(crate) struct ValidationToken {
 impl ValidationToken {
 	// one hour
 	const MAX_TOKEN_AGE: Duration = Duration::from_secs(60 * 60);
+	const NUMERIC_TOKEN_LENGTH: usize = 6;
 	const RANDOM_TOKEN_LENGTH: usize = 16;
 
+	/// A new token for validating `threepid`. Phone numbers are sent a numeric
+	/// code to be typed in, while email addresses are sent a link.
+	pub(super) fn new(threepid: &Threepid) -> Self {
+		match threepid {
+			| Threepid::Email(_) => Self::new_random(),
+			| Threepid::Msisdn(_) => Self::new_numeric(),
+		}
+	}
+
 	pub(super) fn new_random() -> Self {
 		Self {
 			token: utils::random_string(Self::RANDOM_TOKEN_LENGTH),
@@ -53,6 +73,15 @@ pub(super) fn new_random() -> Self {
 		}
 	}
 
+	pub(super) fn new_numeric() -> Self {
+		let token = (0..Self::NUMERIC_TOKEN_LENGTH)
+			.map(|_| rand::random_range(0..10))
+			.map(|digit| char::from_digit(digit, 10).expect("digit is below 10"))
+			.collect();
+
+		Self { token, issued_at: SystemTime::now() }
+	}
+
 	pub(crate) fn is_valid(&self) -> bool {
 		let now = SystemTime::now();
 
@@ -75,14 +104,15 @@ pub(super) fn generate_session_id() -> OwnedSessionId {
 
 	pub(super) fn create_session(
 		&mut self,
-		email: Address,
+		threepid: Threepid,
 		client_secret: OwnedClientSecret,
 	) -> &mut ValidationSession {
 		let session = ValidationSession {
 			session_id: Self::generate_session_id(),
 			client_secret,
-			email,
-			validation_state: ValidationState::Pending(ValidationToken::new_random()),
+			validation_state: ValidationState::Pending(ValidationToken::new(&threepid)),
+			threepid,
+			failed_attempts: 0,
 		};
 
 		self.client_secrets
@@ -113,6 +143,38 @@ pub(super) fn get_session_by_client_secret(
 		Some(session)
 	}
 
+	/// Marks a session as valid if `supplied_token` is its validation token.
+	/// The session is removed once too many wrong tokens have been supplied.
+	pub(super) fn validate_session(
+		&mut self,
+		session_id: &SessionId,
+		supplied_token: &str,
+	) -> Result<(), Cow<'static, str>> {
+		let Some(session) = self.get_session(session_id) else {
+			return Err("Validation session does not exist".into());
+		};
+
+		let ValidationState::Pending(token) = &session.validation_state else {
+			// If the session is already validated, do nothing.
+			return Ok(());
+		};
+
+		if *token == *supplied_token && token.is_valid() {
+			session.validation_state = ValidationState::Validated;
+			return Ok(());
+		}
+
+		session.failed_attempts = session.failed_attempts.saturating_add(1);
+		if session.failed_attempts >= ValidationSession::MAX_FAILED_ATTEMPTS {
+			self.remove_session(session_id);
+			return Err(
+				"Too many invalid validation tokens were supplied, please start over".into()
+			);
+		}
+
+		Err("Validation token is invalid or expired, please request a new one".into())
+	}
+
 	pub(super) fn remove_session(&mut self, session_id: &SessionId) -> ValidationSession {
 		let session = self
 			.sessions
@@ -0,0 +1,77 @@
+use super::msisdn::{normalize_msisdn, to_msisdn};
+
+#[test]
+fn national_numbers_get_calling_code() {
+	assert_eq!(to_msisdn("GB", "07700 900123").as_deref(), Some("447700900123"));
+	assert_eq!(to_msisdn("us", "(201) 555-0123").as_deref(), Some("12015550123"));
+	assert_eq!(to_msisdn("IT", "06 1234 5678").as_deref(), Some("390612345678"));
+}
+
+#[test]
+fn international_numbers_ignore_country() {
+	assert_eq!(to_msisdn("US", "+44 7700 900123").as_deref(), Some("447700900123"));
+	assert_eq!(to_msisdn("", "0044 7700 900123").as_deref(), Some("447700900123"));
+}
+
+#[test]
+fn invalid_numbers_are_rejected() {
+	assert_eq!(to_msisdn("ZZ", "07700 900123"), None);
+	assert_eq!(to_msisdn("GB", "123"), None);
+	assert_eq!(normalize_msisdn("+4477009001234567"), None);
+	assert_eq!(normalize_msisdn("44-7700-900123"), None);
+	assert_eq!(normalize_msisdn("+447700900123").as_deref(), Some("447700900123"));
+}
+
+#[test]
+fn sms_tokens_are_numeric() {
+	use super::{Threepid, session::ValidationToken};
+
+	let token = ValidationToken::new(&Threepid::Msisdn("447700900123".to_owned()));
+	assert_eq!(token.token.len(), 6);
+	assert!(token.token.chars().all(|c| c.is_ascii_digit()));
+}
+
+#[test]
+fn sessions_are_removed_after_failed_attempts() {
+	use ruma::ClientSecret;
+
+	use super::{
+		Threepid,
+		session::{ValidationSession, ValidationSessions, ValidationState},
+	};
+
+	let mut sessions = ValidationSessions::default();
+	let client_secret = ClientSecret::parse("secret").unwrap();
+	let session = sessions
+		.create_session(Threepid::Msisdn("447700900123".to_owned()), client_secret.to_owned());
+
+	let session_id = session.session_id.clone();
+	let ValidationState::Pending(token) = &session.validation_state else {
+		panic!("new session should be pending");
+	};
+
+	// A wrong code is guaranteed as it is not numeric
+	let token = token.token.clone();
+	for _ in 1..ValidationSession::MAX_FAILED_ATTEMPTS {
+		assert!(sessions.validate_session(&session_id, "wrong").is_err());
+	}
+
+	assert!(sessions.validate_session(&session_id, &token).is_ok());
+
+	let session = sessions.create_session(
+		Threepid::Msisdn("447700900124".to_owned()),
+		ClientSecret::parse("other").unwrap().to_owned(),
+	);
+
+	let session_id = session.session_id.clone();
+	for _ in 0..ValidationSession::MAX_FAILED_ATTEMPTS {
+		assert!(sessions.validate_session(&session_id, "wrong").is_err());
+	}
+
+	assert!(sessions.get_session(&session_id).is_none());
+	assert!(
+		sessions
+			.get_session_by_client_secret(&client_secret)
+			.is_some()
+	);
+}
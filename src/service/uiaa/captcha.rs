@@ -0,0 +1,201 @@
+//! Captcha services which may back the `m.login.recaptcha` stage.
+//!
+//! reCaptcha, hCaptcha and Turnstile responses are verified with the
+//! service that issued them. A proof-of-work response is verified locally:
+//! it is a string whose SHA-256 hash, prefixed by the UIAA session ID and a
+//! colon, starts with `captcha.pow_difficulty` zero bits. Tying the work to
+//! the session means a solution is only good for one registration.
+
+use conduwuit::{Err, Result, debug_warn, err, error, implement, utils::response::LimitReadExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use ruma::api::client::error::{ErrorKind, StandardErrorBody};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
+
+#[derive(Deserialize)]
+struct SiteverifyResponse {
+	success: bool,
+	#[serde(default, rename = "error-codes")]
+	error_codes: Vec<String>,
+}
+
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+const MAX_RESPONSE_SIZE: u64 = 64 * 1024;
+const MAX_POW_SOLUTION_LENGTH: usize = 128;
+
+/// Parameters of the `m.login.recaptcha` stage, or `None` if no captcha is
+/// configured.
+#[implement(super::Service)]
+#[must_use]
+pub fn captcha_params(&self) -> Option<Value> {
+	let captcha = &self.services.config.captcha;
+	match captcha.provider.as_str() {
+		| "pow" => Some(json!({
+			"provider": "pow",
+			"algorithm": "sha256",
+			"difficulty": captcha.pow_difficulty,
+		})),
+		| provider => {
+			let (site_key, _) = self.captcha_keys()?;
+			Some(json!({
+				"public_key": site_key,
+				"provider": provider,
+			}))
+		},
+	}
+}
+
+/// Checks the response to the captcha of a UIAA session.
+#[implement(super::Service)]
+pub(super) async fn check_captcha(
+	&self,
+	session: &str,
+	response: &str,
+) -> Result<(), StandardErrorBody> {
+	let captcha = &self.services.config.captcha;
+	let verified = match captcha.provider.as_str() {
+		| "pow" => Ok(check_pow(session, response, captcha.pow_difficulty)),
+		| _ if self.captcha_keys().is_none() => {
+			return Err(StandardErrorBody {
+				kind: ErrorKind::forbidden(),
+				message: "Captcha is not configured".to_owned(),
+			});
+		},
+		| "recaptcha" => self.verify_recaptcha(response).await,
+		| provider => self.siteverify(provider, response).await,
+	};
+
+	match verified {
+		| Ok(true) => Ok(()),
+		| Ok(false) => Err(StandardErrorBody {
+			kind: ErrorKind::forbidden(),
+			message: "Captcha verification failed".to_owned(),
+		}),
+		| Err(e) => {
+			error!("Captcha verification failed: {e}");
+			Err(StandardErrorBody {
+				kind: ErrorKind::forbidden(),
+				message: "Captcha verification failed".to_owned(),
+			})
+		},
+	}
+}
+
+/// The site key and secret key of the configured captcha service. reCaptcha
+/// falls back to the keys it was configured with before other services were
+/// supported.
+#[implement(super::Service)]
+fn captcha_keys(&self) -> Option<(&str, &str)> {
+	let config = &self.services.config;
+	let captcha = &config.captcha;
+	let (site_key, secret_key) = if captcha.provider == "recaptcha" {
+		(
+			captcha
+				.site_key
+				.as_ref()
+				.or(config.recaptcha_site_key.as_ref()),
+			captcha
+				.secret_key
+				.as_ref()
+				.or(config.recaptcha_private_site_key.as_ref()),
+		)
+	} else {
+		(captcha.site_key.as_ref(), captcha.secret_key.as_ref())
+	};
+
+	Some((site_key?, secret_key?))
+}
+
+#[implement(super::Service)]
+async fn verify_recaptcha(&self, response: &str) -> Result<bool> {
+	let Some((_, secret_key)) = self.captcha_keys() else {
+		return Err!(Config("recaptcha_private_site_key", "reCaptcha is not configured."));
+	};
+
+	match recaptcha_verify::verify_v3(secret_key, response, None).await {
+		| Ok(()) => Ok(true),
+		| Err(e) => Err(err!("reCaptcha rejected the response: {e:?}")),
+	}
+}
+
+/// Verifies a response with an hCaptcha or Turnstile compatible `siteverify`
+/// endpoint.
+#[implement(super::Service)]
+async fn siteverify(&self, provider: &str, response: &str) -> Result<bool> {
+	let Some((site_key, secret_key)) = self.captcha_keys() else {
+		return Err!(Config("captcha.secret_key", "{provider} is not configured."));
+	};
+
+	let url = match &self.services.config.captcha.verify_url {
+		| Some(url) => url.as_str(),
+		| None if provider == "hcaptcha" => HCAPTCHA_VERIFY_URL,
+		| None => TURNSTILE_VERIFY_URL,
+	};
+
+	let mut form = form_urlencoded::Serializer::new(String::new());
+	form.append_pair("secret", secret_key)
+		.append_pair("response", response);
+
+	if provider == "hcaptcha" {
+		form.append_pair("sitekey", site_key);
+	}
+
+	let response = self
+		.services
+		.client
+		.default
+		.post(url)
+		.header(ACCEPT, "application/json")
+		.header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+		.body(form.finish())
+		.send()
+		.await?;
+
+	let status = response.status();
+	let body = response.limit_read(MAX_RESPONSE_SIZE).await?;
+	if !status.is_success() {
+		return Err!(BadServerResponse(
+			"{provider} responded with {status}: {}",
+			String::from_utf8_lossy(&body)
+		));
+	}
+
+	let response: SiteverifyResponse = serde_json::from_slice(&body)
+		.map_err(|e| err!(BadServerResponse("Invalid response from {provider}: {e}")))?;
+
+	if !response.success {
+		debug_warn!(errors = ?response.error_codes, "{provider} rejected the response");
+	}
+
+	Ok(response.success)
+}
+
+/// Whether `solution` proves the work asked of the UIAA session `session`.
+pub(super) fn check_pow(session: &str, solution: &str, difficulty: u32) -> bool {
+	if solution.is_empty() || solution.len() > MAX_POW_SOLUTION_LENGTH {
+		return false;
+	}
+
+	let hash = Sha256::new()
+		.chain_update(session)
+		.chain_update(":")
+		.chain_update(solution)
+		.finalize();
+
+	leading_zero_bits(&hash) >= difficulty
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+	let mut bits: u32 = 0;
+	for byte in bytes {
+		bits = bits.saturating_add(byte.leading_zeros());
+		if *byte != 0 {
+			break;
+		}
+	}
+
+	bits
+}
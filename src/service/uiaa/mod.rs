@@ -1,25 +1,33 @@
+mod captcha;
+#[cfg(test)]
+mod tests;
+
 use std::{
 	borrow::Cow,
 	collections::{HashMap, HashSet, hash_map::Entry},
 	sync::Arc,
 };
 
-use conduwuit::{Err, Error, Result, error, utils, utils::hash};
+use conduwuit::{Err, Error, Result, utils, utils::hash};
 use lettre::Address;
 use ruma::{
 	UserId,
 	api::client::{
 		error::{ErrorKind, StandardErrorBody},
 		uiaa::{
-			AuthData, AuthFlow, AuthType, EmailIdentity, Password, ReCaptcha, RegistrationToken,
-			ThirdpartyIdCredentials, UiaaInfo, UserIdentifier,
+			AuthData, AuthFlow, AuthType, EmailIdentity, Msisdn, Password, ReCaptcha,
+			RegistrationToken, ThirdpartyIdCredentials, UiaaInfo, UserIdentifier,
 		},
 	},
 };
 use serde_json::value::RawValue;
 use tokio::sync::Mutex;
 
-use crate::{Dep, config, globals, registration_tokens, threepid, users};
+use crate::{
+	Dep, client, config, globals, registration_tokens,
+	threepid::{self, Threepid, msisdn},
+	users,
+};
 
 pub struct Service {
 	services: Services,
@@ -29,6 +37,7 @@ pub struct Service {
 struct Services {
 	globals: Dep<globals::Service>,
 	users: Dep<users::Service>,
+	client: Dep<client::Service>,
 	config: Dep<config::Service>,
 	registration_tokens: Dep<registration_tokens::Service>,
 	threepid: Dep<threepid::Service>,
@@ -40,6 +49,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			services: Services {
 				globals: args.depend::<globals::Service>("globals"),
 				users: args.depend::<users::Service>("users"),
+				client: args.depend::<client::Service>("client"),
 				config: args.depend::<config::Service>("config"),
 				registration_tokens: args
 					.depend::<registration_tokens::Service>("registration_tokens"),
@@ -80,6 +90,13 @@ pub struct Identity {
 	/// - The user completed a m.login.password stage, and their user ID has an
 	///   associated email
 	pub email: Option<Address>,
+
+	/// The authenticated user's phone number, if it could be determined.
+	///
+	/// This will be Some if:
+	/// - The user completed a m.login.msisdn stage
+	/// - The user completed a m.login.password stage with a phone number
+	pub msisdn: Option<String>,
 }
 
 macro_rules! identity_update_fn {
@@ -105,6 +122,8 @@ impl Identity {
 
 	identity_update_fn!(fn try_set_email(email: Address) else "Email mismatch");
 
+	identity_update_fn!(fn try_set_msisdn(msisdn: String) else "Phone number mismatch");
+
 	/// Create an Identity with the localpart of the provided user ID
 	/// and all other fields set to None.
 	#[must_use]
@@ -205,13 +224,13 @@ async fn create_session(
 	async fn continue_session(
 		&self,
 		auth: &AuthData,
-		session: &str,
+		session_id: &str,
 	) -> Result<Result<Identity, UiaaInfo>> {
 		// Hold this lock for the entire function to make sure that, if try_auth()
 		// is called concurrently with the same session, only one call will succeed
 		let mut uiaa_sessions = self.uiaa_sessions.lock().await;
 
-		let Entry::Occupied(mut session) = uiaa_sessions.entry(session.to_owned()) else {
+		let Entry::Occupied(mut session) = uiaa_sessions.entry(session_id.to_owned()) else {
 			return Err!(Request(InvalidParam("Invalid session")));
 		};
 
@@ -260,7 +279,7 @@ async fn continue_session(
 
 			// If the provided stage hasn't already been completed, check it for completion
 			if !completed_stages.contains(auth_type.as_str()) {
-				match self.check_stage(auth, identity.clone()).await {
+				match self.check_stage(auth, session_id, identity.clone()).await {
 					| Ok((completed_stage, updated_identity)) => {
 						info.auth_error = None;
 						completed_stages.insert(completed_stage.to_string());
@@ -308,6 +327,7 @@ async fn continue_session(
 	async fn check_stage(
 		&self,
 		auth: &AuthData,
+		session_id: &str,
 		mut identity: Identity,
 	) -> Result<(AuthType, Identity), StandardErrorBody> {
 		// Note: This function takes ownership of `identity` because mutations to the
@@ -330,7 +350,7 @@ async fn check_stage(
 					.consume_valid_session(sid, client_secret)
 					.await
 				{
-					| Ok(email) => {
+					| Ok(Threepid::Email(email)) => {
 						if let Some(localpart) =
 							self.services.threepid.get_localpart_for_email(&email).await
 						{
@@ -341,6 +361,44 @@ async fn check_stage(
 
 						Ok(AuthType::EmailIdentity)
 					},
+					| Ok(Threepid::Msisdn(_)) => Err(StandardErrorBody {
+						kind: ErrorKind::ThreepidAuthFailed,
+						message: "This session validated a phone number, not an email".to_owned(),
+					}),
+					| Err(message) => Err(StandardErrorBody {
+						kind: ErrorKind::ThreepidAuthFailed,
+						message: message.into_owned(),
+					}),
+				}
+			},
+			| AuthData::Msisdn(Msisdn {
+				thirdparty_id_creds: ThirdpartyIdCredentials { client_secret, sid, .. },
+				..
+			}) => {
+				match self
+					.services
+					.threepid
+					.consume_valid_session(sid, client_secret)
+					.await
+				{
+					| Ok(Threepid::Msisdn(msisdn)) => {
+						if let Some(localpart) = self
+							.services
+							.threepid
+							.get_localpart_for_msisdn(&msisdn)
+							.await
+						{
+							identity.try_set_localpart(localpart)?;
+						}
+
+						identity.try_set_msisdn(msisdn)?;
+
+						Ok(AuthType::Msisdn)
+					},
+					| Ok(Threepid::Email(_)) => Err(StandardErrorBody {
+						kind: ErrorKind::ThreepidAuthFailed,
+						message: "This session validated an email, not a phone number".to_owned(),
+					}),
 					| Err(message) => Err(StandardErrorBody {
 						kind: ErrorKind::ThreepidAuthFailed,
 						message: message.into_owned(),
@@ -372,6 +430,33 @@ async fn check_stage(
 							});
 						}
 					},
+					| Some(
+						identifier @ (UserIdentifier::Msisdn { .. }
+						| UserIdentifier::PhoneNumber { .. }),
+					) => {
+						let Some(msisdn) = msisdn::from_identifier(identifier) else {
+							return Err(StandardErrorBody {
+								kind: ErrorKind::InvalidParam,
+								message: "Phone number is malformed".to_owned(),
+							});
+						};
+
+						if let Some(localpart) = self
+							.services
+							.threepid
+							.get_localpart_for_msisdn(&msisdn)
+							.await
+						{
+							identity.try_set_msisdn(msisdn)?;
+
+							localpart
+						} else {
+							return Err(StandardErrorBody {
+								kind: ErrorKind::forbidden(),
+								message: "Wrong username or password".to_owned(),
+							});
+						}
+					},
 					| _ => {
 						return Err(StandardErrorBody {
 							kind: ErrorKind::Unrecognized,
@@ -426,26 +511,10 @@ async fn check_stage(
 					})
 				}
 			},
-			| AuthData::ReCaptcha(ReCaptcha { response, .. }) => {
-				let Some(ref private_site_key) = self.services.config.recaptcha_private_site_key
-				else {
-					return Err(StandardErrorBody {
-						kind: ErrorKind::forbidden(),
-						message: "ReCaptcha is not configured".to_owned(),
-					});
-				};
-
-				match recaptcha_verify::verify_v3(private_site_key, response, None).await {
-					| Ok(()) => Ok(AuthType::ReCaptcha),
-					| Err(e) => {
-						error!("ReCaptcha verification failed: {e:?}");
-						Err(StandardErrorBody {
-							kind: ErrorKind::forbidden(),
-							message: "ReCaptcha verification failed".to_owned(),
-						})
-					},
-				}
-			},
+			| AuthData::ReCaptcha(ReCaptcha { response, .. }) => self
+				.check_captcha(session_id, response)
+				.await
+				.map(|()| AuthType::ReCaptcha),
 			| AuthData::RegistrationToken(RegistrationToken { token, .. }) => {
 				let token = token.trim().to_owned();
 
@@ -0,0 +1,27 @@
+use super::captcha::check_pow;
+
+/// Finds a solution the slow way, as a client would.
+fn solve(session: &str, difficulty: u32) -> String {
+	(0_u64..)
+		.map(|nonce| nonce.to_string())
+		.find(|solution| check_pow(session, solution, difficulty))
+		.unwrap()
+}
+
+#[test]
+fn pow_solution_is_accepted() {
+	let solution = solve("session", 8);
+	assert!(check_pow("session", &solution, 8));
+}
+
+#[test]
+fn pow_solution_is_bound_to_session() {
+	let solution = solve("session", 12);
+	let reusable = (0..8).any(|i| check_pow(&format!("other{i}"), &solution, 12));
+	assert!(!reusable);
+}
+
+#[test]
+fn pow_rejects_empty_solution() {
+	assert!(!check_pow("session", "", 0));
+}
@@ -0,0 +1,414 @@
+//! Searchable index of the users we know about.
+//!
+//! Local users and remote users who share a room with us are indexed by the
+//! words of their display name and their user ID, so a search only looks at
+//! users with a term starting with what was searched for. Which users are in
+//! public rooms is tracked alongside; whether two users share a room comes
+//! from the membership index kept by `rooms::state_cache`.
+
+use std::{collections::HashSet, sync::Arc};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Result, Server, debug, implement, info,
+	utils::{ReadyExt, stream::TryIgnore},
+};
+use database::{Deserialized, Ignore, Interfix, Json, Map, serialize_key};
+use futures::StreamExt;
+use ruma::{
+	OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	events::room::{
+		join_rules::JoinRule,
+		member::{MembershipState, RoomMemberEventContent},
+	},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Dep, globals, rooms, users};
+
+pub struct Service {
+	db: Data,
+	services: Services,
+}
+
+struct Data {
+	global: Arc<Map>,
+	termuserid_directory: Arc<Map>,
+	userid_directoryprofile: Arc<Map>,
+	userroomid_public: Arc<Map>,
+}
+
+struct Services {
+	server: Arc<Server>,
+	globals: Dep<globals::Service>,
+	metadata: Dep<rooms::metadata::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+	users: Dep<users::Service>,
+}
+
+/// The profile shown for a user in search results.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DirectoryProfile {
+	pub display_name: Option<String>,
+	pub avatar_url: Option<OwnedMxcUri>,
+
+	/// The terms the user is indexed by.
+	#[serde(default)]
+	terms: Vec<String>,
+}
+
+/// Bounds the index entries a single display name can create.
+const MAX_TERMS: usize = 32;
+
+/// Written once the index has been built by a full pass, so that a pass
+/// interrupted by a shutdown is started over.
+const BUILT_MARKER: &[u8] = b"user_directory_built";
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				global: args.db["global"].clone(),
+				termuserid_directory: args.db["termuserid_directory"].clone(),
+				userid_directoryprofile: args.db["userid_directoryprofile"].clone(),
+				userroomid_public: args.db["userroomid_public"].clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				globals: args.depend::<globals::Service>("globals"),
+				metadata: args.depend::<rooms::metadata::Service>("rooms::metadata"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				users: args.depend::<users::Service>("users"),
+			},
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "user_directory", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result {
+		// The index is built from scratch once, then kept up to date as
+		// memberships and profiles change
+		if self.db.global.get(BUILT_MARKER).await.is_ok() {
+			return Ok(());
+		}
+
+		info!("Building the user directory, this may take a while");
+		self.rebuild().await?;
+		info!("Finished building the user directory");
+
+		Ok(())
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Indexes every local user and every member of every room we know about,
+/// then marks the index as built.
+#[implement(Service)]
+pub async fn rebuild(&self) -> Result {
+	let local_users: Vec<OwnedUserId> = self
+		.services
+		.users
+		.list_local_users()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for user_id in &local_users {
+		self.update_local_profile(user_id).await;
+	}
+
+	let rooms: Vec<OwnedRoomId> = self
+		.services
+		.metadata
+		.iter_ids()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for room_id in &rooms {
+		if !self.services.server.running() {
+			return Err!("The server is shutting down.");
+		}
+
+		let public = self.is_public_room(room_id).await;
+		let members: Vec<OwnedUserId> = self
+			.services
+			.state_cache
+			.room_members(room_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		for user_id in &members {
+			self.set_public(user_id, room_id, public);
+			if self.services.globals.user_is_local(user_id) {
+				continue;
+			}
+
+			if let Ok(member) = self
+				.services
+				.state_accessor
+				.get_member(room_id, user_id)
+				.await
+			{
+				self.update_profile(user_id, member.displayname, member.avatar_url)
+					.await;
+			}
+		}
+	}
+
+	self.db.global.insert(BUILT_MARKER, []);
+
+	Ok(())
+}
+
+/// Updates the index after a user's membership of a room changed.
+#[implement(Service)]
+pub async fn update_membership(
+	&self,
+	room_id: &RoomId,
+	user_id: &UserId,
+	member: &RoomMemberEventContent,
+) {
+	let local = self.services.globals.user_is_local(user_id);
+	match member.membership {
+		| MembershipState::Join => {
+			let public = self.is_public_room(room_id).await;
+			self.set_public(user_id, room_id, public);
+
+			if local {
+				self.update_local_profile(user_id).await;
+			} else {
+				self.update_profile(
+					user_id,
+					member.displayname.clone(),
+					member.avatar_url.clone(),
+				)
+				.await;
+			}
+		},
+		| MembershipState::Leave | MembershipState::Ban => {
+			self.set_public(user_id, room_id, false);
+
+			// Remote users are only searchable while they share a room with us
+			if !local
+				&& self
+					.services
+					.state_cache
+					.rooms_joined(user_id)
+					.next()
+					.await
+					.is_none()
+			{
+				self.remove(user_id).await;
+			}
+		},
+		| _ => {},
+	}
+}
+
+/// Updates which users are in a public room after its join rule changed.
+#[implement(Service)]
+pub async fn update_join_rule(&self, room_id: &RoomId, join_rule: &JoinRule) {
+	let public = matches!(join_rule, JoinRule::Public);
+	self.services
+		.state_cache
+		.room_members(room_id)
+		.ready_for_each(|user_id| self.set_public(user_id, room_id, public))
+		.await;
+}
+
+/// Re-indexes a local user from their global profile, or removes them from
+/// the index if they were deactivated.
+#[implement(Service)]
+pub async fn update_local_profile(&self, user_id: &UserId) {
+	if self
+		.services
+		.users
+		.is_deactivated(user_id)
+		.await
+		.unwrap_or(true)
+	{
+		self.remove(user_id).await;
+		return;
+	}
+
+	let display_name = self.services.users.displayname(user_id).await.ok();
+	let avatar_url = self.services.users.avatar_url(user_id).await.ok();
+	self.update_profile(user_id, display_name, avatar_url).await;
+}
+
+/// Finds up to `limit` users matching every word of `search_term` whom
+/// `sender` may see, and whether there were more.
+#[implement(Service)]
+pub async fn search(
+	&self,
+	sender: &UserId,
+	search_term: &str,
+	limit: usize,
+) -> (Vec<(OwnedUserId, DirectoryProfile)>, bool) {
+	let words: Vec<String> = search_term
+		.to_lowercase()
+		.split_whitespace()
+		.map(|word| word.trim_start_matches('@').to_owned())
+		.filter(|word| !word.is_empty())
+		.collect();
+
+	// The longest word narrows down the candidates the most
+	let Some(prefix) = words.iter().max_by_key(|word| word.len()) else {
+		return (Vec::new(), false);
+	};
+
+	let mut seen: HashSet<OwnedUserId> = HashSet::new();
+	let mut results: Vec<_> = self
+		.db
+		.termuserid_directory
+		.keys_raw_prefix(prefix.as_bytes())
+		.ignore_err()
+		.ready_filter_map(|(_, user_id): (Ignore, &UserId)| {
+			seen.insert(user_id.to_owned()).then(|| user_id.to_owned())
+		})
+		.filter_map(|user_id| {
+			let words = &words;
+			async move {
+				let profile = self.profile(&user_id).await.ok()?;
+				let matches = words.iter().all(|word| {
+					profile
+						.terms
+						.iter()
+						.any(|term| term.starts_with(word.as_str()))
+				});
+
+				(matches && self.is_visible(sender, &user_id).await).then_some((user_id, profile))
+			}
+		})
+		.take(limit.saturating_add(1))
+		.collect()
+		.await;
+
+	let limited = results.len() > limit;
+	results.truncate(limit);
+
+	(results, limited)
+}
+
+#[implement(Service)]
+pub async fn profile(&self, user_id: &UserId) -> Result<DirectoryProfile> {
+	self.db
+		.userid_directoryprofile
+		.get(user_id)
+		.await
+		.deserialized()
+}
+
+/// Whether the user is joined to any room with a public join rule.
+#[implement(Service)]
+pub async fn is_in_public_room(&self, user_id: &UserId) -> bool {
+	self.db
+		.userroomid_public
+		.keys_prefix_raw(&(user_id, Interfix))
+		.ignore_err()
+		.next()
+		.await
+		.is_some()
+}
+
+#[implement(Service)]
+async fn is_visible(&self, sender: &UserId, user_id: &UserId) -> bool {
+	if sender == user_id {
+		return true;
+	}
+
+	if self.services.server.config.user_directory_search_all_users
+		&& self.services.globals.user_is_local(user_id)
+	{
+		return true;
+	}
+
+	self.is_in_public_room(user_id).await
+		|| self
+			.services
+			.state_cache
+			.user_sees_user(sender, user_id)
+			.await
+}
+
+#[implement(Service)]
+async fn is_public_room(&self, room_id: &RoomId) -> bool {
+	matches!(self.services.state_accessor.get_join_rules(room_id).await, JoinRule::Public)
+}
+
+#[implement(Service)]
+fn set_public(&self, user_id: &UserId, room_id: &RoomId, public: bool) {
+	let userroom_id = serialize_key((user_id, room_id)).expect("failed to serialize userroom_id");
+	if public {
+		self.db.userroomid_public.insert(&userroom_id, []);
+	} else {
+		self.db.userroomid_public.remove(&userroom_id);
+	}
+}
+
+#[implement(Service)]
+async fn update_profile(
+	&self,
+	user_id: &UserId,
+	display_name: Option<String>,
+	avatar_url: Option<OwnedMxcUri>,
+) {
+	let terms = terms(user_id, display_name.as_deref());
+	if let Ok(old) = self.profile(user_id).await {
+		for term in old.terms.iter().filter(|term| !terms.contains(term)) {
+			self.db.termuserid_directory.del((term, user_id));
+		}
+	}
+
+	for term in &terms {
+		self.db.termuserid_directory.put_raw((term, user_id), []);
+	}
+
+	let profile = DirectoryProfile { display_name, avatar_url, terms };
+	self.db
+		.userid_directoryprofile
+		.raw_put(user_id, Json(&profile));
+
+	debug!(%user_id, ?profile, "Updated user directory");
+}
+
+#[implement(Service)]
+async fn remove(&self, user_id: &UserId) {
+	let Ok(profile) = self.profile(user_id).await else {
+		return;
+	};
+
+	for term in &profile.terms {
+		self.db.termuserid_directory.del((term, user_id));
+	}
+
+	self.db.userid_directoryprofile.remove(user_id);
+	debug!(%user_id, "Removed from user directory");
+}
+
+/// The lowercased words a user can be found by: their user ID without the
+/// sigil, the parts of its localpart and the words of their display name.
+fn terms(user_id: &UserId, display_name: Option<&str>) -> Vec<String> {
+	let localpart = user_id.localpart().split(|c: char| !c.is_alphanumeric());
+
+	let display_name = display_name.unwrap_or_default().split_whitespace();
+
+	let mut terms: Vec<String> = std::iter::once(user_id.as_str().trim_start_matches('@'))
+		.chain(localpart)
+		.chain(display_name)
+		.filter(|term| !term.is_empty())
+		.map(str::to_lowercase)
+		.collect();
+
+	terms.sort_unstable();
+	terms.dedup();
+	terms.truncate(MAX_TERMS);
+
+	terms
+}
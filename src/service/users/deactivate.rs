@@ -0,0 +1,132 @@
+use conduwuit::{Event, Result, implement, matrix::pdu::PduBuilder, utils::ReadyExt, warn};
+use futures::StreamExt;
+use ruma::{
+	OwnedRoomId, UserId,
+	events::{
+		StateEventType,
+		room::{
+			member::{MembershipState, RoomMemberEventContent},
+			power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+		},
+	},
+};
+
+/// Runs through all the deactivation steps:
+///
+/// - Mark as deactivated
+/// - Removing display name
+/// - Removing avatar URL and blurhash
+/// - Removing all profile data
+/// - Leaving all rooms (and forgets all of them)
+#[implement(super::Service)]
+pub async fn full_deactivate(
+	&self,
+	user_id: &UserId,
+	all_joined_rooms: &[OwnedRoomId],
+) -> Result {
+	self.deactivate_account(user_id).await.ok();
+	self.services
+		.user_directory
+		.update_local_profile(user_id)
+		.await;
+
+	if self.services.globals.user_is_local(user_id) {
+		let _ = self
+			.services
+			.threepid
+			.disassociate_localpart_email(user_id.localpart())
+			.await;
+		let _ = self
+			.services
+			.threepid
+			.disassociate_localpart_msisdn(user_id.localpart())
+			.await;
+	}
+
+	self.all_profile_keys(user_id)
+		.ready_for_each(|(profile_key, _)| {
+			self.set_profile_key(user_id, &profile_key, None);
+		})
+		.await;
+
+	self.services
+		.pusher
+		.get_pushkeys(user_id)
+		.for_each(async |pushkey| {
+			self.services.pusher.delete_pusher(user_id, pushkey).await;
+		})
+		.await;
+
+	// TODO: Rescind all user invites
+
+	let mut pdu_queue: Vec<(PduBuilder, &OwnedRoomId)> = Vec::new();
+
+	for room_id in all_joined_rooms {
+		let room_power_levels = self
+			.services
+			.state_accessor
+			.room_state_get_content::<RoomPowerLevelsEventContent>(
+				room_id,
+				&StateEventType::RoomPowerLevels,
+				"",
+			)
+			.await
+			.ok();
+
+		let user_can_demote_self =
+			room_power_levels
+				.as_ref()
+				.is_some_and(|power_levels_content| {
+					RoomPowerLevels::from(power_levels_content.clone())
+						.user_can_change_user_power_level(user_id, user_id)
+				}) || self
+				.services
+				.state_accessor
+				.room_state_get(room_id, &StateEventType::RoomCreate, "")
+				.await
+				.is_ok_and(|event| event.sender() == user_id);
+
+		if user_can_demote_self {
+			let mut power_levels_content = room_power_levels.unwrap_or_default();
+			power_levels_content.users.remove(user_id);
+			let pl_evt = PduBuilder::state(String::new(), &power_levels_content);
+			pdu_queue.push((pl_evt, room_id));
+		}
+
+		// Leave the room
+		pdu_queue.push((
+			PduBuilder::state(user_id.to_string(), &RoomMemberEventContent {
+				avatar_url: None,
+				blurhash: None,
+				membership: MembershipState::Leave,
+				displayname: None,
+				join_authorized_via_users_server: None,
+				reason: None,
+				is_direct: None,
+				third_party_invite: None,
+				redact_events: None,
+			}),
+			room_id,
+		));
+
+		// TODO: Redact all messages sent by the user in the room
+	}
+
+	for (pdu_builder, room_id) in pdu_queue {
+		let state_lock = self.services.state.mutex.lock(room_id).await;
+		if let Err(e) = self
+			.services
+			.timeline
+			.build_and_append_pdu(pdu_builder, user_id, Some(room_id), &state_lock)
+			.await
+		{
+			warn!(%user_id, %room_id, "Failed to update/send new profile join membership update in room: {e}");
+		}
+	}
+
+	for room_id in all_joined_rooms {
+		self.services.state_cache.forget(room_id, user_id);
+	}
+
+	Ok(())
+}
@@ -1,14 +1,24 @@
+mod deactivate;
 pub(super) mod dehydrated_device;
+mod password_policy;
+mod refresh_token;
+#[cfg(test)]
+mod tests;
 
 #[cfg(feature = "ldap")]
 use std::collections::HashMap;
-use std::{collections::BTreeMap, mem, net::IpAddr, sync::Arc};
+use std::{
+	collections::{BTreeMap, HashSet},
+	mem,
+	net::IpAddr,
+	sync::Arc,
+};
 
 #[cfg(feature = "ldap")]
 use conduwuit::result::LogErr;
 use conduwuit::{
 	Err, Error, Result, Server, debug_warn, err, info, is_equal_to, trace,
-	utils::{self, ReadyExt, stream::TryIgnore, string::Unquoted},
+	utils::{self, MutexMap, ReadyExt, stream::TryIgnore, string::Unquoted},
 };
 #[cfg(feature = "ldap")]
 use conduwuit_core::{debug, error};
@@ -32,7 +42,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{Dep, account_data, admin, appservice, globals, rooms};
+use crate::{
+	Dep, account_data, admin, appservice, globals, pusher, rooms, threepid, user_directory,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSuspension {
@@ -47,6 +59,8 @@ pub struct UserSuspension {
 pub struct Service {
 	services: Services,
 	db: Data,
+	password_blocklist: HashSet<String>,
+	refresh_mutex: MutexMap<Vec<u8>, ()>,
 }
 
 struct Services {
@@ -55,7 +69,13 @@ struct Services {
 	admin: Dep<admin::Service>,
 	appservice: Dep<appservice::Service>,
 	globals: Dep<globals::Service>,
+	pusher: Dep<pusher::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
+	threepid: Dep<threepid::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+	user_directory: Dep<user_directory::Service>,
 }
 
 struct Data {
@@ -64,10 +84,13 @@ struct Data {
 	onetimekeyid_onetimekeys: Arc<Map>,
 	openidtoken_expiresatuserid: Arc<Map>,
 	logintoken_expiresatuserid: Arc<Map>,
+	refreshtoken_info: Arc<Map>,
 	todeviceid_events: Arc<Map>,
 	token_userdeviceid: Arc<Map>,
 	userdeviceid_metadata: Arc<Map>,
+	userdeviceid_refreshtoken: Arc<Map>,
 	userdeviceid_token: Arc<Map>,
+	userdeviceid_tokenexpiresat: Arc<Map>,
 	userfilterid_filter: Arc<Map>,
 	userid_avatarurl: Arc<Map>,
 	userid_blurhash: Arc<Map>,
@@ -95,7 +118,14 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				admin: args.depend::<admin::Service>("admin"),
 				appservice: args.depend::<appservice::Service>("appservice"),
 				globals: args.depend::<globals::Service>("globals"),
+				pusher: args.depend::<pusher::Service>("pusher"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				threepid: args.depend::<threepid::Service>("threepid"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+				user_directory: args.depend::<user_directory::Service>("user_directory"),
 			},
 			db: Data {
 				keychangeid_userid: args.db["keychangeid_userid"].clone(),
@@ -103,10 +133,13 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				onetimekeyid_onetimekeys: args.db["onetimekeyid_onetimekeys"].clone(),
 				openidtoken_expiresatuserid: args.db["openidtoken_expiresatuserid"].clone(),
 				logintoken_expiresatuserid: args.db["logintoken_expiresatuserid"].clone(),
+				refreshtoken_info: args.db["refreshtoken_info"].clone(),
 				todeviceid_events: args.db["todeviceid_events"].clone(),
 				token_userdeviceid: args.db["token_userdeviceid"].clone(),
 				userdeviceid_metadata: args.db["userdeviceid_metadata"].clone(),
+				userdeviceid_refreshtoken: args.db["userdeviceid_refreshtoken"].clone(),
 				userdeviceid_token: args.db["userdeviceid_token"].clone(),
+				userdeviceid_tokenexpiresat: args.db["userdeviceid_tokenexpiresat"].clone(),
 				userfilterid_filter: args.db["userfilterid_filter"].clone(),
 				userid_avatarurl: args.db["userid_avatarurl"].clone(),
 				userid_blurhash: args.db["userid_blurhash"].clone(),
@@ -124,6 +157,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				userid_usersigningkeyid: args.db["userid_usersigningkeyid"].clone(),
 				useridprofilekey_value: args.db["useridprofilekey_value"].clone(),
 			},
+			password_blocklist: password_policy::load_blocklist(
+				&args.server.config.password_policy,
+			)?,
+			refresh_mutex: MutexMap::new(),
 		}))
 	}
 
@@ -498,6 +535,8 @@ pub async fn remove_device(&self, user_id: &UserId, device_id: &DeviceId) {
 			self.db.userdeviceid_token.del(userdeviceid);
 			self.db.token_userdeviceid.remove(&old_token);
 		}
+		self.db.userdeviceid_tokenexpiresat.del(userdeviceid);
+		self.remove_refresh_tokens(user_id, device_id).await;
 
 		// Remove todevice events
 		let prefix = (user_id, device_id, Interfix);
@@ -608,6 +647,9 @@ pub async fn set_token(
 		self.db.userdeviceid_token.put_raw(key, token);
 		self.db.token_userdeviceid.raw_put(token, key);
 
+		// Tokens only expire when issued along with a refresh token
+		self.db.userdeviceid_tokenexpiresat.del(key);
+
 		Ok(())
 	}
 
@@ -1336,7 +1378,7 @@ pub fn set_profile_key(
 	}
 
 	#[cfg(feature = "ldap")]
-	async fn create_ldap_connection(
+	pub(crate) async fn create_ldap_connection(
 		config: &conduwuit_core::config::LdapConfig,
 		uri: &str,
 	) -> Result<(LdapConnAsync, ldap3::Ldap), ldap3::LdapError> {
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use conduwuit::{Err, Result, config::PasswordPolicyConfig, err, implement};
+
+/// The most common passwords, rejected when `block_common_passwords` is set.
+const COMMON_PASSWORDS: &[&str] = &[
+	"000000",
+	"111111",
+	"123123",
+	"123456",
+	"1234567",
+	"654321",
+	"abc123",
+	"dragon",
+	"monkey",
+	"qwerty",
+	"000000000",
+	"111111111",
+	"1111111111",
+	"123123123",
+	"1234567890",
+	"123456789",
+	"12345678",
+	"1q2w3e4r",
+	"1q2w3e4r5t",
+	"987654321",
+	"aa12345678",
+	"abc12345",
+	"abcd1234",
+	"baseball",
+	"football",
+	"iloveyou",
+	"letmein1",
+	"password",
+	"password1",
+	"password123",
+	"passw0rd",
+	"princess",
+	"qwerty123",
+	"qwertyuiop",
+	"sunshine",
+	"superman",
+	"trustno1",
+	"welcome1",
+	"zaq12wsx",
+];
+
+/// Reads `blocklist_file` of the password policy, if it is enforced.
+pub(super) fn load_blocklist(config: &PasswordPolicyConfig) -> Result<HashSet<String>> {
+	let Some(path) = config.blocklist_file.as_ref().filter(|_| config.enable) else {
+		return Ok(HashSet::new());
+	};
+
+	let blocklist = std::fs::read_to_string(path).map_err(|e| {
+		err!(Config(
+			"password_policy.blocklist_file",
+			"Failed to read password blocklist {path:?}: {e}"
+		))
+	})?;
+
+	Ok(blocklist
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty())
+		.map(str::to_lowercase)
+		.collect())
+}
+
+/// Checks that a password which `localpart` is about to set meets the
+/// configured password policy. `localpart` may be unknown before
+/// registration has picked one.
+#[implement(super::Service)]
+pub fn check_password_policy(&self, localpart: Option<&str>, password: &str) -> Result {
+	let policy = &self.services.server.config.password_policy;
+	if !policy.enable {
+		return Ok(());
+	}
+
+	match policy_violation(policy, &self.password_blocklist, localpart, password) {
+		| Some(reason) => Err!(Request(WeakPassword("{reason}"))),
+		| None => Ok(()),
+	}
+}
+
+/// Why `password` does not meet `policy`, if it does not.
+pub(super) fn policy_violation(
+	policy: &PasswordPolicyConfig,
+	blocklist: &HashSet<String>,
+	localpart: Option<&str>,
+	password: &str,
+) -> Option<String> {
+	if password.chars().count() < policy.minimum_length {
+		return Some(format!(
+			"Password must be at least {} characters long.",
+			policy.minimum_length
+		));
+	}
+
+	if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+		return Some("Password must contain a digit.".to_owned());
+	}
+
+	if policy.require_symbol && password.chars().all(char::is_alphanumeric) {
+		return Some("Password must contain a symbol.".to_owned());
+	}
+
+	if policy.require_lowercase && !password.chars().any(char::is_lowercase) {
+		return Some("Password must contain a lowercase letter.".to_owned());
+	}
+
+	if policy.require_uppercase && !password.chars().any(char::is_uppercase) {
+		return Some("Password must contain an uppercase letter.".to_owned());
+	}
+
+	let lowercased = password.to_lowercase();
+	if (policy.block_common_passwords && COMMON_PASSWORDS.contains(&lowercased.as_str()))
+		|| blocklist.contains(&lowercased)
+	{
+		return Some("Password is too common.".to_owned());
+	}
+
+	if policy.forbid_localpart
+		&& localpart
+			.map(str::to_lowercase)
+			.is_some_and(|localpart| !localpart.is_empty() && lowercased.contains(&localpart))
+	{
+		return Some("Password must not contain your username.".to_owned());
+	}
+
+	None
+}
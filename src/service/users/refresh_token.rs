@@ -0,0 +1,335 @@
+use std::time::Duration;
+
+use conduwuit::{Error, Result, debug_warn, implement, info, utils, warn};
+use database::{Deserialized, Json};
+use ruma::{DeviceId, OwnedDeviceId, OwnedUserId, UserId, api::client::error::ErrorKind};
+use serde::{Deserialize, Serialize};
+
+/// A refresh token and the device it was issued to. Each refresh of a device
+/// replaces its token; the replaced token is kept so that its reuse, which
+/// means it leaked, can be noticed.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct RefreshTokenInfo {
+	pub(super) user_id: OwnedUserId,
+	pub(super) device_id: OwnedDeviceId,
+	pub(super) expires_at: Option<u64>,
+	pub(super) used: bool,
+	pub(super) previous: Option<String>,
+
+	/// The token which replaced this one, once used.
+	#[serde(default)]
+	pub(super) replaced_by: Option<String>,
+}
+
+/// What may be done with a refresh token presented at some time.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Verdict {
+	Refresh,
+	Reused,
+	Expired,
+}
+
+impl RefreshTokenInfo {
+	/// A new token for a device, replacing `previous`, which expires `ttl`
+	/// seconds after `now` unless `ttl` is zero.
+	pub(super) fn new(
+		user_id: &UserId,
+		device_id: &DeviceId,
+		ttl: u64,
+		now: u64,
+		previous: Option<&str>,
+	) -> Self {
+		Self {
+			user_id: user_id.to_owned(),
+			device_id: device_id.to_owned(),
+			expires_at: (ttl > 0).then(|| now.saturating_add(ttl.saturating_mul(1000))),
+			used: false,
+			previous: previous.map(ToOwned::to_owned),
+			replaced_by: None,
+		}
+	}
+
+	pub(super) fn verdict(&self, now: u64) -> Verdict {
+		if self.used {
+			Verdict::Reused
+		} else if self.expires_at.is_some_and(|expires_at| expires_at < now) {
+			Verdict::Expired
+		} else {
+			Verdict::Refresh
+		}
+	}
+
+	/// This token once it has been replaced by `replaced_by`, kept only to
+	/// notice its reuse.
+	pub(super) fn into_used(self, replaced_by: &str) -> Self {
+		Self {
+			used: true,
+			previous: None,
+			replaced_by: Some(replaced_by.to_owned()),
+			..self
+		}
+	}
+
+	/// Whether this token replaced `token` and has not been used itself, in
+	/// which case the client never saw it and a retry with `token` is allowed.
+	pub(super) fn is_unseen_replacement_of(&self, token: &str) -> bool {
+		!self.used && self.previous.as_deref() == Some(token)
+	}
+}
+
+/// A refreshed session.
+#[derive(Debug)]
+pub struct Refreshed {
+	pub access_token: String,
+	pub refresh_token: String,
+	pub expires_in: Duration,
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 32;
+
+/// Issues a refresh token for a device whose access token was just set,
+/// making that access token expire. Returns the refresh token and the
+/// access token's lifetime.
+#[implement(super::Service)]
+pub async fn issue_refresh_token(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+) -> (String, Duration) {
+	let _lock = self
+		.refresh_mutex
+		.lock(refresh_key(user_id, device_id).as_slice())
+		.await;
+
+	self.remove_refresh_tokens(user_id, device_id).await;
+
+	let refresh_token = self.put_refresh_token(user_id, device_id, None);
+	let expires_in = self.expire_access_token(user_id, device_id);
+
+	(refresh_token, expires_in)
+}
+
+/// Replaces the access and refresh tokens of the device a refresh token was
+/// issued to. Using a refresh token which was already replaced revokes the
+/// device's tokens, unless its replacement has not been used yet: the client
+/// is then taken to be retrying after losing the response, and gets the same
+/// tokens again.
+#[implement(super::Service)]
+pub async fn refresh(&self, refresh_token: &str) -> Result<Refreshed> {
+	let unknown = || {
+		Error::BadRequest(
+			ErrorKind::UnknownToken { soft_logout: false },
+			"Unknown refresh token.",
+		)
+	};
+
+	let info: RefreshTokenInfo = self
+		.db
+		.refreshtoken_info
+		.get(refresh_token)
+		.await
+		.deserialized()
+		.map_err(|_| unknown())?;
+
+	// Concurrent refreshes of a device must not both succeed with one token
+	let _lock = self
+		.refresh_mutex
+		.lock(refresh_key(&info.user_id, &info.device_id).as_slice())
+		.await;
+
+	let info: RefreshTokenInfo = self
+		.db
+		.refreshtoken_info
+		.get(refresh_token)
+		.await
+		.deserialized()
+		.map_err(|_| unknown())?;
+
+	let (user_id, device_id) = (info.user_id.clone(), info.device_id.clone());
+	match info.verdict(utils::millis_since_unix_epoch()) {
+		| Verdict::Refresh => {},
+		| Verdict::Reused => {
+			if let Some(refreshed) = self.repeat_refresh(refresh_token, &info).await {
+				debug_warn!(%user_id, %device_id, "Refresh was retried, returning the same tokens");
+				return Ok(refreshed);
+			}
+
+			warn!(%user_id, %device_id, "Refresh token was reused, revoking the device's tokens");
+			self.revoke_refresh_tokens(&user_id, &device_id).await;
+			return Err(unknown());
+		},
+		| Verdict::Expired => {
+			self.revoke_refresh_tokens(&user_id, &device_id).await;
+			return Err(Error::BadRequest(
+				ErrorKind::UnknownToken { soft_logout: true },
+				"Refresh token has expired.",
+			));
+		},
+	}
+
+	// Only the token being replaced is kept around to detect reuse
+	if let Some(previous) = &info.previous {
+		self.db.refreshtoken_info.remove(previous);
+	}
+
+	let access_token = self.generate_unique_token().await;
+	self.set_token(&user_id, &device_id, &access_token).await?;
+
+	let new_refresh_token = self.put_refresh_token(&user_id, &device_id, Some(refresh_token));
+	let expires_in = self.expire_access_token(&user_id, &device_id);
+
+	self.db
+		.refreshtoken_info
+		.raw_put(refresh_token, Json(info.into_used(&new_refresh_token)));
+
+	Ok(Refreshed {
+		access_token,
+		refresh_token: new_refresh_token,
+		expires_in,
+	})
+}
+
+/// The tokens the refresh which replaced `refresh_token` returned, if the
+/// client has not used the new refresh token since.
+#[implement(super::Service)]
+async fn repeat_refresh(
+	&self,
+	refresh_token: &str,
+	info: &RefreshTokenInfo,
+) -> Option<Refreshed> {
+	let replaced_by = info.replaced_by.as_deref()?;
+	let replacement: RefreshTokenInfo = self
+		.db
+		.refreshtoken_info
+		.get(replaced_by)
+		.await
+		.deserialized()
+		.ok()?;
+
+	if !replacement.is_unseen_replacement_of(refresh_token) {
+		return None;
+	}
+
+	let key = (&info.user_id, &info.device_id);
+	let access_token: String = self
+		.db
+		.userdeviceid_token
+		.qry(&key)
+		.await
+		.deserialized()
+		.ok()?;
+
+	let expires_at: u64 = self
+		.db
+		.userdeviceid_tokenexpiresat
+		.qry(&key)
+		.await
+		.deserialized()
+		.ok()?;
+
+	let expires_in = expires_at.saturating_sub(utils::millis_since_unix_epoch());
+
+	Some(Refreshed {
+		access_token,
+		refresh_token: replaced_by.to_owned(),
+		expires_in: Duration::from_millis(expires_in),
+	})
+}
+
+/// Revokes the refresh tokens of a device along with its current access
+/// token, logging it out.
+#[implement(super::Service)]
+pub async fn revoke_refresh_tokens(&self, user_id: &UserId, device_id: &DeviceId) {
+	let key = (user_id, device_id);
+	if let Ok(token) = self.db.userdeviceid_token.qry(&key).await {
+		self.db.userdeviceid_token.del(key);
+		self.db.token_userdeviceid.remove(&token);
+	}
+
+	self.db.userdeviceid_tokenexpiresat.del(key);
+	self.remove_refresh_tokens(user_id, device_id).await;
+
+	info!(%user_id, %device_id, "Revoked the device's refresh tokens");
+}
+
+/// Whether the access token of a device has expired.
+#[implement(super::Service)]
+pub async fn is_access_token_expired(&self, user_id: &UserId, device_id: &DeviceId) -> bool {
+	self.db
+		.userdeviceid_tokenexpiresat
+		.qry(&(user_id, device_id))
+		.await
+		.deserialized()
+		.is_ok_and(|expires_at: u64| expires_at < utils::millis_since_unix_epoch())
+}
+
+/// Removes the refresh tokens of a device without touching its access token.
+#[implement(super::Service)]
+pub(super) async fn remove_refresh_tokens(&self, user_id: &UserId, device_id: &DeviceId) {
+	let key = (user_id, device_id);
+	let Ok(token) = self
+		.db
+		.userdeviceid_refreshtoken
+		.qry(&key)
+		.await
+		.deserialized::<String>()
+	else {
+		return;
+	};
+
+	if let Ok(info) = self
+		.db
+		.refreshtoken_info
+		.get(&token)
+		.await
+		.deserialized::<RefreshTokenInfo>()
+		&& let Some(previous) = info.previous
+	{
+		self.db.refreshtoken_info.remove(&previous);
+	}
+
+	self.db.refreshtoken_info.remove(&token);
+	self.db.userdeviceid_refreshtoken.del(key);
+}
+
+#[implement(super::Service)]
+fn put_refresh_token(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	previous: Option<&str>,
+) -> String {
+	let ttl = self.services.server.config.refresh_token_ttl;
+	let now = utils::millis_since_unix_epoch();
+
+	let refresh_token = utils::random_string(REFRESH_TOKEN_LENGTH);
+	self.db.refreshtoken_info.raw_put(
+		&refresh_token,
+		Json(RefreshTokenInfo::new(user_id, device_id, ttl, now, previous)),
+	);
+
+	self.db
+		.userdeviceid_refreshtoken
+		.put_raw((user_id, device_id), &refresh_token);
+
+	refresh_token
+}
+
+#[implement(super::Service)]
+fn expire_access_token(&self, user_id: &UserId, device_id: &DeviceId) -> Duration {
+	let ttl = self.services.server.config.access_token_ttl;
+	let expires_at = utils::millis_since_unix_epoch().saturating_add(ttl.saturating_mul(1000));
+
+	self.db
+		.userdeviceid_tokenexpiresat
+		.put((user_id, device_id), expires_at);
+
+	Duration::from_secs(ttl)
+}
+
+fn refresh_key(user_id: &UserId, device_id: &DeviceId) -> Vec<u8> {
+	let mut key = user_id.as_bytes().to_vec();
+	key.push(0xFF);
+	key.extend_from_slice(device_id.as_bytes());
+	key
+}
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use conduwuit::config::PasswordPolicyConfig;
+
+use super::password_policy::policy_violation;
+
+fn strict() -> PasswordPolicyConfig {
+	PasswordPolicyConfig {
+		enable: true,
+		require_digit: true,
+		require_symbol: true,
+		require_lowercase: true,
+		require_uppercase: true,
+		..PasswordPolicyConfig::default()
+	}
+}
+
+#[test]
+fn password_policy_accepts_strong_password() {
+	let blocklist = HashSet::new();
+	assert_eq!(policy_violation(&strict(), &blocklist, Some("alice"), "Correct-Horse-4"), None);
+}
+
+#[test]
+fn password_policy_rejects_each_rule() {
+	let policy = strict();
+	let blocklist = HashSet::from(["hunter2hunter2!a".to_owned()]);
+	let violation = |password| policy_violation(&policy, &blocklist, Some("Alice"), password);
+
+	assert!(violation("Sh0rt!").is_some());
+	assert!(violation("No-Digits-Here").is_some());
+	assert!(violation("NoSymbols1234").is_some());
+	assert!(violation("NO-LOWERCASE-1").is_some());
+	assert!(violation("no-uppercase-1").is_some());
+	assert!(violation("HUNTER2hunter2!a").is_some());
+	assert!(violation("My-aLiCe-Pass-1").is_some());
+}
+
+#[test]
+fn password_policy_blocks_common_passwords() {
+	let policy = PasswordPolicyConfig {
+		enable: true,
+		..PasswordPolicyConfig::default()
+	};
+	let blocklist = HashSet::new();
+
+	assert!(policy_violation(&policy, &blocklist, None, "Password1").is_some());
+	assert_eq!(policy_violation(&policy, &blocklist, None, "Password2"), None);
+}
+
+#[test]
+fn refresh_token_rotation() {
+	use ruma::{device_id, user_id};
+
+	use super::refresh_token::{RefreshTokenInfo, Verdict};
+
+	let user_id = user_id!("@alice:example.com");
+	let device_id = device_id!("ABCDEFG");
+
+	let first = RefreshTokenInfo::new(user_id, device_id, 0, 1_000, None);
+	assert_eq!(first.verdict(1_000), Verdict::Refresh);
+
+	// The replacement remembers the token it replaced, which is marked used
+	let second = RefreshTokenInfo::new(user_id, device_id, 0, 2_000, Some("first"));
+	assert_eq!(second.previous.as_deref(), Some("first"));
+	assert_eq!(second.verdict(2_000), Verdict::Refresh);
+
+	let first = first.into_used("second");
+	assert!(first.used);
+	assert_eq!(first.previous, None);
+	assert_eq!(first.replaced_by.as_deref(), Some("second"));
+	assert_eq!(first.user_id, user_id);
+	assert_eq!(first.device_id, device_id);
+}
+
+#[test]
+fn refresh_token_reuse_detected() {
+	use ruma::{device_id, user_id};
+
+	use super::refresh_token::{RefreshTokenInfo, Verdict};
+
+	let info =
+		RefreshTokenInfo::new(user_id!("@bob:example.com"), device_id!("XYZ"), 60, 0, None)
+			.into_used("next");
+
+	assert_eq!(info.verdict(0), Verdict::Reused);
+
+	// Reuse is reported even once the token would have expired
+	assert_eq!(info.verdict(u64::MAX), Verdict::Reused);
+}
+
+#[test]
+fn refresh_token_retry_allowed_until_replacement_used() {
+	use ruma::{device_id, user_id};
+
+	use super::refresh_token::RefreshTokenInfo;
+
+	let user_id = user_id!("@dave:example.com");
+	let device_id = device_id!("RETRY");
+
+	// A client which lost the response never saw the replacement
+	let second = RefreshTokenInfo::new(user_id, device_id, 0, 2_000, Some("first"));
+	assert!(second.is_unseen_replacement_of("first"));
+	assert!(!second.is_unseen_replacement_of("other"));
+
+	// Once the replacement is used, the first token must not be used again
+	let second = second.into_used("third");
+	assert!(!second.is_unseen_replacement_of("first"));
+}
+
+#[test]
+fn refresh_token_expiry() {
+	use ruma::{device_id, user_id};
+
+	use super::refresh_token::{RefreshTokenInfo, Verdict};
+
+	let user_id = user_id!("@carol:example.com");
+	let device_id = device_id!("DEVICE");
+
+	let info = RefreshTokenInfo::new(user_id, device_id, 60, 1_000, None);
+	assert_eq!(info.expires_at, Some(61_000));
+	assert_eq!(info.verdict(61_000), Verdict::Refresh);
+	assert_eq!(info.verdict(61_001), Verdict::Expired);
+
+	// A TTL of zero never expires
+	let info = RefreshTokenInfo::new(user_id, device_id, 0, 1_000, None);
+	assert_eq!(info.expires_at, None);
+	assert_eq!(info.verdict(u64::MAX), Verdict::Refresh);
+}
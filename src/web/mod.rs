@@ -91,6 +91,7 @@ pub fn build() -> Router<state::State> {
 				.merge(password_reset::build())
 				.merge(debug::build())
 				.merge(threepid::build())
+				.merge(sso::build())
 				.fallback(async || WebError::NotFound),
 		)
 		.layer(CatchPanicLayer::custom(|panic: Box<dyn Any + Send + 'static>| {
@@ -3,6 +3,7 @@
 pub(super) mod index;
 pub(super) mod password_reset;
 pub(super) mod resources;
+pub(super) mod sso;
 pub(super) mod threepid;
 
 #[derive(Debug)]
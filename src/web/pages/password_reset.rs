@@ -8,8 +8,9 @@
 	response::{IntoResponse, Response},
 	routing::get,
 };
+use ruma::api::client::error::ErrorKind;
 use serde::Deserialize;
-use validator::Validate;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::{
 	WebError, form,
@@ -101,10 +102,31 @@ async fn post_password_reset(
 			};
 			let user_id = token.info.user.clone();
 
-			services
+			match services
 				.password_reset
 				.consume_token(token, &form.new_password)
-				.await?;
+				.await
+			{
+				| Err(e) if matches!(e.kind(), ErrorKind::WeakPassword) => {
+					let mut errors = ValidationErrors::new();
+					errors.add(
+						"new_password",
+						ValidationError::new("weak_password").with_message(e.message().into()),
+					);
+
+					return Ok((
+						StatusCode::BAD_REQUEST,
+						password_reset_form(
+							services,
+							query,
+							PasswordResetForm::build(Some(errors)),
+						)
+						.await,
+					)
+						.into_response());
+				},
+				| result => result?,
+			}
 
 			let user_card = UserCard::for_local_user(&services, &user_id).await;
 			Ok(PasswordReset::new(&services, user_card, PasswordResetBody::Success)
@@ -0,0 +1,103 @@
+use axum::{
+	Router,
+	extract::{Query, State, rejection::QueryRejection},
+	response::{IntoResponse, Redirect, Response},
+	routing::get,
+};
+use conduwuit_core::Error;
+use serde::Deserialize;
+
+use crate::{WebError, pages::components::UserCard, template};
+
+template! {
+	struct SsoConfirm<'a> use "sso_confirm.html.j2" {
+		user_card: UserCard<'a>,
+		client: String,
+		redirect_url: String
+	}
+}
+
+template! {
+	struct SsoSelect<'a> use "sso_select.html.j2" {
+		providers: Vec<(&'a str, &'a str)>,
+		redirect_url: String
+	}
+}
+
+pub(crate) fn build() -> Router<crate::State> {
+	Router::new()
+		.route("/sso/callback", get(sso_callback))
+		.route("/sso/select", get(sso_select))
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+	state: String,
+	code: Option<String>,
+	error: Option<String>,
+	error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SelectQuery {
+	#[serde(rename = "redirectUrl")]
+	redirect_url: String,
+}
+
+async fn sso_callback(
+	State(services): State<crate::State>,
+	query: Result<Query<CallbackQuery>, QueryRejection>,
+) -> Result<Response, WebError> {
+	let Query(query) = query?;
+
+	let Some(code) = query.code else {
+		let error = query
+			.error_description
+			.or(query.error)
+			.unwrap_or_else(|| "no authorization code was returned".to_owned());
+
+		return Err(WebError::BadRequest(format!(
+			"The identity provider could not sign you in: {error}"
+		)));
+	};
+
+	let login = services
+		.sso
+		.complete(&query.state, &code)
+		.await
+		.map_err(|e| match e {
+			| Error::Request(..) => WebError::BadRequest(e.message()),
+			| e => e.into(),
+		})?;
+
+	if login.trusted {
+		return Ok(Redirect::to(login.redirect_url.as_str()).into_response());
+	}
+
+	let client = login
+		.redirect_url
+		.host_str()
+		.unwrap_or_else(|| login.redirect_url.scheme())
+		.to_owned();
+
+	let user_card = UserCard::for_local_user(&services, &login.user_id).await;
+
+	Ok(SsoConfirm::new(&services, user_card, client, login.redirect_url.into()).into_response())
+}
+
+async fn sso_select(
+	State(services): State<crate::State>,
+	query: Result<Query<SelectQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, WebError> {
+	let Query(query) = query?;
+
+	let providers = services
+		.config
+		.sso
+		.providers
+		.iter()
+		.map(|(id, provider)| (id.as_str(), provider.name.as_str()))
+		.collect();
+
+	Ok(SsoSelect::new(&services, providers, query.redirect_url).into_response())
+}